@@ -0,0 +1,6 @@
+/* STM32F4-class part; adjust for your MCU. */
+MEMORY
+{
+  FLASH : ORIGIN = 0x08000000, LENGTH = 256K
+  RAM : ORIGIN = 0x20000000, LENGTH = 64K
+}
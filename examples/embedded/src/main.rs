@@ -0,0 +1,59 @@
+//! 1 kHz tone detector on a Cortex-M4F, without std or an allocator.
+//!
+//! `adc_isr` stands in for the ADC conversion-complete interrupt of a real part and reads a stub
+//! converter that replays a 1 kHz test tone at 8 kHz. Detection changes are reported over
+//! semihosting, so this also runs under a debugger or QEMU.
+//!
+//! Build from this directory with `cargo build --release`.
+
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use cortex_m::interrupt::{self, Mutex};
+use cortex_m_rt::entry;
+use cortex_m_semihosting::hprintln;
+use goertzel_core::GoertzelConst;
+use panic_halt as _;
+
+const SAMPLE_RATE: f32 = 8000.;
+const THRESHOLD: f32 = 0.5;
+
+/// One period of 1 kHz at 8 kHz as 12-bit codes around mid-scale.
+const TEST_TONE: [u16; 8] = [2048, 3496, 4095, 3496, 2048, 600, 0, 600];
+
+static DETECTOR: Mutex<RefCell<GoertzelConst<200>>> =
+  Mutex::new(RefCell::new(GoertzelConst::new(1000., SAMPLE_RATE)));
+static TONE_PRESENT: AtomicBool = AtomicBool::new(false);
+static ADC_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+/// Stub for the ADC data register.
+fn read_adc() -> u16 {
+  let i = ADC_INDEX.fetch_add(1, Ordering::Relaxed);
+  TEST_TONE[i % TEST_TONE.len()]
+}
+
+fn adc_isr() {
+  let sample = (read_adc() as f32 - 2048.) / 2048.;
+  interrupt::free(|cs| {
+    if let Some(power) = DETECTOR.borrow(cs).borrow_mut().push(sample) {
+      TONE_PRESENT.store(power > THRESHOLD, Ordering::Relaxed);
+    }
+  });
+}
+
+#[entry]
+fn main() -> ! {
+  let mut reported = false;
+  loop {
+    // On hardware the ADC interrupt fires at SAMPLE_RATE and this loop would `wfi()`.
+    adc_isr();
+    let present = TONE_PRESENT.load(Ordering::Relaxed);
+    if present != reported {
+      hprintln!("1 kHz tone {}", if present { "on" } else { "off" });
+      reported = present;
+    }
+  }
+}
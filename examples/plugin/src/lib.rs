@@ -0,0 +1,170 @@
+//! The Goertzel detector as a VST3/CLAP audio plugin.
+//!
+//! Audio passes through untouched. The plugin measures the power at the `Frequency` parameter in
+//! 20 ms blocks of the channel average, and sends a MIDI note on when it rises above `Threshold`
+//! and a note off when it drops below, so a DAW can record or route detections like any other
+//! MIDI clip.
+//!
+//! Bundle from this directory with nih-plug's xtask (`cargo xtask bundle goertzel-plugin
+//! --release`), or build the `cdylib` with `cargo build --release`.
+
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+use goertzel_core::Goertzel;
+use nih_plug::prelude::*;
+
+/// Analysis block length.
+const BLOCK_MS: f32 = 20.;
+
+#[derive(Params)]
+struct DetectorParams {
+  #[id = "freq"]
+  freq: FloatParam,
+  #[id = "threshold"]
+  threshold: FloatParam,
+  /// MIDI note sent while the tone is present.
+  #[id = "note"]
+  note: IntParam,
+}
+
+impl Default for DetectorParams {
+  fn default() -> Self {
+    Self {
+      freq: FloatParam::new(
+        "Frequency",
+        440.,
+        FloatRange::Skewed { min: 20., max: 20_000., factor: FloatRange::skew_factor(-2.) },
+      )
+      .with_value_to_string(formatters::v2s_f32_hz_then_khz(1))
+      .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+      threshold: FloatParam::new("Threshold", 0.5, FloatRange::Linear { min: 0., max: 1. })
+        .with_step_size(0.01),
+      note: IntParam::new("Note", 69, IntRange::Linear { min: 0, max: 127 }),
+    }
+  }
+}
+
+struct GoertzelPlugin {
+  params: Arc<DetectorParams>,
+  sample_rate: f32,
+  filter: Goertzel,
+  /// Samples of the block being filled, sized in `initialize` so `process` never allocates.
+  block: Vec<f32>,
+  block_size: usize,
+  /// Note currently held, while the tone is present.
+  playing: Option<u8>,
+}
+
+impl Default for GoertzelPlugin {
+  fn default() -> Self {
+    Self {
+      params: Arc::new(DetectorParams::default()),
+      sample_rate: 44_100.,
+      filter: Goertzel::new(440., 44_100.),
+      block: Vec::new(),
+      block_size: 0,
+      playing: None,
+    }
+  }
+}
+
+impl Plugin for GoertzelPlugin {
+  const NAME: &'static str = "Goertzel Detector";
+  const VENDOR: &'static str = "goertzelrs";
+  const URL: &'static str = "https://github.com/arturaugusto/goertzelrs";
+  const EMAIL: &'static str = "arturaugusto@gmail.com";
+  const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+  const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[
+    AudioIOLayout {
+      main_input_channels: NonZeroU32::new(2),
+      main_output_channels: NonZeroU32::new(2),
+      ..AudioIOLayout::const_default()
+    },
+    AudioIOLayout {
+      main_input_channels: NonZeroU32::new(1),
+      main_output_channels: NonZeroU32::new(1),
+      ..AudioIOLayout::const_default()
+    },
+  ];
+  const MIDI_OUTPUT: MidiConfig = MidiConfig::Basic;
+
+  type SysExMessage = ();
+  type BackgroundTask = ();
+
+  fn params(&self) -> Arc<dyn Params> {
+    self.params.clone()
+  }
+
+  fn initialize(
+    &mut self,
+    _layout: &AudioIOLayout,
+    buffer_config: &BufferConfig,
+    _context: &mut impl InitContext<Self>,
+  ) -> bool {
+    self.sample_rate = buffer_config.sample_rate;
+    self.block_size = (self.sample_rate * BLOCK_MS / 1000.) as usize;
+    self.block = Vec::with_capacity(self.block_size);
+    true
+  }
+
+  fn reset(&mut self) {
+    self.block.clear();
+    self.playing = None;
+  }
+
+  fn process(
+    &mut self,
+    buffer: &mut Buffer,
+    _aux: &mut AuxiliaryBuffers,
+    context: &mut impl ProcessContext<Self>,
+  ) -> ProcessStatus {
+    for (offset, samples) in buffer.iter_samples().enumerate() {
+      let channels = samples.len() as f32;
+      self.block.push(samples.into_iter().map(|s| *s).sum::<f32>() / channels);
+      if self.block.len() < self.block_size {
+        continue;
+      }
+
+      let freq = self.params.freq.value();
+      if freq != self.filter.freq() {
+        self.filter = Goertzel::new(freq, self.sample_rate);
+      }
+      let present = self.filter.block_power(&self.block) >= self.params.threshold.value();
+      self.block.clear();
+
+      let timing = offset as u32;
+      let note = self.params.note.value() as u8;
+      match (present, self.playing) {
+        (true, None) => {
+          context.send_event(NoteEvent::NoteOn { timing, voice_id: None, channel: 0, note, velocity: 1. });
+          self.playing = Some(note);
+        }
+        (false, Some(held)) => {
+          context.send_event(NoteEvent::NoteOff { timing, voice_id: None, channel: 0, note: held, velocity: 0. });
+          self.playing = None;
+        }
+        _ => {}
+      }
+    }
+    ProcessStatus::Normal
+  }
+}
+
+impl ClapPlugin for GoertzelPlugin {
+  const CLAP_ID: &'static str = "com.github.arturaugusto.goertzelrs";
+  const CLAP_DESCRIPTION: Option<&'static str> = Some("Single-frequency tone detector with MIDI output");
+  const CLAP_MANUAL_URL: Option<&'static str> = None;
+  const CLAP_SUPPORT_URL: Option<&'static str> = None;
+  const CLAP_FEATURES: &'static [ClapFeature] =
+    &[ClapFeature::AudioEffect, ClapFeature::Analyzer, ClapFeature::Mono, ClapFeature::Stereo];
+}
+
+impl Vst3Plugin for GoertzelPlugin {
+  const VST3_CLASS_ID: [u8; 16] = *b"GoertzelrsDetect";
+  const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] = &[Vst3SubCategory::Fx, Vst3SubCategory::Analyzer];
+}
+
+nih_export_clap!(GoertzelPlugin);
+nih_export_vst3!(GoertzelPlugin);
@@ -0,0 +1,192 @@
+//! `--capture dir`: a WAV snippet of the audio around every detection, from `--pre-roll` before
+//! its onset to `--post-roll` after it, so what triggered it can be checked by ear.
+//!
+//! Snippets are named after the event's time in the stream, its kind and label, e.g.
+//! `12.480-digit.wav` or `ch1-3.020-tone_on-doorbell.wav`, and written by a thread of their own
+//! so the audio callback never waits for the disk.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use goertzel_core::detector::Event;
+use goertzel_core::history::AudioHistory;
+
+use crate::output;
+
+/// Seconds of audio kept beyond the rolls, for detections reported a while after their onset,
+/// such as tones with a minimum duration.
+const SLACK: f32 = 5.;
+
+/// Where snippets go and how much audio they hold; shared by every channel.
+#[derive(Clone)]
+pub struct Settings {
+  dir: PathBuf,
+  pre_roll: f32,
+  post_roll: f32,
+  writer: mpsc::Sender<Snippet>,
+  /// Snippets handed to the writer and not written yet.
+  unwritten: Arc<AtomicUsize>,
+}
+
+struct Snippet {
+  path: PathBuf,
+  sample_rate: f32,
+  samples: Vec<f32>,
+}
+
+impl Settings {
+  /// Starts the thread writing snippets into `dir`, which is created if needed. The rolls are in
+  /// seconds.
+  pub fn new(dir: &Path, pre_roll: f32, post_roll: f32) -> std::io::Result<Self> {
+    std::fs::create_dir_all(dir)?;
+    let (writer, snippets) = mpsc::channel::<Snippet>();
+    let unwritten = Arc::new(AtomicUsize::new(0));
+    let written = unwritten.clone();
+    std::thread::spawn(move || {
+      for snippet in snippets {
+        if let Err(e) = write_wav(&snippet) {
+          eprintln!("could not write {}: {}", snippet.path.display(), e);
+        }
+        written.fetch_sub(1, Ordering::Release);
+      }
+    });
+    Ok(Settings { dir: dir.to_path_buf(), pre_roll, post_roll, writer, unwritten })
+  }
+
+  /// Waits until every snippet handed over so far was written.
+  pub fn flush(&self) {
+    while self.unwritten.load(Ordering::Acquire) > 0 {
+      std::thread::sleep(Duration::from_millis(1));
+    }
+  }
+}
+
+fn write_wav(snippet: &Snippet) -> Result<(), hound::Error> {
+  let spec = hound::WavSpec {
+    channels: 1,
+    sample_rate: snippet.sample_rate as u32,
+    bits_per_sample: 16,
+    sample_format: hound::SampleFormat::Int,
+  };
+  let mut wav = hound::WavWriter::create(&snippet.path, spec)?;
+  for &sample in &snippet.samples {
+    wav.write_sample((sample.clamp(-1., 1.) * i16::MAX as f32) as i16)?;
+  }
+  wav.finalize()
+}
+
+/// Captures the detections of one channel.
+pub struct Capture {
+  settings: Settings,
+  channel: Option<usize>,
+  pre_roll: u64,
+  post_roll: u64,
+  history: AudioHistory,
+  /// Snippets waiting for their post-roll: first and end frame, and file name.
+  pending: Vec<(u64, u64, String)>,
+}
+
+impl Capture {
+  pub fn new(settings: &Settings, sample_rate: f32, channel: Option<usize>) -> Self {
+    let frames = |seconds: f32| (seconds * sample_rate) as u64;
+    let (pre_roll, post_roll) = (frames(settings.pre_roll), frames(settings.post_roll));
+    let kept = Duration::from_secs_f32(settings.pre_roll + settings.post_roll + SLACK);
+    Capture {
+      settings: settings.clone(),
+      channel,
+      pre_roll,
+      post_roll,
+      history: AudioHistory::new(kept, sample_rate),
+      pending: Vec::new(),
+    }
+  }
+
+  /// Makes the next frame the `frame`th of the stream, forgetting the audio before.
+  pub fn set_position(&mut self, frame: u64) {
+    self.history.set_position(frame);
+    self.pending.clear();
+  }
+
+  /// Takes the next samples and the events detected up to their end.
+  pub fn push(&mut self, samples: &[f32], events: &[Event]) {
+    self.history.push(samples);
+    for event in events.iter().filter(|e| e.kind.is_onset()) {
+      let at = event.time.sample;
+      let name = self.name(event);
+      self.pending.push((at.saturating_sub(self.pre_roll), at + self.post_roll, name));
+    }
+    let end = self.history.end();
+    let pending = std::mem::take(&mut self.pending);
+    let (done, waiting) = pending.into_iter().partition(|(_, stop, _)| *stop <= end);
+    self.pending = waiting;
+    for snippet in done {
+      self.save(snippet);
+    }
+  }
+
+  /// Saves the snippets still waiting with the post-roll there is, at the end of the input, and
+  /// waits until they are written.
+  pub fn finish(&mut self) {
+    for (start, _, name) in std::mem::take(&mut self.pending) {
+      self.save((start, self.history.end(), name));
+    }
+    self.settings.flush();
+  }
+
+  fn save(&self, (start, stop, name): (u64, u64, String)) {
+    let samples = self.history.range(start, stop);
+    let path = self.settings.dir.join(name);
+    self.settings.unwritten.fetch_add(1, Ordering::Release);
+    let snippet = Snippet { path, sample_rate: self.history.sample_rate(), samples };
+    if self.settings.writer.send(snippet).is_err() {
+      self.settings.unwritten.fetch_sub(1, Ordering::Release);
+    }
+  }
+
+  /// `[chN-]<seconds>-<event>[-<label>].wav`.
+  fn name(&self, event: &Event) -> String {
+    let mut name = match self.channel {
+      Some(channel) => format!("ch{}-", channel),
+      None => String::new(),
+    };
+    name.push_str(&format!("{:.3}-{}", event.time.seconds(), output::columns(&event.kind).0));
+    if let Some(label) = &event.label {
+      let label: String =
+        label.chars().map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' }).collect();
+      name.push_str(&format!("-{}", label));
+    }
+    name + ".wav"
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use goertzel_core::detector::{EventKind, Time};
+
+  #[test]
+  fn snippet_holds_the_rolls_around_the_onset() {
+    let dir = std::env::temp_dir().join(format!("goertzelrs-capture-{}", std::process::id()));
+    let settings = Settings::new(&dir, 0.5, 0.25).unwrap();
+    let mut capture = Capture::new(&settings, 8000., Some(1));
+    // A ramp, so every sample tells where it came from; the digit starts at 2 s.
+    let ramp: Vec<f32> = (0..40_000).map(|i| i as f32 / 40_000.).collect();
+    let at = Time { sample: 16_000, sample_rate: 8000. };
+    let digit = Event { label: Some("front door".into()), ..Event::new(at, EventKind::Digit('5')) };
+    for (i, block) in ramp.chunks(800).enumerate() {
+      let events = if i == 20 { vec![digit.clone()] } else { vec![] };
+      capture.push(block, &events);
+    }
+    settings.flush();
+    let path = dir.join("ch1-2.000-digit-front_door.wav");
+    let samples: Vec<i16> =
+      hound::WavReader::open(&path).unwrap().samples().map(Result::unwrap).collect();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(samples.len(), 6000);
+    let first = samples[0] as f32 / i16::MAX as f32;
+    assert!((first - 12_000. / 40_000.).abs() < 1e-3, "{}", first);
+  }
+}
@@ -0,0 +1,160 @@
+//! `--parquet`: events as an Apache Parquet table, which DuckDB, pandas or polars load directly.
+
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow_array::builder::{
+  Float32Builder, Float64Builder, StringBuilder, UInt32Builder, UInt64Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use goertzel_core::detector::{Event, EventKind};
+use parquet::arrow::ArrowWriter;
+
+use crate::output::{self, Origin};
+
+/// Rows buffered before they are written as one record batch.
+const BATCH_ROWS: usize = 65_536;
+
+/// Writes events with the columns of the CSV format, splitting the value into a numeric and a
+/// text column.
+pub struct ParquetWriter {
+  writer: ArrowWriter<File>,
+  schema: SchemaRef,
+  rows: usize,
+  time: Float64Builder,
+  sample: UInt64Builder,
+  source: StringBuilder,
+  channel: UInt32Builder,
+  label: StringBuilder,
+  event: StringBuilder,
+  freq: Float32Builder,
+  value: Float64Builder,
+  text: StringBuilder,
+  confidence: Float32Builder,
+}
+
+impl ParquetWriter {
+  pub fn create(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+    let schema = Arc::new(Schema::new(vec![
+      Field::new("time", DataType::Float64, false),
+      Field::new("sample", DataType::UInt64, false),
+      Field::new("source", DataType::Utf8, true),
+      Field::new("channel", DataType::UInt32, true),
+      Field::new("label", DataType::Utf8, true),
+      Field::new("event", DataType::Utf8, false),
+      Field::new("freq", DataType::Float32, true),
+      Field::new("value", DataType::Float64, true),
+      Field::new("text", DataType::Utf8, true),
+      Field::new("confidence", DataType::Float32, true),
+    ]));
+    let writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), None)?;
+    Ok(ParquetWriter {
+      writer,
+      schema,
+      rows: 0,
+      time: Float64Builder::new(),
+      sample: UInt64Builder::new(),
+      source: StringBuilder::new(),
+      channel: UInt32Builder::new(),
+      label: StringBuilder::new(),
+      event: StringBuilder::new(),
+      freq: Float32Builder::new(),
+      value: Float64Builder::new(),
+      text: StringBuilder::new(),
+      confidence: Float32Builder::new(),
+    })
+  }
+
+  pub fn push(&mut self, origin: Origin, event: &Event) -> Result<(), anyhow::Error> {
+    let (name, freq, value) = output::columns(&event.kind);
+    let textual =
+      matches!(event.kind, EventKind::Digit(_) | EventKind::Match(_) | EventKind::Custom(_));
+    self.time.append_value(event.time.seconds());
+    self.sample.append_value(event.time.sample);
+    self.source.append_option(origin.source);
+    self.channel.append_option(origin.channel.map(|c| c as u32));
+    self.label.append_option(event.label.as_deref());
+    self.event.append_value(name);
+    self.freq.append_option(freq);
+    self.value.append_option(if textual { None } else { value.parse().ok() });
+    self.text.append_option(if textual { Some(value) } else { None });
+    self.confidence.append_option(event.confidence);
+    self.rows += 1;
+    if self.rows == BATCH_ROWS {
+      self.write_batch()?;
+    }
+    Ok(())
+  }
+
+  fn write_batch(&mut self) -> Result<(), anyhow::Error> {
+    let columns: Vec<ArrayRef> = vec![
+      Arc::new(self.time.finish()),
+      Arc::new(self.sample.finish()),
+      Arc::new(self.source.finish()),
+      Arc::new(self.channel.finish()),
+      Arc::new(self.label.finish()),
+      Arc::new(self.event.finish()),
+      Arc::new(self.freq.finish()),
+      Arc::new(self.value.finish()),
+      Arc::new(self.text.finish()),
+      Arc::new(self.confidence.finish()),
+    ];
+    self.writer.write(&RecordBatch::try_new(self.schema.clone(), columns)?)?;
+    self.rows = 0;
+    Ok(())
+  }
+
+  /// Writes the remaining rows and the file footer.
+  pub fn finish(mut self) -> Result<(), anyhow::Error> {
+    if self.rows > 0 {
+      self.write_batch()?;
+    }
+    self.writer.close()?;
+    Ok(())
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use arrow_array::cast::AsArray;
+  use arrow_array::types::Float64Type;
+  use arrow_array::Array;
+  use goertzel_core::detector::Time;
+  use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+  #[test]
+  fn writes_events_as_rows() {
+    let path = std::env::temp_dir().join(format!("goertzelrs-{}.parquet", std::process::id()));
+    let mut writer = ParquetWriter::create(&path).unwrap();
+    let time = Time { sample: 8000, sample_rate: 8000. };
+    let on = Event::new(time, EventKind::ToneOn { freq: 1000., power: 0.5 });
+    let digit = Event {
+      label: Some("line-1-dtmf".into()),
+      ..Event::new(time.offset(800), EventKind::Digit('#'))
+    };
+    writer.push(Origin::default(), &on).unwrap();
+    writer.push(Origin { source: None, channel: Some(1) }, &digit).unwrap();
+    writer.finish().unwrap();
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+      .unwrap()
+      .build()
+      .unwrap();
+    let batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+    assert_eq!(batches.len(), 1);
+    let batch = &batches[0];
+    assert_eq!(batch.num_rows(), 2);
+    let time = batch.column(0).as_primitive::<Float64Type>();
+    assert_eq!((time.value(0), time.value(1)), (1.0, 1.1));
+    let label = batch.column(4).as_string::<i32>();
+    assert_eq!((label.is_null(0), label.value(1)), (true, "line-1-dtmf"));
+    let value = batch.column(7).as_primitive::<Float64Type>();
+    assert_eq!((value.value(0), value.is_null(1)), (0.5, true));
+    assert_eq!(batch.column(8).as_string::<i32>().value(1), "#");
+    std::fs::remove_file(path).unwrap();
+  }
+}
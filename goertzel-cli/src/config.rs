@@ -0,0 +1,420 @@
+//! `--config`: the tones to monitor, read from a JSON file, each with settings of its own.
+//!
+//! ```json
+//! {
+//!   "threshold": 0.5,
+//!   "tones": [
+//!     { "freq": 100, "block_ms": 200, "window": "hann", "min_duration": 0.5, "label": "squelch" },
+//!     { "freq": 4000, "block_ms": 10, "threshold": 0.3, "label": "fire-alarm" },
+//!     { "freq": 1000, "max_duration": 30, "label": "test-tone" },
+//!     { "freq": 1500, "block_ms": 100, "onset_ms": 2, "label": "start-mark" },
+//!     { "freq": 900, "active": "08:00-12:00,14:00-22:00", "label": "doorbell" }
+//!   ],
+//!   "rules": [
+//!     {
+//!       "present": [700, 900, 1100, 1300], "required": 2, "absent": [2600],
+//!       "threshold": 0.2, "min_duration": 0.04, "label": "mf-pair"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Settings at the top level are the defaults of every tone, and their `block_ms` is the block
+//! size the pipeline cuts; tones with another block size are re-blocked on their own. A tone's
+//! `label` tags its events in every output format. A tone lasting longer than its
+//! `max_duration`, in seconds, is reported once as stuck, e.g. a test tone left on the line.
+//! With `onset_ms`, a tone's onset is timed to within that many milliseconds instead of to the
+//! block it was found in, for long blocks whose start times would be too coarse.
+//!
+//! With `silence_dbfs`, a tone is absent from any block quieter than that level relative to a
+//! full-scale sine, e.g. -60, however much of the little energy there is falls on it; otherwise
+//! a quiet line's hum or hiss can read as a tone. `--silence-floor` sets the default.
+//!
+//! A tone with `active` windows of the day, in local time, is only reported within them; the
+//! detector logs a `schedule` event whenever a window opens or closes, and when it starts.
+//!
+//! Instead of `block_ms`, a tone can give the `tolerance` in Hz it should accept, e.g. 15 for
+//! 1000 Hz ±15 Hz; the block size follows from it, and the window is Hann unless set.
+//!
+//! A rule is reported as `coincidence_on` while at least `required` of its `present` tones (all
+//! of them unless set) sound together and none of its `absent` ones does. It takes the same
+//! settings as a tone but `max_duration`, `onset_ms` and `silence_dbfs`; as the tones share the
+//! power of a block, its threshold should be lower than that of a single tone.
+
+use std::path::Path;
+
+use goertzel_core::coincidence::CoincidenceDetector;
+use goertzel_core::detector::{Detector, Labeled, Reblock, ToneDetector};
+use goertzel_core::schedule::{DailyWindow, Scheduled};
+use goertzel_core::Window;
+use serde::Deserialize;
+
+const BLOCK_MS: f32 = 20.;
+const THRESHOLD: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowName {
+  Rectangular,
+  Hann,
+  Hamming,
+  Blackman,
+}
+
+impl From<WindowName> for Window {
+  fn from(name: WindowName) -> Window {
+    match name {
+      WindowName::Rectangular => Window::Rectangular,
+      WindowName::Hann => Window::Hann,
+      WindowName::Hamming => Window::Hamming,
+      WindowName::Blackman => Window::Blackman,
+    }
+  }
+}
+
+/// Detection settings; unset ones fall back to the file's defaults, then to the built-in ones.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Settings {
+  pub block_ms: Option<f32>,
+  /// Hz off the tone frequency still accepted; sets the block size instead of `block_ms`.
+  pub tolerance: Option<f32>,
+  pub threshold: Option<f32>,
+  pub window: Option<WindowName>,
+  /// Seconds a tone must last to be reported.
+  pub min_duration: Option<f32>,
+  /// Seconds after which a tone still on is reported as stuck.
+  pub max_duration: Option<f32>,
+  /// Milliseconds onsets are located to within their block.
+  pub onset_ms: Option<f32>,
+  /// Level in dBFS at or below which a block is silence, in which the tone is absent.
+  pub silence_dbfs: Option<f32>,
+  /// Times of day the tone is watched, e.g. `08:00-22:00`; all day if unset.
+  pub active: Option<String>,
+}
+
+impl Settings {
+  fn or(&self, defaults: &Settings) -> Settings {
+    // The block size and the tolerance are one choice, made where either is set.
+    let sizing = if self.block_ms.is_some() || self.tolerance.is_some() { self } else { defaults };
+    Settings {
+      block_ms: sizing.block_ms,
+      tolerance: sizing.tolerance,
+      threshold: self.threshold.or(defaults.threshold),
+      window: self.window.or(defaults.window),
+      min_duration: self.min_duration.or(defaults.min_duration),
+      max_duration: self.max_duration.or(defaults.max_duration),
+      onset_ms: self.onset_ms.or(defaults.onset_ms),
+      silence_dbfs: self.silence_dbfs.or(defaults.silence_dbfs),
+      active: self.active.clone().or_else(|| defaults.active.clone()),
+    }
+  }
+
+  /// Window and block size of resolved settings at `sample_rate`.
+  fn blocks(&self, sample_rate: f32) -> (Window, usize) {
+    let window = match (self.window, self.tolerance) {
+      (Some(name), _) => Window::from(name),
+      (None, Some(_)) => Window::Hann,
+      (None, None) => Window::Rectangular,
+    };
+    let block_size = match self.tolerance {
+      Some(hz) => window.block_size_for(hz, sample_rate),
+      None => samples(self.block_ms.unwrap_or(BLOCK_MS), sample_rate),
+    };
+    (window, block_size)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Tone {
+  pub freq: f32,
+  /// Name its events are tagged with, e.g. `"doorbell"`.
+  pub label: Option<String>,
+  #[serde(flatten)]
+  pub settings: Settings,
+}
+
+/// Tones that must sound together, k of n, with others that must not.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Rule {
+  pub present: Vec<f32>,
+  /// How many of `present` must be found; all of them if unset.
+  pub required: Option<usize>,
+  #[serde(default)]
+  pub absent: Vec<f32>,
+  pub label: Option<String>,
+  #[serde(flatten)]
+  pub settings: Settings,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Config {
+  #[serde(flatten)]
+  pub defaults: Settings,
+  #[serde(default)]
+  pub tones: Vec<Tone>,
+  #[serde(default)]
+  pub rules: Vec<Rule>,
+}
+
+impl Config {
+  pub fn load(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)?;
+    let config: Config = serde_json::from_str(&text)
+      .map_err(|e| anyhow::anyhow!("bad config {}: {}", path.display(), e))?;
+    config.validate()?;
+    Ok(config)
+  }
+
+  fn validate(&self) -> Result<(), anyhow::Error> {
+    let settings = std::iter::once(&self.defaults)
+      .chain(self.tones.iter().map(|t| &t.settings))
+      .chain(self.rules.iter().map(|r| &r.settings));
+    for s in settings {
+      if s.block_ms.is_some_and(|ms| ms <= 0.) {
+        anyhow::bail!("block_ms must be positive");
+      }
+      if s.tolerance.is_some_and(|hz| hz <= 0.) {
+        anyhow::bail!("tolerance must be positive");
+      }
+      if s.onset_ms.is_some_and(|ms| ms <= 0.) {
+        anyhow::bail!("onset_ms must be positive");
+      }
+      if s.block_ms.is_some() && s.tolerance.is_some() {
+        anyhow::bail!("set block_ms or tolerance, not both");
+      }
+      if let Some(active) = s.active.as_deref().filter(|a| DailyWindow::parse_list(a).is_none()) {
+        anyhow::bail!("bad active `{}`, use e.g. 08:00-22:00", active);
+      }
+    }
+    if let Some(tone) = self.tones.iter().find(|t| t.freq <= 0.) {
+      anyhow::bail!("bad tone frequency {}", tone.freq);
+    }
+    for tone in &self.tones {
+      let s = tone.settings.or(&self.defaults);
+      if s.max_duration.is_some_and(|max| max <= s.min_duration.unwrap_or(0.)) {
+        anyhow::bail!("max_duration of {} Hz must be longer than its min_duration", tone.freq);
+      }
+    }
+    for rule in &self.rules {
+      if rule.present.is_empty() {
+        anyhow::bail!("a rule needs present tones");
+      }
+      if let Some(f) = rule.present.iter().chain(&rule.absent).find(|&&f| f <= 0.) {
+        anyhow::bail!("bad rule frequency {}", f);
+      }
+      if rule.required.is_some_and(|k| k == 0 || k > rule.present.len()) {
+        anyhow::bail!("required must be from 1 to the {} present tones", rule.present.len());
+      }
+      let s = &rule.settings;
+      if s.max_duration.is_some() || s.onset_ms.is_some() || s.silence_dbfs.is_some() {
+        anyhow::bail!("max_duration, onset_ms and silence_dbfs are for tones, not rules");
+      }
+    }
+    Ok(())
+  }
+
+  /// Samples per pipeline block at `sample_rate`.
+  pub fn block_size(&self, sample_rate: f32) -> usize {
+    samples(self.defaults.block_ms.unwrap_or(BLOCK_MS), sample_rate)
+  }
+
+  /// One detector per tone, then one per rule, with its settings resolved. `second_of_day` is
+  /// the local time of day of the first block, for the `active` windows.
+  pub fn detectors(
+    &self,
+    sample_rate: f32,
+    second_of_day: f64,
+  ) -> Vec<Box<dyn Detector + Send>> {
+    let pipeline_block = self.block_size(sample_rate);
+    // Re-blocked, scheduled and labeled as its settings say.
+    let wrap = |detector: Box<dyn Detector + Send>,
+                s: &Settings,
+                block_size: usize,
+                label: &Option<String>|
+     -> Box<dyn Detector + Send> {
+      let detector: Box<dyn Detector + Send> = if block_size == pipeline_block {
+        detector
+      } else {
+        Box::new(Reblock::new(detector, block_size))
+      };
+      let detector: Box<dyn Detector + Send> =
+        match s.active.as_deref().and_then(DailyWindow::parse_list) {
+          Some(windows) => Box::new(Scheduled::new(detector, windows, second_of_day)),
+          None => detector,
+        };
+      match label {
+        Some(label) => Box::new(Labeled::new(detector, label.clone())),
+        None => detector,
+      }
+    };
+    let tones = self.tones.iter().map(|tone| {
+      let s = tone.settings.or(&self.defaults);
+      let (window, block_size) = s.blocks(sample_rate);
+      let detector = ToneDetector::new(tone.freq, sample_rate, s.threshold.unwrap_or(THRESHOLD))
+        .with_window(window)
+        .with_min_duration(s.min_duration.unwrap_or(0.));
+      let detector = match s.max_duration {
+        Some(max) => detector.with_max_duration(max),
+        None => detector,
+      };
+      let detector = match s.onset_ms {
+        Some(ms) => detector.with_onset_refinement(ms / 1000.),
+        None => detector,
+      };
+      let detector = match s.silence_dbfs {
+        Some(dbfs) => detector.with_silence_floor(0.5 * 10f32.powf(dbfs / 10.)),
+        None => detector,
+      };
+      wrap(Box::new(detector), &s, block_size, &tone.label)
+    });
+    let rules = self.rules.iter().map(|rule| {
+      let s = rule.settings.or(&self.defaults);
+      let (window, block_size) = s.blocks(sample_rate);
+      let required = rule.required.unwrap_or(rule.present.len());
+      let threshold = s.threshold.unwrap_or(THRESHOLD);
+      let detector =
+        CoincidenceDetector::new(&rule.present, &rule.absent, required, sample_rate, threshold)
+          .with_window(window)
+          .with_min_duration(s.min_duration.unwrap_or(0.));
+      wrap(Box::new(detector), &s, block_size, &rule.label)
+    });
+    tones.chain(rules).collect()
+  }
+}
+
+/// Seconds since local midnight.
+pub fn local_time_of_day() -> f64 {
+  use chrono::Timelike;
+  let now = chrono::Local::now();
+  now.num_seconds_from_midnight() as f64 + now.nanosecond() as f64 / 1e9
+}
+
+fn samples(ms: f32, sample_rate: f32) -> usize {
+  ((ms / 1000. * sample_rate).round() as usize).max(1)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use goertzel_core::detector::{EventKind, Time};
+  use std::f32::consts::PI;
+
+  #[test]
+  fn tones_carry_their_own_settings() {
+    let config: Config = serde_json::from_str(
+      r#"{
+        "block_ms": 10, "threshold": 0.3,
+        "tones": [
+          { "freq": 100, "block_ms": 200, "window": "hann", "min_duration": 0.5 },
+          { "freq": 3000, "threshold": 0.4, "label": "beep" }
+        ]
+      }"#,
+    )
+    .unwrap();
+    assert_eq!(config.tones[0].settings.or(&config.defaults), Settings {
+      block_ms: Some(200.),
+      tolerance: None,
+      threshold: Some(0.3),
+      window: Some(WindowName::Hann),
+      min_duration: Some(0.5),
+      max_duration: None,
+      onset_ms: None,
+      silence_dbfs: None,
+      active: None,
+    });
+    assert_eq!(config.block_size(8000.), 80);
+
+    // One second of both tones: the beep is seen after one short block, the CTCSS tone after
+    // its minimum duration.
+    let signal: Vec<f32> = (0..8000)
+      .map(|t| {
+        let t = t as f32 / 8000.;
+        0.5 * (2. * PI * 100. * t).sin() + 0.5 * (2. * PI * 3000. * t).sin()
+      })
+      .collect();
+    let mut detectors = config.detectors(8000., 0.);
+    let mut events = Vec::new();
+    for (i, block) in signal.chunks(80).enumerate() {
+      let t = Time { sample: 80 * i as u64, sample_rate: 8000. };
+      for d in &mut detectors {
+        events.extend(d.process_block(block, t).into_iter().map(|e| (i, e.kind, e.label)));
+      }
+    }
+    let (block, kind, label) = &events[0];
+    assert_eq!((*block, label.as_deref()), (0, Some("beep")));
+    assert!(matches!(kind, EventKind::ToneOn { freq, .. } if *freq == 3000.));
+    assert!(matches!(&events[1], (59, EventKind::ToneOn { freq, .. }, None) if *freq == 100.));
+  }
+
+  #[test]
+  fn tolerance_replaces_the_block_size() {
+    let config: Config = serde_json::from_str(
+      r#"{ "tolerance": 15, "tones": [{ "freq": 1000 }, { "freq": 2000, "block_ms": 5 }] }"#,
+    )
+    .unwrap();
+    let first = config.tones[0].settings.or(&config.defaults);
+    let second = config.tones[1].settings.or(&config.defaults);
+    assert_eq!((first.tolerance, first.block_ms), (Some(15.), None));
+    assert_eq!((second.tolerance, second.block_ms), (None, Some(5.)));
+
+    // 1012 Hz is inside the band, 1100 Hz well outside it.
+    let mut detectors = config.detectors(8000., 0.);
+    let tone = |freq: f32| -> Vec<f32> {
+      (0..4000).map(|t| (2. * PI * freq * t as f32 / 8000.).sin()).collect()
+    };
+    let t = Time { sample: 0, sample_rate: 8000. };
+    let on = detectors[0].process_block(&tone(1012.), t);
+    assert!(matches!(on[0].kind, EventKind::ToneOn { .. }), "{:?}", on);
+    let off = detectors[0].process_block(&tone(1100.), t.offset(4000));
+    assert!(matches!(off[0].kind, EventKind::ToneOff { .. }), "{:?}", off);
+  }
+
+  #[test]
+  fn reports_tones_left_on() {
+    let config: Config =
+      serde_json::from_str(r#"{ "tones": [{ "freq": 1000, "max_duration": 1 }] }"#).unwrap();
+    let mut detectors = config.detectors(8000., 0.);
+    let block: Vec<f32> = (0..160).map(|t| (2. * PI * 1000. * t as f32 / 8000.).sin()).collect();
+    let t = Time { sample: 0, sample_rate: 8000. };
+    let events: Vec<EventKind> = (0..100)
+      .flat_map(|i| detectors[0].process_block(&block, t.offset(160 * i)))
+      .map(|e| e.kind)
+      .collect();
+    assert_eq!(events.len(), 2);
+    assert!(matches!(events[1], EventKind::StuckTone { duration, .. } if duration > 1.));
+  }
+
+  #[test]
+  fn quiet_blocks_hold_no_tones() {
+    let config: Config = serde_json::from_str(
+      r#"{
+        "silence_dbfs": -60,
+        "tones": [{ "freq": 1000 }, { "freq": 1000, "silence_dbfs": -70 }]
+      }"#,
+    )
+    .unwrap();
+    // A hum at -66 dBFS is silence to the first tone and a tone to the second.
+    let hum: Vec<f32> =
+      (0..160).map(|t| 5e-4 * (2. * PI * 1000. * t as f32 / 8000.).sin()).collect();
+    let t = Time { sample: 0, sample_rate: 8000. };
+    let mut detectors = config.detectors(8000., 0.);
+    assert!(detectors[0].process_block(&hum, t).is_empty());
+    assert_eq!(detectors[1].process_block(&hum, t).len(), 1);
+  }
+
+  #[test]
+  fn rejects_bad_values() {
+    let config = |json: &str| serde_json::from_str::<Config>(json).unwrap().validate();
+    assert!(config(r#"{ "tones": [{ "freq": 1000, "block_ms": 0 }] }"#).is_err());
+    assert!(config(r#"{ "tones": [{ "freq": -5 }] }"#).is_err());
+    assert!(config(r#"{ "min_duration": 2, "tones": [{ "freq": 1000, "max_duration": 1 }] }"#)
+      .is_err());
+    assert!(config(r#"{ "tones": [{ "freq": 1000, "block_ms": 20, "tolerance": 15 }] }"#).is_err());
+    assert!(config(r#"{ "tones": [{ "freq": 1000, "active": "8-22" }] }"#).is_err());
+    assert!(config(r#"{ "rules": [{ "present": [700, 900], "required": 3 }] }"#).is_err());
+    assert!(config(r#"{ "rules": [{ "present": [700], "onset_ms": 2 }] }"#).is_err());
+    assert!(config(r#"{ "tones": [] }"#).is_ok());
+  }
+}
@@ -0,0 +1,316 @@
+//! Offline analysis of recordings, with checkpoints so a long scan can be resumed.
+//!
+//! Files are streamed in chunks of the caller's choosing, so memory use does not grow with the
+//! file. WAV files are read through their header, headerless PCM with a `RawSpec`.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// What a file is read through: buffered reads, or a memory map.
+trait Input: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> Input for T {}
+
+/// Sample encoding of headerless PCM, always little-endian.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+  I16,
+  I32,
+  F32,
+}
+
+impl Encoding {
+  fn bytes(self) -> usize {
+    match self {
+      Encoding::I16 => 2,
+      Encoding::I32 | Encoding::F32 => 4,
+    }
+  }
+
+  fn decode(self, bytes: &[u8]) -> f32 {
+    match self {
+      Encoding::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.,
+      Encoding::I32 => {
+        i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 2147483648.
+      }
+      Encoding::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    }
+  }
+}
+
+/// Layout of a headerless PCM file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RawSpec {
+  pub encoding: Encoding,
+  pub sample_rate: u32,
+  pub channels: usize,
+}
+
+impl RawSpec {
+  /// Parses `encoding:rate[:channels]`, e.g. `s16le:8000` or `f32le:48000:2`.
+  pub fn parse(spec: &str) -> Option<Self> {
+    let mut parts = spec.split(':');
+    let encoding = match parts.next()? {
+      "s16le" => Encoding::I16,
+      "s32le" => Encoding::I32,
+      "f32le" => Encoding::F32,
+      _ => return None,
+    };
+    let sample_rate = parts.next()?.parse().ok()?;
+    let channels = parts.next().map_or(Some(1), |c| c.parse().ok())?;
+    if parts.next().is_some() || channels == 0 {
+      return None;
+    }
+    Some(RawSpec { encoding, sample_rate, channels })
+  }
+}
+
+enum Reader {
+  Wav(hound::WavReader<Box<dyn Input>>),
+  Raw { input: Box<dyn Input>, encoding: Encoding, bytes: Vec<u8> },
+}
+
+pub struct FileSource {
+  reader: Reader,
+  sample_rate: f32,
+  channels: usize,
+  frames: u64,
+}
+
+impl FileSource {
+  /// Opens a WAV file, or headerless PCM laid out as `raw`. With `mmap` the file is mapped
+  /// instead of read, which saves copies on very large files.
+  pub fn open(
+    path: impl AsRef<Path>,
+    raw: Option<RawSpec>,
+    mmap: bool,
+  ) -> Result<Self, anyhow::Error> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let input: Box<dyn Input> = if mmap { map(file)? } else { Box::new(BufReader::new(file)) };
+    Ok(match raw {
+      Some(spec) => FileSource {
+        reader: Reader::Raw { input, encoding: spec.encoding, bytes: Vec::new() },
+        sample_rate: spec.sample_rate as f32,
+        channels: spec.channels,
+        frames: len / (spec.channels * spec.encoding.bytes()) as u64,
+      },
+      None => {
+        let reader = hound::WavReader::new(input)?;
+        let (spec, frames) = (reader.spec(), reader.duration() as u64);
+        FileSource {
+          reader: Reader::Wav(reader),
+          sample_rate: spec.sample_rate as f32,
+          channels: spec.channels as usize,
+          frames,
+        }
+      }
+    })
+  }
+
+  pub fn sample_rate(&self) -> f32 {
+    self.sample_rate
+  }
+
+  pub fn channels(&self) -> usize {
+    self.channels
+  }
+
+  /// Length of the file in frames.
+  pub fn frames(&self) -> u64 {
+    self.frames
+  }
+
+  /// Moves to the `frame`th frame.
+  pub fn seek(&mut self, frame: u64) -> Result<(), anyhow::Error> {
+    match &mut self.reader {
+      Reader::Wav(reader) => reader.seek(frame as u32)?,
+      Reader::Raw { input, encoding, .. } => {
+        let offset = frame * (self.channels * encoding.bytes()) as u64;
+        input.seek(SeekFrom::Start(offset))?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Appends up to `frames` interleaved frames in [-1, 1] to `out`, returning how many were
+  /// read; 0 at the end of the file.
+  pub fn read(&mut self, frames: usize, out: &mut Vec<f32>) -> Result<usize, anyhow::Error> {
+    let count = frames * self.channels;
+    let start = out.len();
+    match &mut self.reader {
+      Reader::Wav(reader) => {
+        let spec = reader.spec();
+        match spec.sample_format {
+          hound::SampleFormat::Float => {
+            for sample in reader.samples::<f32>().take(count) {
+              out.push(sample?);
+            }
+          }
+          hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            for sample in reader.samples::<i32>().take(count) {
+              out.push(sample? as f32 / scale);
+            }
+          }
+        }
+      }
+      Reader::Raw { input, encoding, bytes } => {
+        let size = encoding.bytes();
+        bytes.resize(count * size, 0);
+        let mut filled = 0;
+        while filled < bytes.len() {
+          match input.read(&mut bytes[filled..])? {
+            0 => break,
+            n => filled += n,
+          }
+        }
+        // A trailing partial frame is dropped.
+        let whole = filled / (size * self.channels) * size * self.channels;
+        out.extend(bytes[..whole].chunks(size).map(|b| encoding.decode(b)));
+      }
+    }
+    Ok((out.len() - start) / self.channels)
+  }
+}
+
+#[cfg(feature = "mmap")]
+fn map(file: File) -> Result<Box<dyn Input>, anyhow::Error> {
+  // SAFETY: the map is read-only; a recording truncated by another process while it is being
+  // analyzed can still fault, as with any mmap reader.
+  let map = unsafe { memmap2::Mmap::map(&file)? };
+  Ok(Box::new(std::io::Cursor::new(map)))
+}
+
+#[cfg(not(feature = "mmap"))]
+fn map(_: File) -> Result<Box<dyn Input>, anyhow::Error> {
+  anyhow::bail!("--mmap needs the binary built with `--features mmap`")
+}
+
+/// Parses a position in a recording: seconds (`750.5`), `MM:SS` or `HH:MM:SS[.frac]`.
+pub fn parse_time(time: &str) -> Option<f64> {
+  let mut seconds = 0.;
+  let parts: Vec<&str> = time.split(':').collect();
+  if parts.len() > 3 {
+    return None;
+  }
+  for (i, part) in parts.iter().enumerate() {
+    let value: f64 = part.parse().ok()?;
+    let last = i + 1 == parts.len();
+    if value < 0. || (!last && value.fract() != 0.) || (i > 0 && value >= 60.) {
+      return None;
+    }
+    seconds = seconds * 60. + value;
+  }
+  Some(seconds)
+}
+
+/// How far a scan of `file` got. Detector state is not stored: on resume it is rebuilt by
+/// analyzing a stretch of audio before `frame` with the monitor quiet, so nothing from before
+/// the checkpoint is reported, counted, captured or recorded again.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+  pub file: String,
+  /// Frames analyzed and reported so far.
+  pub frame: u64,
+}
+
+impl Checkpoint {
+  pub fn load(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+  }
+
+  /// Writes the checkpoint through a temporary file, so an interruption never leaves a
+  /// truncated one behind.
+  pub fn save(&self, path: impl AsRef<Path>) -> Result<(), anyhow::Error> {
+    let path = path.as_ref();
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, serde_json::to_string(self)?)?;
+    std::fs::rename(tmp, path)?;
+    Ok(())
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reads_and_seeks_int_wavs() {
+    let path = std::env::temp_dir().join(format!("goertzelrs-file-{}.wav", std::process::id()));
+    let spec = hound::WavSpec {
+      channels: 2,
+      sample_rate: 8000,
+      bits_per_sample: 16,
+      sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    for i in 0..100i16 {
+      writer.write_sample(i * 100).unwrap();
+      writer.write_sample(-i * 100).unwrap();
+    }
+    writer.finalize().unwrap();
+
+    let mut source = FileSource::open(&path, None, false).unwrap();
+    assert_eq!((source.sample_rate(), source.channels(), source.frames()), (8000., 2, 100));
+    let mut samples = Vec::new();
+    assert_eq!(source.read(60, &mut samples).unwrap(), 60);
+    assert_eq!(source.read(60, &mut samples).unwrap(), 40);
+    assert_eq!(samples[3], -100. / 32768.);
+    source.seek(50).unwrap();
+    samples.clear();
+    source.read(1, &mut samples).unwrap();
+    assert_eq!(samples, [5000. / 32768., -5000. / 32768.]);
+
+    let checkpoint = path.with_extension("json");
+    let saved = Checkpoint { file: "night.wav".into(), frame: 480_000 };
+    saved.save(&checkpoint).unwrap();
+    assert_eq!(Checkpoint::load(&checkpoint).unwrap(), saved);
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(checkpoint).unwrap();
+  }
+
+  #[test]
+  fn parses_positions() {
+    assert_eq!(parse_time("750.5"), Some(750.5));
+    assert_eq!(parse_time("12:30"), Some(750.));
+    assert_eq!(parse_time("00:12:30"), Some(750.));
+    assert_eq!(parse_time("1:00:00.25"), Some(3600.25));
+    assert_eq!(parse_time("00:75"), None);
+    assert_eq!(parse_time("1.5:00"), None);
+    assert_eq!(parse_time("a"), None);
+  }
+
+  #[test]
+  fn reads_raw_pcm_in_chunks() {
+    assert_eq!(RawSpec::parse("s16le:8000"), Some(RawSpec {
+      encoding: Encoding::I16,
+      sample_rate: 8000,
+      channels: 1,
+    }));
+    assert_eq!(RawSpec::parse("f32le:48000:2").map(|s| s.channels), Some(2));
+    assert_eq!(RawSpec::parse("u8:8000"), None);
+
+    let path = std::env::temp_dir().join(format!("goertzelrs-raw-{}.pcm", std::process::id()));
+    let pcm: Vec<u8> = (0..1001i16).flat_map(|i| (i * 10).to_le_bytes()).collect();
+    std::fs::write(&path, pcm).unwrap();
+    let spec = RawSpec::parse("s16le:8000").unwrap();
+    let mut source = FileSource::open(&path, Some(spec), false).unwrap();
+    assert_eq!(source.frames(), 1001);
+    let mut samples = Vec::new();
+    let mut chunks = 0;
+    while source.read(100, &mut samples).unwrap() > 0 {
+      chunks += 1;
+    }
+    assert_eq!((chunks, samples.len()), (11, 1001));
+    source.seek(1000).unwrap();
+    samples.clear();
+    source.read(100, &mut samples).unwrap();
+    assert_eq!(samples, [10000. / 32768.]);
+    std::fs::remove_file(path).unwrap();
+  }
+}
@@ -0,0 +1,261 @@
+//! Desktop frontend: pick an input device and a frequency, watch the power and the detections.
+//!
+//! Build with `cargo run -p goertzel-cli --features gui --bin goertzelrs-gui`. Devices are
+//! opened as `goertzelrs` opens them, with `--input-rate`, `--input-channels` and
+//! `--input-format` honoured where the device allows and made up for where it does not.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use crossbeam_queue::ArrayQueue;
+use eframe::egui;
+use goertzel_core::detector::{Detector, Time, ToneDetector};
+use goertzel_core::Goertzel;
+
+// Shared with `goertzelrs`, which also checks `--buffer-size` against what the device allows.
+#[allow(dead_code)]
+mod negotiate;
+
+/// Analysis block length.
+const BLOCK_MS: f32 = 20.0;
+/// Detections kept in the log.
+const LOG_LINES: usize = 500;
+
+/// What the UI and the audio callback share. Everything is atomic or lock-free so the callback
+/// never waits on the UI.
+struct Shared {
+    freq: AtomicU32,
+    threshold: AtomicU32,
+    /// Normalized power of the last block.
+    power: AtomicU32,
+    events: ArrayQueue<String>,
+}
+
+impl Shared {
+    fn load(value: &AtomicU32) -> f32 {
+        f32::from_bits(value.load(Ordering::Relaxed))
+    }
+
+    fn store(value: &AtomicU32, x: f32) {
+        value.store(x.to_bits(), Ordering::Relaxed);
+    }
+}
+
+struct App {
+    devices: Vec<(String, cpal::Device)>,
+    request: negotiate::Request,
+    selected: usize,
+    stream: Option<cpal::Stream>,
+    error: Option<String>,
+    freq: f32,
+    threshold: f32,
+    shared: Arc<Shared>,
+    log: Vec<String>,
+}
+
+impl App {
+    fn new(request: negotiate::Request) -> Self {
+        let host = cpal::default_host();
+        let devices = host
+            .input_devices()
+            .map(|devices| {
+                devices
+                    .filter_map(|d| Some((d.name().ok()?, d)))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let default = host.default_input_device().and_then(|d| d.name().ok());
+        let selected = devices
+            .iter()
+            .position(|(name, _)| Some(name) == default.as_ref())
+            .unwrap_or(0);
+        let (freq, threshold) = (440.0, 0.5);
+        let shared = Arc::new(Shared {
+            freq: AtomicU32::new(f32::to_bits(freq)),
+            threshold: AtomicU32::new(f32::to_bits(threshold)),
+            power: AtomicU32::new(0),
+            events: ArrayQueue::new(64),
+        });
+        App {
+            devices,
+            request,
+            selected,
+            stream: None,
+            error: None,
+            freq,
+            threshold,
+            shared,
+            log: Vec::new(),
+        }
+    }
+
+    fn start(&mut self) {
+        let result = match self.devices.get(self.selected) {
+            Some((_, device)) => build_input(device, self.request, self.shared.clone()),
+            None => Err(anyhow::anyhow!("no input device")),
+        };
+        match result.and_then(|stream| {
+            stream.play()?;
+            Ok(stream)
+        }) {
+            Ok(stream) => {
+                self.stream = Some(stream);
+                self.error = None;
+            }
+            Err(err) => self.error = Some(err.to_string()),
+        }
+    }
+
+    fn stop(&mut self) {
+        self.stream = None;
+        Shared::store(&self.shared.power, 0.0);
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        while let Some(line) = self.shared.events.pop() {
+            self.log.push(line);
+        }
+        if self.log.len() > LOG_LINES {
+            self.log.drain(..self.log.len() - LOG_LINES);
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let running = self.stream.is_some();
+                ui.add_enabled_ui(!running, |ui| {
+                    let current = self
+                        .devices
+                        .get(self.selected)
+                        .map_or("(none)", |(name, _)| name.as_str());
+                    egui::ComboBox::from_label("Device")
+                        .selected_text(current)
+                        .show_ui(ui, |ui| {
+                            for (i, (name, _)) in self.devices.iter().enumerate() {
+                                ui.selectable_value(&mut self.selected, i, name);
+                            }
+                        });
+                });
+                if running {
+                    if ui.button("Stop").clicked() {
+                        self.stop();
+                    }
+                } else if ui.button("Start").clicked() {
+                    self.start();
+                }
+            });
+            if let Some(err) = &self.error {
+                ui.colored_label(egui::Color32::RED, err);
+            }
+
+            let freq = egui::Slider::new(&mut self.freq, 20.0..=20_000.0)
+                .logarithmic(true)
+                .suffix(" Hz")
+                .text("Frequency");
+            if ui.add(freq).changed() {
+                Shared::store(&self.shared.freq, self.freq);
+            }
+            let threshold = egui::Slider::new(&mut self.threshold, 0.0..=1.0).text("Threshold");
+            if ui.add(threshold).changed() {
+                Shared::store(&self.shared.threshold, self.threshold);
+            }
+
+            let power = Shared::load(&self.shared.power);
+            let detected = power >= self.threshold;
+            let meter = egui::ProgressBar::new(power.clamp(0.0, 1.0))
+                .text(format!("{:.3} ({:.1} dB)", power, 10.0 * power.max(1e-9).log10()))
+                .fill(if detected {
+                    egui::Color32::DARK_GREEN
+                } else {
+                    egui::Color32::DARK_GRAY
+                });
+            ui.add(meter);
+
+            ui.separator();
+            ui.label("Detections");
+            egui::ScrollArea::vertical()
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for line in &self.log {
+                        ui.monospace(line);
+                    }
+                });
+        });
+        ctx.request_repaint_after(Duration::from_millis(33));
+    }
+}
+
+/// Opens `device` and measures its first channel in `BLOCK_MS` blocks, following the frequency
+/// and threshold in `shared`.
+fn build_input(
+    device: &cpal::Device,
+    request: negotiate::Request,
+    shared: Arc<Shared>,
+) -> Result<cpal::Stream, anyhow::Error> {
+    let negotiated = negotiate::Negotiated::new(device, request)?;
+    let sample_rate = negotiated.sample_rate as f32;
+    let channels = negotiated.channels as usize;
+    let block_size = (sample_rate * BLOCK_MS / 1000.0) as usize;
+
+    let mut block = Vec::with_capacity(block_size);
+    let mut sample = 0;
+    let mut settings = (f32::NAN, f32::NAN);
+    let mut filter = Goertzel::new(1000.0, sample_rate);
+    let mut detector = ToneDetector::new(1000.0, sample_rate, 1.0);
+    let input_data_fn = move |data: &[f32], _: &cpal::InputCallbackInfo| {
+        for frame in data.chunks(channels) {
+            block.push(frame[0]);
+            if block.len() < block_size {
+                continue;
+            }
+            let now = (Shared::load(&shared.freq), Shared::load(&shared.threshold));
+            if now != settings {
+                settings = now;
+                filter = Goertzel::new(now.0, sample_rate);
+                detector = ToneDetector::new(now.0, sample_rate, now.1);
+            }
+            Shared::store(&shared.power, filter.block_power(&block));
+            let t = Time { sample, sample_rate };
+            for event in detector.process_block(&block, t) {
+                shared
+                    .events
+                    .force_push(format!("{:9.2}s  {}", event.time.seconds(), event.kind));
+            }
+            sample += block.len() as u64;
+            block.clear();
+        }
+    };
+    let stream = negotiated.build(device, input_data_fn, |err| {
+        eprintln!("an error occurred on stream: {}", err)
+    })?;
+    Ok(stream)
+}
+
+/// The stream config asked for on the command line.
+fn input_request(args: &[String]) -> Result<negotiate::Request, anyhow::Error> {
+    let value = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1));
+    let format = match value("--input-format") {
+        Some(spec) => Some(negotiate::parse_format(spec).ok_or_else(|| {
+            anyhow::anyhow!("bad --input-format `{}`, use f32, i16 or u16", spec)
+        })?),
+        None => None,
+    };
+    Ok(negotiate::Request {
+        sample_rate: value("--input-rate").map(|v| v.parse()).transpose()?,
+        channels: value("--input-channels").map(|v| v.parse()).transpose()?,
+        format,
+    })
+}
+
+fn main() -> Result<(), anyhow::Error> {
+    let request = input_request(&std::env::args().collect::<Vec<_>>())?;
+    eframe::run_native(
+        "goertzelrs",
+        eframe::NativeOptions::default(),
+        Box::new(move |_| Ok(Box::new(App::new(request)))),
+    )
+    .map_err(|e| anyhow::anyhow!("{}", e))
+}
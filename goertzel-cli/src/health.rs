@@ -0,0 +1,265 @@
+//! Transport health counters, so missed detections can be told apart from lost input, and the
+//! drift of a tracked pilot tone.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+use goertzel_core::detector::{Detector, Event, Time};
+use goertzel_core::estimate::{DriftSummary, DriftTracker};
+
+#[derive(Debug)]
+pub struct Health {
+  /// Errors reported by the input streams; ALSA reports overruns this way.
+  pub overruns: AtomicU64,
+  /// Callbacks whose capture time jumped further than the audio they delivered.
+  pub callback_gaps: AtomicU64,
+  /// Chunks a `--workers` thread was too far behind to take.
+  pub late_blocks: AtomicU64,
+}
+
+/// Shared by every input stream.
+pub static HEALTH: Health = Health {
+  overruns: AtomicU64::new(0),
+  callback_gaps: AtomicU64::new(0),
+  late_blocks: AtomicU64::new(0),
+};
+
+impl Health {
+  pub fn count_overrun(&self) {
+    self.overruns.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub fn count_gap(&self) {
+    self.callback_gaps.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub fn count_late_block(&self) {
+    self.late_blocks.fetch_add(1, Ordering::Relaxed);
+  }
+}
+
+/// Whether live monitoring should end, and the wait for it.
+static STOP: (Mutex<bool>, Condvar) = (Mutex::new(false), Condvar::new());
+
+/// Ends live monitoring: on Ctrl-C, SIGTERM or an input that cannot go on.
+pub fn stop() {
+  *STOP.0.lock().unwrap_or_else(|e| e.into_inner()) = true;
+  STOP.1.notify_all();
+}
+
+/// Whether [`stop`] was called.
+pub fn stopped() -> bool {
+  *STOP.0.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Blocks until [`stop`] is called.
+pub fn wait_for_stop() {
+  let mut stopped = STOP.0.lock().unwrap_or_else(|e| e.into_inner());
+  while !*stopped {
+    stopped = STOP.1.wait(stopped).unwrap_or_else(|e| e.into_inner());
+  }
+}
+
+/// Nominal frequency and offsets of the tone followed by `--drift`, for the status line.
+pub static DRIFT: Mutex<Option<(f32, DriftSummary)>> = Mutex::new(None);
+
+/// A drift tracker publishing its summary to [`DRIFT`] after every block. It runs in the audio
+/// callback, so it never waits for the lock: while the status line is reading, the update is
+/// skipped and the next block's goes in instead.
+#[derive(Debug)]
+pub struct PublishedDrift(pub DriftTracker);
+
+impl Detector for PublishedDrift {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let events = self.0.process_block(block, t);
+    if let Ok(mut drift) = DRIFT.try_lock() {
+      *drift = Some((self.0.nominal(), self.0.summary()));
+    }
+    events
+  }
+}
+
+/// The drift part of the status line, empty until the tone has been measured: the last, lowest,
+/// highest and mean offsets from nominal in Hz.
+pub fn drift_status() -> String {
+  // Copied out, so the lock is not held while formatting.
+  let drift = *DRIFT.lock().unwrap_or_else(|e| e.into_inner());
+  match drift {
+    Some((nominal, s)) if s.estimates > 0 => format!(
+      " drift {}Hz last {:+.3} min {:+.3} max {:+.3} mean {:+.3}",
+      nominal, s.last, s.min, s.max, s.mean
+    ),
+    _ => String::new(),
+  }
+}
+
+/// Tells a missing stretch of input from the capture timestamps of consecutive callbacks.
+#[derive(Debug)]
+pub struct GapDetector {
+  sample_rate: f64,
+  /// Capture time of the previous callback and the frames it delivered.
+  last: Option<(f64, usize)>,
+}
+
+impl GapDetector {
+  pub fn new(sample_rate: f32) -> Self {
+    Self { sample_rate: sample_rate as f64, last: None }
+  }
+
+  /// Feeds the capture time (seconds, any origin) of a callback delivering `frames`. Returns
+  /// whether input went missing since the previous callback: more than half a buffer, and more
+  /// than 5 ms, beyond what that callback delivered.
+  pub fn observe(&mut self, at: f64, frames: usize) -> bool {
+    let gap = match self.last {
+      Some((prev, prev_frames)) => {
+        let expected = prev_frames as f64 / self.sample_rate;
+        at - prev > expected + (expected / 2.).max(0.005)
+      }
+      None => false,
+    };
+    self.last = Some((at, frames));
+    gap
+  }
+}
+
+/// Seconds of input measured before the rate is judged, so start-up buffering averages out.
+const RATE_WINDOW: f64 = 10.;
+
+/// Tells a device running at another rate than it was configured for, which mistunes every
+/// detector, from the frames its callbacks deliver against the wall clock.
+#[derive(Debug)]
+pub struct RateCheck {
+  nominal: f64,
+  /// Largest accepted offset, as a fraction of the nominal rate.
+  tolerance: f64,
+  /// Wall-clock time of the first callback.
+  start: Option<f64>,
+  /// Frames delivered since the first callback.
+  frames: u64,
+  warned: bool,
+}
+
+impl RateCheck {
+  pub fn new(sample_rate: f32, tolerance: f32) -> Self {
+    Self {
+      nominal: sample_rate as f64,
+      tolerance: tolerance as f64,
+      start: None,
+      frames: 0,
+      warned: false,
+    }
+  }
+
+  /// Feeds the wall-clock time (seconds, any origin) of a callback delivering `frames`. Returns
+  /// the measured rate the first time it is off by more than the tolerance.
+  pub fn observe(&mut self, now: f64, frames: usize) -> Option<f64> {
+    let Some(start) = self.start else {
+      // The first callback's frames were captured before it ran.
+      self.start = Some(now);
+      return None;
+    };
+    self.frames += frames as u64;
+    let elapsed = now - start;
+    if self.warned || elapsed < RATE_WINDOW {
+      return None;
+    }
+    let rate = self.frames as f64 / elapsed;
+    self.warned = (rate / self.nominal - 1.).abs() > self.tolerance;
+    self.warned.then_some(rate)
+  }
+}
+
+/// Where a device's audio callback leaves the rate [`RateCheck`] measured, for the status thread
+/// to print: the callback must not block on stderr.
+#[derive(Debug)]
+pub struct RateWarning {
+  name: String,
+  configured: f32,
+  /// Bits of the measured rate, 0 while there is none to print.
+  actual: AtomicU64,
+}
+
+/// Every input's [`RateWarning`]; only taken outside the audio callbacks.
+static RATE_WARNINGS: Mutex<Vec<Arc<RateWarning>>> = Mutex::new(Vec::new());
+
+impl RateWarning {
+  /// A warning about the input `name`, configured for `configured` Hz, that
+  /// [`take_rate_warnings`] reads.
+  pub fn register(name: String, configured: f32) -> Arc<Self> {
+    let warning = Arc::new(Self { name, configured, actual: AtomicU64::new(0) });
+    RATE_WARNINGS.lock().unwrap_or_else(|e| e.into_inner()).push(warning.clone());
+    warning
+  }
+
+  /// Records the rate the input was measured at.
+  pub fn raise(&self, actual: f64) {
+    self.actual.store(actual.to_bits(), Ordering::Relaxed);
+  }
+}
+
+/// The messages for the rate warnings raised since the last call.
+pub fn take_rate_warnings() -> Vec<String> {
+  let warnings = RATE_WARNINGS.lock().unwrap_or_else(|e| e.into_inner());
+  let raised = warnings.iter().filter_map(|w| match w.actual.swap(0, Ordering::Relaxed) {
+    0 => None,
+    bits => Some((w, f64::from_bits(bits))),
+  });
+  raised
+    .map(|(w, actual)| {
+      format!(
+        "warning: {} delivers {:.0} Hz, not the {} Hz configured; tones are heard {:+.2}% off",
+        w.name,
+        actual,
+        w.configured,
+        (w.configured as f64 / actual - 1.) * 100.
+      )
+    })
+    .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn detects_missing_buffer() {
+    let mut gaps = GapDetector::new(48000.);
+    // 480-frame (10 ms) buffers, with jitter, then one lost buffer.
+    let times = [0., 0.011, 0.0195, 0.030, 0.050];
+    let found: Vec<bool> = times.iter().map(|&t| gaps.observe(t, 480)).collect();
+    assert_eq!(found, vec![false, false, false, false, true]);
+  }
+
+  #[test]
+  fn waits_until_stopped() {
+    let stopper = std::thread::spawn(|| {
+      std::thread::sleep(std::time::Duration::from_millis(50));
+      stop();
+    });
+    wait_for_stop();
+    stopper.join().unwrap();
+    // Stays stopped.
+    wait_for_stop();
+  }
+
+  #[test]
+  fn warns_once_of_a_device_off_rate() {
+    // Configured for 48 kHz, delivering 44.1 kHz in 10 ms callbacks.
+    let mut check = RateCheck::new(48000., 0.01);
+    let rates: Vec<f64> = (0..2000).filter_map(|i| check.observe(i as f64 * 0.01, 441)).collect();
+    assert_eq!(rates.len(), 1);
+    assert!((rates[0] - 44100.).abs() < 1., "{}", rates[0]);
+    let mut check = RateCheck::new(48000., 0.01);
+    assert!((0..2000).all(|i| check.observe(i as f64 * 0.01, 479).is_none()));
+  }
+
+  #[test]
+  fn rate_warnings_are_taken_once() {
+    let warning = RateWarning::register("hw:1".into(), 48000.);
+    warning.raise(44100.);
+    let taken = take_rate_warnings();
+    assert!(taken.iter().any(|w| w.starts_with("warning: hw:1 delivers 44100 Hz")), "{:?}", taken);
+    assert!(take_rate_warnings().iter().all(|w| !w.contains("hw:1")));
+  }
+}
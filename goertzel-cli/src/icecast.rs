@@ -0,0 +1,105 @@
+//! Icecast/HTTP audio stream input.
+
+use std::fmt;
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder, DecoderOptions};
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::{MediaSourceStream, ReadOnlySource};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+#[derive(Debug)]
+pub enum Error {
+  Http(Box<ureq::Error>),
+  Decode(DecodeError),
+  NoAudioTrack,
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::Http(e) => write!(f, "http: {}", e),
+      Error::Decode(e) => write!(f, "decode: {}", e),
+      Error::NoAudioTrack => write!(f, "stream has no decodable audio track"),
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ureq::Error> for Error {
+  fn from(e: ureq::Error) -> Self {
+    Error::Http(Box::new(e))
+  }
+}
+
+impl From<DecodeError> for Error {
+  fn from(e: DecodeError) -> Self {
+    Error::Decode(e)
+  }
+}
+
+/// Pulls an MP3 or Ogg/Vorbis stream over HTTP and decodes it to mono f32.
+pub struct IcecastSource {
+  format: Box<dyn FormatReader>,
+  decoder: Box<dyn Decoder>,
+  track_id: u32,
+  sample_rate: u32,
+}
+
+impl IcecastSource {
+  pub fn connect(url: &str) -> Result<Self, Error> {
+    let resp = ureq::get(url).call()?;
+    let mut hint = Hint::new();
+    match resp.content_type() {
+      "audio/mpeg" => { hint.with_extension("mp3"); }
+      "application/ogg" | "audio/ogg" => { hint.with_extension("ogg"); }
+      _ => {}
+    }
+    let mss = MediaSourceStream::new(Box::new(ReadOnlySource::new(resp.into_reader())), Default::default());
+    let probed = symphonia::default::get_probe()
+      .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())?;
+    let format = probed.format;
+    let track = format.default_track().ok_or(Error::NoAudioTrack)?;
+    let sample_rate = track.codec_params.sample_rate.ok_or(Error::NoAudioTrack)?;
+    let track_id = track.id;
+    let decoder = symphonia::default::get_codecs()
+      .make(&track.codec_params, &DecoderOptions::default())?;
+    Ok(Self {
+      format,
+      decoder,
+      track_id,
+      sample_rate,
+    })
+  }
+
+  pub fn sample_rate(&self) -> f32 {
+    self.sample_rate as f32
+  }
+
+  /// Decodes the next packet of the stream, appending its samples (downmixed to mono) to `out`.
+  pub fn read(&mut self, out: &mut Vec<f32>) -> Result<(), Error> {
+    loop {
+      let packet = self.format.next_packet()?;
+      if packet.track_id() != self.track_id {
+        continue;
+      }
+      let decoded = match self.decoder.decode(&packet) {
+        Ok(decoded) => decoded,
+        // A corrupt frame in a live stream is not fatal, skip to the next one.
+        Err(DecodeError::DecodeError(_)) => continue,
+        Err(e) => return Err(e.into()),
+      };
+      let spec = *decoded.spec();
+      let channels = spec.channels.count().max(1);
+      let mut buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+      buf.copy_interleaved_ref(decoded);
+      for frame in buf.samples().chunks(channels) {
+        out.push(frame.iter().sum::<f32>() / channels as f32);
+      }
+      return Ok(());
+    }
+  }
+}
@@ -0,0 +1,162 @@
+//! `--rate-limit` and `--dedup`: what a sink is spared, so that a broker or a webhook run by
+//! `--exec` is not hammered when a signal hovers around a threshold and its events chatter.
+//!
+//! Repeats are told by the event's time in the stream, so a file analyzed faster than real time
+//! is deduplicated like live input; the rate, meant for what is on the other end, by the wall
+//! clock.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::results::Repeat;
+
+/// Keys remembered for `--dedup` before the ones too old to matter are forgotten.
+const REMEMBERED: usize = 256;
+
+/// At most `records` records every `seconds`, in bursts of up to `records`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+  pub records: f64,
+  pub seconds: f64,
+}
+
+impl Rate {
+  /// `5/s`, `30/min` or `100/h`.
+  pub fn parse(spec: &str) -> Option<Self> {
+    let (records, per) = spec.split_once('/')?;
+    let records: f64 = records.parse().ok().filter(|&r| r > 0.)?;
+    let seconds = match per {
+      "s" => 1.,
+      "min" => 60.,
+      "h" => 3600.,
+      _ => return None,
+    };
+    Some(Rate { records, seconds })
+  }
+}
+
+/// `500ms` or `2s`, in seconds.
+pub fn parse_duration(spec: &str) -> Option<f64> {
+  let seconds = match spec.strip_suffix("ms") {
+    Some(ms) => ms.parse::<f64>().ok()? / 1000.,
+    None => spec.strip_suffix('s')?.parse().ok()?,
+  };
+  (seconds > 0.).then_some(seconds)
+}
+
+/// The limits of one sink; none unless set.
+#[derive(Debug, Default)]
+pub struct Limit {
+  rate: Option<Rate>,
+  /// Records the rate allows right now, and when they were counted.
+  tokens: f64,
+  counted: Option<Instant>,
+  window: Option<f64>,
+  /// When each event last went through, in stream seconds.
+  passed: HashMap<String, f64>,
+}
+
+impl Limit {
+  pub fn with_rate(mut self, rate: Rate) -> Self {
+    self.rate = Some(rate);
+    self.tokens = rate.records;
+    self
+  }
+
+  /// Holds back an event within `seconds` of the same one having gone through. It goes through
+  /// again once that long has passed, even while it keeps repeating.
+  pub fn with_dedup(mut self, seconds: f64) -> Self {
+    self.window = Some(seconds);
+    self
+  }
+
+  /// Whether a record, which repeats `repeat` if it is an event, may go out at `now`. Only
+  /// an event that goes out holds back its repeats, not one the rate dropped.
+  pub fn admit(&mut self, repeat: Option<&Repeat>, now: Instant) -> bool {
+    let dedup = self.window.zip(repeat);
+    if let Some((window, repeat)) = dedup {
+      if self.passed.get(&repeat.key).is_some_and(|&at| repeat.time - at < window) {
+        return false;
+      }
+    }
+    if !self.take_token(now) {
+      return false;
+    }
+    if let Some((window, repeat)) = dedup {
+      if self.passed.len() >= REMEMBERED {
+        self.passed.retain(|_, at| repeat.time - *at < window);
+      }
+      self.passed.insert(repeat.key.clone(), repeat.time);
+    }
+    true
+  }
+
+  /// Whether the rate allows a record at `now`, counting it if so.
+  fn take_token(&mut self, now: Instant) -> bool {
+    let Some(rate) = self.rate else {
+      return true;
+    };
+    if let Some(counted) = self.counted {
+      let refill = now.duration_since(counted).as_secs_f64() * rate.records / rate.seconds;
+      self.tokens = (self.tokens + refill).min(rate.records);
+    }
+    self.counted = Some(now);
+    if self.tokens < 1. {
+      return false;
+    }
+    self.tokens -= 1.;
+    true
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::Duration;
+
+  fn repeat(key: &str, time: f64) -> Repeat {
+    Repeat { key: key.into(), time }
+  }
+
+  #[test]
+  fn chattering_events_go_through_once_per_window() {
+    let mut limit = Limit::default().with_dedup(2.);
+    let now = Instant::now();
+    let passed: Vec<bool> = (0..10)
+      .map(|i| {
+        let key = if i % 2 == 0 { "tone_on 1000" } else { "tone_off 1000" };
+        limit.admit(Some(&repeat(key, i as f64 * 0.5)), now)
+      })
+      .collect();
+    assert_eq!(passed, [true, true, false, false, true, true, false, false, true, true]);
+    // Lines that are not events are never repeats.
+    assert!(limit.admit(None, now) && limit.admit(None, now));
+  }
+
+  #[test]
+  fn rate_allows_a_burst_then_refills() {
+    let mut limit = Limit::default().with_rate(Rate::parse("2/s").unwrap());
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let passed: Vec<bool> =
+      [0, 0, 0, 400, 500, 600].iter().map(|&ms| limit.admit(None, at(ms))).collect();
+    assert_eq!(passed, [true, true, false, false, true, false]);
+    assert_eq!(Rate::parse("30/min"), Some(Rate { records: 30., seconds: 60. }));
+    assert_eq!((Rate::parse("0/s"), parse_duration("500ms")), (None, Some(0.5)));
+  }
+
+  #[test]
+  fn an_event_the_rate_dropped_is_no_repeat() {
+    let mut limit = Limit::default().with_rate(Rate::parse("1/s").unwrap()).with_dedup(5.);
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    // The digit is dropped by the rate right after the tone, so it still goes out once there
+    // is room, and only then are its repeats held back.
+    let records = [("tone_on", 0.0, 0), ("digit 5", 0.1, 100), ("digit 5", 1.2, 1200)];
+    let mut admit = |(key, time, ms)| limit.admit(Some(&repeat(key, time)), at(ms));
+    let passed: Vec<bool> = records.iter().copied().map(&mut admit).collect();
+    assert_eq!(passed, [true, false, true]);
+    assert!(!admit(("digit 5", 3., 3000)));
+  }
+}
@@ -0,0 +1,1975 @@
+//! Listens for tones on one or more input devices, or in a file, stream or RTP feed, and reports
+//! what it hears as text, CSV or NDJSON records.
+//!
+//! Input devices are opened in the config asked for where they allow it, otherwise in the
+//! nearest one they support, converted on the way in.
+
+extern crate anyhow;
+extern crate cpal;
+extern crate goertzel_core;
+extern crate goertzel_dtmf;
+
+use std::sync::atomic::Ordering;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use goertzel_core::detector::{Detector, Event, EventKind, Reblock, Time, ToneDetector};
+use goertzel_core::envelope::EnvelopeDetector;
+use goertzel_core::episode::Episodes;
+use goertzel_core::estimate::{DriftTolerance, DriftTracker};
+use goertzel_core::evaluate::{self, Sweep};
+use goertzel_core::fm::{self, Deemphasized};
+use goertzel_core::fsk::FSK_BLOCK;
+use goertzel_core::octave::ThirdOctaveAnalyzer;
+use goertzel_core::report::{Aggregate, Decimator, Reporter};
+use goertzel_core::tempo::Tempo;
+use goertzel_core::tonality::FlatnessDetector;
+use goertzel_core::watchdog::Watchdog;
+use goertzel_core::weighting::{LevelMeter, Weighting};
+use goertzel_core::{presets, Goertzel, GoertzelBank, Pipeline, Reading, WarmUp};
+use goertzel_dtmf::DtmfDetector;
+use goertzel_mf::{LineSignals, MfConfig, MfDecoder, R1Decoder, R1Timing, R2Decoder};
+use output::{Format, Origin};
+use limit::Limit;
+use profile::Profile;
+use results::ResultSender;
+
+mod capture;
+#[cfg(feature = "parquet")]
+mod columnar;
+mod config;
+mod file;
+#[cfg(feature = "icecast")]
+mod icecast;
+mod health;
+mod limit;
+mod negotiate;
+mod npy;
+mod output;
+mod pool;
+mod profile;
+mod replay;
+mod results;
+mod rotate;
+mod rtp;
+mod schema;
+mod select;
+mod score;
+#[cfg(feature = "script")]
+mod script;
+#[cfg(feature = "sdr")]
+mod sdr;
+mod sink;
+mod squelch;
+mod syslog;
+mod talkoff;
+
+/// Blocks the detectors keep running after the input falls below `--energy-gate`.
+const ENERGY_GATE_HOLD: usize = 5;
+/// Power share below which `--watch-tone` counts the tone as missing, unless given.
+const WATCH_THRESHOLD: f32 = 0.1;
+/// Audio between two `--checkpoint` saves.
+const CHECKPOINT_SECS: f32 = 60.0;
+/// Audio analyzed silently before the checkpoint on `--resume`, to rebuild detector state.
+const RESUME_PREROLL_SECS: f32 = 5.0;
+/// Intervals between onsets `--tempo` averages.
+const TEMPO_WINDOW: usize = 8;
+/// Offset of a device's sample rate from the configured one warned about, unless given.
+const RATE_TOLERANCE: f32 = 0.01;
+/// Beeps `--appliance-beeps` waits for, and the seconds they must come within.
+const APPLIANCE_CYCLE: (usize, f32) = (3, 3.0);
+
+
+fn main() -> Result<(), anyhow::Error> {
+    run(std::env::args().collect())
+}
+
+fn run(args: Vec<String>) -> Result<(), anyhow::Error> {
+
+    // JSON Schema of the NDJSON records, for validating captured logs: `--print-schema`.
+    if args.iter().any(|a| a == "--print-schema") {
+        println!("{}", serde_json::to_string_pretty(&schema::json_schema())?);
+        return Ok(());
+    }
+
+    // Decode DTMF with a bundled parameter set instead of printing tone power,
+    // e.g. `--profile telephony`.
+    let profile = match flag_value(&args, "--profile")? {
+        Some(name) => Some(
+            Profile::from_name(name).ok_or_else(|| anyhow::anyhow!("unknown profile `{}`", name))?,
+        ),
+        None => None,
+    };
+
+    // False DTMF digits in a directory of speech recordings, with the `--profile` settings
+    // (telephony by default), e.g. `talk-off speech/ --report talkoff.json`.
+    if args.get(1).map(String::as_str) == Some("talk-off") {
+        let dir = args.get(2).ok_or_else(|| anyhow::anyhow!("talk-off needs a directory"))?;
+        let profile = profile.unwrap_or_else(Profile::telephony);
+        let report = talkoff::run(std::path::Path::new(dir), &profile)?;
+        for file in &report.files {
+            println!("{}: {} false digits in {:.1} s", file.path, file.digits.len(), file.seconds);
+        }
+        for skipped in &report.skipped {
+            eprintln!("skipped {}: {}", skipped.path, skipped.error);
+        }
+        println!(
+            "{} false digits in {:.1} s of audio, {:.1} per hour",
+            report.false_digits, report.seconds, report.per_hour
+        );
+        if let Some(path) = flag_value(&args, "--report")? {
+            std::fs::write(path, serde_json::to_string_pretty(&report)?)?;
+        }
+        return Ok(());
+    }
+
+    // Precision, recall and timing error of a detection log against hand-made labels, e.g.
+    // `score --truth labels.csv --detected events.ndjson --tolerance 0.2`; see `score.rs`.
+    if args.get(1).map(String::as_str) == Some("score") {
+        let path = |flag| -> Result<&std::path::Path, anyhow::Error> {
+            let value = flag_value(&args, flag)?;
+            Ok(std::path::Path::new(value.ok_or_else(|| anyhow::anyhow!("score needs {}", flag))?))
+        };
+        let labels = score::load_labels(path("--truth")?)?;
+        let detections = score::load_detections(path("--detected")?)?;
+        let tolerance = flag_value(&args, "--tolerance")?.map(str::parse).transpose()?;
+        let s = score::score(&labels, &detections, tolerance.unwrap_or(score::TOLERANCE));
+        println!("labels {} detections {} matched {}", s.labels, s.detections, s.matched);
+        println!("precision {:.3} recall {:.3}", s.precision(), s.recall());
+        println!("timing error mean {:.3} s max {:.3} s", s.mean_error, s.max_error);
+        return Ok(());
+    }
+
+    // Print the FFT discrepancy of each block instead of the tone power.
+    let verify = args.iter().any(|a| a == "--verify");
+    if verify && !cfg!(feature = "verify") {
+        anyhow::bail!("--verify needs the binary built with `--features verify`");
+    }
+    // Detection rules from a rhai script, e.g. `--script rules.rhai`.
+    let script = flag_value(&args, "--script")?
+        .map(std::fs::read_to_string)
+        .transpose()?;
+    if script.is_some() && !cfg!(feature = "script") {
+        anyhow::bail!("--script needs the binary built with `--features script`");
+    }
+    // Watch for the T3 smoke alarm pattern, e.g. on a Raspberry Pi with a USB microphone.
+    let smoke_alarm = args.iter().any(|a| a == "--smoke-alarm");
+    // Tell when a microwave, washing machine or dishwasher beeps the end of its cycle, at any of
+    // the usual buzzer pitches: `--appliance-beeps`.
+    let appliance_beeps = args.iter().any(|a| a == "--appliance-beeps");
+    // Name the carrier of a 300 baud data modem (Bell 103 or V.21) on a call, to tell modems from
+    // fax and voice: `--modems`.
+    let modems = args.iter().any(|a| a == "--modems");
+    // Name the coins of payphone coin deposit tones in a recording: `--coin-tones`.
+    let coin_tones = args.iter().any(|a| a == "--coin-tones");
+    // Decode legacy inter-register signaling: `--mf r1` (North American MF, KP to ST),
+    // `--mf r2` (both directions of R2 MFC), `--mf ss5` (SS5 register and line signals) or
+    // `--mf sf` (2600 Hz SF supervision).
+    let mf = match flag_value(&args, "--mf")? {
+        Some(system @ ("r1" | "r2" | "ss5" | "sf")) => Some(system.to_string()),
+        Some(other) => anyhow::bail!("unknown --mf `{}`, use r1, r2, ss5 or sf", other),
+        None => None,
+    };
+    // Report the 19 kHz FM stereo pilot and its level, on the demodulated multiplex signal of a
+    // receiver or `--sdr`: `--fm-pilot`. Needs a sample rate of at least 40 kHz.
+    let fm_pilot = args.iter().any(|a| a == "--fm-pilot");
+    // De-emphasize FM audio before the tone detectors, e.g. `--deemphasis 50` (µs, Europe) or
+    // `--deemphasis 75` (the Americas).
+    let deemphasis = match flag_value(&args, "--deemphasis")? {
+        Some("50") => Some(fm::DEEMPHASIS_50US),
+        Some("75") => Some(fm::DEEMPHASIS_75US),
+        Some(other) => anyhow::bail!("bad --deemphasis `{}`, use 50 or 75", other),
+        None => None,
+    };
+    // Tones with settings of their own, e.g. `--config detectors.json`; see `config.rs`.
+    let mut config = flag_value(&args, "--config")?.map(config::Config::load).transpose()?;
+    // Treat blocks quieter than a floor as silence, in which no tone is present, unless a tone
+    // of the config sets its own, e.g. `--silence-floor -60` (dBFS). Without a config it holds
+    // for the built-in tone detectors and the 440 Hz power readings.
+    let mut silence_floor = None;
+    if let Some(floor) = flag_value(&args, "--silence-floor")? {
+        let dbfs: f32 =
+            floor.parse().map_err(|_| anyhow::anyhow!("bad --silence-floor `{}`", floor))?;
+        match config.as_mut() {
+            Some(config) => config.defaults.silence_dbfs = Some(dbfs),
+            None => silence_floor = Some(0.5 * 10f32.powf(dbfs / 10.)),
+        }
+    }
+    // Run the detectors on every channel of the device separately, e.g. one per phone line.
+    let per_channel = args.iter().any(|a| a == "--per-channel");
+    // Analyze one channel of the input, e.g. `--channel 1`, or the one with the clearest signal
+    // after listening to all for a moment: `--channel auto`, the default for live input. Files
+    // are mixed down to one channel otherwise.
+    let channel = match flag_value(&args, "--channel")? {
+        Some(spec) => Some(select::Choice::parse(spec).ok_or_else(|| {
+            anyhow::anyhow!("bad --channel `{}`, use a channel index or auto", spec)
+        })?),
+        None => None,
+    };
+    if channel.is_some() && per_channel {
+        anyhow::bail!("--channel picks one channel, --per-channel analyzes all of them");
+    }
+    // Spread `--per-channel` detection over threads, e.g. `--workers 4`.
+    let workers = flag_value(&args, "--workers")?.map(str::parse).transpose()?;
+    // Save the audio around every detection as a WAV file, from 2 s before its onset to 2 s
+    // after unless set: `--capture snippets/ --pre-roll 1 --post-roll 3`.
+    let capture = match flag_value(&args, "--capture")? {
+        Some(dir) => {
+            let pre_roll = flag_value(&args, "--pre-roll")?.map(str::parse).transpose()?;
+            let post_roll = flag_value(&args, "--post-roll")?.map(str::parse).transpose()?;
+            if workers.is_some() {
+                anyhow::bail!("--capture does not work with --workers");
+            }
+            let (pre_roll, post_roll) = (pre_roll.unwrap_or(2.), post_roll.unwrap_or(2.));
+            Some(capture::Settings::new(std::path::Path::new(dir), pre_roll, post_roll)?)
+        }
+        None => None,
+    };
+    // Record only while a wake tone is heard, and until it has been gone for 5 s unless set,
+    // into a directory or through a command: `--wake-tone 1750 --wake-record scanner/` or
+    // `--wake-tone 1750 --wake-timeout 10s --wake-exec 'lame -r -s 8 - "$(date +%s).mp3"'`.
+    let wake = match flag_value(&args, "--wake-tone")? {
+        Some(freq) => {
+            let timeout = match flag_value(&args, "--wake-timeout")? {
+                Some(spec) => limit::parse_duration(spec).ok_or_else(|| {
+                    anyhow::anyhow!("bad --wake-timeout `{}`, use e.g. 5s or 500ms", spec)
+                })? as f32,
+                None => 5.,
+            };
+            let record = flag_value(&args, "--wake-record")?;
+            let target = match (record, flag_value(&args, "--wake-exec")?) {
+                (Some(dir), None) => {
+                    std::fs::create_dir_all(dir)?;
+                    squelch::Target::Record(dir.into())
+                }
+                (None, Some(command)) => squelch::Target::Exec(command.to_string()),
+                _ => anyhow::bail!("--wake-tone needs one of --wake-record or --wake-exec"),
+            };
+            if workers.is_some() {
+                anyhow::bail!("--wake-tone does not work with --workers");
+            }
+            Some(squelch::Settings { freq: freq.parse()?, timeout, target })
+        }
+        None => None,
+    };
+    // Largest offset of a device's actual sample rate from the configured one before it is
+    // warned about, 1% unless set: `--rate-tolerance 0.5%`.
+    let rate_tolerance = match flag_value(&args, "--rate-tolerance")? {
+        Some(spec) => spec
+            .strip_suffix('%')
+            .and_then(|p| p.parse::<f32>().ok())
+            .filter(|p| *p > 0.)
+            .ok_or_else(|| anyhow::anyhow!("bad --rate-tolerance `{}`, use e.g. 0.5%", spec))?
+            / 100.,
+        None => RATE_TOLERANCE,
+    };
+    // What to open input devices with, where they allow, e.g. `--input-rate 8000
+    // --input-channels 1 --input-format i16`; otherwise the nearest they support, converted.
+    let input = negotiate::Request {
+        sample_rate: flag_value(&args, "--input-rate")?.map(str::parse).transpose()?,
+        channels: flag_value(&args, "--input-channels")?.map(str::parse).transpose()?,
+        format: match flag_value(&args, "--input-format")? {
+            Some(spec) => Some(negotiate::parse_format(spec).ok_or_else(|| {
+                anyhow::anyhow!("bad --input-format `{}`, use f32, i16 or u16", spec)
+            })?),
+            None => None,
+        },
+    };
+    // Fixed cpal buffer, in frames: small for low latency, large for weak hardware.
+    let buffer_size = flag_value(&args, "--buffer-size")?.map(str::parse).transpose()?;
+    // Keep the result writer ahead of other threads.
+    let priority = args.iter().any(|a| a == "--priority");
+    if priority && !cfg!(feature = "priority") {
+        anyhow::bail!("--priority needs the binary built with `--features priority`");
+    }
+    // Ignore start-up transients: `--warm-up 5` skips five blocks, `--warm-up settle` waits for
+    // the input level to settle.
+    let warm_up = match flag_value(&args, "--warm-up")? {
+        Some("settle") => Some(WarmUp::Settled { tolerance_db: 1., max_blocks: 50 }),
+        Some(blocks) => Some(WarmUp::Blocks(blocks.parse()?)),
+        None => None,
+    };
+    // Event output: `text` (default), `csv`, `ndjson`, or the same objects as binary `msgpack`
+    // or `cbor` records.
+    let mut format = match flag_value(&args, "--format")? {
+        Some(name) => {
+            Format::from_name(name).ok_or_else(|| anyhow::anyhow!("unknown format `{}`", name))?
+        }
+        None => Format::Text,
+    };
+    // Send events to the system log with their fields, as RFC 5424 structured data with
+    // `--syslog` or as journal fields with `--journald`.
+    let system_log = if args.iter().any(|a| a == "--journald") {
+        Some((Format::Journald, syslog::JOURNALD_SOCKET))
+    } else if args.iter().any(|a| a == "--syslog") {
+        Some((Format::Syslog, syslog::SYSLOG_SOCKET))
+    } else {
+        None
+    };
+    // Chart the level of a tone, e.g. `--envelope 1000 --attack 10 --release 200` (ms).
+    let ms = |flag, default| -> Result<f32, anyhow::Error> {
+        Ok(flag_value(&args, flag)?.map(str::parse).transpose()?.unwrap_or(default))
+    };
+    let envelope = match flag_value(&args, "--envelope")? {
+        Some(freq) => Some(Envelope {
+            freq: freq.parse()?,
+            attack_ms: ms("--attack", 10.)?,
+            release_ms: ms("--release", 200.)?,
+        }),
+        None => None,
+    };
+    // Report the spectral flatness of every block, and/or ignore blocks flatter than a limit,
+    // e.g. `--flatness --tonal-gate 0.3`.
+    let flatness = args.iter().any(|a| a == "--flatness");
+    let tonal_gate = flag_value(&args, "--tonal-gate")?.map(str::parse).transpose()?;
+    // Duty-cycled listening: only run the detectors while the input is louder than a floor,
+    // e.g. `--energy-gate -50` (dBFS).
+    let energy_gate = flag_value(&args, "--energy-gate")?.map(str::parse).transpose()?;
+    // One value per interval instead of per sample or block, e.g. `--report-every 100ms` or
+    // `--report-every 5-blocks`; the largest is reported unless `--aggregate mean`.
+    let report = match flag_value(&args, "--report-every")? {
+        Some(spec) => {
+            let every = ReportEvery::parse(spec).ok_or_else(|| {
+                anyhow::anyhow!("bad --report-every `{}`, use 100ms or 5-blocks", spec)
+            })?;
+            let aggregate = match flag_value(&args, "--aggregate")? {
+                None | Some("max") => Aggregate::Max,
+                Some("mean") => Aggregate::Mean,
+                Some(name) => anyhow::bail!("unknown aggregate `{}`, use max or mean", name),
+            };
+            Some((every, aggregate))
+        }
+        None => None,
+    };
+    // Print nothing in steady state, only tone starts and ends, digits and other changes:
+    // `--events-only`.
+    let events_only = args.iter().any(|a| a == "--events-only");
+    // Dump the raw filter's recursion state (s_prev, s_prev2, totalpower, n, coeff) as JSON at
+    // the end of every block, to diagnose numerical issues: `--debug-state`.
+    let debug_state = args.iter().any(|a| a == "--debug-state");
+    // Alarm when an expected tone goes missing or the input goes quiet for too long, e.g.
+    // `--watch-tone 19000:2` (Hz:seconds, optionally :threshold) or `--watch-silence -50:5`
+    // (dBFS:seconds).
+    let watch = |flag: &str, fields: std::ops::RangeInclusive<usize>| {
+        flag_value(&args, flag)?
+            .map(|spec| {
+                let values: Option<Vec<f32>> = spec.split(':').map(|v| v.parse().ok()).collect();
+                values.filter(|v| fields.contains(&v.len())).ok_or_else(|| {
+                    anyhow::anyhow!("bad {} `{}`", flag, spec)
+                })
+            })
+            .transpose()
+    };
+    let watch_tone = watch("--watch-tone", 2..=3)?;
+    let watch_silence = watch("--watch-silence", 2..=2)?;
+    // Follow the exact frequency of a pilot tone and alert when it drifts too far, e.g.
+    // `--drift 19000:5ppm` or `--drift 1000:0.5hz`; the status line shows the drift so far.
+    let drift = match flag_value(&args, "--drift")? {
+        Some(spec) => Some(parse_drift(spec).ok_or_else(|| {
+            anyhow::anyhow!("bad --drift `{}`, use e.g. 19000:5ppm or 1000:0.5hz", spec)
+        })?),
+        None => None,
+    };
+    // Broadband level through a weighting, e.g. `--level A` for a rough SPL-style monitor.
+    let level = match flag_value(&args, "--level")? {
+        Some(name) => Some(
+            Weighting::from_name(name)
+                .ok_or_else(|| anyhow::anyhow!("unknown weighting `{}`, use Z, A or C", name))?,
+        ),
+        None => None,
+    };
+    // Tempo of the clicks or beeps of a tone, such as a 1 kHz metronome: `--tempo 1000`.
+    let tempo = flag_value(&args, "--tempo")?.map(str::parse).transpose()?;
+    // Levels of the 1/3-octave bands from 25 Hz to 20 kHz once a second, for a room or noise
+    // survey: `--third-octave`.
+    let third_octave = args.iter().any(|a| a == "--third-octave");
+    // Report each burst of a detector's events, such as the digits of a number, as one episode
+    // once that long went by without another: `--episodes 2s`.
+    let episodes = match flag_value(&args, "--episodes")? {
+        Some(spec) => Some(limit::parse_duration(spec).ok_or_else(|| {
+            anyhow::anyhow!("bad --episodes `{}`, use e.g. 2s or 500ms", spec)
+        })? as f32),
+        None => None,
+    };
+    // A heartbeat record with the health counters every so often of input, so consumers can
+    // tell a quiet line from a dead monitor: `--heartbeat 30s`.
+    let heartbeat = match flag_value(&args, "--heartbeat")? {
+        Some(spec) => Some(limit::parse_duration(spec).ok_or_else(|| {
+            anyhow::anyhow!("bad --heartbeat `{}`, use e.g. 30s or 500ms", spec)
+        })?),
+        None => None,
+    };
+    // Write results to a file instead of stdout, e.g. `--log-file detections.log --rotate daily`
+    // or `--rotate size=100MB`; `--gzip` compresses rotated files.
+    let sink: Box<dyn sink::EventSink> = if let Some((system_format, socket)) = system_log {
+        format = system_format;
+        Box::new(sink::Stream::new(socket, system_log_socket(socket)?))
+    } else if let Some(path) = flag_value(&args, "--log-file")? {
+        let rotate = match flag_value(&args, "--rotate")? {
+            Some(spec) => Some(rotate::Rotate::parse(spec).ok_or_else(|| {
+                anyhow::anyhow!("bad --rotate `{}`, use daily or size=100MB", spec)
+            })?),
+            None => None,
+        };
+        let gzip = args.iter().any(|a| a == "--gzip");
+        Box::new(sink::Stream::new(path, rotate::RotatingFile::open(path, rotate, gzip)?))
+    } else {
+        Box::new(sink::Stream::new("stdout", std::io::stdout()))
+    };
+    let mut sinks = vec![(sink, Limit::default())];
+    // Send every record to more places at once, each flag repeatable: `--osc 127.0.0.1:9000`,
+    // `--mqtt broker:1883/alarms`, `--websocket 0.0.0.0:8080` or `--exec 'notify-send tone'`.
+    // `--rate-limit 10/min` and `--dedup 5s` after one of them spare it more than that many
+    // records and repeats of an event within that time; before any, they apply to the main
+    // output.
+    for flag in ["--osc", "--mqtt", "--websocket", "--exec", "--rate-limit", "--dedup"] {
+        flag_values(&args, flag)?;
+    }
+    for (flag, spec) in args.iter().zip(args.iter().skip(1)) {
+        let (_, limit) = sinks.last_mut().expect("the main output");
+        match flag.as_str() {
+            "--rate-limit" => {
+                let rate = limit::Rate::parse(spec).ok_or_else(|| {
+                    anyhow::anyhow!("bad --rate-limit `{}`, use e.g. 5/s or 30/min", spec)
+                })?;
+                *limit = std::mem::take(limit).with_rate(rate);
+            }
+            "--dedup" => {
+                let window = limit::parse_duration(spec).ok_or_else(|| {
+                    anyhow::anyhow!("bad --dedup `{}`, use e.g. 500ms or 2s", spec)
+                })?;
+                *limit = std::mem::take(limit).with_dedup(window);
+            }
+            flag => {
+                if let Some(sink) = extra_sink(flag, spec)? {
+                    sinks.push((sink, Limit::default()));
+                }
+            }
+        }
+    }
+    let out = results::spawn_printer(results::CAPACITY, priority, sinks);
+    // `evaluate` prints a table of its own instead of records.
+    let records = args.get(1).map(String::as_str) != Some("evaluate");
+    if let Some(header) = format.header().filter(|_| records) {
+        out.send(header.to_string());
+    }
+    let options = Options {
+        warm_up,
+        out,
+        format,
+        envelope,
+        flatness,
+        tonal_gate,
+        energy_gate,
+        level,
+        third_octave,
+        tempo,
+        episodes,
+        heartbeat,
+        capture,
+        wake,
+        report,
+        events_only,
+        debug_state,
+        watch_tone,
+        watch_silence,
+        drift,
+        buffer_size,
+        input,
+        rate_tolerance,
+        profile,
+        config,
+        silence_floor,
+        smoke_alarm,
+        appliance_beeps,
+        modems,
+        coin_tones,
+        mf,
+        fm_pilot,
+        deemphasis,
+        per_channel,
+        channel,
+        workers,
+        #[cfg(feature = "verify")]
+        verify,
+        #[cfg(feature = "script")]
+        script,
+    };
+
+    // Send a recorded NDJSON log through the output again, paced as recorded, e.g.
+    // `replay events.ndjson --speed 10x` (or `--speed max`).
+    if args.get(1).map(String::as_str) == Some("replay") {
+        let path = args.get(2).ok_or_else(|| anyhow::anyhow!("replay needs an NDJSON file"))?;
+        let speed = match flag_value(&args, "--speed")? {
+            Some(spec) => replay::Speed::parse(spec)
+                .ok_or_else(|| anyhow::anyhow!("bad --speed `{}`, use 10x or max", spec))?,
+            None => replay::Speed::Factor(1.),
+        };
+        return replay::run(path, speed, options.format, &options.out);
+    }
+
+    // Detection and false-alarm rates of the configured detectors on synthetic tones in noise,
+    // e.g. `evaluate --freq 1000 --snr 0,5,10 --offset 0,20 --duration 0.05,0.1 --trials 100`.
+    // Without detectors configured, a tone detector at `--freq` is evaluated.
+    if args.get(1).map(String::as_str) == Some("evaluate") {
+        return run_evaluate(&args, &options);
+    }
+
+    // Analyze a recording instead of a live input, e.g. `--file night.wav`, or headerless PCM
+    // with `--raw s16le:8000[:channels]`; `--mmap` maps the file instead of reading it. With
+    // `--checkpoint scan.json` the position is saved every minute of audio, and `--resume`
+    // continues from the saved one after an interruption. `--no-progress` hides the progress
+    // bar, which is only drawn when stderr is a terminal anyway. `--start 00:12:30 --end 00:15:00`
+    // (or `--seek 750 --length 150`) analyzes a slice, still timestamped from the file start.
+    // `--parquet events.parquet` also writes the events as a table, `--export-npy powers.npy
+    // --freq 697 --freq 1209` the block power of each `--freq` (440 Hz by default) as a
+    // blocks x frequencies NumPy array; `--log-bins 20:20000:24` exports 1/24 octave bins from
+    // 20 Hz to 20 kHz instead, each as wide as its spacing. `--gpu` measures the `--freq` bins on
+    // the GPU, for banks of thousands, and on the CPU when there is no GPU.
+    if let Some(path) = flag_value(&args, "--file")? {
+        let raw = match flag_value(&args, "--raw")? {
+            Some(spec) => Some(file::RawSpec::parse(spec).ok_or_else(|| {
+                anyhow::anyhow!("bad --raw `{}`, use e.g. s16le:8000 or f32le:48000:2", spec)
+            })?),
+            None => None,
+        };
+        let mmap = args.iter().any(|a| a == "--mmap");
+        let source = file::FileSource::open(path, raw, mmap)?;
+        let time = |flag| -> Result<Option<f64>, anyhow::Error> {
+            match flag_value(&args, flag)? {
+                Some(t) => Ok(Some(file::parse_time(t).ok_or_else(|| {
+                    anyhow::anyhow!("bad {} `{}`, use seconds or [HH:]MM:SS", flag, t)
+                })?)),
+                None => Ok(None),
+            }
+        };
+        let start = time("--start")?.or(time("--seek")?);
+        let end = match (time("--end")?, time("--length")?) {
+            (Some(end), _) => Some(end),
+            (None, Some(length)) => Some(start.unwrap_or(0.) + length),
+            (None, None) => None,
+        };
+        let scan = Scan {
+            path,
+            checkpoint: flag_value(&args, "--checkpoint")?,
+            resume: args.iter().any(|a| a == "--resume"),
+            progress: !args.iter().any(|a| a == "--no-progress"),
+            start,
+            end,
+            parquet: flag_value(&args, "--parquet")?,
+            npy: flag_value(&args, "--export-npy")?,
+            freqs: flag_values(&args, "--freq")?
+                .into_iter()
+                .map(str::parse)
+                .collect::<Result<_, _>>()?,
+            log_bins: match flag_value(&args, "--log-bins")? {
+                Some(spec) => Some(parse_log_bins(spec).ok_or_else(|| {
+                    anyhow::anyhow!("bad --log-bins `{}`, use e.g. 20:20000:24", spec)
+                })?),
+                None => None,
+            },
+            gpu: args.iter().any(|a| a == "--gpu"),
+            // Replay the file through the whole pipeline again and again, e.g. to soak-test the
+            // sinks: `--loop 100` or `--loop infinite`.
+            loops: match flag_value(&args, "--loop")? {
+                Some("infinite") => None,
+                Some(spec) => Some(spec.parse().ok().filter(|n| *n > 0).ok_or_else(|| {
+                    anyhow::anyhow!("bad --loop `{}`, use a count or `infinite`", spec)
+                })?),
+                None => Some(1),
+            },
+            // Feed the file no faster than a multiple of real time, e.g. `--speed 1x` to try
+            // timing-dependent rules as if live; as fast as it goes (`max`) unless set.
+            speed: match flag_value(&args, "--speed")? {
+                Some(spec) => replay::Speed::parse(spec)
+                    .ok_or_else(|| anyhow::anyhow!("bad --speed `{}`, use 1x or max", spec))?,
+                None => replay::Speed::Max,
+            },
+        };
+        if scan.loops != Some(1) && scan.checkpoint.is_some() {
+            anyhow::bail!("--loop does not work with --checkpoint");
+        }
+        if scan.npy.is_some() && scan.resume {
+            anyhow::bail!("--export-npy covers the whole scan and cannot be used with --resume");
+        }
+        if scan.parquet.is_some() && !cfg!(feature = "parquet") {
+            anyhow::bail!("--parquet needs the binary built with `--features parquet`");
+        }
+        if scan.gpu && !cfg!(feature = "gpu") {
+            anyhow::bail!("--gpu needs the binary built with `--features gpu`");
+        }
+        if scan.gpu && scan.npy.is_none() {
+            anyhow::bail!("--gpu runs the --export-npy bank, and needs --export-npy");
+        }
+        return run_file(&scan, source, &options);
+    }
+
+    // Monitor mirrored VoIP traffic, e.g. `--rtp 0.0.0.0:5004`.
+    if let Some(addr) = flag_value(&args, "--rtp")? {
+        return run_rtp(addr, &options);
+    }
+
+    // Monitor an FM channel instead of the sound card, e.g. `--sdr 162550000`.
+    #[cfg(feature = "sdr")]
+    if let Some(freq) = flag_value(&args, "--sdr")? {
+        return run_sdr(freq.parse()?, &options);
+    }
+
+    // Monitor a remote feed, e.g. `--url http://example.com:8000/stream.mp3`.
+    #[cfg(feature = "icecast")]
+    if let Some(url) = flag_value(&args, "--url")? {
+        return run_icecast(url, &options);
+    }
+
+    let host = cpal::default_host();
+
+    // Monitor several devices at once, each optionally with its own profile, e.g.
+    // `--device "USB Audio" --device "Line 2@telephony"`.
+    let devices = flag_values(&args, "--device")?;
+    if !devices.is_empty() {
+        let mut inputs = Vec::new();
+        for spec in devices {
+            let (name, profile) = match spec.split_once('@') {
+                Some((name, profile)) => (name, Some(profile)),
+                None => (spec, None),
+            };
+            let device = host
+                .input_devices()?
+                .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+                .ok_or_else(|| anyhow::anyhow!("no input device named `{}`", name))?;
+            let mut device_options = options.clone();
+            if let Some(profile) = profile {
+                device_options.profile = Some(
+                    Profile::from_name(profile)
+                        .ok_or_else(|| anyhow::anyhow!("unknown profile `{}`", profile))?,
+                );
+            }
+            println!("Using input device: \"{}\"", name);
+            inputs.push(build_input(&device, Some(name.to_string()), &device_options)?);
+        }
+        return play(inputs, &options.out);
+    }
+
+    // Default device.
+    let input_device = host
+        .default_input_device()
+        .ok_or_else(|| anyhow::anyhow!("no default input device"))?;
+    println!("Using default input device: \"{}\"", input_device.name()?);
+
+    let input_stream = build_input(&input_device, None, &options)?;
+    play(vec![input_stream], &options.out)
+}
+
+/// Input stream of `device` feeding a monitor whose output is tagged with `source`.
+fn build_input(
+    device: &cpal::Device,
+    source: Option<String>,
+    options: &Options,
+) -> Result<cpal::Stream, anyhow::Error> {
+    // A profile's detectors are built for its rate.
+    let profile_rate = options.profile.as_ref().map(|p| p.sample_rate as u32);
+    let request = negotiate::Request {
+        sample_rate: profile_rate.or(options.input.sample_rate),
+        ..options.input
+    };
+    let mut negotiated = negotiate::Negotiated::new(device, request)?;
+    if let (Some(frames), cpal::SupportedBufferSize::Range { min, max }) =
+        (options.buffer_size, &negotiated.buffer_sizes)
+    {
+        if frames < *min || frames > *max {
+            anyhow::bail!("--buffer-size {} is outside the device range {}..={}", frames, min, max);
+        }
+    }
+    if let Some(frames) = options.buffer_size {
+        negotiated.config.buffer_size = cpal::BufferSize::Fixed(frames);
+    }
+
+    let sample_rate = negotiated.sample_rate as f32;
+    let channels = negotiated.channels as usize;
+    let monitor = if options.per_channel {
+        Monitor::per_channel(sample_rate, channels, options)?
+    } else {
+        Monitor::new(sample_rate, options)?
+    };
+    let name = source.clone().unwrap_or_else(|| "the input device".to_string());
+    let warning = health::RateWarning::register(name, sample_rate);
+    let mut monitor = Finishing(match options.workers {
+        Some(workers) if options.per_channel => monitor.tagged(source).pooled(workers, false),
+        _ => monitor.tagged(source),
+    });
+
+    let choice = options.channel.or((channels > 1).then_some(select::Choice::Auto));
+    let mut selector = match choice.filter(|_| !options.per_channel) {
+        Some(choice) => Some(select::Selector::new(choice, channels, sample_rate)?),
+        None => None,
+    };
+    let mut selected = Vec::new();
+    let mut gaps = health::GapDetector::new(sample_rate);
+    let mut rate = health::RateCheck::new(sample_rate, options.rate_tolerance);
+    let started = std::time::Instant::now();
+    let mut first_capture = None;
+    let input_data_fn = move |data: &[f32], info: &cpal::InputCallbackInfo| {
+        let capture = info.timestamp().capture;
+        let origin = *first_capture.get_or_insert(capture);
+        let at = capture.duration_since(&origin).unwrap_or_default().as_secs_f64();
+        if gaps.observe(at, data.len() / channels) {
+            health::HEALTH.count_gap();
+        }
+        let now = started.elapsed().as_secs_f64();
+        if let Some(actual) = rate.observe(now, data.len() / channels) {
+            // Printed by the status thread.
+            warning.raise(actual);
+        }
+        match &mut selector {
+            Some(selector) => {
+                selected.clear();
+                selector.push(data, &mut selected);
+                monitor.0.process(&selected);
+            }
+            None => monitor.0.process(data),
+        }
+    };
+
+    // Build streams.
+    println!("Attempting to build the input stream with `{:?}`.", negotiated.config);
+    let input_stream = negotiated.build(device, input_data_fn, err_fn)?;
+    println!("Successfully built the input stream: {}.", negotiated);
+    Ok(input_stream)
+}
+
+/// Runs the streams until Ctrl-C, SIGTERM or an input that cannot go on, then finishes their
+/// monitors and flushes `out`.
+///
+/// To check by hand that live input keeps going, `goertzelrs --heartbeat 15s --format ndjson`
+/// prints a heartbeat record every 15 s until stopped.
+fn play(streams: Vec<cpal::Stream>, out: &ResultSender) -> Result<(), anyhow::Error> {
+    ctrlc::set_handler(health::stop)?;
+    println!("Starting {} input stream(s).", streams.len());
+    for stream in &streams {
+        stream.play()?;
+    }
+
+    println!("Listening, press Ctrl-C to stop.");
+    health::wait_for_stop();
+    // Dropping a stream drops its callback, which finishes the monitor in it.
+    drop(streams);
+    out.flush();
+    println!("Done!");
+    Ok(())
+}
+
+fn err_fn(err: cpal::StreamError) {
+    health::HEALTH.count_overrun();
+    eprintln!("an error occurred on stream: {}", err);
+    if let cpal::StreamError::DeviceNotAvailable = err {
+        health::stop();
+    }
+}
+
+/// A live stream's monitor, finished when the stream drops its callback.
+struct Finishing(Monitor);
+
+impl Drop for Finishing {
+    fn drop(&mut self) {
+        self.0.finish();
+    }
+}
+
+/// Settings shared by every input.
+#[derive(Clone)]
+struct Options {
+    /// Where every monitor's result lines go.
+    out: ResultSender,
+    format: Format,
+    envelope: Option<Envelope>,
+    flatness: bool,
+    /// Largest spectral flatness of a block passed to the detectors.
+    tonal_gate: Option<f32>,
+    /// Level in dBFS below which the detectors sleep.
+    energy_gate: Option<f32>,
+    level: Option<Weighting>,
+    third_octave: bool,
+    /// Tone whose onsets are timed with `--tempo`.
+    tempo: Option<f32>,
+    /// Longest gap in seconds between the events of one `--episodes` episode.
+    episodes: Option<f32>,
+    /// Seconds of input between `--heartbeat` records.
+    heartbeat: Option<f64>,
+    /// Where `--capture` snippets go.
+    capture: Option<capture::Settings>,
+    /// Tone that starts `--wake-tone` recordings.
+    wake: Option<squelch::Settings>,
+    /// Interval the raw power and per-block levels are aggregated over.
+    report: Option<(ReportEvery, Aggregate)>,
+    /// Leave out the raw power and the per-block readings.
+    events_only: bool,
+    /// Print the raw filter's state every block.
+    debug_state: bool,
+    /// Frequency, longest absence and threshold of the pilot tone to watch for.
+    watch_tone: Option<Vec<f32>>,
+    /// Floor in dBFS and longest silence allowed.
+    watch_silence: Option<Vec<f32>>,
+    /// Pilot tone and how far it may drift.
+    drift: Option<(f32, DriftTolerance)>,
+    warm_up: Option<WarmUp>,
+    /// cpal buffer size in frames, the device default if unset.
+    buffer_size: Option<u32>,
+    /// Largest accepted offset of the device's sample rate, as a fraction.
+    rate_tolerance: f32,
+    /// Stream config asked of input devices; a profile's sample rate comes first.
+    input: negotiate::Request,
+    profile: Option<Profile>,
+    config: Option<config::Config>,
+    /// Mean-square level of `--silence-floor` for the built-in detectors, when there is no
+    /// config to carry it.
+    silence_floor: Option<f32>,
+    smoke_alarm: bool,
+    appliance_beeps: bool,
+    modems: bool,
+    coin_tones: bool,
+    /// Signaling system to decode: r2, ss5 or sf.
+    mf: Option<String>,
+    fm_pilot: bool,
+    /// De-emphasis time constant for the program audio, in seconds.
+    deemphasis: Option<f32>,
+    per_channel: bool,
+    /// The one channel of multi-channel input to analyze.
+    channel: Option<select::Choice>,
+    /// Threads analyzing the channels of a live input, inline in the callback if unset.
+    workers: Option<usize>,
+    #[cfg(feature = "verify")]
+    verify: bool,
+    /// Source of the rhai rules.
+    #[cfg(feature = "script")]
+    script: Option<String>,
+}
+
+/// Interval of `--report-every`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReportEvery {
+    Seconds(f32),
+    Blocks(usize),
+}
+
+impl ReportEvery {
+    /// `100ms`, `2s` or `5-blocks`.
+    fn parse(spec: &str) -> Option<Self> {
+        if let Some(n) = spec.strip_suffix("-blocks") {
+            return n.parse().ok().filter(|&n| n > 0).map(ReportEvery::Blocks);
+        }
+        let seconds = match spec.strip_suffix("ms") {
+            Some(ms) => ms.parse::<f32>().ok()? / 1000.,
+            None => spec.strip_suffix('s')?.parse().ok()?,
+        };
+        (seconds > 0.).then_some(ReportEvery::Seconds(seconds))
+    }
+
+    /// Values in one interval, for values reported every `block_size` samples.
+    fn values(self, sample_rate: f32, block_size: usize) -> usize {
+        match self {
+            ReportEvery::Seconds(s) => {
+                ((s * sample_rate / block_size as f32).round() as usize).max(1)
+            }
+            ReportEvery::Blocks(n) => n,
+        }
+    }
+}
+
+/// Tone whose level is reported with `--envelope`.
+#[derive(Clone, Copy)]
+struct Envelope {
+    freq: f32,
+    attack_ms: f32,
+    release_ms: f32,
+}
+
+/// Whatever is fed by an input, and the name its output is tagged with.
+struct Monitor {
+    source: Option<String>,
+    out: ResultSender,
+    format: Format,
+    /// One mode for the whole input, or one per channel of interleaved input.
+    channels: Vec<Mode>,
+    /// Samples of the channel being processed.
+    scratch: Vec<f32>,
+    /// Detector events reported so far.
+    events: u64,
+    /// Events also handed back to the caller, with their channel, once `collect_events` is on.
+    collected: Option<Vec<(Option<usize>, Event)>>,
+    /// Threads the channels were handed to, which then report on their own.
+    pool: Option<pool::WorkerPool>,
+    heartbeat: Option<Heartbeat>,
+    /// Audio around the detections of each channel, for `--capture`.
+    captures: Vec<capture::Capture>,
+    /// Wake tone recorders of each channel, for `--wake-tone`.
+    squelches: Vec<squelch::Squelch>,
+    /// The output while the monitor is quiet.
+    muted: Option<ResultSender>,
+}
+
+/// How far a monitor is into its input, for `--heartbeat`.
+struct Heartbeat {
+    /// Frames between heartbeats.
+    every: u64,
+    frames: u64,
+    channels: usize,
+    sample_rate: f32,
+}
+
+/// What a monitor does with its input.
+enum Mode {
+    /// Raw power of the 440 Hz filter on every sample, or aggregated with `--report-every`;
+    /// with `--debug-state` also the filter's internals at the end of every block.
+    Power {
+        filter: Goertzel,
+        reporter: Option<Reporter>,
+        debug_state: bool,
+    },
+    /// Detectors run block by block, printing their events; per-block readings are thinned out
+    /// with `--report-every`, or left out with `--events-only`.
+    Detect {
+        pipeline: Pipeline,
+        decimator: Option<Decimator>,
+        events_only: bool,
+    },
+    #[cfg(feature = "verify")]
+    Verify { block: Vec<f32>, sample_rate: f32 },
+}
+
+impl Monitor {
+    fn new(sample_rate: f32, options: &Options) -> Result<Self, anyhow::Error> {
+        Self::per_channel(sample_rate, 1, options)
+    }
+
+    /// Independent detection on each of `channels` interleaved channels, tagged with the
+    /// channel index.
+    fn per_channel(
+        sample_rate: f32,
+        channels: usize,
+        options: &Options,
+    ) -> Result<Self, anyhow::Error> {
+        let heartbeat = options.heartbeat.map(|seconds| Heartbeat {
+            every: ((seconds * sample_rate as f64) as u64).max(1),
+            frames: 0,
+            channels,
+            sample_rate,
+        });
+        let captures = match &options.capture {
+            Some(settings) => (0..channels)
+                .map(|channel| (channels > 1).then_some(channel))
+                .map(|channel| capture::Capture::new(settings, sample_rate, channel))
+                .collect(),
+            None => Vec::new(),
+        };
+        let squelches = match &options.wake {
+            Some(settings) => (0..channels)
+                .map(|channel| (channels > 1).then_some(channel))
+                .map(|channel| squelch::Squelch::new(settings, sample_rate, channel))
+                .collect(),
+            None => Vec::new(),
+        };
+        let channels = (0..channels)
+            .map(|_| Mode::new(sample_rate, options))
+            .collect::<Result<_, _>>()?;
+        Ok(Monitor {
+            source: None,
+            out: options.out.clone(),
+            format: options.format,
+            channels,
+            scratch: Vec::new(),
+            events: 0,
+            collected: None,
+            pool: None,
+            heartbeat,
+            captures,
+            squelches,
+            muted: None,
+        })
+    }
+
+    fn tagged(self, source: Option<String>) -> Self {
+        Monitor { source, ..self }
+    }
+
+    /// Moves the channels to `workers` threads; with `wait`, feeding them waits for room in
+    /// their queues rather than dropping input, as a file can.
+    fn pooled(mut self, workers: usize, wait: bool) -> Self {
+        let modes = std::mem::take(&mut self.channels);
+        let (source, format, out) = (self.source.clone(), self.format, self.out.clone());
+        let pool = pool::WorkerPool::spawn(modes, workers, wait, source, format, out);
+        Monitor { pool: Some(pool), ..self }
+    }
+
+    #[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+    fn collect_events(self) -> Self {
+        Monitor { collected: Some(Vec::new()), ..self }
+    }
+
+    /// Events collected since the last call.
+    #[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+    fn take_events(&mut self) -> Vec<(Option<usize>, Event)> {
+        self.collected.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Samples per analysis block, per channel.
+    fn block_size(&self) -> usize {
+        match (self.channels.first(), &self.pool) {
+            (Some(mode), _) => mode.block_size(),
+            (None, Some(pool)) => pool.block_size(),
+            (None, None) => 1,
+        }
+    }
+
+    /// Makes the next frame the `frame`th of the stream.
+    fn set_position(&mut self, frame: u64) {
+        if let Some(heartbeat) = &mut self.heartbeat {
+            heartbeat.frames = frame;
+        }
+        for capture in &mut self.captures {
+            capture.set_position(frame);
+        }
+        for squelch in &mut self.squelches {
+            squelch.set_position(frame);
+        }
+        for mode in &mut self.channels {
+            if let Mode::Detect { pipeline, .. } = mode {
+                pipeline.set_position(frame);
+            }
+        }
+    }
+
+    /// While `quiet`, the detectors run as usual but nothing is reported, counted, captured or
+    /// recorded, e.g. while `--resume` replays the audio before the checkpoint.
+    fn set_quiet(&mut self, quiet: bool) {
+        if quiet && self.muted.is_none() {
+            self.muted = Some(std::mem::replace(&mut self.out, results::channel(1).0));
+        } else if let (false, Some(out)) = (quiet, self.muted.as_ref()) {
+            self.out = out.clone();
+            self.muted = None;
+        }
+    }
+
+    /// Reports what the detectors still hold back, saves the captures still waiting for their
+    /// post-roll and stops the wake tone recordings, at the end of the input.
+    fn finish(&mut self) {
+        let several = self.channels.len() > 1;
+        let mut finished = Vec::new();
+        for (channel, mode) in self.channels.iter_mut().enumerate() {
+            let channel = several.then_some(channel);
+            let origin = Origin { source: self.source.as_deref(), channel };
+            finished.push((channel, mode.finish(origin, self.format, &self.out)));
+        }
+        if let Some(pool) = self.pool.take() {
+            let events = pool.finish().into_iter().enumerate();
+            finished.extend(events.map(|(channel, events)| (Some(channel), events)));
+        }
+        for (channel, events) in finished {
+            self.events += events.len() as u64;
+            if let Some(capture) = self.captures.get_mut(channel.unwrap_or(0)) {
+                capture.push(&[], &events);
+            }
+            if let Some(collected) = &mut self.collected {
+                collected.extend(events.into_iter().map(|e| (channel, e)));
+            }
+        }
+        for capture in &mut self.captures {
+            capture.finish();
+        }
+        for squelch in &mut self.squelches {
+            squelch.finish();
+        }
+    }
+
+    fn process(&mut self, samples: &[f32]) {
+        self.analyze(samples);
+        let Some(heartbeat) = &mut self.heartbeat else {
+            return;
+        };
+        let due = heartbeat.frames / heartbeat.every;
+        heartbeat.frames += (samples.len() / heartbeat.channels) as u64;
+        if heartbeat.frames / heartbeat.every == due || self.muted.is_some() {
+            return;
+        }
+        let time = Time { sample: heartbeat.frames, sample_rate: heartbeat.sample_rate };
+        let kind = EventKind::Heartbeat {
+            overruns: health::HEALTH.overruns.load(Ordering::Relaxed),
+            callback_gaps: health::HEALTH.callback_gaps.load(Ordering::Relaxed),
+            late_blocks: health::HEALTH.late_blocks.load(Ordering::Relaxed),
+            dropped_results: self.out.dropped_results(),
+        };
+        let origin = Origin { source: self.source.as_deref(), channel: None };
+        self.out.send(self.format.record(origin, &Event::new(time, kind)));
+    }
+
+    fn analyze(&mut self, samples: &[f32]) {
+        if let Some(pool) = &mut self.pool {
+            pool.push(samples);
+            return;
+        }
+        let source = self.source.as_deref();
+        let quiet = self.muted.is_some();
+        if let [mode] = self.channels.as_mut_slice() {
+            let origin = Origin { source, channel: None };
+            let mut events = mode.process(samples, origin, self.format, &self.out);
+            if quiet {
+                events.clear();
+            }
+            self.events += events.len() as u64;
+            if let Some(capture) = self.captures.first_mut() {
+                capture.push(samples, &events);
+            }
+            if let Some(squelch) = self.squelches.first_mut() {
+                squelch.push(samples, quiet);
+            }
+            if let Some(collected) = &mut self.collected {
+                collected.extend(events.into_iter().map(|e| (None, e)));
+            }
+            return;
+        }
+        let count = self.channels.len();
+        for (channel, mode) in self.channels.iter_mut().enumerate() {
+            self.scratch.clear();
+            self.scratch.extend(samples.iter().skip(channel).step_by(count));
+            let origin = Origin { source, channel: Some(channel) };
+            let mut events = mode.process(&self.scratch, origin, self.format, &self.out);
+            if quiet {
+                events.clear();
+            }
+            self.events += events.len() as u64;
+            if let Some(capture) = self.captures.get_mut(channel) {
+                capture.push(&self.scratch, &events);
+            }
+            if let Some(squelch) = self.squelches.get_mut(channel) {
+                squelch.push(&self.scratch, quiet);
+            }
+            if let Some(collected) = &mut self.collected {
+                collected.extend(events.into_iter().map(|e| (Some(channel), e)));
+            }
+        }
+    }
+}
+
+impl Mode {
+    fn new(sample_rate: f32, options: &Options) -> Result<Self, anyhow::Error> {
+        #[cfg(feature = "verify")]
+        if options.verify {
+            return Ok(Mode::Verify { block: Vec::with_capacity(1000), sample_rate });
+        }
+        let block_size = match (&options.config, &options.profile) {
+            (Some(c), _) => c.block_size(sample_rate),
+            (None, Some(p)) => p.block_size_at(sample_rate),
+            (None, None) if options.smoke_alarm => (sample_rate * presets::BEEP_BLOCK) as usize,
+            (None, None) => (sample_rate * 0.02) as usize,
+        };
+        let mut pipeline = Pipeline::new(sample_rate, block_size);
+        if let Some(warm_up) = options.warm_up {
+            pipeline.set_warm_up(warm_up);
+        }
+        if let Some(max_flatness) = options.tonal_gate {
+            pipeline.set_tonality_gate(max_flatness);
+        }
+        if let Some(floor_dbfs) = options.energy_gate {
+            pipeline.set_energy_gate(floor_dbfs, ENERGY_GATE_HOLD);
+        }
+        // Detectors of the program audio hear it de-emphasized with `--deemphasis` and have
+        // their events grouped with `--episodes`; the pilot, watchdog and drift monitors get the
+        // signal as received.
+        let audio = |detector: Box<dyn Detector + Send>| -> Box<dyn Detector + Send> {
+            let detector: Box<dyn Detector + Send> = match options.deemphasis {
+                Some(time_constant) => {
+                    Box::new(Deemphasized::new(detector, time_constant, sample_rate))
+                }
+                None => detector,
+            };
+            match options.episodes {
+                Some(gap) => Box::new(Episodes::new(detector, gap)),
+                None => detector,
+            }
+        };
+        if let Some(c) = &options.config {
+            for detector in c.detectors(sample_rate, config::local_time_of_day()) {
+                pipeline.add(audio(detector));
+            }
+        }
+        if let Some(p) = &options.profile {
+            pipeline.add(audio(Box::new(DtmfDetector::new(sample_rate, block_size, p.dtmf))));
+        }
+        let floor = |tone: &mut ToneDetector| {
+            if let Some(mean_square) = options.silence_floor {
+                tone.set_silence_floor(mean_square);
+            }
+        };
+        if options.smoke_alarm {
+            let mut alarm = presets::smoke_alarm(presets::SMOKE_ALARM, sample_rate);
+            floor(alarm.source_mut());
+            pipeline.add(audio(Box::new(alarm)));
+        }
+        if options.appliance_beeps {
+            let (count, within) = APPLIANCE_CYCLE;
+            let block = (sample_rate * presets::BEEP_BLOCK) as usize;
+            // 4 kHz buzzers need more than telephone-rate input.
+            for &freq in presets::APPLIANCE_BEEPS.iter().filter(|&&f| f < sample_rate / 2.) {
+                let mut beeps = presets::beeps(freq, sample_rate, count, within);
+                floor(beeps.source_mut());
+                pipeline.add(audio(Box::new(Reblock::new(beeps, block))));
+            }
+        }
+        if options.modems {
+            let block = (sample_rate * FSK_BLOCK) as usize;
+            pipeline.add(audio(Box::new(Reblock::new(presets::modems(sample_rate), block))));
+        }
+        if options.coin_tones {
+            let block = (sample_rate * presets::BEEP_BLOCK) as usize;
+            let coins = Reblock::new(presets::coin_tones(sample_rate), block);
+            pipeline.add(audio(Box::new(coins)));
+        }
+        let mf_block = (sample_rate * goertzel_mf::MF_BLOCK) as usize;
+        let mf: Vec<Box<dyn Detector + Send>> = match options.mf.as_deref() {
+            Some("r1") => vec![Box::new(R1Decoder::new(
+                sample_rate,
+                MfConfig::default(),
+                R1Timing::default(),
+            ))],
+            Some("r2") => vec![Box::new(R2Decoder::new(sample_rate, MfConfig::default()))],
+            Some("ss5") => vec![
+                Box::new(MfDecoder::new(goertzel_mf::SS5, sample_rate, MfConfig::default())),
+                Box::new(LineSignals::ss5(sample_rate)),
+            ],
+            Some(_) => vec![Box::new(LineSignals::sf(sample_rate))],
+            None => Vec::new(),
+        };
+        for decoder in mf {
+            pipeline.add(audio(Box::new(Reblock::new(decoder, mf_block))));
+        }
+        if let Some(e) = options.envelope {
+            let envelope = EnvelopeDetector::new(e.freq, sample_rate, e.attack_ms, e.release_ms);
+            pipeline.add(audio(Box::new(envelope)));
+        }
+        if options.flatness {
+            pipeline.add(audio(Box::new(FlatnessDetector::new(sample_rate, 16))));
+        }
+        if let Some(weighting) = options.level {
+            pipeline.add(audio(Box::new(LevelMeter::new(weighting, sample_rate))));
+        }
+        if let Some(freq) = options.tempo {
+            let block = (sample_rate * presets::BEEP_BLOCK) as usize;
+            let mut clicks = ToneDetector::new(freq, sample_rate, 0.5);
+            floor(&mut clicks);
+            pipeline.add(audio(Box::new(Reblock::new(Tempo::new(clicks, TEMPO_WINDOW), block))));
+        }
+        if options.third_octave {
+            pipeline.add(audio(Box::new(ThirdOctaveAnalyzer::new(sample_rate))));
+        }
+        let watchdog = match (&options.watch_tone, &options.watch_silence) {
+            (Some(tone), silence) => {
+                let threshold = tone.get(2).copied().unwrap_or(WATCH_THRESHOLD);
+                let watchdog = Watchdog::tone(tone[0], sample_rate, threshold, tone[1]);
+                Some(match silence {
+                    Some(s) => watchdog.with_silence(s[0], s[1]),
+                    None => watchdog,
+                })
+            }
+            (None, Some(s)) => Some(Watchdog::silence(s[0], s[1])),
+            (None, None) => None,
+        };
+        if let Some(watchdog) = watchdog {
+            pipeline.add(Box::new(watchdog));
+        }
+        if let Some((freq, tolerance)) = options.drift {
+            let tracker = DriftTracker::new(freq, sample_rate, tolerance);
+            pipeline.add(Box::new(health::PublishedDrift(tracker)));
+        }
+        if options.fm_pilot {
+            let pilot_error = |e| anyhow::anyhow!("--fm-pilot: {}", e);
+            pipeline.add(Box::new(presets::fm_pilot(sample_rate).map_err(pilot_error)?));
+            pipeline.add(Box::new(presets::fm_pilot_level(sample_rate).map_err(pilot_error)?));
+        }
+        #[cfg(feature = "script")]
+        if let Some(source) = &options.script {
+            pipeline.add(audio(Box::new(script::ScriptDetector::new(source, sample_rate)?)));
+        }
+        if pipeline.is_empty() && !options.events_only {
+            let reporter = options
+                .report
+                .map(|(every, aggregate)| Reporter::new(every.values(sample_rate, 1), aggregate));
+            return Ok(Mode::Power {
+                filter: Goertzel::new(440., sample_rate)
+                    .with_silence_floor(options.silence_floor.unwrap_or(0.)),
+                reporter,
+                debug_state: options.debug_state,
+            });
+        }
+        let decimator = options.report.map(|(every, aggregate)| {
+            Decimator::new(every.values(sample_rate, block_size), aggregate)
+        });
+        Ok(Mode::Detect { pipeline, decimator, events_only: options.events_only })
+    }
+
+    /// Samples per analysis block.
+    fn block_size(&self) -> usize {
+        match self {
+            Mode::Power { .. } => 1,
+            Mode::Detect { pipeline, .. } => pipeline.block_size(),
+            #[cfg(feature = "verify")]
+            Mode::Verify { block, .. } => block.capacity(),
+        }
+    }
+
+    /// Sends what the detectors still hold back to `out` at the end of the input, and returns it.
+    fn finish(&mut self, origin: Origin, format: Format, out: &ResultSender) -> Vec<Event> {
+        let Mode::Detect { pipeline, .. } = self else {
+            return Vec::new();
+        };
+        let events = pipeline.finish();
+        send_events(&events, origin, format, out);
+        events
+    }
+
+    /// Sends the results of `samples` to `out` and returns the detector events among them.
+    /// Events are written in `format`, the raw power and verification lines are always text.
+    fn process(
+        &mut self,
+        samples: &[f32],
+        origin: Origin,
+        format: Format,
+        out: &ResultSender,
+    ) -> Vec<Event> {
+        let tag = output::text_tag(origin);
+        match self {
+            Mode::Power { filter, reporter, debug_state } => {
+                for &sample in samples {
+                    let power = match filter.filter_reading(sample) {
+                        Reading::Power(power) => power,
+                        Reading::Silence => 0.,
+                    };
+                    if *debug_state {
+                        let state = filter.snapshot();
+                        if state.samples % filter.block_size() as u64 == 0 {
+                            out.send(format!("{}debug {}", tag, debug_state_json(&state)));
+                        }
+                    }
+                    let power = match reporter {
+                        Some(reporter) => match reporter.push(power) {
+                            Some(power) => power,
+                            None => continue,
+                        },
+                        None => power,
+                    };
+                    out.send(format!("{}{:?}", tag, power));
+                }
+            }
+            Mode::Detect { pipeline, decimator, events_only } => {
+                let mut events = pipeline.push(samples);
+                if *events_only {
+                    events.retain(|e| !e.kind.is_measurement());
+                } else if let Some(decimator) = decimator {
+                    events = events.into_iter().filter_map(|e| decimator.push(e)).collect();
+                }
+                send_events(&events, origin, format, out);
+                return events;
+            }
+            #[cfg(feature = "verify")]
+            Mode::Verify { block, sample_rate } => {
+                for &sample in samples {
+                    block.push(sample);
+                    if block.len() == block.capacity() {
+                        let d = goertzel_core::verify::compare_block(440., *sample_rate, block);
+                        out.send(format!(
+                            "{}{} Hz goertzel {:e} fft {:e} rel error {:e}",
+                            tag,
+                            d.freq,
+                            d.goertzel,
+                            d.fft,
+                            d.rel_error()
+                        ));
+                        block.clear();
+                    }
+                }
+            }
+        }
+        Vec::new()
+    }
+}
+
+/// Writes detector events to `out` in `format`.
+fn send_events(events: &[Event], origin: Origin, format: Format, out: &ResultSender) {
+    for event in events {
+        let record = format.record(origin, event);
+        match output::repeat(origin, event) {
+            Some(repeat) => out.send_event(record, repeat),
+            None => out.send(record),
+        }
+    }
+}
+
+/// `--debug-state` line of a filter snapshot.
+fn debug_state_json(state: &goertzel_core::Snapshot) -> serde_json::Value {
+    let filters: Vec<serde_json::Value> = state
+        .filters
+        .iter()
+        .map(|f| {
+            serde_json::json!({
+                "s_prev": f.s_prev, "s_prev2": f.s_prev2, "totalpower": f.totalpower, "n": f.n,
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "freq": state.freq,
+        "coeff": state.coeff,
+        "samples": state.samples,
+        "active": state.active,
+        "filters": filters,
+    })
+}
+
+/// Values following every occurrence of `flag` on the command line.
+fn flag_values<'a>(args: &'a [String], flag: &str) -> Result<Vec<&'a str>, anyhow::Error> {
+    let mut values = Vec::new();
+    for (pos, _) in args.iter().enumerate().filter(|(_, a)| *a == flag) {
+        let value = args.get(pos + 1).ok_or_else(|| anyhow::anyhow!("{} needs a value", flag))?;
+        values.push(value.as_str());
+    }
+    Ok(values)
+}
+
+/// A `--drift` spec: `Hz:tolerance`, the tolerance in `ppm` or `hz`.
+fn parse_drift(spec: &str) -> Option<(f32, DriftTolerance)> {
+    let (freq, tolerance) = spec.split_once(':')?;
+    let tolerance = tolerance.to_ascii_lowercase();
+    let tolerance = if let Some(ppm) = tolerance.strip_suffix("ppm") {
+        DriftTolerance::Ppm(ppm.parse().ok()?)
+    } else {
+        DriftTolerance::Hz(tolerance.strip_suffix("hz")?.parse().ok()?)
+    };
+    Some((freq.parse().ok()?, tolerance))
+}
+
+/// A `--log-bins` spec: `low:high:bins per octave`, in Hz.
+fn parse_log_bins(spec: &str) -> Option<(f32, f32, f32)> {
+    let mut fields = spec.split(':').map(|f| f.parse::<f32>().ok().filter(|v| *v > 0.));
+    let bins = (fields.next()??, fields.next()??, fields.next()??);
+    (fields.next().is_none() && bins.0 < bins.1).then_some(bins)
+}
+
+/// Value following `flag` on the command line, if the flag is present.
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a str>, anyhow::Error> {
+    match args.iter().position(|a| a == flag) {
+        Some(pos) => args
+            .get(pos + 1)
+            .map(|v| Some(v.as_str()))
+            .ok_or_else(|| anyhow::anyhow!("{} needs a value", flag)),
+        None => Ok(None),
+    }
+}
+
+/// How to go through a `--file`.
+struct Scan<'a> {
+    path: &'a str,
+    checkpoint: Option<&'a str>,
+    resume: bool,
+    /// Show a progress bar on stderr (when it is a terminal).
+    progress: bool,
+    /// Slice of the file to analyze, in seconds.
+    start: Option<f64>,
+    end: Option<f64>,
+    /// Also write the events to this Parquet file.
+    parquet: Option<&'a str>,
+    /// Write the block powers of `freqs` to this `.npy` file.
+    npy: Option<&'a str>,
+    freqs: Vec<f32>,
+    /// Export log-spaced bins instead of `freqs`: lowest and highest frequency, bins per octave.
+    log_bins: Option<(f32, f32, f32)>,
+    /// Measure the exported bins on the GPU when there is one.
+    gpu: bool,
+    /// Times to analyze the file over, `None` for ever.
+    loops: Option<u64>,
+    /// Pace of the analysis against the audio's own.
+    speed: replay::Speed,
+}
+
+/// The sink of an `--osc`, `--mqtt`, `--websocket` or `--exec` flag; `None` for other flags.
+fn extra_sink(flag: &str, spec: &str) -> Result<Option<Box<dyn sink::EventSink>>, anyhow::Error> {
+    let sink: Box<dyn sink::EventSink> = match flag {
+        "--osc" => Box::new(
+            sink::Osc::connect(spec)
+                .map_err(|e| anyhow::anyhow!("cannot reach {}: {}", spec, e))?,
+        ),
+        "--mqtt" => Box::new(
+            sink::Mqtt::connect(spec)
+                .map_err(|e| anyhow::anyhow!("cannot connect to {}: {}", spec, e))?,
+        ),
+        "--websocket" => Box::new(
+            sink::WebSocket::bind(spec)
+                .map_err(|e| anyhow::anyhow!("cannot listen on {}: {}", spec, e))?,
+        ),
+        "--exec" => Box::new(sink::Command::new(spec)),
+        _ => return Ok(None),
+    };
+    Ok(Some(sink))
+}
+
+#[cfg(unix)]
+fn system_log_socket(path: &str) -> Result<Box<dyn std::io::Write + Send>, anyhow::Error> {
+    let socket = syslog::Datagram::connect(path)
+        .map_err(|e| anyhow::anyhow!("cannot connect to {}: {}", path, e))?;
+    Ok(Box::new(socket))
+}
+
+#[cfg(not(unix))]
+fn system_log_socket(_: &str) -> Result<Box<dyn std::io::Write + Send>, anyhow::Error> {
+    anyhow::bail!("--syslog and --journald are only available on Unix")
+}
+
+fn run_evaluate(args: &[String], options: &Options) -> Result<(), anyhow::Error> {
+    let list = |flag, default: &[f32]| -> Result<Vec<f32>, anyhow::Error> {
+        match flag_value(args, flag)? {
+            Some(values) => Ok(values.split(',').map(str::parse).collect::<Result<_, _>>()?),
+            None => Ok(default.to_vec()),
+        }
+    };
+    let freq = flag_value(args, "--freq")?.map(str::parse).transpose()?.unwrap_or(1000.);
+    let sample_rate =
+        flag_value(args, "--sample-rate")?.map(str::parse).transpose()?.unwrap_or(8000.);
+    let defaults = Sweep::new(freq, sample_rate);
+    let sweep = Sweep {
+        snr_db: list("--snr", &defaults.snr_db)?,
+        offsets_hz: list("--offset", &defaults.offsets_hz)?,
+        durations: list("--duration", &defaults.durations)?,
+        trials: flag_value(args, "--trials")?.map(str::parse).transpose()?.unwrap_or(50),
+        ..defaults
+    };
+    let configured = matches!(
+        Mode::new(sample_rate, options)?,
+        Mode::Detect { pipeline, .. } if !pipeline.is_empty()
+    );
+    let outcomes = evaluate::evaluate(&sweep, || match Mode::new(sample_rate, options) {
+        Ok(Mode::Detect { pipeline, .. }) if configured => pipeline,
+        _ => {
+            let detector = ToneDetector::new(freq, sample_rate, 0.5)
+                .with_silence_floor(options.silence_floor.unwrap_or(0.));
+            Pipeline::new(sample_rate, (sample_rate * 0.02) as usize).with(Box::new(detector))
+        }
+    });
+    println!(
+        "{:>8} {:>10} {:>11} {:>10} {:>12}",
+        "snr_db", "offset_hz", "duration_s", "detection", "false_alarm"
+    );
+    for o in outcomes {
+        println!(
+            "{:>8.1} {:>10.1} {:>11.3} {:>10.3} {:>12.3}",
+            o.snr_db, o.offset_hz, o.duration, o.detection_rate, o.false_alarm_rate
+        );
+    }
+    Ok(())
+}
+
+fn run_file(
+    scan: &Scan,
+    mut source: file::FileSource,
+    options: &Options,
+) -> Result<(), anyhow::Error> {
+    let sample_rate = source.sample_rate();
+    let channels = source.channels();
+    let mut monitor = if options.per_channel {
+        Monitor::per_channel(sample_rate, channels, options)?
+    } else {
+        Monitor::new(sample_rate, options)?
+    };
+    #[cfg(feature = "parquet")]
+    let mut parquet = match scan.parquet {
+        Some(path) => {
+            monitor = monitor.collect_events();
+            Some(columnar::ParquetWriter::create(path)?)
+        }
+        None => None,
+    };
+    let mut npy = match scan.npy {
+        Some(path) => {
+            let freqs: &[f32] = if scan.freqs.is_empty() { &[440.] } else { &scan.freqs };
+            let block_size = match monitor.block_size() {
+                1 => (sample_rate * 0.02) as usize,
+                n => n,
+            };
+            let bank = match scan.log_bins {
+                Some((low, high, per_octave)) => {
+                    let high = high.min(sample_rate / 2.);
+                    GoertzelBank::log_spaced(low, high, per_octave, sample_rate)
+                }
+                None => GoertzelBank::with_block_size(freqs, sample_rate, block_size),
+            };
+            let export = match scan.gpu {
+                #[cfg(feature = "gpu")]
+                true => npy::PowerExport::create_on_gpu(path, bank, block_size)?,
+                _ => npy::PowerExport::create(path, bank, block_size)?,
+            };
+            if scan.gpu && !export.is_gpu() {
+                eprintln!("no GPU adapter available, measuring the bins on the CPU");
+            }
+            Some(export)
+        }
+        None => None,
+    };
+    let chunk = (sample_rate as usize / 10).max(1);
+    let mut audio = Vec::new();
+    let mut mono = Vec::new();
+    let mut selector = match options.channel {
+        Some(choice) => Some(select::Selector::new(choice, channels, sample_rate)?),
+        None => None,
+    };
+    let mut feed = |monitor: &mut Monitor, audio: &[f32]| {
+        if options.per_channel || (channels == 1 && selector.is_none()) {
+            return monitor.process(audio);
+        }
+        mono.clear();
+        match &mut selector {
+            Some(selector) => selector.push(audio, &mut mono),
+            None => {
+                let frames = audio.chunks(channels);
+                mono.extend(frames.map(|f| f.iter().sum::<f32>() / channels as f32));
+            }
+        }
+        monitor.process(&mono);
+    };
+
+    let end = scan
+        .end
+        .map_or(u64::MAX, |t| (t * sample_rate as f64) as u64)
+        .min(source.frames());
+    let mut frame = 0;
+    if let Some(start) = scan.start {
+        // Timestamps stay relative to the start of the file.
+        frame = (start * sample_rate as f64) as u64;
+        source.seek(frame.min(end))?;
+        monitor.set_position(frame);
+    }
+    if let (Some(workers), true) = (options.workers, options.per_channel) {
+        if scan.resume || scan.parquet.is_some() {
+            anyhow::bail!("--resume and --parquet do not work with --workers");
+        }
+        monitor = monitor.pooled(workers, true);
+    }
+    if scan.resume {
+        let checkpoint = scan
+            .checkpoint
+            .ok_or_else(|| anyhow::anyhow!("--resume needs --checkpoint"))?;
+        let saved = file::Checkpoint::load(checkpoint)?;
+        if saved.file != scan.path {
+            anyhow::bail!("checkpoint {} is for {}, not {}", checkpoint, saved.file, scan.path);
+        }
+        // Start on a block boundary of the uninterrupted run and replay up to the checkpoint
+        // without reporting, so the detectors are where they were.
+        let block = monitor.block_size() as u64;
+        let preroll = (RESUME_PREROLL_SECS * sample_rate) as u64;
+        frame = saved.frame.saturating_sub(preroll) / block * block;
+        source.seek(frame)?;
+        monitor.set_position(frame);
+        monitor.set_quiet(true);
+        while frame < saved.frame {
+            audio.clear();
+            let want = chunk.min((saved.frame - frame) as usize);
+            match source.read(want, &mut audio)? {
+                0 => break,
+                read => frame += read as u64,
+            }
+            feed(&mut monitor, &audio);
+        }
+        monitor.set_quiet(false);
+        println!("Resuming {} at {:.1}s", scan.path, frame as f32 / sample_rate);
+    }
+
+    let length = end.saturating_sub(frame);
+    let progress = if scan.progress {
+        let bar = indicatif::ProgressBar::new(length * scan.loops.unwrap_or(1));
+        bar.set_style(
+            indicatif::ProgressStyle::with_template(
+                "{bar:40} {percent:>3}% {elapsed} ETA {eta} {msg}",
+            )?
+            .progress_chars("=> "),
+        );
+        bar
+    } else {
+        indicatif::ProgressBar::hidden()
+    };
+    let started = (std::time::Instant::now(), frame);
+
+    let every = (CHECKPOINT_SECS * sample_rate) as u64;
+    let mut saved = frame;
+    // Frames analyzed in the passes before this one.
+    let mut before = 0;
+    let mut pass = 1;
+    // Events reported in the passes before this one, to tell drift between them.
+    let mut counted = 0;
+    loop {
+        while frame < end {
+            audio.clear();
+            let read = source.read(chunk.min((end - frame) as usize), &mut audio)?;
+            if read == 0 {
+                break;
+            }
+            frame += read as u64;
+            let played = before + frame - started.1;
+            if let replay::Speed::Factor(factor) = scan.speed {
+                // A chunk is there once the last of it would have been heard.
+                let due = played as f64 / sample_rate as f64 / factor;
+                let due = std::time::Duration::from_secs_f64(due);
+                if let Some(wait) = due.checked_sub(started.0.elapsed()) {
+                    std::thread::sleep(wait);
+                }
+            }
+            options.out.wait_for_room();
+            feed(&mut monitor, &audio);
+            if let Some(npy) = &mut npy {
+                npy.push(&audio, channels)?;
+            }
+            #[cfg(feature = "parquet")]
+            if let Some(parquet) = &mut parquet {
+                for (channel, event) in monitor.take_events() {
+                    parquet.push(Origin { source: None, channel }, &event)?;
+                }
+            }
+            if let Some(path_out) = scan.checkpoint {
+                if frame - saved >= every {
+                    options.out.flush();
+                    file::Checkpoint { file: scan.path.to_string(), frame }.save(path_out)?;
+                    saved = frame;
+                }
+            }
+            let audio_secs = played as f32 / sample_rate;
+            let speed = audio_secs / started.0.elapsed().as_secs_f32().max(1e-3);
+            progress.set_position(played);
+            progress.set_message(format!("{:.0}x realtime, {} events", speed, monitor.events));
+        }
+        if scan.loops != Some(1) {
+            let events = monitor.events - counted;
+            progress.suspend(|| eprintln!("Pass {}: {} events", pass, events));
+            counted = monitor.events;
+        }
+        if scan.loops == Some(pass) || frame == started.1 {
+            break;
+        }
+        if scan.loops.is_none() {
+            progress.set_length(length * (pass + 1));
+        }
+        before += frame - started.1;
+        pass += 1;
+        // The stream time goes on, so the detectors hear the file again straight after itself.
+        frame = started.1;
+        source.seek(frame)?;
+    }
+    progress.finish_and_clear();
+    if let Some(selector) = &mut selector {
+        // The rest of what was heard while picking the channel of a short file.
+        mono.clear();
+        selector.finish(&mut mono);
+        monitor.process(&mono);
+    }
+    monitor.finish();
+    if let Some(npy) = npy {
+        npy.finish()?;
+    }
+    #[cfg(feature = "parquet")]
+    if let Some(mut parquet) = parquet {
+        for (channel, event) in monitor.take_events() {
+            parquet.push(Origin { source: None, channel }, &event)?;
+        }
+        parquet.finish()?;
+    }
+    options.out.flush();
+    if let Some(path_out) = scan.checkpoint {
+        file::Checkpoint { file: scan.path.to_string(), frame }.save(path_out)?;
+    }
+    Ok(())
+}
+
+/// Feeds `monitor` from `read` until Ctrl-C, SIGTERM or a read error, then reports what its
+/// detectors still hold back and flushes `out`, as `play` does for devices.
+fn listen(
+    mut monitor: Monitor,
+    mut read: impl FnMut(&mut Vec<f32>) -> Result<(), anyhow::Error>,
+    out: &ResultSender,
+) -> Result<(), anyhow::Error> {
+    ctrlc::set_handler(health::stop)?;
+    let mut audio = Vec::new();
+    let mut result = Ok(());
+    while result.is_ok() && !health::stopped() {
+        audio.clear();
+        result = read(&mut audio);
+        monitor.process(&audio);
+    }
+    monitor.finish();
+    out.flush();
+    result
+}
+
+fn run_rtp(addr: &str, options: &Options) -> Result<(), anyhow::Error> {
+    let mut source = rtp::RtpSource::bind(addr)?;
+    println!("Listening for G.711 RTP on {}", addr);
+    let monitor = Monitor::new(rtp::SAMPLE_RATE, options)?;
+    listen(monitor, |audio| Ok(source.read(audio)?), &options.out)
+}
+
+#[cfg(feature = "sdr")]
+fn run_sdr(center_freq: u32, options: &Options) -> Result<(), anyhow::Error> {
+    let mut source = sdr::RtlSdrSource::open(0, center_freq)
+        .map_err(|e| anyhow::anyhow!("{}", e))?;
+    println!("Listening on {} Hz", center_freq);
+    let monitor = Monitor::new(source.audio_rate(), options)?;
+    let mut read = |audio: &mut Vec<f32>| source.read(audio).map_err(|e| anyhow::anyhow!("{}", e));
+    listen(monitor, &mut read, &options.out)
+}
+
+#[cfg(feature = "icecast")]
+fn run_icecast(url: &str, options: &Options) -> Result<(), anyhow::Error> {
+    let mut source = icecast::IcecastSource::connect(url)?;
+    println!("Streaming {} at {} Hz", url, source.sample_rate());
+    let monitor = Monitor::new(source.sample_rate(), options)?;
+    listen(monitor, |audio| Ok(source.read(audio)?), &options.out)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn resume_reports_only_what_follows_the_checkpoint() {
+        let dir = std::env::temp_dir().join(format!("goertzelrs-resume-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        // Quarter-second 1 kHz beeps at 1, 3, 5 and 7 s of a 9 s recording.
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut wav = hound::WavWriter::create(path("beeps.wav"), spec).unwrap();
+        for n in 0..72_000 {
+            let beep = n % 16_000 >= 8000 && n % 16_000 < 10_000;
+            let sample = if beep { 0.5 * (2. * PI * 1000. * n as f32 / 8000.).sin() } else { 0. };
+            wav.write_sample((sample * i16::MAX as f32) as i16).unwrap();
+        }
+        wav.finalize().unwrap();
+        std::fs::write(path("tones.json"), r#"{"tones": [{"freq": 1000}]}"#).unwrap();
+
+        let scan = |log: &str, captures: &str, resume: bool| {
+            std::fs::create_dir_all(path(captures)).unwrap();
+            let mut args: Vec<String> = vec!["goertzelrs".into(), "--file".into()];
+            args.push(path("beeps.wav"));
+            for arg in ["--config", &path("tones.json"), "--events-only", "--format", "ndjson"] {
+                args.push(arg.into());
+            }
+            for arg in ["--log-file", &path(log), "--capture", &path(captures), "--no-progress"] {
+                args.push(arg.into());
+            }
+            if resume {
+                for arg in ["--checkpoint", &path("scan.checkpoint"), "--resume"] {
+                    args.push(arg.into());
+                }
+            }
+            run(args).unwrap();
+            let events: Vec<String> = std::fs::read_to_string(path(log))
+                .unwrap()
+                .lines()
+                .filter(|l| l.contains("\"event\""))
+                .map(str::to_string)
+                .collect();
+            let mut snippets: Vec<String> = std::fs::read_dir(path(captures))
+                .unwrap()
+                .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            snippets.sort();
+            (events, snippets)
+        };
+        let (all, all_snippets) = scan("all.ndjson", "all", false);
+        assert_eq!(all.len(), 8);
+        assert_eq!(all_snippets.len(), 4);
+
+        // Interrupted at 4.5 s, between the second and third beep.
+        let checkpoint = file::Checkpoint { file: path("beeps.wav"), frame: 36_000 };
+        checkpoint.save(path("scan.checkpoint")).unwrap();
+        let (rest, rest_snippets) = scan("rest.ndjson", "rest", true);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(rest, all[4..]);
+        assert_eq!(rest_snippets, all_snippets[2..]);
+    }
+
+    #[test]
+    fn workers_report_the_episodes_still_open_at_the_end() {
+        let dir = std::env::temp_dir().join(format!("goertzelrs-workers-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        // Two channels, each with a 1 kHz beep half a second before the end of the file.
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut wav = hound::WavWriter::create(path("beeps.wav"), spec).unwrap();
+        for n in 0..16_000 {
+            let beep = (10_000..12_000).contains(&n);
+            let sample = if beep { 0.5 * (2. * PI * 1000. * n as f32 / 8000.).sin() } else { 0. };
+            for _ in 0..2 {
+                wav.write_sample((sample * i16::MAX as f32) as i16).unwrap();
+            }
+        }
+        wav.finalize().unwrap();
+        std::fs::write(path("tones.json"), r#"{"tones": [{"freq": 1000}]}"#).unwrap();
+
+        let mut args: Vec<String> = vec!["goertzelrs".into(), "--file".into(), path("beeps.wav")];
+        for arg in ["--config", &path("tones.json"), "--per-channel", "--workers", "2"] {
+            args.push(arg.into());
+        }
+        for arg in ["--episodes", "2s", "--events-only", "--format", "ndjson", "--no-progress"] {
+            args.push(arg.into());
+        }
+        args.extend(["--log-file".into(), path("events.ndjson")]);
+        run(args).unwrap();
+        let log = std::fs::read_to_string(path("events.ndjson")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        // The episodes only end with the input, 1.5 s after the beeps.
+        let episodes: Vec<&str> = log.lines().filter(|l| l.contains("\"episode\"")).collect();
+        assert_eq!(episodes.len(), 2, "{}", log);
+        for channel in 0..2 {
+            let tag = format!("\"channel\":{}", channel);
+            assert!(episodes.iter().any(|e| e.contains(&tag)), "{}", log);
+        }
+    }
+    #[test]
+    fn silence_floor_holds_for_the_built_in_readings_without_a_config() {
+        let dir = std::env::temp_dir().join(format!("goertzelrs-floor-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        // Half a second of a 440 Hz tone at -53 dBFS.
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut wav = hound::WavWriter::create(path("quiet.wav"), spec).unwrap();
+        for n in 0..4000 {
+            let sample = 0.003 * (2. * PI * 440. * n as f32 / 8000.).sin();
+            wav.write_sample((sample * i16::MAX as f32) as i16).unwrap();
+        }
+        wav.finalize().unwrap();
+
+        let last_power = |floor: Option<&str>| {
+            let mut args: Vec<String> = vec!["goertzelrs".into(), "--file".into()];
+            args.extend([path("quiet.wav"), "--no-progress".into(), "--log-file".into()]);
+            args.push(path("power.txt"));
+            if let Some(floor) = floor {
+                args.extend(["--silence-floor".into(), floor.into()]);
+            }
+            run(args).unwrap();
+            let log = std::fs::read_to_string(path("power.txt")).unwrap();
+            log.lines().filter_map(|l| l.parse::<f32>().ok()).next_back().unwrap()
+        };
+        let heard = last_power(None);
+        let floored = last_power(Some("-40"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(heard > 0.5, "{}", heard);
+        assert_eq!(floored, 0.);
+    }
+}
@@ -0,0 +1,278 @@
+//! Opening an input device in the config asked for, or the nearest one it supports.
+//!
+//! The sample rate (`--input-rate`, or a profile's), `--input-channels` and `--input-format`
+//! are requests rather than demands: the device's supported configs are ranked by how many of
+//! them they meet, in that order of importance, and whatever the chosen one gets wrong is made
+//! up for on the way in. Samples are converted to f32, the channels mixed down or repeated and
+//! the audio resampled, so the detectors always see the rate and channels they were built for.
+
+use std::fmt;
+
+use cpal::traits::DeviceTrait;
+use cpal::{InputCallbackInfo, Sample, SampleFormat, SampleRate, StreamConfig, StreamError};
+use cpal::SupportedBufferSize;
+use goertzel_core::resample::Resampler;
+
+/// What the stream should deliver, the device default for anything not given.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Request {
+  pub sample_rate: Option<u32>,
+  pub channels: Option<u16>,
+  pub format: Option<SampleFormat>,
+}
+
+/// `f32`, `i16` or `u16`.
+pub fn parse_format(spec: &str) -> Option<SampleFormat> {
+  match spec {
+    "f32" => Some(SampleFormat::F32),
+    "i16" => Some(SampleFormat::I16),
+    "u16" => Some(SampleFormat::U16),
+    _ => None,
+  }
+}
+
+/// One of the ranges of configs a device supports.
+#[derive(Debug, Clone, PartialEq)]
+struct Offer {
+  channels: u16,
+  min_rate: u32,
+  max_rate: u32,
+  format: SampleFormat,
+  buffer_sizes: SupportedBufferSize,
+}
+
+/// The stream a device is opened with, and how its audio is made into what was asked for.
+#[derive(Debug, Clone)]
+pub struct Negotiated {
+  /// What the device runs at.
+  pub config: StreamConfig,
+  pub format: SampleFormat,
+  /// Buffer sizes the device allows in `config`.
+  pub buffer_sizes: SupportedBufferSize,
+  /// What the detectors get.
+  pub sample_rate: u32,
+  pub channels: u16,
+}
+
+impl Negotiated {
+  /// Picks the config of `device` closest to `request`.
+  pub fn new(device: &cpal::Device, request: Request) -> Result<Self, anyhow::Error> {
+    let default = device.default_input_config()?;
+    let fallback = Offer {
+      channels: default.channels(),
+      min_rate: default.sample_rate().0,
+      max_rate: default.sample_rate().0,
+      format: default.sample_format(),
+      buffer_sizes: default.buffer_size().clone(),
+    };
+    let wanted = Request {
+      sample_rate: request.sample_rate.or(Some(fallback.min_rate)),
+      channels: request.channels.or(Some(fallback.channels)),
+      format: request.format.or(Some(fallback.format)),
+    };
+    // Some hosts cannot list their configs; the default is always there.
+    let offers: Vec<Offer> = match device.supported_input_configs() {
+      Ok(configs) => configs
+        .map(|c| Offer {
+          channels: c.channels(),
+          min_rate: c.min_sample_rate().0,
+          max_rate: c.max_sample_rate().0,
+          format: c.sample_format(),
+          buffer_sizes: c.buffer_size().clone(),
+        })
+        .collect(),
+      Err(_) => Vec::new(),
+    };
+    let (offer, rate) = pick(&offers, &fallback, wanted);
+    Ok(Negotiated {
+      config: StreamConfig {
+        channels: offer.channels,
+        sample_rate: SampleRate(rate),
+        buffer_size: cpal::BufferSize::Default,
+      },
+      format: offer.format,
+      buffer_sizes: offer.buffer_sizes.clone(),
+      sample_rate: wanted.sample_rate.unwrap_or(rate),
+      channels: wanted.channels.unwrap_or(offer.channels),
+    })
+  }
+
+  /// Opens the stream, handing `callback` the audio as negotiated: f32 samples, `self.channels`
+  /// interleaved, at `self.sample_rate`.
+  pub fn build<F, E>(
+    &self,
+    device: &cpal::Device,
+    mut callback: F,
+    on_error: E,
+  ) -> Result<cpal::Stream, cpal::BuildStreamError>
+  where
+    F: FnMut(&[f32], &InputCallbackInfo) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+  {
+    let mut convert = Conversion::new(self);
+    let config = &self.config;
+    match self.format {
+      SampleFormat::F32 => device.build_input_stream(
+        config,
+        move |data: &[f32], info: &InputCallbackInfo| callback(convert.run(data), info),
+        on_error,
+      ),
+      SampleFormat::I16 => device.build_input_stream(
+        config,
+        move |data: &[i16], info: &InputCallbackInfo| callback(convert.run(data), info),
+        on_error,
+      ),
+      SampleFormat::U16 => device.build_input_stream(
+        config,
+        move |data: &[u16], info: &InputCallbackInfo| callback(convert.run(data), info),
+        on_error,
+      ),
+    }
+  }
+}
+
+impl fmt::Display for Negotiated {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let (rate, channels) = (self.config.sample_rate.0, self.config.channels);
+    write!(f, "{} Hz, {} ch, {:?}", rate, channels, self.format)?;
+    if channels != self.channels {
+      write!(f, ", as {} ch", self.channels)?;
+    }
+    if rate != self.sample_rate {
+      write!(f, ", resampled to {} Hz", self.sample_rate)?;
+    }
+    Ok(())
+  }
+}
+
+/// The offer closest to `wanted`, whose fields are all set, and the rate to run it at. The
+/// rate counts most, then the channels, then the format, then how near the rate comes; the
+/// device default wins ties.
+fn pick(offers: &[Offer], default: &Offer, wanted: Request) -> (Offer, u32) {
+  let rate = wanted.sample_rate.unwrap_or(default.min_rate);
+  let near = |o: &Offer| rate.clamp(o.min_rate, o.max_rate);
+  let rank = |(i, o): (usize, &Offer)| {
+    (
+      near(o) == rate,
+      Some(o.channels) == wanted.channels,
+      Some(o.format) == wanted.format,
+      // f32 saves a conversion.
+      o.format == SampleFormat::F32,
+      -(near(o) as i64 - rate as i64).abs(),
+      i == 0,
+    )
+  };
+  let candidates = std::iter::once(default).chain(offers);
+  let (_, best) = candidates.enumerate().max_by_key(|&c| rank(c)).expect("the default");
+  (best.clone(), near(best))
+}
+
+/// Turns what the device delivers into what was asked for.
+struct Conversion {
+  from: usize,
+  to: usize,
+  resampler: Option<Resampler>,
+  samples: Vec<f32>,
+  mixed: Vec<f32>,
+  resampled: Vec<f32>,
+}
+
+impl Conversion {
+  fn new(negotiated: &Negotiated) -> Self {
+    let (rate, to) = (negotiated.config.sample_rate.0, negotiated.channels as usize);
+    let resampler = (rate != negotiated.sample_rate)
+      .then(|| Resampler::new(rate as f32, negotiated.sample_rate as f32, to));
+    Conversion {
+      from: negotiated.config.channels as usize,
+      to,
+      resampler,
+      samples: Vec::new(),
+      mixed: Vec::new(),
+      resampled: Vec::new(),
+    }
+  }
+
+  fn run<S: Sample>(&mut self, data: &[S]) -> &[f32] {
+    self.samples.clear();
+    self.samples.extend(data.iter().map(Sample::to_f32));
+    let (from, to) = (self.from, self.to);
+    let mixed = if from == to {
+      &self.samples
+    } else {
+      self.mixed.clear();
+      for frame in self.samples.chunks_exact(from) {
+        if to == 1 {
+          self.mixed.push(frame.iter().sum::<f32>() / from as f32);
+        } else {
+          // Missing channels repeat the ones there are, extra ones are dropped.
+          self.mixed.extend((0..to).map(|c| frame[c % from]));
+        }
+      }
+      &self.mixed
+    };
+    match &mut self.resampler {
+      Some(resampler) => {
+        self.resampled.clear();
+        resampler.process(mixed, &mut self.resampled);
+        &self.resampled
+      }
+      None => mixed,
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn falls_back_to_the_nearest_supported_config() {
+    let offer = |channels, min_rate, max_rate, format| Offer {
+      channels,
+      min_rate,
+      max_rate,
+      format,
+      buffer_sizes: SupportedBufferSize::Unknown,
+    };
+    let default = offer(2, 48000, 48000, SampleFormat::I16);
+    let offers = [
+      offer(2, 44100, 48000, SampleFormat::I16),
+      offer(1, 44100, 48000, SampleFormat::F32),
+      offer(2, 8000, 96000, SampleFormat::F32),
+    ];
+    let want = |sample_rate, channels, format| Request {
+      sample_rate: Some(sample_rate),
+      channels: Some(channels),
+      format: Some(format),
+    };
+    // Exactly what the default is.
+    let exact = pick(&offers, &default, want(48000, 2, SampleFormat::I16));
+    assert_eq!(exact, (default.clone(), 48000));
+    // The rate counts before the channels.
+    let rate = pick(&offers, &default, want(8000, 1, SampleFormat::I16));
+    assert_eq!(rate, (offers[2].clone(), 8000));
+    // No 4 kHz anywhere: the nearest with the channels.
+    let nearest = pick(&offers[..2], &default, want(4000, 1, SampleFormat::I16));
+    assert_eq!(nearest, (offers[1].clone(), 44100));
+
+    let negotiated = Negotiated {
+      config: StreamConfig {
+        channels: 2,
+        sample_rate: SampleRate(16000),
+        buffer_size: cpal::BufferSize::Default,
+      },
+      format: SampleFormat::I16,
+      buffer_sizes: SupportedBufferSize::Unknown,
+      sample_rate: 8000,
+      channels: 1,
+    };
+    let effective = "16000 Hz, 2 ch, I16, as 1 ch, resampled to 8000 Hz";
+    assert_eq!(negotiated.to_string(), effective);
+    let mut convert = Conversion::new(&negotiated);
+    let stereo: Vec<i16> = (0..3200).map(|i| if i % 2 == 0 { 16384 } else { 0 }).collect();
+    let mono = convert.run(&stereo);
+    assert_eq!(mono.len(), 800);
+    assert!((mono[400] - 0.25).abs() < 1e-3, "{}", mono[400]);
+  }
+}
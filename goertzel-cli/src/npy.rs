@@ -0,0 +1,223 @@
+//! `--export-npy`: the per-block power of a set of frequencies as a NumPy array.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+#[cfg(feature = "gpu")]
+use goertzel_core::gpu::BankEngine;
+use goertzel_core::GoertzelBank;
+
+/// Room for the header, so it can be rewritten with the final shape. A multiple of 64, as the
+/// format asks for the data to be aligned.
+const HEADER_LEN: usize = 128;
+
+/// Streams a 2-D little-endian `f32` array to an `.npy` file row by row.
+pub struct NpyWriter {
+  file: BufWriter<File>,
+  columns: usize,
+  rows: usize,
+}
+
+impl NpyWriter {
+  pub fn create(path: impl AsRef<Path>, columns: usize) -> Result<Self, anyhow::Error> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&header(0, columns))?;
+    Ok(NpyWriter { file, columns, rows: 0 })
+  }
+
+  pub fn push_row(&mut self, row: &[f32]) -> Result<(), anyhow::Error> {
+    assert_eq!(row.len(), self.columns, "one value per column");
+    for value in row {
+      self.file.write_all(&value.to_le_bytes())?;
+    }
+    self.rows += 1;
+    Ok(())
+  }
+
+  /// Writes the final shape into the header.
+  pub fn finish(mut self) -> Result<(), anyhow::Error> {
+    self.file.seek(SeekFrom::Start(0))?;
+    self.file.write_all(&header(self.rows, self.columns))?;
+    self.file.flush()?;
+    Ok(())
+  }
+}
+
+/// Version 1.0 header of a `rows` × `columns` C-order `<f4` array, padded to `HEADER_LEN`.
+fn header(rows: usize, columns: usize) -> Vec<u8> {
+  let shape = format!("({}, {})", rows, columns);
+  let dict = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}", shape);
+  let mut header = b"\x93NUMPY\x01\x00".to_vec();
+  header.extend_from_slice(&((HEADER_LEN - 10) as u16).to_le_bytes());
+  header.extend_from_slice(dict.as_bytes());
+  header.resize(HEADER_LEN - 1, b' ');
+  header.push(b'\n');
+  header
+}
+
+/// Measures the mono mix of the input block by block and writes one row of powers per block.
+///
+/// A filter with a longer block than the rows' (the low bins of a log-spaced bank) measures
+/// the latest samples of its own length.
+pub struct PowerExport {
+  bank: Bank,
+  /// The latest samples, as many as the longest filter needs.
+  history: VecDeque<f32>,
+  longest: usize,
+  /// Samples until the next row.
+  due: usize,
+  block_size: usize,
+  row: Vec<f32>,
+  writer: NpyWriter,
+}
+
+/// Where the rows are measured.
+enum Bank {
+  Cpu(GoertzelBank),
+  /// `--gpu`: on the GPU when there is one.
+  #[cfg(feature = "gpu")]
+  Engine(BankEngine),
+}
+
+impl Bank {
+  fn filters(&self) -> &GoertzelBank {
+    match self {
+      Bank::Cpu(bank) => bank,
+      #[cfg(feature = "gpu")]
+      Bank::Engine(engine) => engine.bank(),
+    }
+  }
+}
+
+impl PowerExport {
+  pub fn create(
+    path: impl AsRef<Path>,
+    bank: GoertzelBank,
+    block_size: usize,
+  ) -> Result<Self, anyhow::Error> {
+    Self::with_bank(path, Bank::Cpu(bank), block_size)
+  }
+
+  /// `create`, measuring the rows on the GPU when there is one. The filters must all measure
+  /// `block_size` samples, as the GPU takes the whole row for each.
+  #[cfg(feature = "gpu")]
+  pub fn create_on_gpu(
+    path: impl AsRef<Path>,
+    bank: GoertzelBank,
+    block_size: usize,
+  ) -> Result<Self, anyhow::Error> {
+    if bank.block_sizes().into_iter().any(|n| n != block_size) {
+      anyhow::bail!("--gpu needs filters of one block size, which --log-bins does not give");
+    }
+    Self::with_bank(path, Bank::Engine(BankEngine::new(bank)), block_size)
+  }
+
+  fn with_bank(
+    path: impl AsRef<Path>,
+    bank: Bank,
+    block_size: usize,
+  ) -> Result<Self, anyhow::Error> {
+    let filters = bank.filters();
+    let longest = filters.block_sizes().into_iter().max().unwrap_or(0).max(block_size);
+    Ok(PowerExport {
+      writer: NpyWriter::create(path, filters.len())?,
+      row: vec![0.; filters.len()],
+      history: VecDeque::with_capacity(longest),
+      longest,
+      due: block_size,
+      block_size,
+      bank,
+    })
+  }
+
+  /// Feeds interleaved frames of `channels` channels.
+  pub fn push(&mut self, audio: &[f32], channels: usize) -> Result<(), anyhow::Error> {
+    for frame in audio.chunks(channels) {
+      if self.history.len() == self.longest {
+        self.history.pop_front();
+      }
+      self.history.push_back(frame.iter().sum::<f32>() / channels as f32);
+      self.due -= 1;
+      if self.due == 0 {
+        let history = self.history.make_contiguous();
+        match &mut self.bank {
+          Bank::Cpu(bank) => {
+            for (g, out) in bank.filters().iter().zip(&mut self.row) {
+              *out = g.block_power(&history[history.len().saturating_sub(g.block_size())..]);
+            }
+          }
+          #[cfg(feature = "gpu")]
+          Bank::Engine(engine) => engine.process_into(&[history], &mut self.row),
+        }
+        self.writer.push_row(&self.row)?;
+        self.due = self.block_size;
+      }
+    }
+    Ok(())
+  }
+
+  /// Whether the rows are measured on the GPU.
+  pub fn is_gpu(&self) -> bool {
+    match &self.bank {
+      Bank::Cpu(_) => false,
+      #[cfg(feature = "gpu")]
+      Bank::Engine(engine) => engine.is_gpu(),
+    }
+  }
+
+  pub fn finish(self) -> Result<(), anyhow::Error> {
+    self.writer.finish()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn writes_a_loadable_matrix() {
+    let path = std::env::temp_dir().join(format!("goertzelrs-{}.npy", std::process::id()));
+    let mut writer = NpyWriter::create(&path, 3).unwrap();
+    writer.push_row(&[1., 2., 3.]).unwrap();
+    writer.push_row(&[4., 5., 6.]).unwrap();
+    writer.finish().unwrap();
+
+    let bytes = std::fs::read(&path).unwrap();
+    assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+    assert_eq!(u16::from_le_bytes([bytes[8], bytes[9]]) as usize + 10, HEADER_LEN);
+    let dict = std::str::from_utf8(&bytes[10..HEADER_LEN]).unwrap();
+    assert!(dict.contains("'shape': (2, 3)") && dict.ends_with('\n'), "{}", dict);
+    assert_eq!(bytes.len(), HEADER_LEN + 6 * 4);
+    assert_eq!(&bytes[HEADER_LEN + 20..], 6f32.to_le_bytes());
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[cfg(feature = "gpu")]
+  #[test]
+  fn gpu_rows_match_the_cpu_ones() {
+    let dir = std::env::temp_dir();
+    let path = |name: &str| dir.join(format!("goertzelrs-{}-{}.npy", name, std::process::id()));
+    let bank = || GoertzelBank::with_block_size(&[697., 1209., 3000.], 8000., 160);
+    let mut cpu = PowerExport::create(path("cpu"), bank(), 160).unwrap();
+    let mut gpu = PowerExport::create_on_gpu(path("gpu"), bank(), 160).unwrap();
+    let audio: Vec<f32> = (0..1600).map(|n| (n as f32 * 0.55).sin() * 0.5).collect();
+    cpu.push(&audio, 1).unwrap();
+    gpu.push(&audio, 1).unwrap();
+    cpu.finish().unwrap();
+    gpu.finish().unwrap();
+    let (cpu, gpu) = (std::fs::read(path("cpu")).unwrap(), std::fs::read(path("gpu")).unwrap());
+    assert_eq!(cpu.len(), gpu.len());
+    let values = |bytes: &[u8]| -> Vec<f32> {
+      let data = bytes[HEADER_LEN..].chunks(4);
+      data.map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect()
+    };
+    for (a, b) in values(&cpu).into_iter().zip(values(&gpu)) {
+      assert!((a - b).abs() < 1e-4, "{} vs {}", a, b);
+    }
+    std::fs::remove_file(path("cpu")).unwrap();
+    std::fs::remove_file(path("gpu")).unwrap();
+  }
+}
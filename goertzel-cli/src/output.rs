@@ -0,0 +1,296 @@
+//! Formatting of detector events for `--format`.
+
+use goertzel_core::detector::{self, Event, EventKind};
+use serde_json::{json, Value};
+
+use crate::results::{Record, Repeat};
+use crate::{schema, syslog};
+
+/// Where an event came from, for tagging multi-device and per-channel output.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Origin<'a> {
+  pub source: Option<&'a str>,
+  pub channel: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+  /// `[source chN] <seconds> [label:] <event>`, for people.
+  Text,
+  /// `time,source,channel,event,freq,value,label,confidence` rows.
+  Csv,
+  /// One JSON object per line, as described by `--print-schema`.
+  Ndjson,
+  /// The NDJSON objects as MessagePack maps, back to back.
+  Msgpack,
+  /// The NDJSON objects as CBOR maps, back to back.
+  Cbor,
+  /// RFC 5424 syslog messages, one per record (`--syslog`).
+  Syslog,
+  /// Native journald entries, one per record (`--journald`).
+  Journald,
+}
+
+impl Format {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "text" => Some(Format::Text),
+      "csv" => Some(Format::Csv),
+      "ndjson" => Some(Format::Ndjson),
+      "msgpack" => Some(Format::Msgpack),
+      "cbor" => Some(Format::Cbor),
+      _ => None,
+    }
+  }
+
+  /// Line to print before any event.
+  pub fn header(&self) -> Option<&'static str> {
+    match self {
+      Format::Csv => Some("time,source,channel,event,freq,value,label,confidence"),
+      _ => None,
+    }
+  }
+
+  /// The event as one output record: a line, or bytes for the binary formats.
+  pub fn record(&self, origin: Origin, event: &Event) -> Record {
+    match self {
+      Format::Msgpack => Record::Binary(
+        rmp_serde::to_vec_named(&versioned(origin, event)).expect("JSON values always encode"),
+      ),
+      Format::Cbor => {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&versioned(origin, event), &mut bytes).expect("writing to a Vec");
+        Record::Binary(bytes)
+      }
+      Format::Syslog => Record::Binary(syslog::syslog_record(origin, event)),
+      Format::Journald => Record::Binary(syslog::journald_record(origin, event)),
+      _ => Record::Line(self.event(origin, event)),
+    }
+  }
+
+  /// The event as a line of one of the text formats.
+  pub fn event(&self, origin: Origin, event: &Event) -> String {
+    let time = event.time.seconds();
+    match self {
+      Format::Text => match &event.label {
+        Some(label) => format!("{}{:.3} {}: {}", text_tag(origin), time, label, event.kind),
+        None => format!("{}{:.3} {}", text_tag(origin), time, event.kind),
+      },
+      Format::Csv => {
+        let (name, freq, value) = columns(&event.kind);
+        format!(
+          "{:.6},{},{},{},{},{},{},{}",
+          time,
+          csv_field(origin.source.unwrap_or("")),
+          origin.channel.map(|c| c.to_string()).unwrap_or_default(),
+          name,
+          freq.map(|f| f.to_string()).unwrap_or_default(),
+          csv_field(&value),
+          csv_field(event.label.as_deref().unwrap_or("")),
+          event.confidence.map(|c| c.to_string()).unwrap_or_default(),
+        )
+      }
+      _ => versioned(origin, event).to_string(),
+    }
+  }
+}
+
+/// `object` with the `schema` version in front, as NDJSON and the binary formats write it.
+fn versioned(origin: Origin, event: &Event) -> Value {
+  let mut record = json!({ "schema": schema::VERSION });
+  if let (Value::Object(record), Value::Object(fields)) = (&mut record, object(origin, event)) {
+    record.extend(fields);
+  }
+  record
+}
+
+/// The event as one JSON object, shared by NDJSON and the binary formats.
+pub fn object(origin: Origin, event: &Event) -> Value {
+  let mut record = json!({ "time": event.time.seconds(), "sample": event.time.sample });
+  if let Some(source) = origin.source {
+    record["source"] = json!(source);
+  }
+  if let Some(channel) = origin.channel {
+    record["channel"] = json!(channel);
+  }
+  if let Some(label) = &event.label {
+    record["label"] = json!(label);
+  }
+  if let Some(confidence) = event.confidence {
+    record["confidence"] = json!(confidence);
+  }
+  if let (Value::Object(record), Value::Object(fields)) = (&mut record, fields(&event.kind)) {
+    record.extend(fields);
+  }
+  record
+}
+
+/// `[source chN] ` prefix of the text format, empty without an origin.
+pub fn text_tag(origin: Origin) -> String {
+  match (origin.source, origin.channel) {
+    (Some(source), Some(channel)) => format!("[{} ch{}] ", source, channel),
+    (Some(source), None) => format!("[{}] ", source),
+    (None, Some(channel)) => format!("[ch{}] ", channel),
+    (None, None) => String::new(),
+  }
+}
+
+/// Event name, frequency and value columns of the CSV format.
+pub fn columns(kind: &EventKind) -> (&'static str, Option<f32>, String) {
+  match kind {
+    EventKind::ToneOn { freq, power } => ("tone_on", Some(*freq), power.to_string()),
+    EventKind::ToneOff { freq } => ("tone_off", Some(*freq), String::new()),
+    EventKind::StuckTone { freq, duration } => ("stuck_tone", Some(*freq), duration.to_string()),
+    EventKind::ToneLost { freq, duration } => ("tone_lost", Some(*freq), duration.to_string()),
+    EventKind::Silence { duration } => ("silence", None, duration.to_string()),
+    EventKind::Drift { nominal, freq } => ("drift", Some(*nominal), freq.to_string()),
+    EventKind::DualToneOn { f1, f2 } => ("dual_tone_on", Some(*f1), f2.to_string()),
+    EventKind::DualToneOff { f1, f2 } => ("dual_tone_off", Some(*f1), f2.to_string()),
+    EventKind::CoincidenceOn { freqs } => ("coincidence_on", None, detector::joined(freqs)),
+    EventKind::CoincidenceOff => ("coincidence_off", None, String::new()),
+    EventKind::Level { freq, level } => ("level", Some(*freq), level.to_string()),
+    EventKind::SoundLevel { weighting, dbfs } => (sound_level(*weighting), None, dbfs.to_string()),
+    EventKind::BandLevel { freq, dbfs } => ("band_level", Some(*freq), dbfs.to_string()),
+    EventKind::Tempo { bpm, jitter } => ("tempo", None, format!("{} {}", bpm, jitter)),
+    EventKind::Flatness { value } => ("flatness", None, value.to_string()),
+    EventKind::Digit(d) => ("digit", None, d.to_string()),
+    EventKind::Match(name) => ("match", None, name.clone()),
+    EventKind::Custom(text) => ("custom", None, text.clone()),
+    EventKind::Schedule { active } => {
+      ("schedule", None, if *active { "on" } else { "off" }.to_string())
+    }
+    EventKind::Heartbeat { overruns, callback_gaps, late_blocks, dropped_results } => (
+      "heartbeat",
+      None,
+      format!("{} {} {} {}", overruns, callback_gaps, late_blocks, dropped_results),
+    ),
+    EventKind::Episode { duration, events } => {
+      ("episode", None, format!("{} {}", duration, events.len()))
+    }
+  }
+}
+
+/// What makes an event a repeat of another for `--dedup`: where it came from, what it is, its
+/// frequency, digit or name and its label, but not its time or strength. Measurements have none.
+pub fn repeat(origin: Origin, event: &Event) -> Option<Repeat> {
+  if event.kind.is_measurement() {
+    return None;
+  }
+  let (name, freq, value) = columns(&event.kind);
+  let value = match event.kind {
+    EventKind::DualToneOn { .. }
+    | EventKind::DualToneOff { .. }
+    | EventKind::CoincidenceOn { .. }
+    | EventKind::Digit(_)
+    | EventKind::Match(_)
+    | EventKind::Custom(_) => value,
+    _ => String::new(),
+  };
+  let label = event.label.as_deref().unwrap_or("");
+  let key = format!("{}{} {:?} {} {}", text_tag(origin), name, freq, value, label);
+  Some(Repeat { key, time: event.time.seconds() })
+}
+
+/// Event fields of the NDJSON format. The events of an episode are objects of their own, without
+/// the origin of the episode.
+fn fields(kind: &EventKind) -> Value {
+  match kind {
+    EventKind::ToneOn { freq, power } => json!({ "event": "tone_on", "freq": freq, "power": power }),
+    EventKind::ToneOff { freq } => json!({ "event": "tone_off", "freq": freq }),
+    EventKind::StuckTone { freq, duration } => {
+      json!({ "event": "stuck_tone", "freq": freq, "duration": duration })
+    }
+    EventKind::ToneLost { freq, duration } => {
+      json!({ "event": "tone_lost", "freq": freq, "duration": duration })
+    }
+    EventKind::Silence { duration } => json!({ "event": "silence", "duration": duration }),
+    EventKind::Drift { nominal, freq } => {
+      json!({ "event": "drift", "nominal": nominal, "freq": freq })
+    }
+    EventKind::DualToneOn { f1, f2 } => json!({ "event": "dual_tone_on", "f1": f1, "f2": f2 }),
+    EventKind::DualToneOff { f1, f2 } => json!({ "event": "dual_tone_off", "f1": f1, "f2": f2 }),
+    EventKind::CoincidenceOn { freqs } => json!({ "event": "coincidence_on", "freqs": freqs }),
+    EventKind::CoincidenceOff => json!({ "event": "coincidence_off" }),
+    EventKind::Level { freq, level } => json!({ "event": "level", "freq": freq, "level": level }),
+    EventKind::SoundLevel { weighting, dbfs } => {
+      json!({ "event": "sound_level", "weighting": weighting.to_string(), "dbfs": dbfs })
+    }
+    EventKind::BandLevel { freq, dbfs } => {
+      json!({ "event": "band_level", "freq": freq, "dbfs": dbfs })
+    }
+    EventKind::Tempo { bpm, jitter } => json!({ "event": "tempo", "bpm": bpm, "jitter": jitter }),
+    EventKind::Flatness { value } => json!({ "event": "flatness", "value": value }),
+    EventKind::Digit(d) => json!({ "event": "digit", "digit": d.to_string() }),
+    EventKind::Match(name) => json!({ "event": "match", "name": name }),
+    EventKind::Custom(text) => json!({ "event": "custom", "text": text }),
+    EventKind::Schedule { active } => json!({ "event": "schedule", "active": active }),
+    EventKind::Heartbeat { overruns, callback_gaps, late_blocks, dropped_results } => json!({
+      "event": "heartbeat",
+      "overruns": overruns,
+      "callback_gaps": callback_gaps,
+      "late_blocks": late_blocks,
+      "dropped_results": dropped_results,
+    }),
+    EventKind::Episode { duration, events } => json!({
+      "event": "episode",
+      "duration": duration,
+      "end": events.last().map_or(0., |e| e.time.seconds()),
+      "events": events.iter().map(|e| object(Origin::default(), e)).collect::<Vec<_>>(),
+    }),
+  }
+}
+
+/// CSV event name of a sound level, carrying its weighting.
+fn sound_level(weighting: char) -> &'static str {
+  match weighting {
+    'A' => "sound_level_a",
+    'C' => "sound_level_c",
+    _ => "sound_level_z",
+  }
+}
+
+/// Quotes a CSV field when it needs it.
+fn csv_field(s: &str) -> String {
+  if s.contains([',', '"', '\n']) {
+    format!("\"{}\"", s.replace('"', "\"\""))
+  } else {
+    s.to_string()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use goertzel_core::detector::Time;
+
+  #[test]
+  fn formats_tagged_event() {
+    let time = Time { sample: 8000, sample_rate: 8000. };
+    let event = Event::new(time, EventKind::ToneOn { freq: 1000., power: 0.5 });
+    let origin = Origin { source: Some("line, 1"), channel: Some(2) };
+    assert_eq!(Format::Text.event(origin, &event), "[line, 1 ch2] 1.000 tone on 1000 Hz (0.50)");
+    assert_eq!(Format::Csv.event(origin, &event), "1.000000,\"line, 1\",2,tone_on,1000,0.5,,");
+    let json: Value = serde_json::from_str(&Format::Ndjson.event(origin, &event)).unwrap();
+    assert_eq!(json, json!({
+      "schema": schema::VERSION, "time": 1.0, "sample": 8000, "source": "line, 1", "channel": 2,
+      "event": "tone_on", "freq": 1000.0, "power": 0.5,
+    }));
+
+    for format in [Format::Msgpack, Format::Cbor] {
+      let Record::Binary(bytes) = format.record(origin, &event) else { panic!("binary") };
+      let decoded: Value = match format {
+        Format::Msgpack => rmp_serde::from_slice(&bytes).unwrap(),
+        _ => ciborium::from_reader(bytes.as_slice()).unwrap(),
+      };
+      assert_eq!(decoded, json);
+    }
+
+    let event = Event { label: Some("doorbell".into()), ..event }.with_confidence(0.75);
+    let text = Format::Text.event(origin, &event);
+    assert_eq!(text, "[line, 1 ch2] 1.000 doorbell: tone on 1000 Hz (0.50)");
+    assert!(Format::Csv.event(origin, &event).ends_with(",0.5,doorbell,0.75"));
+    let json: Value = serde_json::from_str(&Format::Ndjson.event(origin, &event)).unwrap();
+    assert_eq!((&json["label"], &json["confidence"]), (&json!("doorbell"), &json!(0.75)));
+  }
+}
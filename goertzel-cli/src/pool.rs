@@ -0,0 +1,195 @@
+//! `--workers`: per-channel detection spread over a pool of threads, so many channels times
+//! many frequencies keep up in real time.
+//!
+//! Each channel belongs to one worker for the whole run, so its blocks are analyzed in order
+//! and its results come out in order. The capture callback only splits the channels and hands
+//! them over through bounded queues; it never waits. A worker too far behind loses the chunk,
+//! counted as `late_blocks`. File input, which can wait, is queued without loss instead.
+//!
+//! At the end of the input, `finish` lets the workers drain their queues and report what their
+//! detectors still hold back.
+
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crossbeam_queue::ArrayQueue;
+use goertzel_core::detector::Event;
+
+use crate::health::HEALTH;
+use crate::output::{Format, Origin};
+use crate::results::ResultSender;
+use crate::Mode;
+
+/// Chunks waiting per worker before new ones are dropped.
+const QUEUE_LEN: usize = 64;
+
+/// Samples of one channel, and the channel.
+struct Chunk {
+  channel: usize,
+  samples: Vec<f32>,
+}
+
+/// What a worker's detectors still held back at the end, by channel.
+type Finished = Vec<(usize, Vec<Event>)>;
+
+pub struct WorkerPool {
+  queues: Vec<SyncSender<Chunk>>,
+  workers: Vec<JoinHandle<Finished>>,
+  channels: usize,
+  /// Samples per analysis block, per channel.
+  block_size: usize,
+  /// Whether `push` waits for room rather than dropping chunks.
+  wait: bool,
+  /// Sample buffers handed back by the workers, reused so the callback need not allocate.
+  spare: Arc<ArrayQueue<Vec<f32>>>,
+}
+
+impl WorkerPool {
+  /// Starts `workers` threads sharing the modes of the channels, channel `c` going to worker
+  /// `c % workers`. With `wait`, `push` blocks while a worker's queue is full.
+  pub fn spawn(
+    modes: Vec<Mode>,
+    workers: usize,
+    wait: bool,
+    source: Option<String>,
+    format: Format,
+    out: ResultSender,
+  ) -> Self {
+    let channels = modes.len();
+    let block_size = modes.first().map_or(1, Mode::block_size);
+    let workers = workers.clamp(1, channels.max(1));
+    let spare = Arc::new(ArrayQueue::new(QUEUE_LEN * workers + channels));
+    let mut owned: Vec<Vec<(usize, Mode)>> = (0..workers).map(|_| Vec::new()).collect();
+    for (channel, mode) in modes.into_iter().enumerate() {
+      owned[channel % workers].push((channel, mode));
+    }
+    let (queues, workers) = owned
+      .into_iter()
+      .map(|modes| {
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_LEN);
+        let (source, out, spare) = (source.clone(), out.clone(), spare.clone());
+        (sender, thread::spawn(move || work(receiver, modes, source, format, out, spare)))
+      })
+      .unzip();
+    WorkerPool { queues, workers, channels, block_size, wait, spare }
+  }
+
+  pub fn block_size(&self) -> usize {
+    self.block_size
+  }
+
+  /// Splits interleaved `samples` and queues each channel to its worker.
+  pub fn push(&mut self, samples: &[f32]) {
+    let workers = self.queues.len();
+    for channel in 0..self.channels {
+      let mut buffer = self.spare.pop().unwrap_or_default();
+      buffer.clear();
+      buffer.extend(samples.iter().skip(channel).step_by(self.channels));
+      let chunk = Chunk { channel, samples: buffer };
+      let queue = &self.queues[channel % workers];
+      if self.wait {
+        if let Err(mpsc::SendError(chunk)) = queue.send(chunk) {
+          let _ = self.spare.push(chunk.samples);
+        }
+        continue;
+      }
+      match queue.try_send(chunk) {
+        Ok(()) => {}
+        Err(TrySendError::Full(chunk)) | Err(TrySendError::Disconnected(chunk)) => {
+          HEALTH.count_late_block();
+          let _ = self.spare.push(chunk.samples);
+        }
+      }
+    }
+  }
+
+  /// Waits for the workers to analyze what is queued and to report what their detectors still
+  /// hold back, and returns those events of each channel, in channel order.
+  pub fn finish(self) -> Vec<Vec<Event>> {
+    drop(self.queues);
+    let mut finished: Finished = self
+      .workers
+      .into_iter()
+      .flat_map(|worker| worker.join().unwrap_or_default())
+      .collect();
+    finished.sort_by_key(|&(channel, _)| channel);
+    finished.into_iter().map(|(_, events)| events).collect()
+  }
+}
+
+fn work(
+  chunks: Receiver<Chunk>,
+  mut modes: Vec<(usize, Mode)>,
+  source: Option<String>,
+  format: Format,
+  out: ResultSender,
+  spare: Arc<ArrayQueue<Vec<f32>>>,
+) -> Finished {
+  let origin = |channel| Origin { source: source.as_deref(), channel: Some(channel) };
+  for chunk in chunks {
+    if let Some((channel, mode)) = modes.iter_mut().find(|(c, _)| *c == chunk.channel) {
+      mode.process(&chunk.samples, origin(*channel), format, &out);
+    }
+    let _ = spare.push(chunk.samples);
+  }
+  modes
+    .into_iter()
+    .map(|(channel, mut mode)| (channel, mode.finish(origin(channel), format, &out)))
+    .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::results;
+  use goertzel_core::detector::{Detector, Event, EventKind, Time};
+  use goertzel_core::Pipeline;
+
+  /// Reports the first sample of every block, so the order of the output shows the order the
+  /// blocks were analyzed in.
+  struct FirstSample;
+
+  impl Detector for FirstSample {
+    fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+      vec![Event::new(t, EventKind::Custom(block[0].to_string()))]
+    }
+  }
+
+  #[test]
+  fn keeps_each_channel_in_order() {
+    let (out, receiver) = results::channel(10_000);
+    let modes = (0..4)
+      .map(|_| Mode::Detect {
+        pipeline: Pipeline::new(8000., 10).with(Box::new(FirstSample)),
+        decimator: None,
+        events_only: false,
+      })
+      .collect();
+    let mut pool = WorkerPool::spawn(modes, 3, false, None, Format::Text, out);
+    // Channel c carries 1000 * c + frame index.
+    for chunk in 0..50 {
+      let frames: Vec<f32> =
+        (0..40).flat_map(|f| (0..4).map(move |c| (1000 * c + chunk * 40 + f) as f32)).collect();
+      pool.push(&frames);
+      // Stay within the queues, as real time input would.
+      thread::sleep(std::time::Duration::from_millis(1));
+    }
+    let mut seen = vec![Vec::new(); 4];
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while seen.iter().map(Vec::len).sum::<usize>() < 800 && std::time::Instant::now() < deadline {
+      match receiver.recv().map(|e| e.record) {
+        Some(results::Record::Line(line)) => {
+          let channel: usize = line[3..4].parse().unwrap();
+          seen[channel].push(line.rsplit(' ').next().unwrap().parse::<u32>().unwrap());
+        }
+        _ => thread::sleep(std::time::Duration::from_millis(1)),
+      }
+    }
+    for (c, values) in seen.iter().enumerate() {
+      let expected: Vec<u32> = (0..200).map(|b| 1000 * c as u32 + 10 * b).collect();
+      assert_eq!(values, &expected);
+    }
+  }
+}
@@ -0,0 +1,55 @@
+//! Bundled settings for common deployments, selected with `--profile`.
+
+use goertzel_dtmf::DtmfConfig;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+  pub sample_rate: f32,
+  /// Analysis block size at `sample_rate`.
+  pub block_size: usize,
+  pub dtmf: DtmfConfig,
+}
+
+impl Profile {
+  /// 8 kHz G.711 lines: 102-sample (12.75 ms) blocks and the Q.24 twist limits of 8 dB normal
+  /// and 4 dB reverse.
+  pub fn telephony() -> Self {
+    Self {
+      sample_rate: 8000.,
+      block_size: 102,
+      dtmf: DtmfConfig {
+        threshold: 0.2,
+        max_normal_twist_db: 8.,
+        max_reverse_twist_db: 4.,
+        min_pause: 0.03,
+        max_digit_rate: 10.,
+      },
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "telephony" => Some(Self::telephony()),
+      _ => None,
+    }
+  }
+
+  /// Block size with the same duration when the input runs at a different rate.
+  pub fn block_size_at(&self, sample_rate: f32) -> usize {
+    (self.block_size as f32 * sample_rate / self.sample_rate).round() as usize
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn block_duration_is_kept_across_rates() {
+    let p = Profile::from_name("telephony").unwrap();
+    assert_eq!(p.block_size_at(8000.), 102);
+    assert_eq!(p.block_size_at(48000.), 612);
+    assert!(Profile::from_name("nope").is_none());
+  }
+}
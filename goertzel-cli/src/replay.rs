@@ -0,0 +1,194 @@
+//! `goertzelrs replay events.ndjson --speed 10x`: a recorded NDJSON log sent again through the
+//! configured output, paced like the original, to exercise whatever consumes the events without
+//! live audio.
+
+use std::io::BufRead;
+use std::time::{Duration, Instant};
+
+use goertzel_core::detector::{Event, EventKind, Time};
+use serde_json::Value;
+
+use crate::output::{Format, Origin};
+use crate::results::ResultSender;
+use crate::schema;
+
+/// Sample rate assumed for records at sample 0, before any other tells it.
+const DEFAULT_SAMPLE_RATE: f32 = 8000.;
+
+/// How fast to replay: a multiple of the recorded pace, or as fast as the output takes it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+  Factor(f64),
+  Max,
+}
+
+impl Speed {
+  /// `max`, `1x`, `10x` or `0.5x`.
+  pub fn parse(spec: &str) -> Option<Self> {
+    if spec == "max" {
+      return Some(Speed::Max);
+    }
+    let factor: f64 = spec.strip_suffix('x')?.parse().ok()?;
+    (factor > 0.).then_some(Speed::Factor(factor))
+  }
+}
+
+/// One record read back: where it came from and the event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recorded {
+  pub source: Option<String>,
+  pub channel: Option<usize>,
+  pub event: Event,
+}
+
+/// Parses an NDJSON record, `sample_rate` standing in when its own cannot be worked out.
+pub fn parse(line: &str, sample_rate: f32) -> Result<Recorded, anyhow::Error> {
+  let record: Value = serde_json::from_str(line)?;
+  if let Some(version) = record.get("schema") {
+    if version != schema::VERSION {
+      anyhow::bail!("record schema {} is not {}", version, schema::VERSION);
+    }
+  }
+  let number = |key: &str| -> Result<f32, anyhow::Error> {
+    let value = record.get(key).and_then(Value::as_f64);
+    Ok(value.ok_or_else(|| anyhow::anyhow!("record without `{}`", key))? as f32)
+  };
+  let text = |key: &str| -> Result<String, anyhow::Error> {
+    let value = record.get(key).and_then(Value::as_str);
+    Ok(value.ok_or_else(|| anyhow::anyhow!("record without `{}`", key))?.to_string())
+  };
+  let count = |key: &str| -> Result<u64, anyhow::Error> {
+    let value = record.get(key).and_then(Value::as_u64);
+    value.ok_or_else(|| anyhow::anyhow!("record without `{}`", key))
+  };
+  let sample = record.get("sample").and_then(Value::as_u64).unwrap_or(0);
+  let time = record.get("time").and_then(Value::as_f64).unwrap_or(0.);
+  let sample_rate =
+    if sample > 0 && time > 0. { (sample as f64 / time) as f32 } else { sample_rate };
+  let kind = match text("event")?.as_str() {
+    "tone_on" => EventKind::ToneOn { freq: number("freq")?, power: number("power")? },
+    "tone_off" => EventKind::ToneOff { freq: number("freq")? },
+    "stuck_tone" => EventKind::StuckTone { freq: number("freq")?, duration: number("duration")? },
+    "tone_lost" => EventKind::ToneLost { freq: number("freq")?, duration: number("duration")? },
+    "silence" => EventKind::Silence { duration: number("duration")? },
+    "drift" => EventKind::Drift { nominal: number("nominal")?, freq: number("freq")? },
+    "dual_tone_on" => EventKind::DualToneOn { f1: number("f1")?, f2: number("f2")? },
+    "dual_tone_off" => EventKind::DualToneOff { f1: number("f1")?, f2: number("f2")? },
+    "coincidence_on" => EventKind::CoincidenceOn {
+      freqs: record
+        .get("freqs")
+        .and_then(Value::as_array)
+        .and_then(|freqs| freqs.iter().map(|f| Some(f.as_f64()? as f32)).collect())
+        .ok_or_else(|| anyhow::anyhow!("record without `freqs`"))?,
+    },
+    "coincidence_off" => EventKind::CoincidenceOff,
+    "level" => EventKind::Level { freq: number("freq")?, level: number("level")? },
+    "sound_level" => EventKind::SoundLevel {
+      weighting: text("weighting")?.chars().next().unwrap_or('Z'),
+      dbfs: number("dbfs")?,
+    },
+    "band_level" => EventKind::BandLevel { freq: number("freq")?, dbfs: number("dbfs")? },
+    "tempo" => EventKind::Tempo { bpm: number("bpm")?, jitter: number("jitter")? },
+    "flatness" => EventKind::Flatness { value: number("value")? },
+    "digit" => EventKind::Digit(text("digit")?.chars().next().unwrap_or('?')),
+    "match" => EventKind::Match(text("name")?),
+    "custom" => EventKind::Custom(text("text")?),
+    "schedule" => EventKind::Schedule {
+      active: record.get("active").and_then(Value::as_bool).ok_or_else(|| {
+        anyhow::anyhow!("record without `active`")
+      })?,
+    },
+    "heartbeat" => EventKind::Heartbeat {
+      overruns: count("overruns")?,
+      callback_gaps: count("callback_gaps")?,
+      late_blocks: count("late_blocks")?,
+      dropped_results: count("dropped_results")?,
+    },
+    "episode" => EventKind::Episode {
+      duration: number("duration")?,
+      events: record
+        .get("events")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow::anyhow!("record without `events`"))?
+        .iter()
+        .map(|e| Ok(parse(&e.to_string(), sample_rate)?.event))
+        .collect::<Result<_, anyhow::Error>>()?,
+    },
+    other => anyhow::bail!("unknown event `{}`", other),
+  };
+  let mut event = Event::new(Time { sample, sample_rate }, kind);
+  event.label = record.get("label").and_then(Value::as_str).map(str::to_string);
+  event.confidence = record.get("confidence").and_then(Value::as_f64).map(|c| c as f32);
+  Ok(Recorded {
+    source: record.get("source").and_then(Value::as_str).map(str::to_string),
+    channel: record.get("channel").and_then(Value::as_u64).map(|c| c as usize),
+    event,
+  })
+}
+
+/// Sends every record of `path` to `out` in `format`, waiting out the recorded gaps between
+/// them divided by `speed`.
+pub fn run(
+  path: &str,
+  speed: Speed,
+  format: Format,
+  out: &ResultSender,
+) -> Result<(), anyhow::Error> {
+  let file = std::io::BufReader::new(std::fs::File::open(path)?);
+  let started = Instant::now();
+  let mut first: Option<f64> = None;
+  let mut sample_rate = DEFAULT_SAMPLE_RATE;
+  for (number, line) in file.lines().enumerate() {
+    let line = line?;
+    if line.trim().is_empty() {
+      continue;
+    }
+    let recorded =
+      parse(&line, sample_rate).map_err(|e| anyhow::anyhow!("{}:{}: {}", path, number + 1, e))?;
+    sample_rate = recorded.event.time.sample_rate;
+    let at = recorded.event.time.seconds();
+    if let Speed::Factor(factor) = speed {
+      let due = (at - *first.get_or_insert(at)).max(0.) / factor;
+      let due = Duration::from_secs_f64(due);
+      if let Some(wait) = due.checked_sub(started.elapsed()) {
+        std::thread::sleep(wait);
+      }
+    }
+    out.wait_for_room();
+    let origin = Origin { source: recorded.source.as_deref(), channel: recorded.channel };
+    out.send(format.record(origin, &recorded.event));
+  }
+  out.flush();
+  Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reads_back_what_ndjson_wrote() {
+    let origin = Origin { source: Some("line 1"), channel: Some(3) };
+    let mut event = Event::new(
+      Time { sample: 12000, sample_rate: 8000. },
+      EventKind::ToneOn { freq: 1000., power: 0.5 },
+    );
+    event.label = Some("doorbell".into());
+    event.confidence = Some(0.5);
+    let recorded = parse(&Format::Ndjson.event(origin, &event), 44100.).unwrap();
+    assert_eq!(recorded.event, event);
+    assert_eq!((recorded.source.as_deref(), recorded.channel), (Some("line 1"), Some(3)));
+    let episode = Event::new(
+      Time { sample: 16000, sample_rate: 8000. },
+      EventKind::Episode { duration: 0.5, events: vec![event.clone(), event] },
+    );
+    assert_eq!(parse(&Format::Ndjson.event(origin, &episode), 44100.).unwrap().event, episode);
+
+    let future = r#"{"schema": 99, "time": 0.0, "sample": 0, "event": "digit", "digit": "5"}"#;
+    assert!(parse(future, 8000.).is_err());
+    assert_eq!(Speed::parse("10x"), Some(Speed::Factor(10.)));
+    assert_eq!(Speed::parse("max"), Some(Speed::Max));
+    assert_eq!(Speed::parse("fast"), None);
+  }
+}
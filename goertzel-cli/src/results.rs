@@ -0,0 +1,320 @@
+//! Hand-off of result lines from the audio callbacks to the threads that write them out, one
+//! per sink.
+//!
+//! Every sink has its own bounded, lock-free queue: when its writer falls behind, the oldest
+//! lines pending for it are dropped and counted, so a slow stdout or network sink can never stall
+//! the audio callback, nor hold back the other sinks.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_queue::ArrayQueue;
+
+use crate::health::{drift_status, take_rate_warnings, HEALTH};
+use crate::limit::Limit;
+use crate::sink::EventSink;
+
+/// Lines waiting for the writer thread.
+pub const CAPACITY: usize = 4096;
+
+/// What the writer thread writes out: a line of text, or a binary record written as it is.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Record {
+  Line(String),
+  Binary(Vec<u8>),
+}
+
+impl From<String> for Record {
+  fn from(line: String) -> Self {
+    Record::Line(line)
+  }
+}
+
+/// What makes an event a repeat of another, and when in the stream it happened, for `--dedup`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Repeat {
+  pub key: String,
+  pub time: f64,
+}
+
+/// A record as queued for a sink.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+  pub record: Record,
+  /// Set for events.
+  pub repeat: Option<Repeat>,
+}
+
+struct Shared {
+  queue: ArrayQueue<Entry>,
+  dropped: AtomicU64,
+  /// Lines queued and lines written out, for `flush`.
+  sent: AtomicU64,
+  written: AtomicU64,
+  /// Set once the sink failed; nothing is queued for it any more.
+  closed: AtomicBool,
+}
+
+impl Shared {
+  fn pending(&self) -> bool {
+    !self.closed.load(Ordering::Acquire)
+      && self.written.load(Ordering::Acquire) + self.dropped.load(Ordering::Relaxed)
+        < self.sent.load(Ordering::Relaxed)
+  }
+}
+
+/// Producer end, cheap to clone into every callback.
+#[derive(Clone)]
+pub struct ResultSender {
+  lanes: Arc<[Arc<Shared>]>,
+}
+
+impl ResultSender {
+  /// Queues a record for every sink, dropping the oldest record queued for a sink whose queue is
+  /// full. Never blocks.
+  pub fn send(&self, record: impl Into<Record>) {
+    self.queue(Entry { record: record.into(), repeat: None });
+  }
+
+  /// Like `send`, for the record of an event.
+  pub fn send_event(&self, record: Record, repeat: Repeat) {
+    self.queue(Entry { record, repeat: Some(repeat) });
+  }
+
+  fn queue(&self, entry: Entry) {
+    let mut entry = Some(entry);
+    let mut open = self.lanes.iter().filter(|l| !l.closed.load(Ordering::Relaxed)).peekable();
+    while let Some(lane) = open.next() {
+      // The last sink takes the entry itself.
+      let copy = match open.peek() {
+        Some(_) => entry.clone(),
+        None => entry.take(),
+      };
+      lane.sent.fetch_add(1, Ordering::Relaxed);
+      if copy.and_then(|r| lane.queue.force_push(r)).is_some() {
+        lane.dropped.fetch_add(1, Ordering::Relaxed);
+      }
+    }
+  }
+
+  /// Waits until every queue is at most half full. Offline inputs, which can produce lines
+  /// faster than they are written, call this instead of dropping them; never call it from an
+  /// audio callback.
+  pub fn wait_for_room(&self) {
+    let full = |l: &Arc<Shared>| {
+      !l.closed.load(Ordering::Acquire) && l.queue.len() > l.queue.capacity() / 2
+    };
+    while self.lanes.iter().any(full) {
+      thread::sleep(Duration::from_millis(1));
+    }
+  }
+
+  /// Waits until every line sent so far was written out (or dropped) by every working sink.
+  pub fn flush(&self) {
+    while self.lanes.iter().any(|l| l.pending()) {
+      thread::sleep(Duration::from_millis(1));
+    }
+  }
+
+  /// Lines dropped so far because a queue was full, over all sinks.
+  pub fn dropped_results(&self) -> u64 {
+    self.lanes.iter().map(|l| l.dropped.load(Ordering::Relaxed)).sum()
+  }
+}
+
+pub struct ResultReceiver {
+  shared: Arc<Shared>,
+}
+
+impl ResultReceiver {
+  pub fn recv(&self) -> Option<Entry> {
+    self.shared.queue.pop()
+  }
+
+  /// Marks a received line as written (or held back by the sink's limits), releasing
+  /// `ResultSender::flush`.
+  pub fn written(&self) {
+    self.shared.written.fetch_add(1, Ordering::Release);
+  }
+
+  /// Gives up on the sink: nothing more is queued for it, and `flush` no longer waits for it.
+  pub fn close(&self) {
+    self.shared.closed.store(true, Ordering::Release);
+    while self.shared.queue.pop().is_some() {}
+  }
+}
+
+pub fn channel(capacity: usize) -> (ResultSender, ResultReceiver) {
+  let (sender, mut receivers) = fan_out(capacity, 1);
+  (sender, receivers.remove(0))
+}
+
+/// One sender queuing every record for each of `sinks` receivers, `capacity` records each.
+pub fn fan_out(capacity: usize, sinks: usize) -> (ResultSender, Vec<ResultReceiver>) {
+  let lanes: Vec<Arc<Shared>> = (0..sinks)
+    .map(|_| {
+      Arc::new(Shared {
+        queue: ArrayQueue::new(capacity),
+        dropped: AtomicU64::new(0),
+        sent: AtomicU64::new(0),
+        written: AtomicU64::new(0),
+        closed: AtomicBool::new(false),
+      })
+    })
+    .collect();
+  let receivers = lanes.iter().map(|shared| ResultReceiver { shared: shared.clone() }).collect();
+  (ResultSender { lanes: lanes.into() }, receivers)
+}
+
+/// Starts a thread per sink writing the records queued for it, and one printing a status line
+/// with the stream health counters, the `dropped_results` count and the `--drift` summary to
+/// stderr whenever one of them changed (at most once a second). `high_priority` asks the OS to
+/// schedule the writer threads ahead of others, which usually needs privileges; failing to get
+/// it is only warned about.
+///
+/// Each sink skips what its `Limit` holds back. A sink that fails is reported and given up on
+/// while the others carry on; once none is left the program stops, quietly if the last one was a
+/// closed pipe (e.g. piped into `head`), like other tools do.
+pub fn spawn_printer(
+  capacity: usize,
+  high_priority: bool,
+  sinks: Vec<(Box<dyn EventSink>, Limit)>,
+) -> ResultSender {
+  let (sender, receivers) = fan_out(capacity, sinks.len());
+  let working = Arc::new(AtomicUsize::new(sinks.len()));
+  for ((mut sink, mut limit), receiver) in sinks.into_iter().zip(receivers) {
+    let working = working.clone();
+    thread::spawn(move || {
+      if high_priority {
+        raise_priority();
+      }
+      loop {
+        let Some(entry) = receiver.recv() else {
+          thread::sleep(Duration::from_millis(10));
+          continue;
+        };
+        if !limit.admit(entry.repeat.as_ref(), Instant::now()) {
+          receiver.written();
+          continue;
+        }
+        match sink.write(&entry.record) {
+          Ok(()) => receiver.written(),
+          Err(e) => {
+            let broken_pipe = e.kind() == std::io::ErrorKind::BrokenPipe;
+            if !broken_pipe {
+              eprintln!("could not write results to {}: {}", sink.name(), e);
+            }
+            receiver.close();
+            if working.fetch_sub(1, Ordering::AcqRel) == 1 {
+              std::process::exit(if broken_pipe { 0 } else { 1 });
+            }
+            return;
+          }
+        }
+      }
+    });
+  }
+  let status = sender.clone();
+  thread::spawn(move || {
+    let mut reported = (0, 0, 0, 0, String::new());
+    let mut last_report = Instant::now();
+    loop {
+      thread::sleep(Duration::from_millis(10));
+      // Raised by the audio callbacks, which must not write to stderr themselves.
+      for warning in take_rate_warnings() {
+        eprintln!("{}", warning);
+      }
+      let counters = (
+        HEALTH.overruns.load(Ordering::Relaxed),
+        HEALTH.callback_gaps.load(Ordering::Relaxed),
+        HEALTH.late_blocks.load(Ordering::Relaxed),
+        status.dropped_results(),
+        drift_status(),
+      );
+      if counters != reported && last_report.elapsed() >= Duration::from_secs(1) {
+        eprintln!(
+          "status overruns {} callback_gaps {} late_blocks {} dropped_results {}{}",
+          counters.0, counters.1, counters.2, counters.3, counters.4
+        );
+        reported = counters;
+        last_report = Instant::now();
+      }
+    }
+  });
+  sender
+}
+
+#[cfg(feature = "priority")]
+fn raise_priority() {
+  use thread_priority::{set_current_thread_priority, ThreadPriority};
+  if let Err(e) = set_current_thread_priority(ThreadPriority::Max) {
+    eprintln!("could not raise the writer thread priority: {:?}", e);
+  }
+}
+
+#[cfg(not(feature = "priority"))]
+fn raise_priority() {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn full_queue_drops_oldest_lines() {
+    let (sender, receiver) = channel(2);
+    for line in ["a", "b", "c", "d"] {
+      sender.send(line.to_string());
+    }
+    assert_eq!(sender.dropped_results(), 2);
+    assert_eq!(receiver.recv().map(|e| e.record), Some(Record::Line("c".into())));
+    assert_eq!(receiver.recv().map(|e| e.record), Some(Record::Line("d".into())));
+    assert_eq!(receiver.recv(), None);
+  }
+
+  type Records = Arc<std::sync::Mutex<Vec<Record>>>;
+
+  struct Collect(Records);
+
+  impl EventSink for Collect {
+    fn name(&self) -> String {
+      "collect".into()
+    }
+
+    fn write(&mut self, record: &Record) -> std::io::Result<()> {
+      self.0.lock().unwrap().push(record.clone());
+      Ok(())
+    }
+  }
+
+  struct Broken;
+
+  impl EventSink for Broken {
+    fn name(&self) -> String {
+      "broken".into()
+    }
+
+    fn write(&mut self, _: &Record) -> std::io::Result<()> {
+      Err(std::io::Error::other("gone"))
+    }
+  }
+
+  #[test]
+  fn failing_sink_leaves_the_others_alone() {
+    let (first, second): (Records, Records) = (Arc::default(), Arc::default());
+    let sinks: Vec<(Box<dyn EventSink>, Limit)> = vec![
+      (Box::new(Collect(first.clone())), Limit::default()),
+      (Box::new(Broken), Limit::default()),
+      (Box::new(Collect(second.clone())), Limit::default()),
+    ];
+    let sender = spawn_printer(16, false, sinks);
+    for line in ["a", "b", "c"] {
+      sender.send(line.to_string());
+    }
+    sender.flush();
+    for records in [first, second] {
+      assert_eq!(records.lock().unwrap().len(), 3);
+    }
+  }
+}
@@ -0,0 +1,207 @@
+//! `--log-file`: results written to a file that is rotated by day or size, so an always-on
+//! monitor needs no logrotate setup. Live input runs until Ctrl-C or SIGTERM, which flushes the
+//! file; a quiet line still gets a daily file if `--heartbeat` writes a record now and then.
+//!
+//! Rotated files are renamed to `<path>.<UTC time the file was started>`, and with gzip
+//! compressed in the background to `<that>.gz`.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rotate {
+  /// At the first write after UTC midnight.
+  Daily,
+  /// Once the file reaches this many bytes.
+  Size(u64),
+}
+
+impl Rotate {
+  /// Parses `daily` or `size=<n>[K|M|G][B]`, e.g. `size=100MB`.
+  pub fn parse(spec: &str) -> Option<Self> {
+    if spec == "daily" {
+      return Some(Rotate::Daily);
+    }
+    let size = spec.strip_prefix("size=")?.to_ascii_uppercase();
+    let size = size.strip_suffix('B').unwrap_or(&size);
+    let (digits, unit) = match size.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+      Some((i, _)) => size.split_at(i),
+      None => (size, ""),
+    };
+    let scale = match unit {
+      "" => 1,
+      "K" => 1 << 10,
+      "M" => 1 << 20,
+      "G" => 1 << 30,
+      _ => return None,
+    };
+    let bytes = digits.parse::<u64>().ok()? * scale;
+    (bytes > 0).then_some(Rotate::Size(bytes))
+  }
+}
+
+/// Appends to `path`, moving it aside when the policy says so. Rotation only happens on
+/// `flush`, which the writer calls between records, so a record is never split across files.
+pub struct RotatingFile {
+  path: PathBuf,
+  rotate: Option<Rotate>,
+  gzip: bool,
+  file: File,
+  /// Bytes in the current file.
+  size: u64,
+  /// When the current file was started, in seconds since the epoch.
+  started: u64,
+}
+
+impl RotatingFile {
+  pub fn open(path: impl AsRef<Path>, rotate: Option<Rotate>, gzip: bool) -> io::Result<Self> {
+    let path = path.as_ref().to_path_buf();
+    let file = OpenOptions::new().create(true).append(true).open(&path)?;
+    let size = file.metadata()?.len();
+    Ok(RotatingFile { path, rotate, gzip, file, size, started: now() })
+  }
+
+  fn due(&self) -> bool {
+    match self.rotate {
+      None => false,
+      Some(Rotate::Daily) => now() / 86_400 != self.started / 86_400,
+      Some(Rotate::Size(limit)) => self.size >= limit,
+    }
+  }
+
+  fn rotate(&mut self) -> io::Result<()> {
+    let mut rotated = self.path.clone().into_os_string();
+    rotated.push(format!(".{}", timestamp(self.started)));
+    let mut target = PathBuf::from(&rotated);
+    for n in 1.. {
+      if !target.exists() && !gz_path(&target).exists() {
+        break;
+      }
+      target = PathBuf::from(format!("{}.{}", rotated.to_string_lossy(), n));
+    }
+    std::fs::rename(&self.path, &target)?;
+    self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+    self.size = 0;
+    self.started = now();
+    if self.gzip {
+      std::thread::spawn(move || {
+        if let Err(e) = compress(&target) {
+          eprintln!("could not compress {}: {}", target.display(), e);
+        }
+      });
+    }
+    Ok(())
+  }
+}
+
+impl Write for RotatingFile {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let written = self.file.write(buf)?;
+    self.size += written as u64;
+    Ok(written)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.file.flush()?;
+    if self.due() {
+      self.rotate()?;
+    }
+    Ok(())
+  }
+}
+
+/// `path` with `.gz` appended.
+fn gz_path(path: &Path) -> PathBuf {
+  let mut gz = path.as_os_str().to_owned();
+  gz.push(".gz");
+  PathBuf::from(gz)
+}
+
+/// Replaces `path` with `path.gz`.
+fn compress(path: &Path) -> io::Result<()> {
+  let gz = File::create(gz_path(path))?;
+  let mut encoder = flate2::write::GzEncoder::new(gz, flate2::Compression::default());
+  io::copy(&mut File::open(path)?, &mut encoder)?;
+  encoder.finish()?;
+  std::fs::remove_file(path)
+}
+
+fn now() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// `YYYY-MM-DDTHHMMSS` in UTC, which sorts chronologically.
+fn timestamp(secs: u64) -> String {
+  // Civil date from days since the epoch (Howard Hinnant's algorithm).
+  let days = (secs / 86_400) as i64 + 719_468;
+  let era = days.div_euclid(146_097);
+  let doe = days.rem_euclid(146_097);
+  let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + (month <= 2) as i64;
+  let time = secs % 86_400;
+  format!(
+    "{:04}-{:02}-{:02}T{:02}{:02}{:02}",
+    year,
+    month,
+    day,
+    time / 3600,
+    time / 60 % 60,
+    time % 60
+  )
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_policies_and_dates() {
+    assert_eq!(Rotate::parse("daily"), Some(Rotate::Daily));
+    assert_eq!(Rotate::parse("size=100MB"), Some(Rotate::Size(100 << 20)));
+    assert_eq!(Rotate::parse("size=64k"), Some(Rotate::Size(64 << 10)));
+    assert_eq!(Rotate::parse("size=500"), Some(Rotate::Size(500)));
+    assert_eq!(Rotate::parse("size=0"), None);
+    assert_eq!(Rotate::parse("weekly"), None);
+    assert_eq!(timestamp(0), "1970-01-01T000000");
+    assert_eq!(timestamp(1_791_978_303), "2026-10-14T114503");
+  }
+
+  #[test]
+  fn rotates_between_records() {
+    let dir = std::env::temp_dir().join(format!("goertzelrs-rotate-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("detections.log");
+    let mut log = RotatingFile::open(&path, Some(Rotate::Size(10)), false).unwrap();
+    for line in ["first line", "second", "third"] {
+      writeln!(log, "{}", line).unwrap();
+      log.flush().unwrap();
+    }
+    let mut files: Vec<String> = std::fs::read_dir(&dir)
+      .unwrap()
+      .map(|e| std::fs::read_to_string(e.unwrap().path()).unwrap())
+      .collect();
+    files.sort();
+    assert_eq!(files, ["", "first line\n", "second\nthird\n"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // A file started yesterday is moved aside at the first record of today.
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut log = RotatingFile::open(&path, Some(Rotate::Daily), false).unwrap();
+    writeln!(log, "yesterday").unwrap();
+    log.flush().unwrap();
+    log.started -= 86_400;
+    let yesterday = format!("detections.log.{}", timestamp(log.started));
+    writeln!(log, "today").unwrap();
+    log.flush().unwrap();
+    assert_eq!(std::fs::read_to_string(dir.join(yesterday)).unwrap(), "yesterday\ntoday\n");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+}
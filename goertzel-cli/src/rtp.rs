@@ -0,0 +1,125 @@
+//! RTP/UDP input carrying G.711 audio.
+
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+pub const SAMPLE_RATE: f32 = 8000.;
+
+const PT_PCMU: u8 = 0;
+const PT_PCMA: u8 = 8;
+
+/// How long [`RtpSource::read`] waits for a packet, so callers can poll for a stop request.
+const READ_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Decodes a G.711 µ-law byte to a sample in [-1, 1).
+pub fn ulaw_to_linear(u: u8) -> f32 {
+  let u = !u;
+  let exponent = (u >> 4) & 0x07;
+  let mantissa = (u & 0x0F) as i32;
+  let sample = (((mantissa << 3) + 0x84) << exponent) - 0x84;
+  let sample = if u & 0x80 != 0 { -sample } else { sample };
+  sample as f32 / 32768.
+}
+
+/// Decodes a G.711 A-law byte to a sample in [-1, 1).
+pub fn alaw_to_linear(a: u8) -> f32 {
+  let a = a ^ 0x55;
+  let exponent = (a >> 4) & 0x07;
+  let mantissa = (a & 0x0F) as i32;
+  let mut sample = (mantissa << 4) + 8;
+  if exponent != 0 {
+    sample += 0x100;
+  }
+  if exponent > 1 {
+    sample <<= exponent - 1;
+  }
+  let sample = if a & 0x80 != 0 { sample } else { -sample };
+  sample as f32 / 32768.
+}
+
+/// Splits an RTP packet into its payload type and payload, skipping CSRCs, the header extension
+/// and padding. Returns `None` for anything that isn't a well-formed RTP v2 packet.
+pub fn parse_packet(packet: &[u8]) -> Option<(u8, &[u8])> {
+  if packet.len() < 12 || packet[0] >> 6 != 2 {
+    return None;
+  }
+  let padding = packet[0] & 0x20 != 0;
+  let extension = packet[0] & 0x10 != 0;
+  let csrc_count = (packet[0] & 0x0F) as usize;
+  let payload_type = packet[1] & 0x7F;
+
+  let mut start = 12 + 4 * csrc_count;
+  if extension {
+    let ext = packet.get(start..start + 4)?;
+    let words = u16::from_be_bytes([ext[2], ext[3]]) as usize;
+    start += 4 + 4 * words;
+  }
+  let mut end = packet.len();
+  if padding {
+    end = end.checked_sub(*packet.last()? as usize)?;
+  }
+  if start > end {
+    return None;
+  }
+  Some((payload_type, &packet[start..end]))
+}
+
+/// Receives G.711 µ-law (PT 0) or A-law (PT 8) RTP on a UDP socket.
+pub struct RtpSource {
+  socket: UdpSocket,
+  buf: Vec<u8>,
+}
+
+impl RtpSource {
+  pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+    let socket = UdpSocket::bind(addr)?;
+    socket.set_read_timeout(Some(READ_TIMEOUT))?;
+    Ok(Self { socket, buf: vec![0; 2048] })
+  }
+
+  /// Waits for the next packet, up to `READ_TIMEOUT`, and appends its decoded samples to `out`.
+  /// Packets with other payload types are silently dropped, and nothing is appended if none
+  /// came.
+  pub fn read(&mut self, out: &mut Vec<f32>) -> io::Result<()> {
+    let len = match self.socket.recv(&mut self.buf) {
+      Ok(len) => len,
+      Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+        return Ok(());
+      }
+      Err(e) => return Err(e),
+    };
+    match parse_packet(&self.buf[..len]) {
+      Some((PT_PCMU, payload)) => out.extend(payload.iter().map(|&b| ulaw_to_linear(b))),
+      Some((PT_PCMA, payload)) => out.extend(payload.iter().map(|&b| alaw_to_linear(b))),
+      _ => {}
+    }
+    Ok(())
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn g711_reference_values() {
+    assert_eq!(ulaw_to_linear(0xFF), 0.);
+    assert_eq!(ulaw_to_linear(0x00), -32124. / 32768.);
+    assert_eq!(ulaw_to_linear(0x80), 32124. / 32768.);
+    assert_eq!(alaw_to_linear(0xD5), 8. / 32768.);
+    assert_eq!(alaw_to_linear(0x55), -8. / 32768.);
+    assert_eq!(alaw_to_linear(0xAA), 32256. / 32768.);
+  }
+
+  #[test]
+  fn parses_header_with_csrc_and_padding() {
+    let mut packet = vec![0xA1, 0x08, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0];
+    packet.extend_from_slice(&[9, 9, 9, 9]);
+    packet.extend_from_slice(&[1, 2, 3]);
+    packet.extend_from_slice(&[0, 2]);
+    assert_eq!(parse_packet(&packet), Some((8, &[1u8, 2, 3][..])));
+    assert_eq!(parse_packet(&packet[..8]), None);
+  }
+}
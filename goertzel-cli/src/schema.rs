@@ -0,0 +1,156 @@
+//! `--print-schema`: JSON Schema of the records NDJSON (and msgpack, CBOR) output is made of.
+//!
+//! Every record carries `schema`, the version below. It goes up whenever a field is renamed,
+//! removed or changes meaning, so parsers can refuse logs they do not understand; new event
+//! kinds and new optional fields keep the version.
+
+use serde_json::{json, Map, Value};
+
+pub const VERSION: u64 = 1;
+
+/// Fields every record has, or may have, with their JSON types.
+const COMMON: &[(&str, &str, bool)] = &[
+  ("schema", "integer", true),
+  ("time", "number", true),
+  ("sample", "integer", true),
+  ("source", "string", false),
+  ("channel", "integer", false),
+  ("label", "string", false),
+  ("confidence", "number", false),
+];
+
+/// Each `event` name with the fields it requires.
+const EVENTS: &[(&str, &[(&str, &str)])] = &[
+  ("tone_on", &[("freq", "number"), ("power", "number")]),
+  ("tone_off", &[("freq", "number")]),
+  ("stuck_tone", &[("freq", "number"), ("duration", "number")]),
+  ("tone_lost", &[("freq", "number"), ("duration", "number")]),
+  ("silence", &[("duration", "number")]),
+  ("drift", &[("nominal", "number"), ("freq", "number")]),
+  ("dual_tone_on", &[("f1", "number"), ("f2", "number")]),
+  ("dual_tone_off", &[("f1", "number"), ("f2", "number")]),
+  ("coincidence_on", &[("freqs", "array")]),
+  ("coincidence_off", &[]),
+  ("level", &[("freq", "number"), ("level", "number")]),
+  ("sound_level", &[("weighting", "string"), ("dbfs", "number")]),
+  ("band_level", &[("freq", "number"), ("dbfs", "number")]),
+  ("tempo", &[("bpm", "number"), ("jitter", "number")]),
+  ("flatness", &[("value", "number")]),
+  ("digit", &[("digit", "string")]),
+  ("match", &[("name", "string")]),
+  ("custom", &[("text", "string")]),
+  ("schedule", &[("active", "boolean")]),
+  (
+    "heartbeat",
+    &[
+      ("overruns", "integer"),
+      ("callback_gaps", "integer"),
+      ("late_blocks", "integer"),
+      ("dropped_results", "integer"),
+    ],
+  ),
+  ("episode", &[("duration", "number"), ("end", "number"), ("events", "array")]),
+];
+
+pub fn json_schema() -> Value {
+  let mut properties = Map::new();
+  for (name, kind, _) in COMMON {
+    properties.insert(name.to_string(), json!({ "type": kind }));
+  }
+  properties.insert("schema".into(), json!({ "const": VERSION }));
+  let events: Vec<&str> = EVENTS.iter().map(|e| e.0).collect();
+  properties.insert("event".into(), json!({ "enum": events }));
+  let mut kinds = Vec::new();
+  for (event, fields) in EVENTS {
+    for (name, kind) in fields.iter() {
+      properties.insert(name.to_string(), json!({ "type": kind }));
+    }
+    let names: Vec<&str> = fields.iter().map(|f| f.0).collect();
+    kinds.push(json!({
+      "if": { "properties": { "event": { "const": event } } },
+      "then": { "required": names },
+    }));
+  }
+  let mut required: Vec<&str> = COMMON.iter().filter(|f| f.2).map(|f| f.0).collect();
+  required.push("event");
+  json!({
+    "$schema": "https://json-schema.org/draft/2020-12/schema",
+    "title": format!("goertzelrs event record, version {}", VERSION),
+    "type": "object",
+    "properties": properties,
+    "required": required,
+    "allOf": kinds,
+  })
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::output::{Format, Origin};
+  use goertzel_core::detector::{Event, EventKind, Time};
+
+  /// Checks `record` against the schema: known fields of the right type, required ones present.
+  fn conforms(schema: &Value, record: &Value) -> bool {
+    let type_ok = |kind: &Value, value: &Value| match kind.as_str() {
+      Some("number") => value.is_number(),
+      Some("integer") => value.is_u64(),
+      Some("string") => value.is_string(),
+      Some("boolean") => value.is_boolean(),
+      Some("array") => value.is_array(),
+      _ => true,
+    };
+    let properties = &schema["properties"];
+    let record = record.as_object().unwrap();
+    let known = record.iter().all(|(k, v)| {
+      properties.get(k).is_some_and(|p| p.get("type").is_none_or(|t| type_ok(t, v)))
+    });
+    let event = &record["event"];
+    let kind = schema["allOf"].as_array().unwrap().iter().find(|k| {
+      &k["if"]["properties"]["event"]["const"] == event
+    });
+    let required = schema["required"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .chain(kind.unwrap()["then"]["required"].as_array().unwrap())
+      .all(|name| record.contains_key(name.as_str().unwrap()));
+    known && required && record["schema"] == VERSION
+  }
+
+  #[test]
+  fn records_follow_the_schema() {
+    let schema = json_schema();
+    let time = Time { sample: 80, sample_rate: 8000. };
+    let kinds = [
+      EventKind::ToneOn { freq: 1000., power: 0.5 },
+      EventKind::ToneOff { freq: 1000. },
+      EventKind::StuckTone { freq: 1000., duration: 3. },
+      EventKind::ToneLost { freq: 19000., duration: 2. },
+      EventKind::Silence { duration: 5. },
+      EventKind::Drift { nominal: 19000., freq: 19000.2 },
+      EventKind::DualToneOn { f1: 350., f2: 440. },
+      EventKind::DualToneOff { f1: 350., f2: 440. },
+      EventKind::CoincidenceOn { freqs: vec![700., 1100.] },
+      EventKind::CoincidenceOff,
+      EventKind::Level { freq: 1000., level: 0.1 },
+      EventKind::SoundLevel { weighting: 'A', dbfs: -20. },
+      EventKind::BandLevel { freq: 31.5, dbfs: -40. },
+      EventKind::Tempo { bpm: 120., jitter: 0.004 },
+      EventKind::Flatness { value: 0.2 },
+      EventKind::Digit('5'),
+      EventKind::Match("ring".into()),
+      EventKind::Custom("x".into()),
+      EventKind::Schedule { active: true },
+      EventKind::Heartbeat { overruns: 1, callback_gaps: 0, late_blocks: 2, dropped_results: 0 },
+      EventKind::Episode { duration: 0.5, events: vec![Event::new(time, EventKind::Digit('1'))] },
+    ];
+    assert_eq!(kinds.len(), EVENTS.len());
+    let origin = Origin { source: Some("line 1"), channel: Some(0) };
+    for kind in kinds {
+      let event = Event { label: Some("door".into()), ..Event::new(time, kind) };
+      let record: Value = serde_json::from_str(&Format::Ndjson.event(origin, &event)).unwrap();
+      assert!(conforms(&schema, &record), "{}", record);
+    }
+  }
+}
@@ -0,0 +1,160 @@
+//! `goertzelrs score --truth labels.csv --detected events.ndjson`: detections checked against
+//! hand-made labels, so a change of configuration or version can be measured.
+//!
+//! The truth file has one `start,end[,label]` row per expected detection, times in seconds; a
+//! header row is skipped. A detection is any onset in the NDJSON log (tone on, pair, digit,
+//! match). It matches a label when it starts between `tolerance` seconds before the labeled
+//! interval and `tolerance` after its end, and, if the row has a label, when that names the
+//! event: its label, digit, match name, or frequency in Hz. Each row takes at most one
+//! detection, the earliest.
+
+use std::path::Path;
+
+use goertzel_core::detector::{Event, EventKind};
+
+use crate::replay;
+
+/// Seconds a detection may be off the labeled interval by default.
+pub const TOLERANCE: f64 = 0.1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+  pub start: f64,
+  pub end: f64,
+  pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Score {
+  pub labels: usize,
+  pub detections: usize,
+  pub matched: usize,
+  /// Mean and largest distance between a matched detection and the start of its label, in
+  /// seconds.
+  pub mean_error: f64,
+  pub max_error: f64,
+}
+
+impl Score {
+  /// Share of the detections that match a label.
+  pub fn precision(&self) -> f64 {
+    if self.detections == 0 { 1. } else { self.matched as f64 / self.detections as f64 }
+  }
+
+  /// Share of the labels that were detected.
+  pub fn recall(&self) -> f64 {
+    if self.labels == 0 { 1. } else { self.matched as f64 / self.labels as f64 }
+  }
+}
+
+pub fn load_labels(path: &Path) -> Result<Vec<Label>, anyhow::Error> {
+  let text = std::fs::read_to_string(path)?;
+  let mut labels = Vec::new();
+  for (number, line) in text.lines().enumerate() {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    if line.trim().is_empty() || (number == 0 && fields[0].parse::<f64>().is_err()) {
+      continue;
+    }
+    let time = |i: usize| -> Result<f64, anyhow::Error> {
+      let field = fields.get(i).copied().unwrap_or("");
+      field.parse().map_err(|_| {
+        anyhow::anyhow!("{}:{}: bad time `{}`", path.display(), number + 1, field)
+      })
+    };
+    let name = fields.get(2).filter(|n| !n.is_empty()).map(|n| n.to_string());
+    labels.push(Label { start: time(0)?, end: time(1)?, name });
+  }
+  Ok(labels)
+}
+
+/// The onsets of an NDJSON event log.
+pub fn load_detections(path: &Path) -> Result<Vec<Event>, anyhow::Error> {
+  let text = std::fs::read_to_string(path)?;
+  let mut events = Vec::new();
+  let mut sample_rate = 8000.;
+  for (number, line) in text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+    let recorded = replay::parse(line, sample_rate)
+      .map_err(|e| anyhow::anyhow!("{}:{}: {}", path.display(), number + 1, e))?;
+    sample_rate = recorded.event.time.sample_rate;
+    if recorded.event.kind.is_onset() {
+      events.push(recorded.event);
+    }
+  }
+  Ok(events)
+}
+
+pub fn score(labels: &[Label], detections: &[Event], tolerance: f64) -> Score {
+  let mut detections: Vec<&Event> = detections.iter().collect();
+  detections.sort_by(|a, b| a.time.seconds().total_cmp(&b.time.seconds()));
+  let mut taken = vec![false; detections.len()];
+  let mut errors = Vec::new();
+  for label in labels {
+    let found = detections.iter().enumerate().position(|(i, event)| {
+      let t = event.time.seconds();
+      !taken[i]
+        && t >= label.start - tolerance
+        && t <= label.end + tolerance
+        && label.name.as_deref().is_none_or(|name| names(event, name))
+    });
+    if let Some(i) = found {
+      taken[i] = true;
+      errors.push((detections[i].time.seconds() - label.start).abs());
+    }
+  }
+  let matched = errors.len();
+  Score {
+    labels: labels.len(),
+    detections: detections.len(),
+    matched,
+    mean_error: if matched == 0 { 0. } else { errors.iter().sum::<f64>() / matched as f64 },
+    max_error: errors.iter().copied().fold(0., f64::max),
+  }
+}
+
+/// Whether `name` is the event's label, digit, match name or (within 1%) frequency.
+fn names(event: &Event, name: &str) -> bool {
+  if event.label.as_deref() == Some(name) {
+    return true;
+  }
+  let freq = match &event.kind {
+    EventKind::Digit(d) => return name.chars().eq(Some(*d)),
+    EventKind::Match(m) => return m == name,
+    EventKind::ToneOn { freq, .. }
+    | EventKind::StuckTone { freq, .. }
+    | EventKind::ToneLost { freq, .. } => *freq,
+    EventKind::DualToneOn { f1, .. } => *f1,
+    _ => return false,
+  };
+  name.parse::<f32>().is_ok_and(|hz| (hz - freq).abs() <= 0.01 * freq)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use goertzel_core::detector::Time;
+
+  #[test]
+  fn scores_detections_against_labels() {
+    let at = |s: f64, kind| {
+      Event::new(Time { sample: (s * 8000.) as u64, sample_rate: 8000. }, kind)
+    };
+    let labels = [
+      Label { start: 1.0, end: 1.5, name: Some("5".into()) },
+      Label { start: 3.0, end: 4.0, name: Some("1000".into()) },
+      Label { start: 6.0, end: 6.2, name: None },
+    ];
+    let detections = vec![
+      at(1.02, EventKind::Digit('5')),
+      // Wrong digit in the first interval, and a tone off.
+      at(1.1, EventKind::Digit('6')),
+      at(3.05, EventKind::ToneOn { freq: 1000., power: 0.9 }),
+      at(4.0, EventKind::ToneOff { freq: 1000. }),
+    ];
+    let onsets: Vec<Event> = detections.into_iter().filter(|e| e.kind.is_onset()).collect();
+    let score = score(&labels, &onsets, TOLERANCE);
+    assert_eq!((score.labels, score.detections, score.matched), (3, 3, 2));
+    assert!((score.precision() - 2. / 3.).abs() < 1e-9 && (score.recall() - 2. / 3.).abs() < 1e-9);
+    assert!((score.mean_error - 0.035).abs() < 1e-3 && (score.max_error - 0.05).abs() < 1e-3);
+  }
+}
@@ -0,0 +1,123 @@
+//! Detection rules written in rhai.
+//!
+//! A script defines `frequencies()`, returning the bank to analyze, and `on_block(powers, time)`,
+//! called for every block with the normalized power of each frequency and the block time in
+//! seconds. `this` is a map kept between calls for the script's own state. Returning a string,
+//! or an array of strings, emits them as events:
+//!
+//! ```text
+//! fn frequencies() { [1000.0] }
+//!
+//! fn on_block(powers, time) {
+//!   if this.beeps == () { this.beeps = 0; this.on = false; }
+//!   let on = powers[0] > 0.5;
+//!   if on && !this.on { this.beeps += 1; }
+//!   this.on = on;
+//!   if this.beeps == 3 { this.beeps = 0; return "three beeps"; }
+//! }
+//! ```
+
+use goertzel_core::detector::{Detector, Event, EventKind, Time};
+use goertzel_core::GoertzelBank;
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+
+pub struct ScriptDetector {
+  engine: Engine,
+  ast: AST,
+  scope: Scope<'static>,
+  state: Dynamic,
+  bank: GoertzelBank,
+}
+
+impl ScriptDetector {
+  pub fn new(source: &str, sample_rate: f32) -> Result<Self, anyhow::Error> {
+    let engine = Engine::new();
+    let ast = engine.compile(source)?;
+    let mut scope = Scope::new();
+    // Runs the top level once, so the script can set up constants.
+    engine.run_ast_with_scope(&mut scope, &ast)?;
+    let freqs: Array = engine.call_fn_with_options(
+      CallFnOptions::new().eval_ast(false),
+      &mut scope,
+      &ast,
+      "frequencies",
+      (),
+    )?;
+    let freqs = freqs
+      .into_iter()
+      .map(|f| f.as_float().map(|f| f as f32))
+      .collect::<Result<Vec<f32>, _>>()
+      .map_err(|t| anyhow::anyhow!("frequencies() must return floats, got {}", t))?;
+    Ok(Self {
+      engine,
+      ast,
+      scope,
+      state: Dynamic::from(Map::new()),
+      bank: GoertzelBank::new(&freqs, sample_rate),
+    })
+  }
+}
+
+impl Detector for ScriptDetector {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let powers: Array = self
+      .bank
+      .block_power(block)
+      .into_iter()
+      .map(|p| Dynamic::from_float(p as f64))
+      .collect();
+    let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut self.state);
+    let result = self.engine.call_fn_with_options::<Dynamic>(
+      options,
+      &mut self.scope,
+      &self.ast,
+      "on_block",
+      (powers, t.seconds()),
+    );
+    let messages = match result {
+      Ok(r) if r.is_unit() => return Vec::new(),
+      Ok(r) if r.is_array() => r.cast::<Array>(),
+      Ok(r) => vec![r],
+      Err(e) => {
+        eprintln!("script error at {:.3}s: {}", t.seconds(), e);
+        return Vec::new();
+      }
+    };
+    messages
+      .into_iter()
+      .map(|m| Event::new(t, EventKind::Custom(m.to_string())))
+      .collect()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::f32::consts::PI;
+
+  #[test]
+  fn script_counts_beeps() {
+    let source = r#"
+      fn frequencies() { [1000.0] }
+      fn on_block(powers, time) {
+        if this.beeps == () { this.beeps = 0; this.on = false; }
+        let on = powers[0] > 0.5;
+        if on && !this.on { this.beeps += 1; }
+        this.on = on;
+        if this.beeps == 3 { this.beeps = 0; return "three beeps"; }
+      }
+    "#;
+    let mut d = ScriptDetector::new(source, 8000.).unwrap();
+    let tone: Vec<f32> = (0..160).map(|t| (2. * PI * 1000. * t as f32 / 8000.).sin()).collect();
+    let silence = vec![0.; 160];
+    let mut events = Vec::new();
+    for i in 0..6 {
+      let block = if i % 2 == 0 { &tone } else { &silence };
+      events.extend(d.process_block(block, Time { sample: i * 160, sample_rate: 8000. }));
+    }
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, EventKind::Custom("three beeps".into()));
+    assert_eq!(events[0].time.sample, 640);
+  }
+}
@@ -0,0 +1,46 @@
+//! RTL-SDR input backend.
+
+use goertzel_core::fm::FmDemod;
+use rtlsdr::{RTLSDRDevice, RTLSDRError};
+
+/// IQ rate the dongle is tuned to. 240 kHz decimated by 5 gives 48 kHz audio.
+pub const IQ_RATE: u32 = 240_000;
+const DECIMATION: usize = 5;
+const READ_LEN: usize = 16 * 1024;
+
+/// Pulls IQ from an RTL-SDR and returns FM demodulated audio at `audio_rate()`.
+pub struct RtlSdrSource {
+  dev: RTLSDRDevice,
+  demod: FmDemod,
+}
+
+impl RtlSdrSource {
+  pub fn open(index: i32, center_freq: u32) -> Result<Self, RTLSDRError> {
+    let mut dev = rtlsdr::open(index)?;
+    dev.set_center_freq(center_freq)?;
+    dev.set_sample_rate(IQ_RATE)?;
+    dev.set_tuner_gain_mode(false)?;
+    dev.reset_buffer()?;
+    Ok(Self {
+      dev,
+      demod: FmDemod::new(DECIMATION),
+    })
+  }
+
+  pub fn audio_rate(&self) -> f32 {
+    IQ_RATE as f32 / DECIMATION as f32
+  }
+
+  /// Blocks until the next buffer of IQ arrives and appends the demodulated audio to `out`.
+  pub fn read(&mut self, out: &mut Vec<f32>) -> Result<(), RTLSDRError> {
+    let buf = self.dev.read_sync(READ_LEN)?;
+    for iq in buf.chunks_exact(2) {
+      let i = (iq[0] as f32 - 127.5) / 127.5;
+      let q = (iq[1] as f32 - 127.5) / 127.5;
+      if let Some(s) = self.demod.push(i, q) {
+        out.push(s);
+      }
+    }
+    Ok(())
+  }
+}
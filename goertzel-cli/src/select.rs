@@ -0,0 +1,185 @@
+//! `--channel 1` or `--channel auto`: the one channel of a multi-channel input to analyze.
+//!
+//! `auto` listens to every channel for a moment first and takes the one with the clearest
+//! signal, so a stereo card with one input plugged in is not heard as half silence. The audio
+//! heard meanwhile is analyzed once the choice is made, so nothing is lost.
+
+/// Seconds of input measured before `auto` picks.
+const LISTEN_SECS: f32 = 2.;
+/// Level in dBFS below which a channel counts as silent.
+const SILENT_DBFS: f32 = -60.;
+/// Seconds per measured block.
+const BLOCK_SECS: f32 = 0.02;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Choice {
+  Index(usize),
+  Auto,
+}
+
+impl Choice {
+  /// `auto` or a channel index from 0.
+  pub fn parse(spec: &str) -> Option<Self> {
+    match spec {
+      "auto" => Some(Choice::Auto),
+      n => n.parse().ok().map(Choice::Index),
+    }
+  }
+}
+
+/// How a channel sounded while it was measured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quality {
+  /// Overall level, in dBFS.
+  pub dbfs: f32,
+  /// Loud blocks against quiet ones, in dB: the 90th against the 10th percentile of the block
+  /// levels.
+  pub snr_db: f32,
+}
+
+impl Quality {
+  /// Of one channel's samples.
+  fn of(samples: &[f32], block: usize) -> Self {
+    let power = |s: &[f32]| s.iter().map(|x| x * x).sum::<f32>() / s.len().max(1) as f32;
+    let db = |p: f32| 10. * p.max(1e-12).log10();
+    let mut blocks: Vec<f32> = samples.chunks(block.max(1)).map(power).collect();
+    blocks.sort_by(f32::total_cmp);
+    let percentile = |p: usize| blocks.get(blocks.len().saturating_sub(1) * p / 100).copied();
+    let snr_db = match (percentile(90), percentile(10)) {
+      (Some(loud), Some(quiet)) => db(loud) - db(quiet),
+      _ => 0.,
+    };
+    // Relative to a full-scale sine, like the level meters.
+    Quality { dbfs: db(power(samples) * 2.), snr_db }
+  }
+
+  fn silent(&self) -> bool {
+    self.dbfs < SILENT_DBFS
+  }
+}
+
+/// Takes one channel out of interleaved input.
+pub struct Selector {
+  channels: usize,
+  chosen: Option<usize>,
+  /// Interleaved input held while listening.
+  heard: Vec<f32>,
+  listen: usize,
+  block: usize,
+}
+
+impl Selector {
+  pub fn new(choice: Choice, channels: usize, sample_rate: f32) -> Result<Self, anyhow::Error> {
+    let chosen = match choice {
+      Choice::Index(n) if n >= channels => {
+        anyhow::bail!("--channel {}: the input has {} channels", n, channels)
+      }
+      Choice::Index(n) => Some(n),
+      Choice::Auto if channels == 1 => Some(0),
+      Choice::Auto => None,
+    };
+    Ok(Selector {
+      channels,
+      chosen,
+      heard: Vec::new(),
+      listen: (LISTEN_SECS * sample_rate) as usize * channels,
+      block: (BLOCK_SECS * sample_rate) as usize,
+    })
+  }
+
+  /// Appends the chosen channel of `interleaved` to `out`, and what was held while listening
+  /// once the choice is made.
+  pub fn push(&mut self, interleaved: &[f32], out: &mut Vec<f32>) {
+    let channel = match self.chosen {
+      Some(channel) => channel,
+      None => {
+        self.heard.extend_from_slice(interleaved);
+        if self.heard.len() < self.listen {
+          return;
+        }
+        let channel = self.choose();
+        let heard = std::mem::take(&mut self.heard);
+        out.extend(heard.iter().skip(channel).step_by(self.channels));
+        return;
+      }
+    };
+    out.extend(interleaved.iter().skip(channel).step_by(self.channels));
+  }
+
+  /// Picks from what was heard so far, e.g. when the input ends while listening, and appends
+  /// it to `out`.
+  pub fn finish(&mut self, out: &mut Vec<f32>) {
+    if self.chosen.is_none() && !self.heard.is_empty() {
+      let channel = self.choose();
+      let heard = std::mem::take(&mut self.heard);
+      out.extend(heard.iter().skip(channel).step_by(self.channels));
+    }
+  }
+
+  fn choose(&mut self) -> usize {
+    let qualities: Vec<Quality> = (0..self.channels)
+      .map(|c| {
+        let samples: Vec<f32> =
+          self.heard.iter().skip(c).step_by(self.channels).copied().collect();
+        Quality::of(&samples, self.block)
+      })
+      .collect();
+    let channel = best(&qualities);
+    let q = qualities[channel];
+    if q.silent() {
+      eprintln!("warning: all {} channels are silent, using channel {}", self.channels, channel);
+    } else {
+      println!(
+        "Using channel {} of {} ({:.1} dBFS, SNR {:.1} dB)",
+        channel, self.channels, q.dbfs, q.snr_db
+      );
+    }
+    self.chosen = Some(channel);
+    channel
+  }
+}
+
+/// The channel with the clearest signal among those that are not silent, or the loudest.
+fn best(qualities: &[Quality]) -> usize {
+  let by = |key: fn(&Quality) -> f32| {
+    move |a: &(usize, &Quality), b: &(usize, &Quality)| key(a.1).total_cmp(&key(b.1))
+  };
+  let loudest = qualities.iter().enumerate().max_by(by(|q| q.dbfs));
+  let heard = qualities.iter().enumerate().filter(|(_, q)| !q.silent());
+  let clearest = heard.max_by(by(|q| q.snr_db));
+  clearest.or(loudest).map_or(0, |(channel, _)| channel)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::f32::consts::PI;
+
+  #[test]
+  fn picks_the_channel_with_the_signal() {
+    // Channel 0 unconnected hiss, 1 beeping, 2 louder but steady hum.
+    let frames = 20_000;
+    let mut interleaved = Vec::new();
+    for n in 0..frames {
+      let t = n as f32 / 8000.;
+      let hiss = 1e-4 * ((n * 7919 % 101) as f32 / 50. - 1.);
+      let beep = if (n / 2000) % 2 == 0 { 0.3 * (2. * PI * 1000. * t).sin() } else { 0. };
+      let hum = 0.5 * (2. * PI * 50. * t).sin();
+      interleaved.extend([hiss, beep + hiss, hum]);
+    }
+    let mut selector = Selector::new(Choice::Auto, 3, 8000.).unwrap();
+    let mut out = Vec::new();
+    for chunk in interleaved.chunks(3 * 800) {
+      selector.push(chunk, &mut out);
+    }
+    assert_eq!(selector.chosen, Some(1));
+    let beeping: Vec<f32> = interleaved.iter().skip(1).step_by(3).copied().collect();
+    assert_eq!(out, beeping);
+
+    let silent = [Quality { dbfs: -90., snr_db: 3. }, Quality { dbfs: -80., snr_db: 1. }];
+    assert_eq!(best(&silent), 1);
+    assert_eq!(Choice::parse("auto"), Some(Choice::Auto));
+    assert!(Selector::new(Choice::Index(2), 2, 8000.).is_err());
+  }
+}
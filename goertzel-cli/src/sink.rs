@@ -0,0 +1,403 @@
+//! Where result records go. Besides stdout, a `--log-file` or the system log, every record can
+//! be fanned out to any number of `--osc`, `--mqtt`, `--websocket` and `--exec` sinks, each
+//! written by a thread of its own (see `results::spawn_printer`).
+//!
+//! The network sinks speak their protocols over plain std sockets: OSC 1.0 messages, MQTT 3.1.1
+//! publishes at QoS 0 and RFC 6455 WebSocket frames.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::results::Record;
+
+/// OSC address of the messages, their single argument being the record.
+const OSC_ADDRESS: &str = "/goertzelrs";
+const MQTT_PORT: u16 = 1883;
+const MQTT_TOPIC: &str = "goertzelrs";
+/// Longest a WebSocket client may block a write before it is disconnected.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+/// Appended to a client's key to accept its WebSocket handshake.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+pub trait EventSink: Send {
+  /// What to call the sink in error messages, e.g. `mqtt localhost:1883`.
+  fn name(&self) -> String;
+
+  /// Writes one record out. An error gives up on the sink.
+  fn write(&mut self, record: &Record) -> io::Result<()>;
+}
+
+/// A byte stream: lines are written with a newline, binary records as they are, and it is
+/// flushed after every record. Stdout, log files and the system log sockets.
+pub struct Stream<W> {
+  name: String,
+  out: W,
+}
+
+impl<W: Write + Send> Stream<W> {
+  pub fn new(name: impl Into<String>, out: W) -> Self {
+    Self { name: name.into(), out }
+  }
+}
+
+impl<W: Write + Send> EventSink for Stream<W> {
+  fn name(&self) -> String {
+    self.name.clone()
+  }
+
+  fn write(&mut self, record: &Record) -> io::Result<()> {
+    match record {
+      Record::Line(line) => writeln!(self.out, "{}", line)?,
+      Record::Binary(bytes) => self.out.write_all(bytes)?,
+    }
+    self.out.flush()
+  }
+}
+
+/// `--osc host:port`: every record as an OSC message to `/goertzelrs`, one UDP datagram each,
+/// the record a string argument (a blob for binary formats).
+pub struct Osc {
+  target: String,
+  socket: UdpSocket,
+}
+
+impl Osc {
+  pub fn connect(target: &str) -> io::Result<Self> {
+    let addr = target.to_socket_addrs()?.next().ok_or_else(|| no_address(target))?;
+    let socket = UdpSocket::bind(if addr.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" })?;
+    socket.connect(addr)?;
+    Ok(Osc { target: target.to_string(), socket })
+  }
+}
+
+impl EventSink for Osc {
+  fn name(&self) -> String {
+    format!("osc {}", self.target)
+  }
+
+  fn write(&mut self, record: &Record) -> io::Result<()> {
+    self.socket.send(&osc_message(record)).map(|_| ())
+  }
+}
+
+pub fn osc_message(record: &Record) -> Vec<u8> {
+  let mut message = Vec::new();
+  osc_string(&mut message, OSC_ADDRESS.as_bytes());
+  match record {
+    Record::Line(line) => {
+      osc_string(&mut message, b",s");
+      osc_string(&mut message, line.as_bytes());
+    }
+    Record::Binary(bytes) => {
+      osc_string(&mut message, b",b");
+      message.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+      message.extend_from_slice(bytes);
+      message.resize(message.len().next_multiple_of(4), 0);
+    }
+  }
+  message
+}
+
+/// Appends `bytes` with the one to four nulls that end an OSC string on a multiple of 4.
+fn osc_string(message: &mut Vec<u8>, bytes: &[u8]) {
+  message.extend_from_slice(bytes);
+  message.resize((message.len() + 1).next_multiple_of(4), 0);
+}
+
+/// `--mqtt host[:port][/topic]`: every record published to the topic (`goertzelrs` unless
+/// given) of an MQTT broker, at most once. The session is clean and kept without keep-alive.
+pub struct Mqtt {
+  broker: String,
+  topic: String,
+  stream: TcpStream,
+}
+
+impl Mqtt {
+  pub fn connect(spec: &str) -> io::Result<Self> {
+    let (broker, topic) = spec.split_once('/').unwrap_or((spec, MQTT_TOPIC));
+    let broker = match broker.contains(':') {
+      true => broker.to_string(),
+      false => format!("{}:{}", broker, MQTT_PORT),
+    };
+    let mut stream = TcpStream::connect(&broker)?;
+    let mut connect = Vec::new();
+    mqtt_string(&mut connect, b"MQTT");
+    // Protocol level 4 (3.1.1), clean session, no keep-alive.
+    connect.extend_from_slice(&[4, 0x02, 0, 0]);
+    mqtt_string(&mut connect, format!("goertzelrs-{}", std::process::id()).as_bytes());
+    stream.write_all(&mqtt_packet(0x10, &connect))?;
+    let mut connack = [0; 4];
+    io::Read::read_exact(&mut stream, &mut connack)?;
+    if connack[0] != 0x20 || connack[3] != 0 {
+      let message = format!("{} refused the connection (code {})", broker, connack[3]);
+      return Err(io::Error::new(io::ErrorKind::ConnectionRefused, message));
+    }
+    Ok(Mqtt { broker, topic: topic.to_string(), stream })
+  }
+}
+
+impl EventSink for Mqtt {
+  fn name(&self) -> String {
+    format!("mqtt {}", self.broker)
+  }
+
+  fn write(&mut self, record: &Record) -> io::Result<()> {
+    self.stream.write_all(&mqtt_publish(&self.topic, record))
+  }
+}
+
+pub fn mqtt_publish(topic: &str, record: &Record) -> Vec<u8> {
+  let mut body = Vec::new();
+  mqtt_string(&mut body, topic.as_bytes());
+  body.extend_from_slice(match record {
+    Record::Line(line) => line.as_bytes(),
+    Record::Binary(bytes) => bytes,
+  });
+  mqtt_packet(0x30, &body)
+}
+
+/// A control packet: its type and flags, the remaining length in 7-bit groups, the body.
+fn mqtt_packet(kind: u8, body: &[u8]) -> Vec<u8> {
+  let mut packet = vec![kind];
+  let mut length = body.len();
+  loop {
+    let byte = (length % 128) as u8;
+    length /= 128;
+    packet.push(if length > 0 { byte | 0x80 } else { byte });
+    if length == 0 {
+      break;
+    }
+  }
+  packet.extend_from_slice(body);
+  packet
+}
+
+fn mqtt_string(out: &mut Vec<u8>, bytes: &[u8]) {
+  out.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+  out.extend_from_slice(bytes);
+}
+
+/// `--websocket addr:port`: serves every record to all the WebSocket clients connected at the
+/// time, as a text message (binary for binary formats). The request path is not looked at;
+/// clients that go away or cannot keep up are dropped.
+pub struct WebSocket {
+  addr: String,
+  clients: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl WebSocket {
+  pub fn bind(addr: &str) -> io::Result<Self> {
+    let listener = TcpListener::bind(addr)?;
+    let clients = Arc::new(Mutex::new(Vec::new()));
+    let accepted = clients.clone();
+    std::thread::spawn(move || {
+      for stream in listener.incoming().flatten() {
+        match websocket_handshake(&stream) {
+          Ok(()) => accepted.lock().unwrap().push(stream),
+          Err(e) => eprintln!("websocket handshake failed: {}", e),
+        }
+      }
+    });
+    Ok(WebSocket { addr: addr.to_string(), clients })
+  }
+}
+
+impl EventSink for WebSocket {
+  fn name(&self) -> String {
+    format!("websocket {}", self.addr)
+  }
+
+  fn write(&mut self, record: &Record) -> io::Result<()> {
+    let frame = websocket_frame(record);
+    let mut clients = self.clients.lock().unwrap();
+    clients.retain_mut(|client| client.write_all(&frame).is_ok());
+    Ok(())
+  }
+}
+
+/// Answers a client's opening handshake.
+fn websocket_handshake(stream: &TcpStream) -> io::Result<()> {
+  stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+  stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+  let mut key = None;
+  for line in BufReader::new(stream).lines() {
+    let line = line?;
+    if line.is_empty() {
+      break;
+    }
+    if let Some((name, value)) = line.split_once(':') {
+      if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+        key = Some(value.trim().to_string());
+      }
+    }
+  }
+  let key = key.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no websocket key"))?;
+  let mut stream = stream;
+  write!(
+    stream,
+    "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+     Sec-WebSocket-Accept: {}\r\n\r\n",
+    websocket_accept(&key)
+  )
+}
+
+/// The `Sec-WebSocket-Accept` answer to a client's key.
+pub fn websocket_accept(key: &str) -> String {
+  base64(&sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()))
+}
+
+/// One final, unmasked frame holding the record.
+pub fn websocket_frame(record: &Record) -> Vec<u8> {
+  let (opcode, payload) = match record {
+    Record::Line(line) => (0x1, line.as_bytes()),
+    Record::Binary(bytes) => (0x2, bytes.as_slice()),
+  };
+  let mut frame = vec![0x80 | opcode];
+  match payload.len() {
+    len if len < 126 => frame.push(len as u8),
+    len if len <= u16::MAX as usize => {
+      frame.push(126);
+      frame.extend_from_slice(&(len as u16).to_be_bytes());
+    }
+    len => {
+      frame.push(127);
+      frame.extend_from_slice(&(len as u64).to_be_bytes());
+    }
+  }
+  frame.extend_from_slice(payload);
+  frame
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+  let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+  let mut message = data.to_vec();
+  message.push(0x80);
+  message.resize((message.len() + 8).next_multiple_of(64) - 8, 0);
+  message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+  for chunk in message.chunks(64) {
+    let mut w = [0u32; 80];
+    for i in 0..80 {
+      w[i] = match i {
+        0..=15 => {
+          let b = &chunk[4 * i..];
+          u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+        }
+        _ => (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1),
+      };
+    }
+    let [mut a, mut b, mut c, mut d, mut e] = h;
+    for (i, &word) in w.iter().enumerate() {
+      let (f, k) = match i {
+        0..=19 => ((b & c) | (!b & d), 0x5A827999),
+        20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+        40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+        _ => (b ^ c ^ d, 0xCA62C1D6),
+      };
+      let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+      (e, d, c, b, a) = (d, c, b.rotate_left(30), a, t);
+    }
+    for (word, v) in h.iter_mut().zip([a, b, c, d, e]) {
+      *word = word.wrapping_add(v);
+    }
+  }
+  let mut digest = [0; 20];
+  for (i, word) in h.iter().enumerate() {
+    digest[4 * i..4 * i + 4].copy_from_slice(&word.to_be_bytes());
+  }
+  digest
+}
+
+fn base64(data: &[u8]) -> String {
+  const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+  let mut out = String::new();
+  for chunk in data.chunks(3) {
+    let bits =
+      chunk.iter().enumerate().fold(0u32, |bits, (i, &b)| bits | (b as u32) << (16 - 8 * i));
+    for i in 0..4 {
+      match i <= chunk.len() {
+        true => out.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char),
+        false => out.push('='),
+      }
+    }
+  }
+  out
+}
+
+/// `--exec 'command'`: runs the command through the shell for every record, with the record on
+/// its standard input, e.g. to send a notification. One runs at a time; a command that fails is
+/// warned about but keeps the sink.
+pub struct Command {
+  command: String,
+}
+
+impl Command {
+  pub fn new(command: &str) -> Self {
+    Command { command: command.to_string() }
+  }
+}
+
+impl EventSink for Command {
+  fn name(&self) -> String {
+    format!("`{}`", self.command)
+  }
+
+  fn write(&mut self, record: &Record) -> io::Result<()> {
+    let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let mut child = std::process::Command::new(shell)
+      .args([flag, &self.command])
+      .stdin(Stdio::piped())
+      .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+      // A command that does not read its input is fine.
+      let _ = match record {
+        Record::Line(line) => writeln!(stdin, "{}", line),
+        Record::Binary(bytes) => stdin.write_all(bytes),
+      };
+    }
+    let status = child.wait()?;
+    if !status.success() {
+      eprintln!("`{}` failed: {}", self.command, status);
+    }
+    Ok(())
+  }
+}
+
+fn no_address(target: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::NotFound, format!("no address for {}", target))
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn osc_message_pads_its_strings() {
+    let message = osc_message(&Record::Line("1.000 digit 5".into()));
+    assert_eq!(&message[..12], b"/goertzelrs\0");
+    assert_eq!(&message[12..16], b",s\0\0");
+    assert_eq!(&message[16..], b"1.000 digit 5\0\0\0");
+    let message = osc_message(&Record::Binary(vec![1, 2, 3, 4, 5]));
+    assert_eq!(&message[12..], [b',', b'b', 0, 0, 0, 0, 0, 5, 1, 2, 3, 4, 5, 0, 0, 0]);
+  }
+
+  #[test]
+  fn mqtt_publish_carries_topic_and_record() {
+    let line = "x".repeat(200);
+    let packet = mqtt_publish("tones", &Record::Line(line.clone()));
+    // 2 + 5 + 200 bytes remain: 207 in two 7-bit groups.
+    assert_eq!(&packet[..3], [0x30, (207 - 128) | 0x80, 1]);
+    assert_eq!(&packet[3..10], b"\0\x05tones");
+    assert_eq!(&packet[10..], line.as_bytes());
+  }
+
+  #[test]
+  fn websocket_accepts_the_rfc_example_key() {
+    assert_eq!(websocket_accept("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    let frame = websocket_frame(&Record::Line("a".repeat(300)));
+    assert_eq!(&frame[..4], [0x81, 126, 1, 44]);
+  }
+}
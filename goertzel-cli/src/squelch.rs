@@ -0,0 +1,312 @@
+//! `--wake-tone 1750`: a tone-squelched logger, e.g. for scanner audio. Hearing the wake tone
+//! starts a recording in `--wake-record dir`, or feeds the audio to `--wake-exec command` as
+//! 16-bit little-endian PCM on its standard input, from the block the tone was found in. It stops
+//! once the tone has been gone for `--wake-timeout`, 5 s unless set.
+//!
+//! Recordings are named after the stream time they start at, e.g. `12.480-wake.wav`, or
+//! `ch1-12.480-wake.wav` per channel. Commands get the sample rate in `GOERTZELRS_SAMPLE_RATE`:
+//!
+//! ```text
+//! --wake-exec 'sox -t raw -r $GOERTZELRS_SAMPLE_RATE -e signed -b 16 -c 1 - $(date +%s).flac'
+//! ```
+//!
+//! Files are created, commands started and the audio written by a thread of each channel's own,
+//! fed through a bounded queue, so the audio callback never waits for the disk or a process.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use goertzel_core::detector::{EventKind, ToneDetector};
+use goertzel_core::history::AudioHistory;
+use goertzel_core::Pipeline;
+
+const BLOCK_SECS: f32 = 0.02;
+const THRESHOLD: f32 = 0.5;
+/// Messages waiting for the writer before new audio is dropped.
+const QUEUE_LEN: usize = 256;
+
+/// Where the audio of a wake goes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+  Record(PathBuf),
+  Exec(String),
+}
+
+/// The wake tone and what it does; shared by every channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+  pub freq: f32,
+  /// Seconds without the tone before the recording stops.
+  pub timeout: f32,
+  pub target: Target,
+}
+
+/// Listens for the wake tone on one channel.
+pub struct Squelch {
+  channel: Option<usize>,
+  pipeline: Pipeline,
+  /// The latest blocks, so a recording starts with the block the tone was found in.
+  history: AudioHistory,
+  timeout: u64,
+  tone: bool,
+  /// Frame the tone was last heard at.
+  heard: u64,
+  recording: bool,
+  /// The queue to the writer thread, and the thread.
+  writer: Option<(SyncSender<Message>, JoinHandle<()>)>,
+  /// Audio chunks the writer had no room for.
+  dropped: Arc<AtomicUsize>,
+}
+
+/// What the squelch tells its writer thread.
+enum Message {
+  /// Start a recording named `name`, for the tone heard at `at` seconds.
+  Open { name: String, at: f64 },
+  Audio(Vec<f32>),
+  /// Stop the recording at `at` seconds.
+  Close { at: f64 },
+}
+
+impl Squelch {
+  pub fn new(settings: &Settings, sample_rate: f32, channel: Option<usize>) -> Self {
+    let block = (sample_rate * BLOCK_SECS) as usize;
+    let pipeline = Pipeline::new(sample_rate, block)
+      .with(Box::new(ToneDetector::new(settings.freq, sample_rate, THRESHOLD)));
+    let (sender, messages) = mpsc::sync_channel(QUEUE_LEN);
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let writer = {
+      let (settings, dropped) = (settings.clone(), dropped.clone());
+      std::thread::spawn(move || write(settings, sample_rate, messages, dropped))
+    };
+    Squelch {
+      channel,
+      pipeline,
+      history: AudioHistory::new(Duration::from_secs(1), sample_rate),
+      timeout: (settings.timeout * sample_rate) as u64,
+      tone: false,
+      heard: 0,
+      recording: false,
+      writer: Some((sender, writer)),
+      dropped,
+    }
+  }
+
+  /// Makes the next frame the `frame`th of the stream.
+  pub fn set_position(&mut self, frame: u64) {
+    self.pipeline.set_position(frame);
+    self.history.set_position(frame);
+  }
+
+  /// Listens to `samples`, recording them if the wake tone was heard; never while `quiet`,
+  /// which only keeps the squelch in step with the stream.
+  pub fn push(&mut self, samples: &[f32], quiet: bool) {
+    self.history.push(samples);
+    let mut onset = None;
+    for event in self.pipeline.push(samples) {
+      match event.kind {
+        EventKind::ToneOn { .. } => {
+          self.tone = true;
+          onset = onset.or(Some(event.time.sample));
+        }
+        EventKind::ToneOff { .. } => {
+          self.tone = false;
+          self.heard = event.time.sample;
+        }
+        _ => {}
+      }
+    }
+    let end = self.history.end();
+    if self.tone {
+      self.heard = end;
+    }
+    match (self.recording, onset) {
+      (false, Some(_)) if quiet => {}
+      (false, Some(onset)) => self.start(onset),
+      (true, _) if end - self.heard < self.timeout => self.send(Message::Audio(samples.to_vec())),
+      (true, _) => self.stop(),
+      (false, None) => {}
+    }
+  }
+
+  /// Stops the recording, if any, and waits until everything recorded was written.
+  pub fn finish(&mut self) {
+    self.stop();
+    if let Some((sender, writer)) = self.writer.take() {
+      drop(sender);
+      let _ = writer.join();
+    }
+  }
+
+  fn start(&mut self, onset: u64) {
+    let at = onset as f64 / self.history.sample_rate() as f64;
+    let name = match self.channel {
+      Some(channel) => format!("ch{}-{:.3}-wake", channel, at),
+      None => format!("{:.3}-wake", at),
+    };
+    self.recording = true;
+    self.send(Message::Open { name, at });
+    self.send(Message::Audio(self.history.range(onset, self.history.end())));
+  }
+
+  fn stop(&mut self) {
+    if std::mem::take(&mut self.recording) {
+      let at = self.history.end() as f64 / self.history.sample_rate() as f64;
+      self.send(Message::Close { at });
+    }
+  }
+
+  /// Hands `message` to the writer without waiting; audio it has no room for is dropped.
+  fn send(&self, message: Message) {
+    let Some((sender, _)) = &self.writer else {
+      return;
+    };
+    if sender.try_send(message).is_err() {
+      self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+  }
+}
+
+/// 16-bit PCM of a sample.
+fn pcm(sample: f32) -> i16 {
+  (sample.clamp(-1., 1.) * i16::MAX as f32) as i16
+}
+
+/// A recording in progress.
+enum Sink {
+  Wav(PathBuf, hound::WavWriter<BufWriter<File>>),
+  Command(String, Child, ChildStdin),
+}
+
+impl Sink {
+  fn open(target: &Target, name: &str, sample_rate: f32) -> Result<Self, anyhow::Error> {
+    match target {
+      Target::Record(dir) => {
+        let path = dir.join(format!("{}.wav", name));
+        let spec = hound::WavSpec {
+          channels: 1,
+          sample_rate: sample_rate as u32,
+          bits_per_sample: 16,
+          sample_format: hound::SampleFormat::Int,
+        };
+        let wav = hound::WavWriter::create(&path, spec)?;
+        Ok(Sink::Wav(path, wav))
+      }
+      Target::Exec(command) => {
+        let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+        let mut child = std::process::Command::new(shell)
+          .args([flag, command])
+          .env("GOERTZELRS_SAMPLE_RATE", (sample_rate as u32).to_string())
+          .stdin(Stdio::piped())
+          .spawn()?;
+        let stdin = child.stdin.take().expect("piped stdin");
+        Ok(Sink::Command(command.clone(), child, stdin))
+      }
+    }
+  }
+
+  fn write(&mut self, chunk: &[f32]) -> Result<(), anyhow::Error> {
+    match self {
+      Sink::Wav(_, wav) => chunk.iter().try_for_each(|&s| wav.write_sample(pcm(s)))?,
+      Sink::Command(_, _, stdin) => {
+        let bytes: Vec<u8> = chunk.iter().flat_map(|&s| pcm(s).to_le_bytes()).collect();
+        stdin.write_all(&bytes)?;
+      }
+    }
+    Ok(())
+  }
+
+  fn close(self) {
+    match self {
+      Sink::Wav(path, wav) => {
+        if let Err(e) = wav.finalize() {
+          eprintln!("could not write {}: {}", path.display(), e);
+        }
+      }
+      Sink::Command(command, mut child, stdin) => {
+        drop(stdin);
+        match child.wait() {
+          Ok(status) if !status.success() => eprintln!("`{}` failed: {}", command, status),
+          Err(e) => eprintln!("`{}` failed: {}", command, e),
+          Ok(_) => {}
+        }
+      }
+    }
+  }
+}
+
+/// Opens, fills and closes the recordings of one channel as `messages` say, until the squelch
+/// goes.
+fn write(
+  settings: Settings,
+  sample_rate: f32,
+  messages: Receiver<Message>,
+  dropped: Arc<AtomicUsize>,
+) {
+  let mut sink: Option<Sink> = None;
+  for message in messages {
+    match message {
+      Message::Open { name, at } => match Sink::open(&settings.target, &name, sample_rate) {
+        Ok(opened) => {
+          eprintln!("wake tone {} Hz at {:.3} s", settings.freq, at);
+          sink = Some(opened);
+        }
+        Err(e) => eprintln!("could not start recording: {}", e),
+      },
+      Message::Audio(chunk) => {
+        if let Some(Err(e)) = sink.as_mut().map(|s| s.write(&chunk)) {
+          eprintln!("could not record: {}", e);
+          sink = None;
+        }
+      }
+      Message::Close { at } => {
+        if let Some(sink) = sink.take() {
+          sink.close();
+          eprintln!("wake tone {} Hz gone, stopped at {:.3} s", settings.freq, at);
+        }
+        let lost = dropped.swap(0, Ordering::Relaxed);
+        if lost > 0 {
+          eprintln!("the recording lost {} chunks of audio the disk could not keep up with", lost);
+        }
+      }
+    }
+  }
+  if let Some(sink) = sink {
+    sink.close();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::f32::consts::PI;
+
+  #[test]
+  fn records_from_the_tone_until_the_timeout() {
+    let dir = std::env::temp_dir().join(format!("goertzelrs-wake-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let settings = Settings { freq: 1750., timeout: 1., target: Target::Record(dir.clone()) };
+    let mut squelch = Squelch::new(&settings, 8000., None);
+    // Quiet for 1 s, the tone for 0.5 s, then 3 s of quiet again.
+    let audio: Vec<f32> = (0..36_000)
+      .map(|n| match n {
+        8000..=11_999 => (2. * PI * 1750. * n as f32 / 8000.).sin(),
+        _ => 0.,
+      })
+      .collect();
+    for chunk in audio.chunks(800) {
+      squelch.push(chunk, false);
+    }
+    squelch.finish();
+    let samples = hound::WavReader::open(dir.join("1.000-wake.wav")).unwrap().len();
+    std::fs::remove_dir_all(&dir).unwrap();
+    // From the onset to one second after the tone, give or take a chunk.
+    assert!((11_200..=12_000).contains(&samples), "{}", samples);
+  }
+}
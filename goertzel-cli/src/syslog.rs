@@ -0,0 +1,154 @@
+//! `--syslog` and `--journald`: events sent to the system log with their fields attached, so
+//! deployments using the standard Linux logging need no collector of their own.
+//!
+//! Both go over the local datagram sockets: RFC 5424 messages with a structured-data element to
+//! `/dev/log`, and journal entries in the native protocol to journald.
+
+use std::io;
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+
+use goertzel_core::detector::Event;
+use serde_json::Value;
+
+use crate::output::{self, Origin};
+
+pub const SYSLOG_SOCKET: &str = "/dev/log";
+pub const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+const IDENTIFIER: &str = "goertzelrs";
+/// `notice`: detections are normal but significant.
+const SEVERITY: u8 = 5;
+/// `user` facility.
+const FACILITY: u8 = 1;
+/// SD-ID of the structured-data element, under the private enterprise number reserved for
+/// documentation (RFC 5612).
+const SD_ID: &str = "goertzel@32473";
+
+/// One RFC 5424 message. The timestamp and host are left to the syslog daemon.
+pub fn syslog_record(origin: Origin, event: &Event) -> Vec<u8> {
+  let mut params = String::new();
+  for (key, value) in fields(origin, event) {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace(']', "\\]");
+    params.push_str(&format!(" {}=\"{}\"", key, value));
+  }
+  format!(
+    "<{}>1 - - {} {} - [{}{}] {}",
+    FACILITY * 8 + SEVERITY,
+    IDENTIFIER,
+    std::process::id(),
+    SD_ID,
+    params,
+    message(origin, event)
+  )
+  .into_bytes()
+}
+
+/// One journal entry: the text line as `MESSAGE`, every event field as `GOERTZEL_<NAME>`.
+pub fn journald_record(origin: Origin, event: &Event) -> Vec<u8> {
+  let mut entry = Vec::new();
+  journal_field(&mut entry, "MESSAGE", &message(origin, event));
+  journal_field(&mut entry, "PRIORITY", &SEVERITY.to_string());
+  journal_field(&mut entry, "SYSLOG_IDENTIFIER", IDENTIFIER);
+  for (key, value) in fields(origin, event) {
+    journal_field(&mut entry, &format!("GOERTZEL_{}", key.to_ascii_uppercase()), &value);
+  }
+  entry
+}
+
+fn message(origin: Origin, event: &Event) -> String {
+  output::Format::Text.event(origin, event)
+}
+
+/// The NDJSON fields with plain string values.
+fn fields(origin: Origin, event: &Event) -> Vec<(String, String)> {
+  match output::object(origin, event) {
+    Value::Object(map) => map
+      .into_iter()
+      .map(|(key, value)| match value {
+        Value::String(s) => (key, s),
+        other => (key, other.to_string()),
+      })
+      .collect(),
+    _ => Vec::new(),
+  }
+}
+
+/// Appends `KEY=value\n`, or the length-prefixed form the protocol needs for values with a
+/// newline.
+fn journal_field(entry: &mut Vec<u8>, key: &str, value: &str) {
+  entry.extend_from_slice(key.as_bytes());
+  if value.contains('\n') {
+    entry.push(b'\n');
+    entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+  } else {
+    entry.push(b'=');
+  }
+  entry.extend_from_slice(value.as_bytes());
+  entry.push(b'\n');
+}
+
+/// Sends every write as one datagram, so each record arrives as one log entry.
+#[cfg(unix)]
+pub struct Datagram(UnixDatagram);
+
+#[cfg(unix)]
+impl Datagram {
+  pub fn connect(path: &str) -> io::Result<Self> {
+    let socket = UnixDatagram::unbound()?;
+    socket.connect(path)?;
+    Ok(Datagram(socket))
+  }
+}
+
+#[cfg(unix)]
+impl io::Write for Datagram {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.0.send(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use goertzel_core::detector::{EventKind, Time};
+
+  fn event(kind: EventKind) -> Event {
+    Event::new(Time { sample: 8000, sample_rate: 8000. }, kind)
+  }
+
+  #[test]
+  fn syslog_message_carries_structured_data() {
+    let origin = Origin { source: Some("line \"2\""), channel: None };
+    let record = syslog_record(origin, &event(EventKind::ToneOn { freq: 1000., power: 0.5 }));
+    let record = String::from_utf8(record).unwrap();
+    let pid = std::process::id();
+    assert_eq!(
+      record,
+      format!(
+        "<13>1 - - goertzelrs {} - [goertzel@32473 event=\"tone_on\" freq=\"1000.0\" \
+         power=\"0.5\" sample=\"8000\" source=\"line \\\"2\\\"\" time=\"1.0\"] \
+         [line \"2\"] 1.000 tone on 1000 Hz (0.50)",
+        pid
+      )
+    );
+  }
+
+  #[test]
+  fn journal_entry_has_native_fields() {
+    let entry = journald_record(Origin::default(), &event(EventKind::Match("a\nb".into())));
+    let text = String::from_utf8_lossy(&entry);
+    assert!(text.starts_with("MESSAGE\n"), "{}", text);
+    assert!(text.contains("\nPRIORITY=5\nSYSLOG_IDENTIFIER=goertzelrs\nGOERTZEL_EVENT=match\n"));
+    assert!(text.contains("\nGOERTZEL_SAMPLE=8000\n"));
+    let name = b"GOERTZEL_NAME\n";
+    let at = entry.windows(name.len()).position(|w| w == name).unwrap() + name.len();
+    assert_eq!(&entry[at..at + 8], 3u64.to_le_bytes());
+    assert_eq!(&entry[at + 8..at + 12], b"a\nb\n");
+  }
+}
@@ -0,0 +1,146 @@
+//! `goertzelrs talk-off speech/`: the DTMF decoder run over a directory of recordings that
+//! contain no key presses, typically speech or music. Every digit it reports is a false one, so
+//! the count per hour of audio (the talk-off rate) shows how a threshold or twist setting holds
+//! up against real voices.
+//!
+//! `--report talkoff.json` writes the digits found in each file as JSON.
+
+use std::path::Path;
+
+use goertzel_core::detector::{Detector, EventKind, Time};
+use goertzel_dtmf::DtmfDetector;
+use serde::Serialize;
+
+use crate::file::FileSource;
+use crate::profile::Profile;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FalseDigit {
+  /// Seconds from the start of the file.
+  pub time: f64,
+  pub digit: char,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileResult {
+  pub path: String,
+  pub seconds: f64,
+  pub digits: Vec<FalseDigit>,
+}
+
+/// A file that could not be read, and why.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Skipped {
+  pub path: String,
+  pub error: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+  pub files: Vec<FileResult>,
+  pub skipped: Vec<Skipped>,
+  /// Audio scanned, in seconds.
+  pub seconds: f64,
+  pub false_digits: usize,
+  /// False digits per hour of audio.
+  pub per_hour: f64,
+}
+
+/// Decodes one recording, mixed down to mono, with the settings of `profile`.
+pub fn scan_file(path: &Path, profile: &Profile) -> Result<FileResult, anyhow::Error> {
+  let mut source = FileSource::open(path, None, false)?;
+  let (sample_rate, channels) = (source.sample_rate(), source.channels().max(1));
+  let block_size = profile.block_size_at(sample_rate);
+  let mut detector = DtmfDetector::new(sample_rate, block_size, profile.dtmf);
+  let (mut audio, mut mono) = (Vec::new(), Vec::new());
+  let mut digits = Vec::new();
+  let mut frame = 0u64;
+  loop {
+    audio.clear();
+    let read = source.read(block_size, &mut audio)?;
+    if read == 0 {
+      break;
+    }
+    mono.clear();
+    mono.extend(audio.chunks(channels).map(|f| f.iter().sum::<f32>() / channels as f32));
+    for event in detector.process_block(&mono, Time { sample: frame, sample_rate }) {
+      if let EventKind::Digit(digit) = event.kind {
+        digits.push(FalseDigit { time: event.time.seconds(), digit });
+      }
+    }
+    frame += read as u64;
+  }
+  Ok(FileResult {
+    path: path.display().to_string(),
+    seconds: frame as f64 / sample_rate as f64,
+    digits,
+  })
+}
+
+/// Scans every file of `dir`, in name order; files that are not recordings are skipped.
+pub fn run(dir: &Path, profile: &Profile) -> Result<Report, anyhow::Error> {
+  let mut paths: Vec<_> = std::fs::read_dir(dir)?
+    .map(|entry| entry.map(|e| e.path()))
+    .collect::<Result<_, _>>()?;
+  paths.retain(|p| p.is_file());
+  paths.sort();
+  let (mut files, mut skipped) = (Vec::new(), Vec::new());
+  for path in paths {
+    match scan_file(&path, profile) {
+      Ok(result) => files.push(result),
+      Err(e) => skipped.push(Skipped { path: path.display().to_string(), error: e.to_string() }),
+    }
+  }
+  let seconds: f64 = files.iter().map(|f| f.seconds).sum();
+  let false_digits = files.iter().map(|f| f.digits.len()).sum();
+  let per_hour = if seconds > 0. { false_digits as f64 * 3600. / seconds } else { 0. };
+  Ok(Report { files, skipped, seconds, false_digits, per_hour })
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::f32::consts::PI;
+
+  fn write_wav(path: &Path, samples: impl Iterator<Item = f32>) {
+    let spec = hound::WavSpec {
+      channels: 1,
+      sample_rate: 8000,
+      bits_per_sample: 16,
+      sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).unwrap();
+    for s in samples {
+      writer.write_sample((s * 16000.) as i16).unwrap();
+    }
+    writer.finalize().unwrap();
+  }
+
+  #[test]
+  fn counts_digits_in_every_recording() {
+    let dir = std::env::temp_dir().join(format!("goertzelrs-talkoff-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // A vowel-like harmonic series stays quiet; a stray 770 + 1336 Hz pair reads as a 5.
+    let vowel = (0..16000).map(|t| {
+      (1..8).map(|h| (2. * PI * 120. * h as f32 * t as f32 / 8000.).sin() / h as f32).sum::<f32>()
+        / 3.
+    });
+    let pair = (0..4000).map(|t| {
+      let t = t as f32 / 8000.;
+      0.4 * (2. * PI * 770. * t).sin() + 0.4 * (2. * PI * 1336. * t).sin()
+    });
+    write_wav(&dir.join("a.wav"), vowel);
+    write_wav(&dir.join("b.wav"), pair);
+    std::fs::write(dir.join("notes.txt"), "not audio").unwrap();
+
+    let report = run(&dir, &Profile::telephony()).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(report.files.len(), 2);
+    assert_eq!(report.skipped.len(), 1);
+    assert!(report.files[0].digits.is_empty());
+    assert_eq!(report.files[1].digits.iter().map(|d| d.digit).collect::<Vec<_>>(), ['5']);
+    assert_eq!((report.seconds, report.false_digits), (2.5, 1));
+    assert_eq!(report.per_hour, 1440.);
+  }
+}
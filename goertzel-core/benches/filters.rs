@@ -0,0 +1,113 @@
+//! Throughput of the filter variants: per sample against per block, the scalar bank against
+//! [`SimdBank`], and f32 against [`GoertzelF64`] and [`GoertzelFixed`].
+//!
+//! Run with `cargo bench -p goertzel-core --bench filters`. Criterion reports the throughput of
+//! each benchmark in samples/s, its `thrpt` line, and keeps the results under
+//! `target/criterion`; the numbers depend on the machine, so compare runs on the same one with
+//! `-- --save-baseline before`, then `-- --baseline before` after a change.
+//!
+//! The tandem per-sample filter pays for two recursions and a division on every sample; block
+//! evaluation only computes the power once per block.
+
+use std::f32::consts::PI;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use goertzel_core::precision::{GoertzelF64, GoertzelFixed};
+use goertzel_core::simd::SimdBank;
+use goertzel_core::{presets, Goertzel, GoertzelBank, GoertzelConst};
+
+const SAMPLE_RATE: f32 = 8000.;
+const LEN: usize = 8000;
+
+fn signal() -> Vec<f32> {
+  (0..LEN).map(|t| (2.*PI*1000.*(t as f32)/SAMPLE_RATE).sin()).collect()
+}
+
+fn single(c: &mut Criterion) {
+  let input = signal();
+  let mut group = c.benchmark_group("single");
+  group.throughput(Throughput::Elements(LEN as u64));
+  group.bench_function("per_sample", |b| {
+    let mut g = Goertzel::new(1000., SAMPLE_RATE);
+    b.iter(|| {
+      for &s in &input {
+        black_box(g.filter(s));
+      }
+    })
+  });
+  group.bench_function("block", |b| {
+    let g = Goertzel::new(1000., SAMPLE_RATE);
+    b.iter(|| {
+      for block in input.chunks(200) {
+        black_box(g.block_power(block));
+      }
+    })
+  });
+  group.bench_function("const_block", |b| {
+    let mut g = GoertzelConst::<200>::new(1000., SAMPLE_RATE);
+    b.iter(|| {
+      for &s in &input {
+        black_box(g.push(s));
+      }
+    })
+  });
+  group.finish();
+}
+
+fn bank(c: &mut Criterion) {
+  let input = signal();
+  let mut group = c.benchmark_group("bank");
+  group.throughput(Throughput::Elements(LEN as u64));
+  group.bench_function("ctcss_per_sample", |b| {
+    let mut bank = presets::ctcss(SAMPLE_RATE);
+    b.iter(|| {
+      for &s in &input {
+        black_box(bank.filter(s));
+      }
+    })
+  });
+  group.bench_function("ctcss_block", |b| {
+    let bank = GoertzelBank::new(&presets::CTCSS_TONES, SAMPLE_RATE);
+    b.iter(|| black_box(bank.block_power(&input)))
+  });
+  group.bench_function("ctcss_simd", |b| {
+    let bank = SimdBank::new(&presets::CTCSS_TONES, SAMPLE_RATE);
+    b.iter(|| black_box(bank.block_power(&input)))
+  });
+  group.finish();
+}
+
+fn precision(c: &mut Criterion) {
+  let input = signal();
+  let fixed_input: Vec<i16> = input.iter().map(|x| (x * 32767.) as i16).collect();
+  let mut group = c.benchmark_group("precision");
+  group.throughput(Throughput::Elements(LEN as u64));
+  group.bench_function("f32", |b| {
+    let g = Goertzel::new(1000., SAMPLE_RATE);
+    b.iter(|| {
+      for block in input.chunks(200) {
+        black_box(g.block_power(block));
+      }
+    })
+  });
+  group.bench_function("f64", |b| {
+    let g = GoertzelF64::new(1000., SAMPLE_RATE as f64);
+    b.iter(|| {
+      for block in input.chunks(200) {
+        black_box(g.block_power(block));
+      }
+    })
+  });
+  group.bench_function("fixed", |b| {
+    let g = GoertzelFixed::new(1000., SAMPLE_RATE);
+    b.iter(|| {
+      for block in fixed_input.chunks(200) {
+        black_box(g.block_power(block));
+      }
+    })
+  });
+  group.finish();
+}
+
+criterion_group!(benches, single, bank, precision);
+criterion_main!(benches);
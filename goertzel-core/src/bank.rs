@@ -0,0 +1,393 @@
+use alloc::vec::Vec;
+
+use crate::units::{SampleRate, Samples};
+use crate::{math, CoefficientTable, Goertzel, Normalization, Reading};
+
+/// How a bank fits its frequencies to the analysis block.
+///
+/// A frequency that is an exact bin (`k * samplef / block_size`) is orthogonal to the mirror image
+/// of a real input and to the other bins over one block, so its level reads exactly; in between
+/// bins the reading is off by up to a few percent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alignment {
+  /// Use the frequencies as given, at fractional bins where they fall in between; see
+  /// `Goertzel::block_dft`.
+  Exact,
+  /// Move each frequency to the nearest bin of the block size.
+  SnapFrequency,
+  /// Keep the frequencies and pick, for each one, the block size within a quarter of the
+  /// requested one that puts it closest to a bin.
+  AdjustBlockSize,
+}
+
+/// A set of Goertzel filters fed with the same samples.
+#[derive(Debug)]
+pub struct GoertzelBank {
+  filters: Vec<Goertzel>,
+  /// Whether the block methods give each filter only the first `block_size` samples.
+  own_block_sizes: bool,
+}
+
+impl GoertzelBank {
+  pub fn new(freqs: &[f32], samplef: impl Into<SampleRate>) -> Self {
+    let samplef = samplef.into();
+    Self {
+      filters: freqs.iter().map(|&f| Goertzel::new(f, samplef)).collect(),
+      own_block_sizes: false,
+    }
+  }
+
+  pub fn with_block_size(
+    freqs: &[f32],
+    samplef: impl Into<SampleRate>,
+    block_size: impl Into<Samples>,
+  ) -> Self {
+    let (samplef, block_size) = (samplef.into(), block_size.into());
+    Self {
+      filters: freqs.iter().map(|&f| Goertzel::with_block_size(f, samplef, block_size)).collect(),
+      own_block_sizes: false,
+    }
+  }
+
+  /// Bank of `freqs` at `samplef`, taking the coefficients from `table` when it covers
+  /// `samplef`, so no `cos()` runs, and computing them otherwise.
+  pub fn from_table<const N: usize>(
+    freqs: &[f32; N],
+    table: &CoefficientTable<N>,
+    samplef: f32,
+    block_size: usize,
+  ) -> Self {
+    let filters = match table.get(samplef) {
+      Some(coeffs) => freqs
+        .iter()
+        .zip(coeffs)
+        .map(|(&f, &coeff)| Goertzel::with_coeff(f, samplef, block_size, coeff))
+        .collect(),
+      None => return Self::with_block_size(freqs, samplef, block_size),
+    };
+    Self { filters, own_block_sizes: false }
+  }
+
+  /// Bank whose frequencies, or block sizes, are fitted to exact bins. `frequencies` and
+  /// `block_sizes` report what is actually analyzed.
+  pub fn aligned(freqs: &[f32], samplef: f32, block_size: usize, alignment: Alignment) -> Self {
+    let n = block_size.max(1);
+    let filter = |f: f32| match alignment {
+      Alignment::Exact => Goertzel::with_block_size(f, samplef, n),
+      Alignment::SnapFrequency => {
+        let k = math::round(f * n as f32 / samplef);
+        Goertzel::with_block_size(k * samplef / n as f32, samplef, n)
+      }
+      Alignment::AdjustBlockSize => Goertzel::with_block_size(f, samplef, best_block_size(f, samplef, n)),
+    };
+    Self {
+      filters: freqs.iter().map(|&f| filter(f)).collect(),
+      own_block_sizes: alignment == Alignment::AdjustBlockSize,
+    }
+  }
+
+  /// Bank of filters set up one by one, each keeping its own block size, window and
+  /// normalization, e.g. a long block for a CTCSS tone next to a short one for a beep. The
+  /// block methods give each filter the first `block_size` samples of the block, so blocks
+  /// should be as long as the longest member.
+  pub fn from_filters(filters: Vec<Goertzel>) -> Self {
+    Self { filters, own_block_sizes: true }
+  }
+
+  /// Bank of `low`, then every `1 / per_octave` octave up to `high` Hz (24 to the octave for
+  /// 1/24 octave bins), each filter with the block size that makes its bin as wide as the step
+  /// to the next: the bins have constant relative bandwidth, as the ear and musical scales do.
+  ///
+  /// Low bins need long blocks, e.g. 1.7 s for 20 Hz at 48 kHz in 1/24 octave; as with
+  /// `from_filters`, each filter measures the first `block_size` samples of a block.
+  pub fn log_spaced(low: f32, high: f32, per_octave: f32, samplef: f32) -> Self {
+    let step = math::powf(2., 1. / per_octave);
+    let mut filters = Vec::new();
+    let mut freq = low;
+    while freq <= high * 1.0001 {
+      let block_size = math::round(samplef / (freq * (step - 1.))).max(1.) as usize;
+      filters.push(Goertzel::with_block_size(freq, samplef, block_size));
+      freq *= step;
+    }
+    Self::from_filters(filters)
+  }
+
+  /// Same normalization for every member.
+  pub fn with_normalization(mut self, normalization: Normalization) -> Self {
+    for g in &mut self.filters {
+      g.set_normalization(normalization);
+    }
+    self
+  }
+
+  /// What member `index` reports, e.g. `Amplitude` for a meter next to members that keep the
+  /// default share of energy for detection.
+  ///
+  /// Panics if `index` is out of range.
+  pub fn set_normalization_at(&mut self, index: usize, normalization: Normalization) {
+    self.filters[index].set_normalization(normalization);
+  }
+
+  /// Same silence floor for every member; see [`Goertzel::with_silence_floor`].
+  pub fn with_silence_floor(mut self, mean_square: f32) -> Self {
+    for g in &mut self.filters {
+      g.set_silence_floor(mean_square);
+    }
+    self
+  }
+
+  pub fn normalizations(&self) -> Vec<Normalization> {
+    self.filters.iter().map(|g| g.normalization()).collect()
+  }
+
+  pub fn len(&self) -> usize {
+    self.filters.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.filters.is_empty()
+  }
+
+  pub fn frequencies(&self) -> Vec<f32> {
+    self.filters.iter().map(|g| g.freq()).collect()
+  }
+
+  pub fn filters(&self) -> &[Goertzel] {
+    &self.filters
+  }
+
+  pub fn block_sizes(&self) -> Vec<usize> {
+    self.filters.iter().map(|g| g.block_size()).collect()
+  }
+
+  /// `filter` writing into `out`, one power per frequency, without allocating. Safe to call from
+  /// an audio callback.
+  ///
+  /// Panics if `out` does not have one entry per frequency.
+  pub fn filter_into(&mut self, sample: f32, out: &mut [f32]) {
+    assert_eq!(out.len(), self.filters.len(), "one output per frequency");
+    for (g, out) in self.filters.iter_mut().zip(out) {
+      *out = g.filter(sample);
+    }
+  }
+
+  /// `block_power` writing into `out`, one power per frequency, without allocating. Safe to call
+  /// from an audio callback.
+  ///
+  /// Panics if `out` does not have one entry per frequency.
+  pub fn process_into(&self, block: &[f32], out: &mut [f32]) {
+    assert_eq!(out.len(), self.filters.len(), "one output per frequency");
+    for (g, out) in self.filters.iter().zip(out) {
+      *out = g.block_power(&block[..self.span(g, block.len())]);
+    }
+  }
+
+  /// How many samples of a `len`-sample block `g` measures.
+  pub(crate) fn span(&self, g: &Goertzel, len: usize) -> usize {
+    match self.own_block_sizes {
+      true => len.min(g.block_size()),
+      false => len,
+    }
+  }
+
+  /// Runs every filter on `sample`, returning one power per frequency.
+  pub fn filter(&mut self, sample: f32) -> Vec<f32> {
+    self.filters.iter_mut().map(|g| g.filter(sample)).collect()
+  }
+
+  /// `Goertzel::block_power` for every frequency. With `Alignment::AdjustBlockSize`, or members
+  /// from `from_filters`, each filter only looks at the first `block_size` samples of its own.
+  pub fn block_power(&self, block: &[f32]) -> Vec<f32> {
+    let mut out = alloc::vec![0.; self.filters.len()];
+    self.process_into(block, &mut out);
+    out
+  }
+
+  /// `Goertzel::block_reading` for every frequency, over the same samples as `block_power`.
+  pub fn block_readings(&self, block: &[f32]) -> Vec<Reading> {
+    self.filters.iter().map(|g| g.block_reading(&block[..self.span(g, block.len())])).collect()
+  }
+
+  /// `block_power` of each channel of `interleaved` frames of `channels` samples, in channel
+  /// order. A trailing partial frame is dropped.
+  pub fn process_frames(&self, interleaved: &[f32], channels: usize) -> Vec<Vec<f32>> {
+    let channels = channels.max(1);
+    let frames = interleaved.len() / channels;
+    let mut block = Vec::with_capacity(frames);
+    (0..channels)
+      .map(|channel| {
+        block.clear();
+        block.extend(interleaved.iter().skip(channel).step_by(channels).take(frames));
+        self.block_power(&block)
+      })
+      .collect()
+  }
+
+  /// `Goertzel::block_dft` for every frequency.
+  pub fn block_dft(&self, block: &[f32]) -> Vec<(f32, f32)> {
+    self.filters.iter().map(|g| g.block_dft(block)).collect()
+  }
+}
+
+/// Block size within a quarter of `n` that puts `freq` closest to a bin, the nearest to `n` on
+/// a tie.
+fn best_block_size(freq: f32, samplef: f32, n: usize) -> usize {
+  let misalignment = |m: usize| {
+    let k = freq * m as f32 / samplef;
+    (k - math::round(k)).abs()
+  };
+  let (low, high) = (n - n / 4, n + n / 4);
+  (low..=high)
+    .min_by(|&a, &b| {
+      misalignment(a)
+        .partial_cmp(&misalignment(b))
+        .unwrap_or(core::cmp::Ordering::Equal)
+        .then(a.abs_diff(n).cmp(&b.abs_diff(n)))
+    })
+    .unwrap_or(n)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::alloc::{GlobalAlloc, Layout, System};
+  use std::cell::Cell;
+  use std::f32::consts::PI;
+
+  /// Counts the allocations of the current thread, so tests can check hot paths stay clear of
+  /// the heap.
+  struct CountingAlloc;
+
+  thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+  }
+
+  unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+      ALLOCATIONS.with(|n| n.set(n.get() + 1));
+      System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+      System.dealloc(ptr, layout)
+    }
+  }
+
+  #[global_allocator]
+  static GLOBAL: CountingAlloc = CountingAlloc;
+
+  fn allocations() -> usize {
+    ALLOCATIONS.with(|n| n.get())
+  }
+
+  #[test]
+  fn only_matching_member_responds() {
+    let mut bank = GoertzelBank::new(&[500., 1000., 2000.], 8000.);
+    let mut out = vec![];
+    for t in 0..3000 {
+      out = bank.filter((2.*PI*1000.*(t as f32)/8000.).sin());
+    }
+    assert!(out[1] > 0.8);
+    assert!(out[0] < 0.02 && out[2] < 0.02);
+  }
+
+  #[test]
+  fn members_report_their_own_output() {
+    let mut bank = GoertzelBank::with_block_size(&[1000., 1000., 1000.], 8000., 400);
+    bank.set_normalization_at(1, Normalization::PerSampleCount);
+    bank.set_normalization_at(2, Normalization::Amplitude);
+    let block: Vec<f32> = (0..400).map(|t| 0.5 * (2.*PI*1000.*(t as f32)/8000.).sin()).collect();
+    let out = bank.block_power(&block);
+    assert!((out[0] - 1.).abs() < 1e-3);
+    assert!((out[1] - 0.125).abs() < 1e-3);
+    assert!((out[2] - 0.5).abs() < 1e-3);
+
+    // Quiet enough, every member reports silence rather than a ratio.
+    let bank = bank.with_silence_floor(1e-8);
+    let faint: Vec<f32> = block.iter().map(|x| x * 1e-4).collect();
+    assert_eq!(bank.block_readings(&faint), vec![Reading::Silence; 3]);
+    let readings = bank.block_readings(&block);
+    assert!(matches!(readings[1], Reading::Power(p) if (p - 0.125).abs() < 1e-3));
+  }
+
+  #[test]
+  fn measures_each_channel_of_interleaved_frames() {
+    let bank = GoertzelBank::new(&[500., 1000.], 8000.);
+    let tone = |f: f32, t: usize| (2.*PI*f*(t as f32)/8000.).sin();
+    let frames: Vec<f32> = (0..400).flat_map(|t| vec![tone(500., t), tone(1000., t)]).collect();
+    let out = bank.process_frames(&frames, 2);
+    assert!(out[0][0] > 0.9 && out[0][1] < 0.05);
+    assert!(out[1][1] > 0.9 && out[1][0] < 0.05);
+  }
+
+  #[test]
+  fn into_apis_do_not_allocate() {
+    let mut bank = crate::presets::ctcss(8000.);
+    let block: Vec<f32> = (0..400).map(|t| (2.*PI*100.*(t as f32)/8000.).sin()).collect();
+    let mut out = vec![0.; bank.len()];
+    let before = allocations();
+    bank.process_into(&block, &mut out);
+    for &sample in &block {
+      bank.filter_into(sample, &mut out);
+    }
+    assert_eq!(allocations(), before);
+    assert_eq!(bank.block_power(&block), {
+      bank.process_into(&block, &mut out);
+      out
+    });
+  }
+
+  #[test]
+  fn alignment_puts_frequencies_on_bins() {
+    let snapped = GoertzelBank::aligned(&[1000.], 8000., 205, Alignment::SnapFrequency);
+    assert!((snapped.frequencies()[0] - 26. * 8000. / 205.).abs() < 1e-3);
+    let adjusted = GoertzelBank::aligned(&[1000., 697.], 8000., 205, Alignment::AdjustBlockSize);
+    assert_eq!(adjusted.frequencies(), vec![1000., 697.]);
+    assert_eq!(adjusted.block_sizes()[0], 208);
+
+    // A tone on its bin reads exactly full scale, unlike one between bins.
+    let tone: Vec<f32> = (0..208).map(|t| (2.*PI*1000.*(t as f32)/8000. + 0.3).sin()).collect();
+    let on_bin = adjusted.block_power(&tone)[0];
+    let off_bin = GoertzelBank::with_block_size(&[1000.], 8000., 205).block_power(&tone[..205])[0];
+    assert!((on_bin - 1.).abs() < 1e-3, "{}", on_bin);
+    assert!((off_bin - 1.).abs() > (on_bin - 1.).abs());
+  }
+
+  #[test]
+  fn members_keep_their_own_settings() {
+    let bank = GoertzelBank::from_filters(vec![
+      Goertzel::with_block_size(100., 8000., 800).with_window(crate::Window::Hann),
+      Goertzel::with_block_size(3000., 8000., 80),
+    ]);
+    assert_eq!(bank.block_sizes(), vec![800, 80]);
+    // A 3 kHz beep over the first 10 ms only, on a steady 100 Hz tone.
+    let block: Vec<f32> = (0..800)
+      .map(|t| {
+        let t = t as f32 / 8000.;
+        let beep = if t < 0.01 { (2.*PI*3000.*t).sin() } else { 0. };
+        0.1 * (2.*PI*100.*t).sin() + beep
+      })
+      .collect();
+    let powers = bank.block_power(&block);
+    // Over the long block the beep has ten times the energy of the tone.
+    assert!((powers[0] - 1. / 11.).abs() < 0.01, "{:?}", powers);
+    assert!(powers[1] > 0.9, "{:?}", powers);
+  }
+
+  #[test]
+  fn log_spaced_bins_keep_their_relative_width() {
+    let bank = GoertzelBank::log_spaced(110., 880., 12., 8000.);
+    let (freqs, sizes) = (bank.frequencies(), bank.block_sizes());
+    assert_eq!(freqs.len(), 37);
+    assert!((freqs[12] - 220.).abs() < 0.01 && (freqs[36] - 880.).abs() < 0.05, "{:?}", freqs);
+    // An octave up, half the block.
+    assert_eq!(sizes[0], 1223);
+    assert!(sizes[12].abs_diff(612) <= 1 && sizes[36].abs_diff(153) <= 1, "{:?}", sizes);
+
+    // A semitone apart, 440 Hz stands out of 466 Hz and 415 Hz.
+    let tone: Vec<f32> = (0..1223).map(|t| (2.*PI*440.*(t as f32)/8000.).sin()).collect();
+    let powers = bank.block_power(&tone);
+    assert!(powers[24] > 0.9 && powers[23] < 0.1 && powers[25] < 0.1, "{:?}", &powers[23..26]);
+  }
+}
@@ -0,0 +1,257 @@
+//! On/off timing of a tone, classified against known cadences such as busy (0.5 s on, 0.5 s
+//! off) or ringback (2 s on, 4 s off), or by the beeps of a burst such as a coin tone.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::detector::{Detector, Event, EventKind, Time};
+
+/// A repeating on/off pattern, durations in seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CadenceTemplate {
+  pub name: String,
+  pub on: f32,
+  pub off: f32,
+}
+
+impl CadenceTemplate {
+  pub fn new(name: impl Into<String>, on: f32, off: f32) -> Self {
+    Self { name: name.into(), on, off }
+  }
+
+  /// Whether a measured cycle is within `tolerance` (a fraction of each duration) of this one.
+  pub fn fits(&self, on: f32, off: f32, tolerance: f32) -> bool {
+    (on - self.on).abs() <= tolerance * self.on && (off - self.off).abs() <= tolerance * self.off
+  }
+}
+
+/// Measures the on/off cycles of the tone reported by `source` and emits `Match(name)` once
+/// `cycles` consecutive cycles fit the same template.
+///
+/// Both single tones and dual tones count; the source's own events are passed through. The match
+/// is reported once, at the onset that completes the last cycle, and again only after the
+/// cadence changed.
+#[derive(Debug)]
+pub struct Cadence<D> {
+  source: D,
+  templates: Vec<CadenceTemplate>,
+  tolerance: f32,
+  cycles: usize,
+  /// Onset of the current (or last) on period.
+  on_since: Option<Time>,
+  /// Length and end of the last on period.
+  last_on: Option<(f32, Time)>,
+  /// Last complete cycle, as on and off durations.
+  last_cycle: Option<(f32, f32)>,
+  /// Template the recent cycles fit, and how many in a row.
+  run: Option<(usize, usize)>,
+}
+
+impl<D: Detector> Cadence<D> {
+  pub fn new(source: D, templates: Vec<CadenceTemplate>, tolerance: f32, cycles: usize) -> Self {
+    Self {
+      source,
+      templates,
+      tolerance,
+      cycles: cycles.max(1),
+      on_since: None,
+      last_on: None,
+      last_cycle: None,
+      run: None,
+    }
+  }
+
+  /// The detector whose on and off periods are timed.
+  pub fn source_mut(&mut self) -> &mut D {
+    &mut self.source
+  }
+
+  /// On and off durations of the most recent complete cycle.
+  pub fn last_cycle(&self) -> Option<(f32, f32)> {
+    self.last_cycle
+  }
+
+  /// Template matched by the recent cycles, once `cycles` of them fit.
+  pub fn current(&self) -> Option<&CadenceTemplate> {
+    match self.run {
+      Some((i, n)) if n >= self.cycles => Some(&self.templates[i]),
+      _ => None,
+    }
+  }
+
+  /// Tracks one edge; returns the template a completed cycle newly settled on.
+  fn edge(&mut self, on: bool, time: Time) -> Option<usize> {
+    if !on {
+      if let Some(start) = self.on_since.take() {
+        self.last_on = Some(((time.seconds() - start.seconds()) as f32, time));
+      }
+      return None;
+    }
+    self.on_since = Some(time);
+    let (on, off_since) = self.last_on.take()?;
+    let off = (time.seconds() - off_since.seconds()) as f32;
+    self.last_cycle = Some((on, off));
+    let fit = self.templates.iter().position(|t| t.fits(on, off, self.tolerance));
+    self.run = match (fit, self.run) {
+      (Some(i), Some((j, n))) if i == j => Some((i, n + 1)),
+      (Some(i), _) => Some((i, 1)),
+      (None, _) => None,
+    };
+    match self.run {
+      Some((i, n)) if n == self.cycles => Some(i),
+      _ => None,
+    }
+  }
+}
+
+impl<D: Detector> Detector for Cadence<D> {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let mut events = Vec::new();
+    for event in self.source.process_block(block, t) {
+      let edge = match event.kind {
+        EventKind::ToneOn { .. } | EventKind::DualToneOn { .. } => Some(true),
+        EventKind::ToneOff { .. } | EventKind::DualToneOff { .. } => Some(false),
+        _ => None,
+      };
+      let time = event.time;
+      events.push(event);
+      if let Some(i) = edge.and_then(|on| self.edge(on, time)) {
+        events.push(Event::new(time, EventKind::Match(self.templates[i].name.clone())));
+      }
+    }
+    events
+  }
+}
+
+/// A burst of `beeps` beeps of `on` seconds each.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BurstTemplate {
+  pub name: String,
+  pub beeps: usize,
+  pub on: f32,
+}
+
+impl BurstTemplate {
+  pub fn new(name: impl Into<String>, beeps: usize, on: f32) -> Self {
+    Self { name: name.into(), beeps, on }
+  }
+}
+
+/// Counts the beeps reported by `source` in bursts ended by `max_gap` seconds without one, and
+/// emits `Match(name)` when a burst has the count of a template and each beep its length within
+/// `tolerance` (a fraction of it).
+///
+/// The match is timed from the start of the burst but only known once the burst is over. The
+/// source's own events are passed through.
+#[derive(Debug)]
+pub struct Bursts<D> {
+  source: D,
+  templates: Vec<BurstTemplate>,
+  tolerance: f32,
+  max_gap: f32,
+  /// Onset of the current beep.
+  on_since: Option<Time>,
+  /// Start of the burst, the lengths of its beeps so far and the end of the last one.
+  burst: Option<(Time, Vec<f32>, Time)>,
+}
+
+impl<D: Detector> Bursts<D> {
+  pub fn new(source: D, templates: Vec<BurstTemplate>, tolerance: f32, max_gap: f32) -> Self {
+    Self { source, templates, tolerance, max_gap, on_since: None, burst: None }
+  }
+
+  fn classify(&self, beeps: &[f32]) -> Option<&BurstTemplate> {
+    self.templates.iter().find(|t| {
+      t.beeps == beeps.len() && beeps.iter().all(|on| (on - t.on).abs() <= self.tolerance * t.on)
+    })
+  }
+}
+
+impl<D: Detector> Detector for Bursts<D> {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let mut events = Vec::new();
+    for event in self.source.process_block(block, t) {
+      match event.kind {
+        EventKind::ToneOn { .. } | EventKind::DualToneOn { .. } => {
+          self.on_since = Some(event.time);
+        }
+        EventKind::ToneOff { .. } | EventKind::DualToneOff { .. } => {
+          if let Some(start) = self.on_since.take() {
+            let on = (event.time.seconds() - start.seconds()) as f32;
+            let (_, beeps, end) = self.burst.get_or_insert((start, Vec::new(), start));
+            beeps.push(on);
+            *end = event.time;
+          }
+        }
+        _ => {}
+      }
+      events.push(event);
+    }
+    let over = match &self.burst {
+      Some((_, _, end)) if self.on_since.is_none() => {
+        (t.offset(block.len()).seconds() - end.seconds()) as f32 > self.max_gap
+      }
+      _ => false,
+    };
+    if over {
+      let (start, beeps, _) = self.burst.take().unwrap_or((t, Vec::new(), t));
+      if let Some(template) = self.classify(&beeps) {
+        events.push(Event::new(start, EventKind::Match(template.name.clone())));
+      }
+    }
+    events
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::presets::call_progress_cadences;
+
+  /// Reports the tone as on for the given (start, end) ranges of 10 ms blocks.
+  struct Keyed(Vec<(u64, u64)>);
+
+  impl Detector for Keyed {
+    fn process_block(&mut self, _: &[f32], t: Time) -> Vec<Event> {
+      let block = t.sample / 80;
+      let kind = if self.0.iter().any(|&(start, _)| start == block) {
+        EventKind::ToneOn { freq: 480., power: 1. }
+      } else if self.0.iter().any(|&(_, end)| end == block) {
+        EventKind::ToneOff { freq: 480. }
+      } else {
+        return Vec::new();
+      };
+      alloc::vec![Event::new(t, kind)]
+    }
+  }
+
+  fn classify(on: u64, off: u64, count: u64) -> (Vec<Event>, Cadence<Keyed>) {
+    let periods = (0..count).map(|i| (i * (on + off), i * (on + off) + on)).collect();
+    let mut cadence = Cadence::new(Keyed(periods), call_progress_cadences(), 0.1, 2);
+    let events = (0..count * (on + off))
+      .flat_map(|i| cadence.process_block(&[], Time { sample: i * 80, sample_rate: 8000. }))
+      .filter(|e| matches!(e.kind, EventKind::Match(_)))
+      .collect();
+    (events, cadence)
+  }
+
+  #[test]
+  fn classifies_busy_after_two_cycles() {
+    let (events, cadence) = classify(50, 50, 5);
+    // Onsets at 0, 1 and 2 s complete the first two cycles.
+    assert_eq!(events, alloc::vec![Event::new(
+      Time { sample: 16000, sample_rate: 8000. },
+      EventKind::Match("busy".into()),
+    )]);
+    assert_eq!(cadence.current().map(|t| t.name.as_str()), Some("busy"));
+    assert_eq!(cadence.last_cycle(), Some((0.5, 0.5)));
+  }
+
+  #[test]
+  fn unknown_cadence_is_not_classified() {
+    let (events, cadence) = classify(100, 100, 5);
+    assert!(events.is_empty());
+    assert_eq!(cadence.current(), None);
+  }
+}
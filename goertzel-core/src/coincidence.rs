@@ -0,0 +1,127 @@
+//! Signals made of several tones at once, such as any three of a set of four, possibly with
+//! others that must be absent, for signaling schemes beyond single and dual tones.
+
+use alloc::vec::Vec;
+
+use crate::detector::{Detector, Event, EventKind, Time};
+use crate::{Goertzel, Window};
+
+/// Reports `CoincidenceOn` once at least `required` of the `present` tones and none of the
+/// `absent` ones have passed the threshold in every block for `min_duration`, and
+/// `CoincidenceOff` when that stops.
+///
+/// Powers are relative to the block's total power, so they are shared between the tones
+/// sounding together: a threshold for three tones is about a third of one for a single tone.
+/// The onset is timed from the first block that met the rule.
+#[derive(Debug)]
+pub struct CoincidenceDetector {
+  present: Vec<Goertzel>,
+  absent: Vec<Goertzel>,
+  required: usize,
+  threshold: f32,
+  min_duration: f32,
+  /// Start of the blocks meeting the rule so far.
+  since: Option<u64>,
+  on: bool,
+}
+
+impl CoincidenceDetector {
+  pub fn new(
+    present: &[f32],
+    absent: &[f32],
+    required: usize,
+    sample_rate: f32,
+    threshold: f32,
+  ) -> Self {
+    let filters = |freqs: &[f32]| freqs.iter().map(|&f| Goertzel::new(f, sample_rate)).collect();
+    Self {
+      present: filters(present),
+      absent: filters(absent),
+      required: required.clamp(1, present.len().max(1)),
+      threshold,
+      min_duration: 0.,
+      since: None,
+      on: false,
+    }
+  }
+
+  pub fn with_window(mut self, window: Window) -> Self {
+    let windowed = |filters: Vec<Goertzel>| filters.into_iter().map(|f| f.with_window(window));
+    self.present = windowed(self.present).collect();
+    self.absent = windowed(self.absent).collect();
+    self
+  }
+
+  /// Seconds the rule must hold to be reported.
+  pub fn with_min_duration(mut self, seconds: f32) -> Self {
+    self.min_duration = seconds;
+    self
+  }
+}
+
+impl Detector for CoincidenceDetector {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let threshold = self.threshold;
+    let found: Vec<f32> = self
+      .present
+      .iter()
+      .filter(|f| f.block_power(block) >= threshold)
+      .map(Goertzel::freq)
+      .collect();
+    let blocked = self.absent.iter().any(|f| f.block_power(block) >= threshold);
+    if found.len() < self.required || blocked {
+      self.since = None;
+      if self.on {
+        self.on = false;
+        return alloc::vec![Event::new(t, EventKind::CoincidenceOff)];
+      }
+      return Vec::new();
+    }
+    let since = *self.since.get_or_insert(t.sample);
+    let lasted = (t.sample + block.len() as u64 - since) as f32 / t.sample_rate;
+    if self.on || lasted < self.min_duration {
+      return Vec::new();
+    }
+    self.on = true;
+    let onset = Time { sample: since, ..t };
+    alloc::vec![Event::new(onset, EventKind::CoincidenceOn { freqs: found })]
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use core::f32::consts::PI;
+
+  fn tones(freqs: &[f32]) -> Vec<f32> {
+    (0..160)
+      .map(|n| freqs.iter().map(|f| (2. * PI * f * n as f32 / 8000.).sin()).sum::<f32>() / 3.)
+      .collect()
+  }
+
+  #[test]
+  fn two_of_three_without_the_guard_tone() {
+    let mut detector = CoincidenceDetector::new(&[700., 900., 1100.], &[2600.], 2, 8000., 0.2)
+      .with_min_duration(0.03);
+    let blocks = [
+      tones(&[700.]),
+      tones(&[700., 1100.]),
+      tones(&[700., 1100.]),
+      tones(&[700., 900., 1100.]),
+      tones(&[700., 1100., 2600.]),
+      tones(&[900., 1100.]),
+    ];
+    let t = Time { sample: 0, sample_rate: 8000. };
+    let events: Vec<(u64, EventKind)> = blocks
+      .iter()
+      .enumerate()
+      .flat_map(|(i, b)| detector.process_block(b, t.offset(i * 160)))
+      .map(|e| (e.time.sample, e.kind))
+      .collect();
+    assert_eq!(events, vec![
+      (160, EventKind::CoincidenceOn { freqs: vec![700., 1100.] }),
+      (640, EventKind::CoincidenceOff),
+    ]);
+  }
+}
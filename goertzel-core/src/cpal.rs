@@ -0,0 +1,172 @@
+//! Pipelines fed straight from a cpal input stream.
+//!
+//! `input_stream` opens the stream in whatever sample format the device uses, converts each
+//! buffer the callback gets to `f32` (`f32` buffers are used as they are), mixes the channels
+//! down and pushes them through the pipeline. Events come out of the returned receiver, on any
+//! thread:
+//!
+//! ```no_run
+//! use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+//! use goertzel_core::detector::ToneDetector;
+//! use goertzel_core::Pipeline;
+//!
+//! let device = cpal::default_host().default_input_device().unwrap();
+//! let supported = device.default_input_config().unwrap();
+//! let (format, config) = (supported.sample_format(), supported.config());
+//! let rate = config.sample_rate.0 as f32;
+//! let pipeline = Pipeline::new(rate, 400).with(Box::new(ToneDetector::new(1000., rate, 0.5)));
+//! let (stream, events) =
+//!   goertzel_core::cpal::input_stream(&device, &config, format, pipeline, |e| eprintln!("{}", e))
+//!     .unwrap();
+//! stream.play().unwrap();
+//! for event in events {
+//!   println!("{:.2}s {}", event.time.seconds(), event.kind);
+//! }
+//! ```
+
+use std::sync::mpsc::{self, Receiver, SyncSender};
+
+use cpal::traits::DeviceTrait;
+use cpal::{BuildStreamError, InputCallbackInfo, Sample, SampleFormat, StreamConfig, StreamError};
+
+use crate::detector::Event;
+use crate::{ChannelPipelines, Pipeline};
+
+/// Events the receiver holds before the callback drops new ones, so the audio thread never
+/// waits on a slow reader.
+pub const CAPACITY: usize = 1024;
+
+/// Stream returned with the receiver of its events.
+pub type Opened<D, T> = (<D as DeviceTrait>::Stream, Receiver<T>);
+
+/// Runs `pipeline`, built for `config.sample_rate`, on the mono mix of the input.
+pub fn input_stream<D, E>(
+  device: &D,
+  config: &StreamConfig,
+  format: SampleFormat,
+  pipeline: Pipeline,
+  on_error: E,
+) -> Result<Opened<D, Event>, BuildStreamError>
+where
+  D: DeviceTrait,
+  E: FnMut(StreamError) + Send + 'static,
+{
+  let (sender, receiver) = mpsc::sync_channel(CAPACITY);
+  let mut mono = Mono { pipeline, channels: config.channels as usize, mix: Vec::new(), sender };
+  let stream = build(device, config, format, move |data| mono.feed(data), on_error)?;
+  Ok((stream, receiver))
+}
+
+/// Runs one pipeline of `pipelines` on each channel of the input; events come with their
+/// channel index.
+pub fn channel_input_stream<D, E>(
+  device: &D,
+  config: &StreamConfig,
+  format: SampleFormat,
+  mut pipelines: ChannelPipelines,
+  on_error: E,
+) -> Result<Opened<D, (usize, Event)>, BuildStreamError>
+where
+  D: DeviceTrait,
+  E: FnMut(StreamError) + Send + 'static,
+{
+  let (sender, receiver) = mpsc::sync_channel(CAPACITY);
+  let channels = config.channels as usize;
+  let feed = move |data: &[f32]| {
+    for (channel, events) in pipelines.process_frames(data, channels).into_iter().enumerate() {
+      for event in events {
+        let _ = sender.try_send((channel, event));
+      }
+    }
+  };
+  Ok((build(device, config, format, feed, on_error)?, receiver))
+}
+
+/// Opens the stream in `format`, handing `feed` every buffer as `f32`.
+fn build<D, F, E>(
+  device: &D,
+  config: &StreamConfig,
+  format: SampleFormat,
+  mut feed: F,
+  on_error: E,
+) -> Result<D::Stream, BuildStreamError>
+where
+  D: DeviceTrait,
+  F: FnMut(&[f32]) + Send + 'static,
+  E: FnMut(StreamError) + Send + 'static,
+{
+  let mut buffer = Vec::new();
+  match format {
+    SampleFormat::F32 => device.build_input_stream(
+      config,
+      move |data: &[f32], _: &InputCallbackInfo| feed(data),
+      on_error,
+    ),
+    SampleFormat::I16 => device.build_input_stream(
+      config,
+      move |data: &[i16], _: &InputCallbackInfo| {
+        buffer.clear();
+        buffer.extend(data.iter().map(Sample::to_f32));
+        feed(&buffer)
+      },
+      on_error,
+    ),
+    SampleFormat::U16 => device.build_input_stream(
+      config,
+      move |data: &[u16], _: &InputCallbackInfo| {
+        buffer.clear();
+        buffer.extend(data.iter().map(Sample::to_f32));
+        feed(&buffer)
+      },
+      on_error,
+    ),
+  }
+}
+
+/// A pipeline on the average of the channels.
+struct Mono {
+  pipeline: Pipeline,
+  channels: usize,
+  mix: Vec<f32>,
+  sender: SyncSender<Event>,
+}
+
+impl Mono {
+  fn feed(&mut self, data: &[f32]) {
+    let events = if self.channels <= 1 {
+      self.pipeline.push(data)
+    } else {
+      let channels = self.channels;
+      self.mix.clear();
+      self.mix.extend(data.chunks_exact(channels).map(|f| f.iter().sum::<f32>() / channels as f32));
+      self.pipeline.push(&self.mix)
+    };
+    for event in events {
+      let _ = self.sender.try_send(event);
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::detector::{EventKind, ToneDetector};
+  use std::f32::consts::PI;
+
+  #[test]
+  fn mixes_down_and_forwards_events() {
+    let (sender, receiver) = mpsc::sync_channel(CAPACITY);
+    let pipeline = Pipeline::new(8000., 160).with(Box::new(ToneDetector::new(1000., 8000., 0.5)));
+    let mut mono = Mono { pipeline, channels: 2, mix: Vec::new(), sender };
+    // The tone on the left channel only, in i16 as a device would deliver it.
+    let data: Vec<i16> = (0..1600)
+      .flat_map(|t| vec![((2. * PI * 1000. * t as f32 / 8000.).sin() * 16000.) as i16, 0])
+      .collect();
+    let converted: Vec<f32> = data.iter().map(Sample::to_f32).collect();
+    mono.feed(&converted);
+    let event = receiver.try_recv().unwrap();
+    assert!(matches!(event.kind, EventKind::ToneOn { freq, .. } if freq == 1000.));
+    assert_eq!(event.time.sample, 0);
+  }
+}
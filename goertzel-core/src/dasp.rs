@@ -0,0 +1,93 @@
+//! Interop with the `dasp` crates: feed `Frame`s to the filters and measure `Signal`s block by
+//! block, without converting samples by hand.
+//!
+//! Multi-channel frames are mixed down to their channel average.
+
+use dasp::sample::ToSample;
+use dasp::{Frame, Signal};
+
+use crate::Goertzel;
+
+/// `frame` as one `f32` sample: the average of its channels.
+pub fn mono<F: Frame>(frame: F) -> f32
+where
+  F::Sample: ToSample<f32>,
+{
+  let sum: f32 = frame.channels().map(ToSample::to_sample_).sum();
+  sum / F::CHANNELS as f32
+}
+
+/// Fills `block` with the next frames of `signal`, mixed down.
+pub fn fill_block<S: Signal>(signal: &mut S, block: &mut [f32])
+where
+  <S::Frame as Frame>::Sample: ToSample<f32>,
+{
+  for sample in block {
+    *sample = mono(signal.next());
+  }
+}
+
+impl Goertzel {
+  /// `filter` on a dasp frame.
+  pub fn filter_frame<F: Frame>(&mut self, frame: F) -> f32
+  where
+    F::Sample: ToSample<f32>,
+  {
+    self.filter(mono(frame))
+  }
+}
+
+impl crate::GoertzelBank {
+  /// `filter_into` on a dasp frame.
+  pub fn filter_frame_into<F: Frame>(&mut self, frame: F, out: &mut [f32])
+  where
+    F::Sample: ToSample<f32>,
+  {
+    self.filter_into(mono(frame), out)
+  }
+}
+
+/// Block powers of `signal` at `filter`'s frequency, one per `filter.block_size()` frames, until
+/// the signal is exhausted.
+pub fn block_powers<S: Signal>(mut signal: S, filter: Goertzel) -> impl Iterator<Item = f32>
+where
+  <S::Frame as Frame>::Sample: ToSample<f32>,
+{
+  let mut block = vec![0.; filter.block_size()];
+  core::iter::from_fn(move || {
+    if signal.is_exhausted() {
+      return None;
+    }
+    fill_block(&mut signal, &mut block);
+    Some(filter.block_power(&block))
+  })
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use dasp::signal;
+
+  #[test]
+  fn stereo_frames_are_mixed_down() {
+    assert_eq!(mono([0.5f32, -0.25]), 0.125);
+    assert_eq!(mono(0.5f64), 0.5);
+    let full: f32 = i16::MAX.to_sample_();
+    assert_eq!(mono([i16::MAX, 0]), full / 2.);
+  }
+
+  #[test]
+  fn signals_are_measured_per_block() {
+    let tone = signal::rate(8000.).const_hz(440.).sine().take(2000);
+    let filter = Goertzel::with_block_size(440., 8000., 400);
+    let powers: Vec<f32> = block_powers(signal::from_iter(tone), filter).collect();
+    assert_eq!(powers.len(), 5);
+    assert!(powers.iter().all(|&p| p > 0.9), "{:?}", powers);
+
+    let mut filter = Goertzel::new(1000., 8000.);
+    let mut quiet = signal::rate(8000.).const_hz(440.).sine();
+    let power = (0..800).map(|_| filter.filter_frame(quiet.next())).last().unwrap();
+    assert!(power < 0.05, "{}", power);
+  }
+}
@@ -0,0 +1,779 @@
+//! Extension point for the detection pipeline.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::units::{Hertz, SampleRate};
+use crate::{math, Goertzel, Normalization, Reading, Window};
+
+/// Position in the input stream.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Time {
+  /// Samples since the start of the stream.
+  pub sample: u64,
+  pub sample_rate: f32,
+}
+
+impl Time {
+  pub fn seconds(&self) -> f64 {
+    self.sample as f64 / self.sample_rate as f64
+  }
+
+  /// The time `samples` later.
+  pub fn offset(self, samples: usize) -> Time {
+    Time { sample: self.sample + samples as u64, ..self }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventKind {
+  /// A tone rose above its detector's threshold.
+  ToneOn { freq: f32, power: f32 },
+  /// The tone fell below the threshold again.
+  ToneOff { freq: f32 },
+  /// The tone has been on for longer than its detector allows, `duration` seconds so far.
+  StuckTone { freq: f32, duration: f32 },
+  /// A tone that should always be present has been missing for `duration` seconds.
+  ToneLost { freq: f32, duration: f32 },
+  /// The input has been quieter than a floor for `duration` seconds.
+  Silence { duration: f32 },
+  /// A tone expected at `nominal` Hz is now measured at `freq`, beyond its tolerance.
+  Drift { nominal: f32, freq: f32 },
+  /// Both tones of a pair became present together.
+  DualToneOn { f1: f32, f2: f32 },
+  /// The pair is no longer present.
+  DualToneOff { f1: f32, f2: f32 },
+  /// Enough of a set of tones, those at `freqs`, became present together, and none that must be
+  /// absent; see [`CoincidenceDetector`](crate::coincidence::CoincidenceDetector).
+  CoincidenceOn { freqs: Vec<f32> },
+  /// The tones no longer meet the rule.
+  CoincidenceOff,
+  /// Smoothed amplitude of the tone at `freq`, reported every block.
+  Level { freq: f32, level: f32 },
+  /// Broadband level of a block through a frequency weighting (`'A'`, `'C'` or `'Z'`), in dB
+  /// relative to a full-scale sine.
+  SoundLevel { weighting: char, dbfs: f32 },
+  /// Level of the fractional-octave band with nominal centre `freq`, in dB relative to a
+  /// full-scale sine.
+  BandLevel { freq: f32, dbfs: f32 },
+  /// Tempo of the recent onsets of a tone in beats per minute, and the standard deviation of
+  /// the intervals between them in seconds.
+  Tempo { bpm: f32, jitter: f32 },
+  /// Spectral flatness of a block, from 0 (a pure tone) to 1 (white noise).
+  Flatness { value: f32 },
+  /// A decoded DTMF (or similar) digit.
+  Digit(char),
+  /// A named pattern of earlier events completed.
+  Match(String),
+  /// Anything a user-supplied detector wants to report.
+  Custom(String),
+  /// A detector's time-of-day schedule started or stopped passing on its events.
+  Schedule { active: bool },
+  /// Sent every so often by whatever runs the detectors, to show it is alive, with its
+  /// transport health counters: errors of the input stream, gaps between its callbacks, blocks
+  /// too late to analyze and results dropped because writing them out fell behind.
+  Heartbeat { overruns: u64, callback_gaps: u64, late_blocks: u64, dropped_results: u64 },
+  /// A burst of related events, the last of them `duration` seconds after the first; see
+  /// [`Episodes`](crate::episode::Episodes).
+  Episode { duration: f32, events: Vec<Event> },
+}
+
+impl fmt::Display for EventKind {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      EventKind::ToneOn { freq, power } => write!(f, "tone on {} Hz ({:.2})", freq, power),
+      EventKind::ToneOff { freq } => write!(f, "tone off {} Hz", freq),
+      EventKind::StuckTone { freq, duration } => write!(f, "stuck tone {} Hz ({:.1} s)", freq, duration),
+      EventKind::ToneLost { freq, duration } => write!(f, "tone lost {} Hz ({:.1} s)", freq, duration),
+      EventKind::Silence { duration } => write!(f, "silence ({:.1} s)", duration),
+      EventKind::Drift { nominal, freq } => {
+        write!(f, "drift {} Hz now {:.3} Hz ({:+.3} Hz)", nominal, freq, freq - nominal)
+      }
+      EventKind::DualToneOn { f1, f2 } => write!(f, "dual tone on {}+{} Hz", f1, f2),
+      EventKind::DualToneOff { f1, f2 } => write!(f, "dual tone off {}+{} Hz", f1, f2),
+      EventKind::CoincidenceOn { freqs } => write!(f, "coincidence on {} Hz", joined(freqs)),
+      EventKind::CoincidenceOff => f.write_str("coincidence off"),
+      EventKind::Level { freq, level } => write!(f, "level {} Hz {:.4}", freq, level),
+      EventKind::SoundLevel { weighting, dbfs } => write!(f, "level {:.1} dB({})", dbfs, weighting),
+      EventKind::BandLevel { freq, dbfs } => write!(f, "band {} Hz {:.1} dB", freq, dbfs),
+      EventKind::Tempo { bpm, jitter } => {
+        write!(f, "tempo {:.1} BPM (jitter {:.1} ms)", bpm, jitter * 1000.)
+      }
+      EventKind::Flatness { value } => write!(f, "flatness {:.3}", value),
+      EventKind::Digit(d) => write!(f, "digit {}", d),
+      EventKind::Match(name) => write!(f, "match {}", name),
+      EventKind::Custom(s) => f.write_str(s),
+      EventKind::Schedule { active: true } => f.write_str("schedule on"),
+      EventKind::Schedule { active: false } => f.write_str("schedule off"),
+      EventKind::Heartbeat { overruns, callback_gaps, late_blocks, dropped_results } => write!(
+        f,
+        "heartbeat (overruns {}, callback gaps {}, late blocks {}, dropped results {})",
+        overruns, callback_gaps, late_blocks, dropped_results
+      ),
+      EventKind::Episode { duration, events } => {
+        write!(f, "episode of {} events ({:.2} s)", events.len(), duration)?;
+        for (i, event) in events.iter().enumerate() {
+          write!(f, "{}{}", if i == 0 { ": " } else { ", " }, event.kind)?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+/// Frequencies joined with `+`, e.g. `697+1209+1633`.
+pub fn joined(freqs: &[f32]) -> String {
+  let mut text = String::new();
+  for (i, freq) in freqs.iter().enumerate() {
+    if i > 0 {
+      text.push('+');
+    }
+    text.push_str(&alloc::format!("{}", freq));
+  }
+  text
+}
+
+impl EventKind {
+  /// A reading, such as those reported on every block (`Level`, `SoundLevel`, `BandLevel`,
+  /// `Flatness`) or a `Tempo`, as opposed to a change of state.
+  pub fn is_measurement(&self) -> bool {
+    matches!(
+      self,
+      EventKind::Level { .. }
+        | EventKind::SoundLevel { .. }
+        | EventKind::BandLevel { .. }
+        | EventKind::Tempo { .. }
+        | EventKind::Flatness { .. }
+    )
+  }
+
+  /// Something started: a tone, a pair, a digit, a match. Not an end, a reading, a schedule
+  /// change nor a heartbeat.
+  pub fn is_onset(&self) -> bool {
+    !self.is_measurement()
+      && !matches!(
+        self,
+        EventKind::ToneOff { .. }
+          | EventKind::DualToneOff { .. }
+          | EventKind::CoincidenceOff
+          | EventKind::Schedule { .. }
+          | EventKind::Heartbeat { .. }
+      )
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+  pub time: Time,
+  pub kind: EventKind,
+  /// Name of the detector that reported it, e.g. `"doorbell"`; see [`Labeled`].
+  pub label: Option<String>,
+  /// How sure the detector is of a detection, from 0 to 1; see [`confidence`]. Unset for
+  /// events that are not detections, or from detectors that do not rate them.
+  pub confidence: Option<f32>,
+}
+
+impl Event {
+  pub fn new(time: Time, kind: EventKind) -> Self {
+    Self { time, kind, label: None, confidence: None }
+  }
+
+  pub fn with_confidence(mut self, confidence: f32) -> Self {
+    self.confidence = Some(confidence);
+    self
+  }
+}
+
+/// A detection's confidence from 0 to 1: the mean of its signal-to-noise ratio and its contrast
+/// with the guard bins around it, each counting fully from 20 dB, and of its duration margin,
+/// how far past the minimum duration it lasted as a share of that minimum (1 with no minimum).
+///
+/// A clean tone well above the noise and held long enough scores close to 1, one that barely
+/// made it close to 0, so consumers can pick their own cut-off.
+pub fn confidence(snr_db: f32, contrast_db: f32, duration_margin: f32) -> f32 {
+  let term = |value: f32| if value.is_nan() { 0. } else { value.clamp(0., 1.) };
+  (term(snr_db / 20.) + term(contrast_db / 20.) + term(duration_margin)) / 3.
+}
+
+/// Signal-to-noise ratio, in dB, of a tone holding `share` of a block's energy.
+pub fn share_snr_db(share: f32) -> f32 {
+  10. * math::log10(share / (1. - share).max(1e-6))
+}
+
+/// How many dB the tone of `filter` stands above bins two bin widths of `block` either side of
+/// it, the nearer of which the windows in use leave nearly empty.
+pub fn guard_contrast_db(filter: &Goertzel, block: &[f32]) -> f32 {
+  let (freq, sample_rate) = (filter.freq(), filter.sample_rate());
+  let offset = 2. * sample_rate / block.len().max(1) as f32;
+  let guard = |f: f32| {
+    Goertzel::with_block_size(f, sample_rate, block.len())
+      .with_window(filter.window())
+      .with_normalization(Normalization::PerSampleCount)
+      .block_power(block)
+  };
+  let tone = Goertzel::with_block_size(freq, sample_rate, block.len())
+    .with_window(filter.window())
+    .with_normalization(Normalization::PerSampleCount)
+    .block_power(block);
+  let loudest = guard(freq - offset).max(guard(freq + offset));
+  10. * math::log10(tone / loudest.max(tone * 1e-6).max(1e-20))
+}
+
+/// A stage of the pipeline: gets every block of input and reports what it found in it.
+///
+/// `t` is the time of the first sample of `block`.
+pub trait Detector {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event>;
+
+  /// Events still held back when the input ends; none unless the detector holds any back.
+  fn finish(&mut self) -> Vec<Event> {
+    Vec::new()
+  }
+}
+
+impl<D: Detector + ?Sized> Detector for Box<D> {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    (**self).process_block(block, t)
+  }
+
+  fn finish(&mut self) -> Vec<Event> {
+    (**self).finish()
+  }
+}
+
+/// Tags every event of a detector with a name, so consumers need not map frequencies back to
+/// what they mean. Events already labeled by an inner `Labeled` keep their label.
+#[derive(Debug)]
+pub struct Labeled<D> {
+  inner: D,
+  label: String,
+}
+
+impl<D: Detector> Labeled<D> {
+  pub fn new(inner: D, label: impl Into<String>) -> Self {
+    Self { inner, label: label.into() }
+  }
+}
+
+impl<D: Detector> Detector for Labeled<D> {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let mut events = self.inner.process_block(block, t);
+    for event in &mut events {
+      event.label.get_or_insert_with(|| self.label.clone());
+    }
+    events
+  }
+
+  fn finish(&mut self) -> Vec<Event> {
+    let mut events = self.inner.finish();
+    for event in &mut events {
+      event.label.get_or_insert_with(|| self.label.clone());
+    }
+    events
+  }
+}
+
+/// Reports a single tone going above and below a power threshold.
+///
+/// With a minimum duration, shorter tones are ignored and `ToneOn` is reported once the tone
+/// lasted that long, timed from its onset. With a maximum duration, a tone still on past it is
+/// reported once as `StuckTone`.
+///
+/// Onsets are timed from the start of the block the tone was first seen in, unless refined, see
+/// [`with_onset_refinement`](Self::with_onset_refinement).
+#[derive(Debug)]
+pub struct ToneDetector {
+  filter: Goertzel,
+  threshold: f32,
+  min_duration: f32,
+  max_duration: Option<f32>,
+  /// Start of the tone, while it is present.
+  onset: Option<Time>,
+  on: bool,
+  stuck: bool,
+  /// Short filter locating onsets within a block, and the previous block with its time.
+  refine: Option<(Goertzel, Vec<f32>, Option<Time>)>,
+}
+
+impl ToneDetector {
+  pub fn new(freq: impl Into<Hertz>, sample_rate: impl Into<SampleRate>, threshold: f32) -> Self {
+    Self {
+      filter: Goertzel::new(freq, sample_rate),
+      threshold,
+      min_duration: 0.,
+      max_duration: None,
+      onset: None,
+      on: false,
+      stuck: false,
+      refine: None,
+    }
+  }
+
+  /// Ignores tones shorter than `seconds`, e.g. 0.04 for telephony signalling.
+  pub fn with_min_duration(mut self, seconds: f32) -> Self {
+    self.min_duration = seconds;
+    self
+  }
+
+  /// Reports tones lasting longer than `seconds` as stuck.
+  pub fn with_max_duration(mut self, seconds: f32) -> Self {
+    self.max_duration = Some(seconds);
+    self
+  }
+
+  /// Tapers each block before measuring it; see [`Window`].
+  pub fn with_window(mut self, window: Window) -> Self {
+    self.filter = self.filter.with_window(window);
+    self
+  }
+
+  /// Treats blocks whose mean-square level is at or below `mean_square` as silence, in which
+  /// the tone is absent whatever share of the little energy there is falls on its frequency.
+  pub fn with_silence_floor(mut self, mean_square: f32) -> Self {
+    self.set_silence_floor(mean_square);
+    self
+  }
+
+  pub fn set_silence_floor(&mut self, mean_square: f32) {
+    self.filter.set_silence_floor(mean_square);
+  }
+
+  /// Times onsets to within `seconds` (e.g. 0.002) rather than to the block: the block the tone
+  /// is first seen in and the one before are cut into windows that long, and the tone starts in
+  /// the first window of the run up to the loudest one that is at least half as loud.
+  ///
+  /// Short windows hear a wide band around the tone, so the search only runs once the long
+  /// block has found it.
+  pub fn with_onset_refinement(mut self, seconds: f32) -> Self {
+    let (freq, sample_rate) = (self.filter.freq(), self.filter.sample_rate());
+    let window = ((sample_rate * seconds) as usize).max(1);
+    let probe = Goertzel::with_block_size(freq, sample_rate, window)
+      .with_normalization(Normalization::PerSampleCount);
+    self.refine = Some((probe, Vec::new(), None));
+    self
+  }
+
+  /// Where the tone first seen in `block` started.
+  fn locate_onset(&self, block: &[f32], t: Time) -> Time {
+    let Some((probe, previous, previous_start)) = &self.refine else {
+      return t;
+    };
+    let previous = match previous_start {
+      Some(start) if start.offset(previous.len()) == t => previous.as_slice(),
+      _ => &[],
+    };
+    let samples: Vec<f32> = previous.iter().chain(block).copied().collect();
+    let window = probe.block_size();
+    let levels: Vec<f32> = samples.chunks(window).map(|w| probe.block_power(w)).collect();
+    let Some(loudest) = (0..levels.len()).max_by(|&a, &b| levels[a].total_cmp(&levels[b])) else {
+      return t;
+    };
+    let quiet = (0..loudest).rev().find(|&i| levels[i] < 0.5 * levels[loudest]);
+    let first = quiet.map_or(0, |i| i + 1);
+    Time { sample: t.sample - previous.len() as u64 + (first * window) as u64, ..t }
+  }
+
+  fn detect(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let power = match self.filter.block_reading(block) {
+      Reading::Power(power) => power,
+      Reading::Silence => 0.,
+    };
+    let freq = self.filter.freq();
+    if power < self.threshold {
+      self.onset = None;
+      self.stuck = false;
+      if !core::mem::replace(&mut self.on, false) {
+        return Vec::new();
+      }
+      return alloc::vec![Event::new(t, EventKind::ToneOff { freq })];
+    }
+
+    let onset = match self.onset {
+      Some(onset) => onset,
+      None => *self.onset.insert(self.locate_onset(block, t)),
+    };
+    let duration = (t.offset(block.len()).seconds() - onset.seconds()) as f32;
+    if !self.on && duration >= self.min_duration {
+      self.on = true;
+      let margin = match self.min_duration {
+        0. => 1.,
+        min => duration / min - 1.,
+      };
+      let confidence =
+        confidence(share_snr_db(power), guard_contrast_db(&self.filter, block), margin);
+      let event = Event::new(onset, EventKind::ToneOn { freq, power });
+      return alloc::vec![event.with_confidence(confidence)];
+    }
+    match self.max_duration {
+      Some(max) if self.on && !self.stuck && duration > max => {
+        self.stuck = true;
+        alloc::vec![Event::new(t, EventKind::StuckTone { freq, duration })]
+      }
+      _ => Vec::new(),
+    }
+  }
+}
+
+impl Detector for ToneDetector {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let events = self.detect(block, t);
+    if let Some((_, previous, previous_start)) = &mut self.refine {
+      previous.clear();
+      previous.extend_from_slice(block);
+      *previous_start = Some(t);
+    }
+    events
+  }
+}
+
+/// Runs a detector on blocks of its own size, whatever size the pipeline cuts, so detectors
+/// with different time resolutions can share one pipeline.
+///
+/// Incoming blocks are buffered and split; a jump in the stream position drops the partly
+/// filled block.
+#[derive(Debug)]
+pub struct Reblock<D> {
+  inner: D,
+  block: Vec<f32>,
+  block_size: usize,
+  /// Time of `block[0]`.
+  start: Option<Time>,
+}
+
+impl<D: Detector> Reblock<D> {
+  pub fn new(inner: D, block_size: usize) -> Self {
+    let block_size = block_size.max(1);
+    Self { inner, block: Vec::with_capacity(block_size), block_size, start: None }
+  }
+
+  pub fn inner(&self) -> &D {
+    &self.inner
+  }
+}
+
+impl<D: Detector> Detector for Reblock<D> {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    if let Some(start) = self.start {
+      if start.offset(self.block.len()) != t {
+        self.block.clear();
+      }
+    }
+    let mut events = Vec::new();
+    for (i, &sample) in block.iter().enumerate() {
+      if self.block.is_empty() {
+        self.start = Some(t.offset(i));
+      }
+      self.block.push(sample);
+      if self.block.len() == self.block_size {
+        let start = self.start.unwrap_or(t);
+        events.extend(self.inner.process_block(&self.block, start));
+        self.block.clear();
+        self.start = Some(start.offset(self.block_size));
+      }
+    }
+    events
+  }
+}
+
+/// Acceptance limits of a [`DualToneDetector`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DualToneOptions {
+  /// Minimum normalized power each tone must reach. An equal-level pair with nothing else in the
+  /// signal gives 0.5 per tone.
+  pub threshold: f32,
+  /// How many dB either tone may be above the other.
+  pub max_twist_db: f32,
+  /// Longest gap, in seconds, allowed between the onsets of the two tones.
+  pub max_onset_skew: f32,
+  /// How long, in seconds, both tones must be present together before the pair is reported.
+  pub min_duration: f32,
+}
+
+impl Default for DualToneOptions {
+  fn default() -> Self {
+    Self {
+      threshold: 0.2,
+      max_twist_db: 6.,
+      max_onset_skew: 0.05,
+      min_duration: 0.,
+    }
+  }
+}
+
+/// Shortest analysis block of a [`DualToneDetector`], in seconds.
+const DUAL_TONE_MIN_BLOCK: f32 = 0.01;
+
+/// Reports two tones sounding together, e.g. the 853+960 Hz EAS attention signal or the
+/// 440+480 Hz ringback tone.
+///
+/// The detector measures blocks of its own, long enough to resolve the pair: each tone lies two
+/// bins from the other's filter, where a lone tone reads next to nothing, so one tone is not
+/// taken for both. Each tone's onset is taken from the first block it passes the threshold in.
+/// The pair is accepted when both onsets are within `max_onset_skew` of each other and the tones
+/// have then overlapped for `min_duration`; the `DualToneOn` event is timed from the later onset.
+#[derive(Debug)]
+pub struct DualToneDetector(Reblock<Pair>);
+
+/// The pair logic of a [`DualToneDetector`], on blocks that resolve the two tones.
+#[derive(Debug)]
+struct Pair {
+  filters: [Goertzel; 2],
+  options: DualToneOptions,
+  /// Onset sample of each tone while it is present.
+  onsets: [Option<u64>; 2],
+  /// The current overlap failed the skew check; wait for a tone to drop out.
+  rejected: bool,
+  on: bool,
+}
+
+impl DualToneDetector {
+  pub fn new(f1: f32, f2: f32, sample_rate: f32, options: DualToneOptions) -> Self {
+    let pair = Pair {
+      filters: [Goertzel::new(f1, sample_rate), Goertzel::new(f2, sample_rate)],
+      options,
+      onsets: [None; 2],
+      rejected: false,
+      on: false,
+    };
+    Self(Reblock::new(pair, Self::block_size(f1, f2, sample_rate)))
+  }
+
+  /// Samples per block at `sample_rate`: a whole number of the blocks putting each tone two bins
+  /// from the other, at least `DUAL_TONE_MIN_BLOCK` long.
+  pub fn block_size(f1: f32, f2: f32, sample_rate: f32) -> usize {
+    let spacing = (f1 - f2).abs().max(1.);
+    let resolving = math::ceil(2. * sample_rate / spacing).max(1.);
+    let blocks = math::ceil(DUAL_TONE_MIN_BLOCK * sample_rate / resolving).max(1.);
+    (blocks * resolving) as usize
+  }
+}
+
+impl Detector for DualToneDetector {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    self.0.process_block(block, t)
+  }
+}
+
+impl Pair {
+  fn event(&self, time: Time, on: bool) -> Event {
+    let (f1, f2) = (self.filters[0].freq(), self.filters[1].freq());
+    let kind = if on { EventKind::DualToneOn { f1, f2 } } else { EventKind::DualToneOff { f1, f2 } };
+    Event::new(time, kind)
+  }
+}
+
+impl Detector for Pair {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let powers = [self.filters[0].block_power(block), self.filters[1].block_power(block)];
+    let twist_db = 10. * math::log10(powers[0] / powers[1]);
+    for (onset, &power) in self.onsets.iter_mut().zip(&powers) {
+      if power < self.options.threshold {
+        *onset = None;
+      } else if onset.is_none() {
+        *onset = Some(t.sample);
+      }
+    }
+
+    let mut events = Vec::new();
+    let (a, b) = match self.onsets {
+      [Some(a), Some(b)] if twist_db.abs() <= self.options.max_twist_db => (a, b),
+      _ => {
+        self.rejected = false;
+        if self.on {
+          self.on = false;
+          events.push(self.event(t, false));
+        }
+        return events;
+      }
+    };
+    if self.on || self.rejected {
+      return events;
+    }
+    let skew = a.abs_diff(b) as f32 / t.sample_rate;
+    if skew > self.options.max_onset_skew {
+      self.rejected = true;
+      return events;
+    }
+    let start = a.max(b);
+    let end = t.sample + block.len() as u64;
+    let overlap = (end - start) as f32 / t.sample_rate;
+    if overlap >= self.options.min_duration {
+      self.on = true;
+      let margin = match self.options.min_duration {
+        0. => 1.,
+        min => overlap / min - 1.,
+      };
+      let contrast = guard_contrast_db(&self.filters[0], block)
+        .min(guard_contrast_db(&self.filters[1], block));
+      let confidence = confidence(share_snr_db(powers[0] + powers[1]), contrast, margin);
+      events.push(self.event(Time { sample: start, ..t }, true).with_confidence(confidence));
+    }
+    events
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::f32::consts::PI;
+
+  #[test]
+  fn tone_detector_reports_edges() {
+    let mut d = ToneDetector::new(1000., 8000., 0.5);
+    let tone: Vec<f32> = (0..80).map(|t| (2.*PI*1000.*(t as f32)/8000.).sin()).collect();
+    let silence = vec![0.; 80];
+    let t = |sample| Time { sample, sample_rate: 8000. };
+    assert!(d.process_block(&silence, t(0)).is_empty());
+    let on = d.process_block(&tone, t(80));
+    assert!(matches!(on[0].kind, EventKind::ToneOn { freq, .. } if freq == 1000.));
+    assert!(d.process_block(&tone, t(160)).is_empty());
+    assert_eq!(d.process_block(&silence, t(240))[0], Event::new(t(240), EventKind::ToneOff { freq: 1000. }));
+  }
+
+  #[test]
+  fn tone_detector_hears_no_tone_in_silence() {
+    // A faint hum on an otherwise dead line is all its own energy; below the floor it is
+    // silence rather than a tone.
+    let hum: Vec<f32> = burst(1000., 0..800, 800).iter().map(|x| x * 1e-3).collect();
+    assert!(!run(&mut ToneDetector::new(1000., 8000., 0.5), &hum).is_empty());
+    let mut d = ToneDetector::new(1000., 8000., 0.5).with_silence_floor(1e-6);
+    assert!(run(&mut d, &hum).is_empty());
+  }
+
+  #[test]
+  fn tone_detector_applies_duration_limits() {
+    let mut d = ToneDetector::new(1000., 8000., 0.5).with_min_duration(0.04).with_max_duration(0.1);
+    // A 20 ms blip, then a tone held for 200 ms.
+    let mut signal = burst(1000., 0..160, 400);
+    signal.extend(burst(1000., 0..1600, 2400));
+    let events = run(&mut d, &signal);
+    let times: Vec<u64> = events.iter().map(|e| e.time.sample).collect();
+    assert_eq!(times, vec![400, 1200, 2000]);
+    assert!(matches!(events[0].kind, EventKind::ToneOn { .. }));
+    assert!(matches!(events[1].kind, EventKind::StuckTone { duration, .. } if (duration - 0.11).abs() < 1e-6));
+    assert_eq!(events[2].kind, EventKind::ToneOff { freq: 1000. });
+  }
+
+  #[test]
+  fn refined_onsets_fall_within_the_block() {
+    // 100 ms blocks; tones starting 41 ms and 88 ms into the second block.
+    for (start, unrefined) in [(1130, 800), (1500, 1600)] {
+      let signal = burst(1000., start..4000, 4000);
+      let onset = |detector: &mut ToneDetector| {
+        let t = |i: usize| Time { sample: i as u64 * 800, sample_rate: 8000. };
+        let mut events = signal.chunks(800).enumerate().flat_map(|(i, block)| {
+          detector.process_block(block, t(i))
+        });
+        events.next().unwrap().time.sample
+      };
+      assert_eq!(onset(&mut ToneDetector::new(1000., 8000., 0.5)), unrefined);
+      let mut refined = ToneDetector::new(1000., 8000., 0.5).with_onset_refinement(0.002);
+      let at = onset(&mut refined);
+      assert!(at >= start as u64 && at <= start as u64 + 16, "{} for {}", at, start);
+    }
+  }
+
+  #[test]
+  fn confidence_reflects_noise_and_duration_margin() {
+    let confidence = |detector: ToneDetector, noise: f32| {
+      let mut detector = detector;
+      let mut state = 1u32;
+      let signal: Vec<f32> = burst(1000., 0..800, 800)
+        .into_iter()
+        .map(|s| {
+          state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+          s + noise * (state as f32 / u32::MAX as f32 - 0.5)
+        })
+        .collect();
+      run(&mut detector, &signal)[0].confidence.unwrap()
+    };
+    let clean = confidence(ToneDetector::new(1000., 8000., 0.5), 0.);
+    let noisy = confidence(ToneDetector::new(1000., 8000., 0.5), 0.8);
+    // Reported the moment it has lasted 40 ms: no margin.
+    let barely = confidence(ToneDetector::new(1000., 8000., 0.5).with_min_duration(0.04), 0.);
+    assert!(clean > 0.95, "{}", clean);
+    assert!(noisy < 0.8, "{}", noisy);
+    assert!((barely - 2. / 3.).abs() < 0.05, "{}", barely);
+  }
+
+  #[test]
+  fn labels_tag_every_event() {
+    let tone = ToneDetector::new(1000., 8000., 0.5);
+    let mut d = Labeled::new(Labeled::new(tone, "doorbell"), "outer");
+    let events = run(&mut d, &burst(1000., 0..160, 320));
+    assert_eq!(events.len(), 2);
+    assert!(events.iter().all(|e| e.label.as_deref() == Some("doorbell")));
+  }
+
+  #[test]
+  fn reblock_runs_on_its_own_block_size() {
+    let mut d = Reblock::new(ToneDetector::new(1000., 8000., 0.5), 200);
+    let t = |sample| Time { sample, sample_rate: 8000. };
+    let mut events = Vec::new();
+    for (i, chunk) in burst(1000., 400..1000, 1600).chunks(80).enumerate() {
+      events.extend(d.process_block(chunk, t(80 * i as u64)));
+    }
+    let times: Vec<u64> = events.iter().map(|e| e.time.sample).collect();
+    assert_eq!(times, vec![400, 1000]);
+
+    // After a jump the partial block is dropped and blocks start at the new position.
+    d.process_block(&[0.; 80], t(1600));
+    let events = d.process_block(&burst(1000., 0..400, 400), t(50_000));
+    assert_eq!(events[0].time, t(50_000));
+  }
+
+  fn run(d: &mut impl Detector, signal: &[f32]) -> Vec<Event> {
+    signal
+      .chunks(80)
+      .enumerate()
+      .flat_map(|(i, block)| d.process_block(block, Time { sample: i as u64 * 80, sample_rate: 8000. }))
+      .collect()
+  }
+
+  /// Tone of amplitude 0.5 at `freq` during `on`, silence elsewhere.
+  fn burst(freq: f32, on: core::ops::Range<usize>, len: usize) -> Vec<f32> {
+    (0..len)
+      .map(|t| if on.contains(&t) { 0.5 * (2.*PI*freq*(t as f32)/8000.).sin() } else { 0. })
+      .collect()
+  }
+
+  fn mix(a: Vec<f32>, b: Vec<f32>) -> Vec<f32> {
+    a.iter().zip(&b).map(|(a, b)| a + b).collect()
+  }
+
+  #[test]
+  fn dual_tone_needs_both_tones_for_min_duration() {
+    let options = DualToneOptions { min_duration: 0.1, ..Default::default() };
+    let mut d = DualToneDetector::new(440., 480., 8000., options);
+    // 40 ms of ringback is too short, 400 ms is reported from its start.
+    let short = mix(burst(440., 0..320, 800), burst(480., 0..320, 800));
+    let long = mix(burst(440., 0..3200, 4000), burst(480., 0..3200, 4000));
+    let signal: Vec<f32> = short.into_iter().chain(long).collect();
+    let events: Vec<_> = run(&mut d, &signal).into_iter().map(|e| (e.time.sample, e.kind)).collect();
+    assert_eq!(events, vec![
+      (800, EventKind::DualToneOn { f1: 440., f2: 480. }),
+      (4000, EventKind::DualToneOff { f1: 440., f2: 480. }),
+    ]);
+  }
+
+  #[test]
+  fn dual_tone_needs_both_of_a_close_pair() {
+    assert_eq!(DualToneDetector::block_size(440., 480., 8000.), 400);
+    assert_eq!(DualToneDetector::block_size(1700., 2200., 8000.), 96);
+    for freq in [440., 480.] {
+      let mut d = DualToneDetector::new(440., 480., 8000., DualToneOptions::default());
+      assert!(run(&mut d, &burst(freq, 0..8000, 8000)).is_empty(), "{} Hz alone", freq);
+    }
+  }
+
+  #[test]
+  fn dual_tone_rejects_staggered_onsets() {
+    let mut d = DualToneDetector::new(853., 960., 8000., DualToneOptions::default());
+    // The second tone starts 200 ms after the first.
+    let signal = mix(burst(853., 0..4000, 4000), burst(960., 1600..4000, 4000));
+    assert!(run(&mut d, &signal).is_empty());
+  }
+}
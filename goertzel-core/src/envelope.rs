@@ -0,0 +1,107 @@
+//! Tone level over time, for charting rather than yes/no detection.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::detector::{Detector, Event, EventKind, Time};
+use crate::math;
+use crate::units::Millis;
+#[cfg(feature = "alloc")]
+use crate::units::{Hertz, SampleRate};
+#[cfg(feature = "alloc")]
+use crate::Goertzel;
+
+/// Smooths per-block magnitudes into an envelope with separate attack and release times.
+///
+/// Each time constant is how long the envelope takes to cover about 63% of a step up (attack) or
+/// down (release).
+#[derive(Debug, Clone)]
+pub struct AmplitudeTracker {
+  attack_ms: f32,
+  release_ms: f32,
+  level: f32,
+}
+
+impl AmplitudeTracker {
+  pub fn new(attack: impl Into<Millis>, release: impl Into<Millis>) -> Self {
+    Self { attack_ms: attack.into().0, release_ms: release.into().0, level: 0. }
+  }
+
+  /// Feeds the magnitude of a block lasting `block_ms`, returning the updated envelope.
+  pub fn push(&mut self, magnitude: f32, block_ms: f32) -> f32 {
+    let time_constant = if magnitude > self.level { self.attack_ms } else { self.release_ms };
+    let keep = if time_constant > 0. { math::exp(-block_ms / time_constant) } else { 0. };
+    self.level = keep * self.level + (1. - keep) * magnitude;
+    self.level
+  }
+
+  pub fn level(&self) -> f32 {
+    self.level
+  }
+}
+
+/// Reports the envelope of the amplitude at `freq` as a `Level` event on every block. A full-scale
+/// tone settles at 1.
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct EnvelopeDetector {
+  filter: Goertzel,
+  tracker: AmplitudeTracker,
+}
+
+#[cfg(feature = "alloc")]
+impl EnvelopeDetector {
+  pub fn new(
+    freq: impl Into<Hertz>,
+    sample_rate: impl Into<SampleRate>,
+    attack: impl Into<Millis>,
+    release: impl Into<Millis>,
+  ) -> Self {
+    Self {
+      filter: Goertzel::new(freq, sample_rate),
+      tracker: AmplitudeTracker::new(attack, release),
+    }
+  }
+}
+
+#[cfg(feature = "alloc")]
+impl Detector for EnvelopeDetector {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let (re, im) = self.filter.block_dft(block);
+    // A real tone of amplitude a has a DFT magnitude of a * N / 2.
+    let magnitude = 2. * math::sqrt(re*re + im*im) / block.len().max(1) as f32;
+    let block_ms = 1000. * block.len() as f32 / t.sample_rate;
+    let level = self.tracker.push(magnitude, block_ms);
+    alloc::vec![Event::new(t, EventKind::Level { freq: self.filter.freq(), level })]
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn attack_is_faster_than_release() {
+    let mut env = AmplitudeTracker::new(10., 100.);
+    // 10 ms blocks: one attack time constant up, then one block of release.
+    let up = env.push(1., 10.);
+    assert!((up - (1. - (-1f32).exp())).abs() < 1e-6);
+    let down = env.push(0., 10.);
+    assert!((down - up * (-0.1f32).exp()).abs() < 1e-6);
+  }
+
+  #[test]
+  fn envelope_of_tone_settles_at_amplitude() {
+    use std::f32::consts::PI;
+    let mut d = EnvelopeDetector::new(1000., 8000., 5., 50.);
+    let tone: Vec<f32> = (0..8000).map(|t| 0.25 * (2.*PI*1000.*(t as f32)/8000.).sin()).collect();
+    let levels: Vec<f32> = tone
+      .chunks(80)
+      .flat_map(|b| d.process_block(b, Time { sample: 0, sample_rate: 8000. }))
+      .map(|e| match e.kind { EventKind::Level { level, .. } => level, _ => unreachable!() })
+      .collect();
+    assert!((levels.last().unwrap() - 0.25).abs() < 1e-3);
+  }
+}
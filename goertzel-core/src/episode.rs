@@ -0,0 +1,116 @@
+//! Bursts of related events, such as the digits of a dialed number or the beeps of an alarm,
+//! reported together as one episode.
+
+use alloc::vec::Vec;
+
+use crate::detector::{Detector, Event, EventKind, Time};
+
+/// Holds back the events of `source` and reports each burst of them as one `Episode`, once
+/// `gap` seconds went by without another.
+///
+/// The episode is timed from its first event and labeled like it. Readings, schedule changes
+/// and heartbeats are not part of any and are passed on straight away. An episode still open
+/// when the input ends is reported by [`finish`](Detector::finish).
+#[derive(Debug)]
+pub struct Episodes<D> {
+  source: D,
+  gap: f64,
+  open: Vec<Event>,
+}
+
+impl<D: Detector> Episodes<D> {
+  pub fn new(source: D, gap: f32) -> Self {
+    Self { source, gap: gap as f64, open: Vec::new() }
+  }
+
+  /// The open episode as an event, if there is one.
+  fn close(&mut self) -> Option<Event> {
+    let events = core::mem::take(&mut self.open);
+    let (first, last) = (events.first()?, events.last()?);
+    let duration = (last.time.seconds() - first.time.seconds()) as f32;
+    let label = first.label.clone();
+    let time = first.time;
+    Some(Event { label, ..Event::new(time, EventKind::Episode { duration, events }) })
+  }
+
+  /// Whether the open episode is over at `seconds`.
+  fn over(&self, seconds: f64) -> bool {
+    self.open.last().is_some_and(|last| seconds - last.time.seconds() > self.gap)
+  }
+}
+
+impl<D: Detector> Detector for Episodes<D> {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let mut events = Vec::new();
+    for event in self.source.process_block(block, t) {
+      if event.kind.is_measurement()
+        || matches!(event.kind, EventKind::Schedule { .. } | EventKind::Heartbeat { .. })
+      {
+        events.push(event);
+        continue;
+      }
+      if self.over(event.time.seconds()) {
+        events.extend(self.close());
+      }
+      self.open.push(event);
+    }
+    if self.over(t.offset(block.len()).seconds()) {
+      events.extend(self.close());
+    }
+    events
+  }
+
+  fn finish(&mut self) -> Vec<Event> {
+    let mut events = self.source.finish();
+    events.extend(self.close());
+    events
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Reports a digit at each of the given samples, and a level on every block.
+  struct Keypad(Vec<(u64, char)>);
+
+  impl Detector for Keypad {
+    fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+      let end = t.sample + block.len() as u64;
+      let level = Event::new(t, EventKind::Level { freq: 697., level: 0.1 });
+      let digits = self.0.iter().filter(|(s, _)| (t.sample..end).contains(s));
+      let digits = digits.map(|&(s, d)| Event::new(Time { sample: s, ..t }, EventKind::Digit(d)));
+      core::iter::once(level).chain(digits).collect()
+    }
+  }
+
+  #[test]
+  fn digits_close_together_are_one_episode() {
+    // "123" dialed a quarter second apart, then "9" three seconds later.
+    let keys = vec![(8000, '1'), (10_000, '2'), (12_000, '3'), (36_000, '9')];
+    let mut episodes = Episodes::new(Keypad(keys), 1.);
+    let mut events = Vec::new();
+    for i in 0..500 {
+      events.extend(episodes.process_block(&[0.; 80], Time { sample: i * 80, sample_rate: 8000. }));
+    }
+    events.extend(episodes.finish());
+    assert_eq!(events.iter().filter(|e| e.kind.is_measurement()).count(), 500);
+    let found: Vec<(u64, f32, Vec<&EventKind>)> = events
+      .iter()
+      .filter_map(|e| match &e.kind {
+        EventKind::Episode { duration, events } => {
+          Some((e.time.sample, *duration, events.iter().map(|e| &e.kind).collect()))
+        }
+        _ => None,
+      })
+      .collect();
+    let digits = |keys: &str| keys.chars().map(EventKind::Digit).collect::<Vec<_>>();
+    let (first, last) = (digits("123"), digits("9"));
+    let expected = vec![(8000, 0.5, first.iter().collect()), (36_000, 0., last.iter().collect())];
+    assert_eq!(found, expected);
+    // The first episode is reported once a second passed after its last digit.
+    let at = events.iter().position(|e| matches!(e.kind, EventKind::Episode { .. })).unwrap();
+    assert_eq!(events[at - 1].time.sample, 20_000);
+  }
+}
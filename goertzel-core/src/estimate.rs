@@ -0,0 +1,228 @@
+//! Fine frequency estimation from the phase advance between consecutive blocks, and drift
+//! alerts built on it.
+
+#[cfg(feature = "alloc")]
+use alloc::collections::VecDeque;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::f32::consts::PI;
+
+#[cfg(feature = "alloc")]
+use crate::detector::{Detector, Event, EventKind, Time};
+use crate::{math, Goertzel};
+
+/// Measures the actual frequency of a tone near `freq` to a fraction of a hertz, e.g. to follow
+/// the drift of an oscillator or a pilot tone.
+///
+/// Each block's phase at `freq` is measured with `Goertzel::block_dft`. A tone `d` Hz off
+/// `freq` advances `2 pi d N / samplef` radians more per N-sample block than one exactly at
+/// `freq` would, which resolves `d` unambiguously within half a bin (`samplef / 2N`) of `freq`.
+#[derive(Debug)]
+pub struct FrequencyEstimator {
+  filter: Goertzel,
+  samplef: f32,
+  /// Phase and length of the previous block.
+  last: Option<(f32, usize)>,
+}
+
+impl FrequencyEstimator {
+  pub fn new(freq: f32, samplef: f32) -> Self {
+    Self { filter: Goertzel::new(freq, samplef), samplef, last: None }
+  }
+
+  /// Feeds the next block of a contiguous stream. Returns the tone frequency measured from this
+  /// block and the previous one, which must have the same length.
+  pub fn push_block(&mut self, block: &[f32]) -> Option<f32> {
+    let (re, im) = self.filter.block_dft(block);
+    let phase = math::atan2(im, re);
+    let last = self.last.replace((phase, block.len()));
+    let (last_phase, n) = last.filter(|&(_, n)| n == block.len() && n > 0)?;
+    let freq = self.filter.freq();
+    // Advance a tone exactly at `freq` would have, modulo a full turn.
+    let bin = freq * n as f32 / self.samplef;
+    let nominal = 2.*PI*(bin - math::round(bin));
+    let extra = wrap(phase - last_phase - nominal);
+    Some(freq + extra * self.samplef / (2.*PI*n as f32))
+  }
+
+  /// Forgets the previous block, e.g. after a gap in the stream.
+  pub fn reset(&mut self) {
+    self.last = None;
+  }
+}
+
+/// How far a tone may drift from its nominal frequency.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DriftTolerance {
+  Hz(f32),
+  /// Parts per million of the nominal frequency, as oscillators are specified.
+  Ppm(f32),
+}
+
+impl DriftTolerance {
+  pub fn hz(self, nominal: f32) -> f32 {
+    match self {
+      DriftTolerance::Hz(hz) => hz,
+      DriftTolerance::Ppm(ppm) => nominal * ppm * 1e-6,
+    }
+  }
+}
+
+/// Offsets from the nominal frequency seen so far, in Hz.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DriftSummary {
+  pub estimates: u64,
+  pub last: f32,
+  pub min: f32,
+  pub max: f32,
+  pub mean: f32,
+}
+
+/// Estimates kept by [`DriftTracker::history`].
+#[cfg(feature = "alloc")]
+pub const DRIFT_HISTORY: usize = 256;
+/// Estimates averaged before comparing with the tolerance, so one noisy block does not alert.
+#[cfg(feature = "alloc")]
+const DRIFT_SMOOTHING: usize = 8;
+
+/// Follows the frequency of a pilot tone and reports `Drift` when the average of the last few
+/// estimates moves further from nominal than the tolerance, once per excursion.
+///
+/// Blocks where the tone reads below the threshold are skipped, as their phase means nothing.
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct DriftTracker {
+  estimator: FrequencyEstimator,
+  level: Goertzel,
+  threshold: f32,
+  tolerance: DriftTolerance,
+  history: VecDeque<(Time, f32)>,
+  summary: DriftSummary,
+  /// Whether the tone is currently out of tolerance.
+  drifted: bool,
+}
+
+#[cfg(feature = "alloc")]
+impl DriftTracker {
+  pub fn new(freq: f32, sample_rate: f32, tolerance: DriftTolerance) -> Self {
+    Self {
+      estimator: FrequencyEstimator::new(freq, sample_rate),
+      level: Goertzel::new(freq, sample_rate),
+      threshold: 0.1,
+      tolerance,
+      history: VecDeque::with_capacity(DRIFT_HISTORY),
+      summary: DriftSummary::default(),
+      drifted: false,
+    }
+  }
+
+  /// Power share the tone must read for its block to be measured; 0.1 unless set.
+  pub fn with_threshold(mut self, threshold: f32) -> Self {
+    self.threshold = threshold;
+    self
+  }
+
+  pub fn nominal(&self) -> f32 {
+    self.level.freq()
+  }
+
+  /// The latest estimates, oldest first, each with the time of its block.
+  pub fn history(&self) -> Vec<(Time, f32)> {
+    self.history.iter().copied().collect()
+  }
+
+  pub fn summary(&self) -> DriftSummary {
+    self.summary
+  }
+
+  fn record(&mut self, t: Time, freq: f32) {
+    if self.history.len() == DRIFT_HISTORY {
+      self.history.pop_front();
+    }
+    self.history.push_back((t, freq));
+    let offset = freq - self.nominal();
+    let s = &mut self.summary;
+    if s.estimates == 0 {
+      (s.min, s.max) = (offset, offset);
+    }
+    s.estimates += 1;
+    s.last = offset;
+    s.min = s.min.min(offset);
+    s.max = s.max.max(offset);
+    s.mean += (offset - s.mean) / s.estimates as f32;
+  }
+}
+
+#[cfg(feature = "alloc")]
+impl Detector for DriftTracker {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    if self.level.block_power(block) < self.threshold {
+      self.estimator.reset();
+      return Vec::new();
+    }
+    let Some(freq) = self.estimator.push_block(block) else {
+      return Vec::new();
+    };
+    self.record(t, freq);
+    let recent = self.history.len().min(DRIFT_SMOOTHING);
+    let average =
+      self.history.iter().rev().take(recent).map(|h| h.1).sum::<f32>() / recent as f32;
+    let nominal = self.nominal();
+    let drifted = (average - nominal).abs() > self.tolerance.hz(nominal);
+    if drifted && !core::mem::replace(&mut self.drifted, drifted) {
+      return alloc::vec![Event::new(t, EventKind::Drift { nominal, freq: average })];
+    }
+    self.drifted = drifted;
+    Vec::new()
+  }
+}
+
+/// `x` wrapped to (-pi, pi].
+fn wrap(x: f32) -> f32 {
+  let (sin, cos) = math::sin_cos(x);
+  math::atan2(sin, cos)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn resolves_offset_within_half_a_bin() {
+    let samplef = 8000.;
+    for &actual in &[1000.3, 1004.7, 995.2] {
+      let mut est = FrequencyEstimator::new(1000., samplef);
+      let signal: Vec<f32> = (0..4000).map(|t| (2.*PI*actual*(t as f32)/samplef).sin()).collect();
+      let estimates: Vec<f32> = signal.chunks(400).filter_map(|b| est.push_block(b)).collect();
+      assert_eq!(estimates.len(), 9);
+      for f in estimates {
+        assert!((f - actual).abs() < 0.05, "{} for {}", f, actual);
+      }
+    }
+  }
+
+  #[test]
+  fn alerts_once_when_the_pilot_drifts() {
+    let mut tracker = DriftTracker::new(1000., 8000., DriftTolerance::Ppm(500.));
+    // 1000.2 Hz for two seconds (200 ppm), then 1001 Hz (1000 ppm).
+    let mut phase = 0f32;
+    let signal: Vec<f32> = (0..32000)
+      .map(|t| {
+        phase = (phase + 2.*PI*(if t < 16000 { 1000.2 } else { 1001. })/8000.) % (2.*PI);
+        phase.sin()
+      })
+      .collect();
+    let mut events = Vec::new();
+    for (i, block) in signal.chunks(400).enumerate() {
+      let t = Time { sample: 400 * i as u64, sample_rate: 8000. };
+      events.extend(tracker.process_block(block, t));
+    }
+    assert_eq!(events.len(), 1);
+    assert!(events[0].time.sample > 16000 && events[0].time.sample < 20000);
+    let summary = tracker.summary();
+    assert_eq!(summary.estimates, 79);
+    assert!((summary.min - 0.2).abs() < 0.05 && (summary.last - 1.).abs() < 0.05);
+    assert_eq!(tracker.history().len(), 79);
+  }
+}
@@ -0,0 +1,149 @@
+//! FM broadcast reception: demodulation and de-emphasis.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::f32::consts::PI;
+
+#[cfg(feature = "alloc")]
+use crate::detector::{Detector, Event, Time};
+use crate::math;
+
+/// De-emphasis time constant of Europe and most of the world, in seconds.
+pub const DEEMPHASIS_50US: f32 = 50e-6;
+/// De-emphasis time constant of the Americas and South Korea, in seconds.
+pub const DEEMPHASIS_75US: f32 = 75e-6;
+
+/// Quadrature FM discriminator with a boxcar decimator.
+///
+/// Takes baseband IQ samples and produces audio at `1/decimation` of the input rate, scaled so
+/// that a deviation of half the input sample rate maps to full scale.
+#[derive(Debug)]
+pub struct FmDemod {
+  prev: (f32, f32),
+  decimation: usize,
+  acc: f32,
+  count: usize,
+}
+
+impl FmDemod {
+  pub fn new(decimation: usize) -> Self {
+    Self {
+      prev: (1., 0.),
+      decimation: decimation.max(1),
+      acc: 0.,
+      count: 0,
+    }
+  }
+
+  /// Feeds one IQ sample, returning an audio sample every `decimation` calls.
+  pub fn push(&mut self, i: f32, q: f32) -> Option<f32> {
+    let (pi, pq) = self.prev;
+    // x[n] * conj(x[n-1])
+    let re = i*pi + q*pq;
+    let im = q*pi - i*pq;
+    self.prev = (i, q);
+    self.acc += math::atan2(im, re) / PI;
+    self.count += 1;
+    if self.count < self.decimation {
+      return None;
+    }
+    let out = self.acc / self.decimation as f32;
+    self.acc = 0.;
+    self.count = 0;
+    Some(out)
+  }
+}
+
+/// First-order low-pass undoing the treble boost broadcasters apply before transmission, so
+/// demodulated audio has its original balance.
+#[derive(Debug, Clone)]
+pub struct Deemphasis {
+  keep: f32,
+  out: f32,
+}
+
+impl Deemphasis {
+  /// De-emphasis with `time_constant` seconds, [`DEEMPHASIS_50US`] or [`DEEMPHASIS_75US`].
+  pub fn new(time_constant: f32, sample_rate: f32) -> Self {
+    Self { keep: math::exp(-1. / (time_constant * sample_rate)), out: 0. }
+  }
+
+  pub fn process(&mut self, x: f32) -> f32 {
+    self.out = self.keep * self.out + (1. - self.keep) * x;
+    self.out
+  }
+
+  pub fn process_block(&mut self, block: &mut [f32]) {
+    for sample in block {
+      *sample = self.process(*sample);
+    }
+  }
+}
+
+/// Runs a detector on de-emphasized audio.
+///
+/// Detectors of the stereo pilot must stay outside: de-emphasis takes over 12 dB off 19 kHz.
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct Deemphasized<D> {
+  inner: D,
+  filter: Deemphasis,
+  block: Vec<f32>,
+}
+
+#[cfg(feature = "alloc")]
+impl<D: Detector> Deemphasized<D> {
+  pub fn new(inner: D, time_constant: f32, sample_rate: f32) -> Self {
+    Self { inner, filter: Deemphasis::new(time_constant, sample_rate), block: Vec::new() }
+  }
+}
+
+#[cfg(feature = "alloc")]
+impl<D: Detector> Detector for Deemphasized<D> {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    self.block.clear();
+    self.block.extend_from_slice(block);
+    self.filter.process_block(&mut self.block);
+    self.inner.process_block(&self.block, t)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{Goertzel, Normalization};
+
+  #[test]
+  fn recovers_modulating_tone() {
+    let iq_rate = 240e3;
+    let mut demod = FmDemod::new(5);
+    let mut g = Goertzel::new(1000., iq_rate / 5.);
+    let mut phase = 0f32;
+    let mut power = 0.;
+    for t in 0..240_000 {
+      let audio = (2.*PI*1000.*(t as f32)/iq_rate).sin();
+      phase += 2.*PI*5e3*audio/iq_rate;
+      if let Some(s) = demod.push(phase.cos(), phase.sin()) {
+        power = g.filter(s);
+      }
+    }
+    assert!(power > 0.8, "demodulated tone power {}", power);
+  }
+
+  #[test]
+  fn deemphasis_keeps_the_bass_and_cuts_the_pilot() {
+    let gain = |freq: f32| {
+      let mut filter = Deemphasis::new(DEEMPHASIS_50US, 48000.);
+      let mut g = Goertzel::with_block_size(freq, 48000., 4800)
+        .with_normalization(Normalization::Amplitude);
+      let mut amplitude = 0.;
+      for t in 0..9600 {
+        amplitude = g.filter(filter.process((2.*PI*freq*t as f32/48000.).sin()));
+      }
+      20. * math::log10(amplitude)
+    };
+    assert!(gain(100.) > -0.1);
+    assert!(gain(19000.) < -12.);
+  }
+}
@@ -0,0 +1,208 @@
+//! Carriers of 300 baud FSK modems (Bell 103, V.21), recognized by their mark/space pair so
+//! that calls answered by a data modem can be told from fax and voice.
+
+use alloc::vec::Vec;
+
+use crate::detector::{Detector, Event, EventKind, Time};
+use crate::Goertzel;
+
+/// The two frequencies an FSK channel keys between, in Hz.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FskCarrier {
+  pub name: &'static str,
+  pub mark: f32,
+  pub space: f32,
+}
+
+pub const BELL_103_ORIGINATE: FskCarrier =
+  FskCarrier { name: "bell103 originate", mark: 1270., space: 1070. };
+/// The answering modem's carrier; its idle mark is the 2225 Hz Bell answer tone.
+pub const BELL_103_ANSWER: FskCarrier =
+  FskCarrier { name: "bell103 answer", mark: 2225., space: 2025. };
+pub const V21_ORIGINATE: FskCarrier =
+  FskCarrier { name: "v21 originate", mark: 980., space: 1180. };
+/// The answering modem's carrier. T.30 fax negotiation runs on this channel too.
+pub const V21_ANSWER: FskCarrier = FskCarrier { name: "v21 answer", mark: 1650., space: 1850. };
+
+pub const MODEM_CARRIERS: [FskCarrier; 4] =
+  [BELL_103_ORIGINATE, BELL_103_ANSWER, V21_ORIGINATE, V21_ANSWER];
+
+/// Block length the detector is meant to be fed, in seconds. Its filters are spaced by the
+/// 50 Hz bin width of such blocks.
+pub const FSK_BLOCK: f32 = 0.02;
+
+/// Spacing of the filters across each pair, in Hz.
+const PROBE_SPACING: f32 = 50.;
+
+/// Reports `Match(name)` once FSK energy has held the input for `min_duration`, naming the
+/// carrier it fits best and timed from its onset; again only after a break.
+///
+/// Keyed data spreads a carrier's energy across and around its pair rather than onto the two
+/// tones, so each pair is watched by a row of filters spanning it with 100 Hz to spare. A block
+/// counts when the row of some pair holds more than the threshold of its energy. Rows overlap:
+/// the Bell 103 and V.21 originate pairs are only 90 Hz apart. Of the pairs filled well enough
+/// over the whole run, the one closest to its centre of energy is reported. Idle lines (a
+/// steady mark) and data count alike; other steady tones, such as the 1100 Hz fax calling tone
+/// or the 2100 Hz answer tone, do not.
+#[derive(Debug)]
+pub struct FskDetector {
+  carriers: Vec<(FskCarrier, Vec<Goertzel>)>,
+  threshold: f32,
+  min_duration: f32,
+  /// Start of the current run of FSK blocks, and whether it was reported.
+  run: Option<(Time, bool)>,
+  /// Energy share each row has held during the run, summed over blocks, unweighted and weighted
+  /// by frequency.
+  energy: Vec<(f32, f32)>,
+  /// Blocks in the run.
+  blocks: usize,
+}
+
+impl FskDetector {
+  pub fn new(carriers: &[FskCarrier], sample_rate: f32) -> Self {
+    let carriers: Vec<(FskCarrier, Vec<Goertzel>)> = carriers
+      .iter()
+      .map(|&c| {
+        let low = c.mark.min(c.space) - 2. * PROBE_SPACING;
+        let probes = ((c.mark - c.space).abs() / PROBE_SPACING) as usize + 5;
+        let row = (0..probes)
+          .map(|i| Goertzel::new(low + i as f32 * PROBE_SPACING, sample_rate))
+          .collect();
+        (c, row)
+      })
+      .collect();
+    let energy = alloc::vec![(0., 0.); carriers.len()];
+    Self { carriers, threshold: 0.6, min_duration: 0.2, run: None, energy, blocks: 0 }
+  }
+
+  /// Share of a block's energy a pair's filters must hold; 0.6 unless set.
+  pub fn with_threshold(mut self, threshold: f32) -> Self {
+    self.threshold = threshold;
+    self
+  }
+
+  /// How long FSK must last to be reported, in seconds; 0.2 unless set.
+  pub fn with_min_duration(mut self, seconds: f32) -> Self {
+    self.min_duration = seconds;
+    self
+  }
+
+  /// The pair filled well enough during the run whose centre is closest to the energy's.
+  fn best(&self) -> Option<FskCarrier> {
+    let blocks = self.blocks.max(1) as f32;
+    self
+      .carriers
+      .iter()
+      .zip(&self.energy)
+      .filter(|(_, (share, _))| share / blocks >= self.threshold)
+      .map(|((c, _), (share, weighted))| (c, (weighted / share - (c.mark + c.space) / 2.).abs()))
+      .min_by(|a, b| a.1.total_cmp(&b.1))
+      .map(|(c, _)| *c)
+  }
+}
+
+impl Detector for FskDetector {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let mut present = false;
+    for ((carrier, row), energy) in self.carriers.iter().zip(&mut self.energy) {
+      let powers: Vec<f32> = row.iter().map(|probe| probe.block_power(block)).collect();
+      let share: f32 = powers.iter().sum();
+      let weighted: f32 = powers.iter().zip(row).map(|(p, probe)| p * probe.freq()).sum();
+      present |= share >= self.threshold && !foreign_tone(carrier, row, &powers, share);
+      energy.0 += share;
+      energy.1 += weighted;
+    }
+    self.blocks += 1;
+    if !present {
+      self.run = None;
+      self.energy.iter_mut().for_each(|e| *e = (0., 0.));
+      self.blocks = 0;
+      return Vec::new();
+    }
+    let (since, reported) = *self.run.get_or_insert((t, false));
+    let lasted = (t.offset(block.len()).seconds() - since.seconds()) as f32;
+    if reported || lasted < self.min_duration {
+      return Vec::new();
+    }
+    let Some(carrier) = self.best() else {
+      return Vec::new();
+    };
+    self.run = Some((since, true));
+    alloc::vec![Event::new(since, EventKind::Match(carrier.name.into()))]
+  }
+}
+
+/// Whether the row's energy is one steady tone other than the mark or space, such as a fax
+/// calling or answer tone: two neighbouring filters then hold nearly all of it.
+fn foreign_tone(carrier: &FskCarrier, row: &[Goertzel], powers: &[f32], share: f32) -> bool {
+  let Some(i) = (0..powers.len() - 1).max_by(|&a, &b| {
+    (powers[a] + powers[a + 1]).total_cmp(&(powers[b] + powers[b + 1]))
+  }) else {
+    return false;
+  };
+  let pair = powers[i] + powers[i + 1];
+  if pair < 0.8 * share {
+    return false;
+  }
+  let freq = (powers[i] * row[i].freq() + powers[i + 1] * row[i + 1].freq()) / pair;
+  let off = |tone: f32| (freq - tone).abs() > PROBE_SPACING / 2.;
+  off(carrier.mark) && off(carrier.space)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::f32::consts::PI;
+
+  /// 300 baud FSK on `carrier` of `text` sent as 8N1 characters between stretches of idle mark,
+  /// with continuous phase.
+  fn fsk(carrier: FskCarrier, text: &[u8]) -> Vec<f32> {
+    let mut bits = vec![true; 30];
+    for byte in text {
+      bits.push(false);
+      bits.extend((0..8).map(|i| byte >> i & 1 == 1));
+      bits.push(true);
+    }
+    bits.extend([true; 30]);
+    let mut phase = 0f32;
+    bits
+      .into_iter()
+      .flat_map(|bit| core::iter::repeat_n(bit, 8000 / 300))
+      .map(|mark| {
+        let freq = if mark { carrier.mark } else { carrier.space };
+        phase = (phase + 2. * PI * freq / 8000.) % (2. * PI);
+        0.5 * phase.sin()
+      })
+      .collect()
+  }
+
+  fn run(signal: &[f32]) -> Vec<Event> {
+    let mut detector = FskDetector::new(&MODEM_CARRIERS, 8000.);
+    signal
+      .chunks(160)
+      .enumerate()
+      .flat_map(|(i, block)| {
+        detector.process_block(block, Time { sample: (i * 160) as u64, sample_rate: 8000. })
+      })
+      .collect()
+  }
+
+  #[test]
+  fn names_the_carrier_of_each_modem() {
+    for carrier in MODEM_CARRIERS {
+      let mut signal = vec![0.; 1600];
+      signal.extend(fsk(carrier, b"CONNECT 300\r\nlogin: "));
+      let at = Time { sample: 1600, sample_rate: 8000. };
+      assert_eq!(run(&signal), vec![Event::new(at, EventKind::Match(carrier.name.into()))]);
+    }
+  }
+
+  #[test]
+  fn ignores_fax_tones() {
+    for freq in [1100., 2100.] {
+      let tone: Vec<f32> = (0..8000).map(|i| (2. * PI * freq * i as f32 / 8000.).sin()).collect();
+      assert_eq!(run(&tone), vec![], "{} Hz", freq);
+    }
+  }
+}
@@ -0,0 +1,162 @@
+//! Test signals: sine tones and seedable noise, so detection rates at a given SNR come out the
+//! same on every run and can be asserted.
+//!
+//! Every source is an endless `Iterator<Item = f32>`; combine them with `zip` and `take`.
+
+use core::f32::consts::{LN_10, PI};
+
+use crate::math;
+
+/// SplitMix64: small, fast and good enough for test noise. Not for cryptography.
+#[derive(Debug, Clone)]
+pub struct Rng {
+  state: u64,
+}
+
+impl Rng {
+  pub fn new(seed: u64) -> Self {
+    Self { state: seed }
+  }
+
+  pub fn next_u64(&mut self) -> u64 {
+    self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = self.state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+  }
+
+  /// Uniform in (0, 1].
+  pub fn uniform(&mut self) -> f32 {
+    ((self.next_u64() >> 40) + 1) as f32 / (1u64 << 24) as f32
+  }
+}
+
+/// A sine of constant amplitude.
+#[derive(Debug, Clone)]
+pub struct Sine {
+  phase: f32,
+  step: f32,
+  amplitude: f32,
+}
+
+impl Sine {
+  pub fn new(freq: f32, sample_rate: f32, amplitude: f32) -> Self {
+    Self { phase: 0., step: 2. * PI * freq / sample_rate, amplitude }
+  }
+}
+
+impl Iterator for Sine {
+  type Item = f32;
+
+  fn next(&mut self) -> Option<f32> {
+    let sample = self.amplitude * math::sin_cos(self.phase).0;
+    self.phase = (self.phase + self.step) % (2. * PI);
+    Some(sample)
+  }
+}
+
+/// White Gaussian noise of a given RMS level.
+#[derive(Debug, Clone)]
+pub struct GaussianNoise {
+  rng: Rng,
+  rms: f32,
+  /// Second value of the last Box-Muller pair.
+  spare: Option<f32>,
+}
+
+impl GaussianNoise {
+  pub fn new(seed: u64, rms: f32) -> Self {
+    Self { rng: Rng::new(seed), rms, spare: None }
+  }
+
+  /// Noise `snr_db` below a sine of `amplitude`, whose power is `amplitude² / 2`.
+  pub fn for_snr(seed: u64, amplitude: f32, snr_db: f32) -> Self {
+    let noise_power = amplitude * amplitude / 2. / math::powf(10., snr_db / 10.);
+    Self::new(seed, math::sqrt(noise_power))
+  }
+}
+
+impl Iterator for GaussianNoise {
+  type Item = f32;
+
+  fn next(&mut self) -> Option<f32> {
+    if let Some(spare) = self.spare.take() {
+      return Some(spare);
+    }
+    let radius = math::sqrt(-2. * math::log10(self.rng.uniform()) * LN_10);
+    let (sin, cos) = math::sin_cos(2. * PI * self.rng.uniform());
+    self.spare = Some(self.rms * radius * sin);
+    Some(self.rms * radius * cos)
+  }
+}
+
+/// Pink (1/f) noise: white noise through Paul Kellet's filter, scaled to about the given RMS
+/// level.
+#[derive(Debug, Clone)]
+pub struct PinkNoise {
+  white: GaussianNoise,
+  b: [f32; 7],
+}
+
+impl PinkNoise {
+  /// RMS of the filter's output for unit white noise.
+  const GAIN: f32 = 3.04;
+
+  pub fn new(seed: u64, rms: f32) -> Self {
+    Self { white: GaussianNoise::new(seed, rms / Self::GAIN), b: [0.; 7] }
+  }
+}
+
+impl Iterator for PinkNoise {
+  type Item = f32;
+
+  fn next(&mut self) -> Option<f32> {
+    let w = self.white.next()?;
+    let b = &mut self.b;
+    b[0] = 0.99886 * b[0] + w * 0.055_518;
+    b[1] = 0.99332 * b[1] + w * 0.075_076;
+    b[2] = 0.96900 * b[2] + w * 0.153_852;
+    b[3] = 0.86650 * b[3] + w * 0.310_486;
+    b[4] = 0.55000 * b[4] + w * 0.532_952;
+    b[5] = -0.7616 * b[5] - w * 0.016_898;
+    let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + w * 0.5362;
+    b[6] = w * 0.115926;
+    Some(pink)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Goertzel;
+
+  fn rms(samples: impl Iterator<Item = f32>, n: usize) -> f32 {
+    math::sqrt(samples.take(n).map(|x| x * x).sum::<f32>() / n as f32)
+  }
+
+  #[test]
+  fn noise_is_reproducible_and_at_its_level() {
+    let a: Vec<f32> = GaussianNoise::new(7, 1.).take(100).collect();
+    let b: Vec<f32> = GaussianNoise::new(7, 1.).take(100).collect();
+    let c: Vec<f32> = GaussianNoise::new(8, 1.).take(100).collect();
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+    assert!((rms(GaussianNoise::new(1, 0.3), 100_000) - 0.3).abs() < 0.01);
+    assert!((rms(PinkNoise::new(1, 0.3), 100_000) - 0.3).abs() < 0.03);
+  }
+
+  #[test]
+  fn detects_nearly_every_block_at_10_db() {
+    let filter = Goertzel::with_block_size(1000., 8000., 160);
+    let tone = Sine::new(1000., 8000., 0.5);
+    let noise = GaussianNoise::for_snr(42, 0.5, 10.);
+    let signal: Vec<f32> = tone.zip(noise).map(|(s, n)| s + n).take(200 * 160).collect();
+    let hits = signal.chunks(160).filter(|b| filter.block_power(b) > 0.5).count();
+    assert!(hits >= 198, "{} of 200", hits);
+    let noise: Vec<f32> = GaussianNoise::new(43, 0.5).take(200 * 160).collect();
+    let false_alarms = noise.chunks(160).filter(|b| filter.block_power(b) > 0.5).count();
+    assert_eq!(false_alarms, 0);
+  }
+}
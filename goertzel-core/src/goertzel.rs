@@ -0,0 +1,681 @@
+//https://netwerkt.wordpress.com/2011/08/25/goertzel-filter/
+
+use core::f32::consts::PI;
+
+use crate::math;
+use crate::units::{Hertz, SampleRate, Samples};
+
+const BLOCK_SIZE: usize = 1000;
+
+/// Added to the denominators of the relative normalizations so silence reads 0, not NaN.
+pub const DEFAULT_EPSILON: f32 = 1e-7;
+
+/// How a filter scales the power it reports.
+///
+/// `n` is the number of samples the power was accumulated over. For real input the tone's
+/// energy is taken as twice `power / n`, since a real sinusoid puts half of it at the mirror
+/// frequency; IQ input has no mirror.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Normalization {
+  /// The squared magnitude of the Goertzel output as is; grows with the square of `n`.
+  None,
+  /// Mean-square level of the tone: a real tone of amplitude `a` reads `a²/2` (a complex one
+  /// `a²`) whatever the block length, so thresholds can be absolute levels.
+  PerSampleCount,
+  /// Peak amplitude of the tone: a tone of amplitude `a` reads `a`, for meters in linear units.
+  Amplitude,
+  /// Share of the block's energy found at the filter frequency: a pure tone reads 1 whatever its
+  /// level. The default.
+  #[default]
+  RelativeToTotalPower,
+  /// The tone's energy over the rest of the block's energy: a linear signal-to-noise ratio.
+  RelativeToNoiseFloor,
+}
+
+impl Normalization {
+  pub(crate) fn apply(self, power: f32, totalpower: f32, n: usize, real: bool, epsilon: f32) -> f32 {
+    let power = power.max(0.);
+    let n = n.max(1) as f32;
+    let tone = if real { 2. * power / n } else { power / n };
+    match self {
+      Normalization::None => power,
+      Normalization::PerSampleCount => tone / n,
+      Normalization::Amplitude => math::sqrt(if real { 2. * tone / n } else { tone / n }),
+      Normalization::RelativeToTotalPower => tone / (totalpower + epsilon),
+      Normalization::RelativeToNoiseFloor => tone / ((totalpower - tone).max(0.) + epsilon),
+    }
+  }
+}
+
+/// Share of a real block's energy found at the filter frequency: a pure tone reads 1.
+pub(crate) fn normalize_real(power: f32, totalpower: f32, n: usize) -> f32 {
+  Normalization::RelativeToTotalPower.apply(power, totalpower, n, true, DEFAULT_EPSILON)
+}
+
+/// Taper applied to the samples of a block before it is measured.
+///
+/// Tapering trades a wider main lobe for much lower leakage from strong tones in other bins.
+/// Readings are corrected for the window's gain, so a pure tone reads the same as without one.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Window {
+  /// No taper. The default.
+  #[default]
+  Rectangular,
+  Hann,
+  Hamming,
+  Blackman,
+}
+
+impl Window {
+  /// Weight of sample `i` of an `n`-sample block (the periodic form of the window).
+  pub fn weight(self, i: usize, n: usize) -> f32 {
+    let x = 2. * PI * i as f32 / n.max(1) as f32;
+    match self {
+      Window::Rectangular => 1.,
+      Window::Hann => 0.5 - 0.5 * math::cos(x),
+      Window::Hamming => 0.54 - 0.46 * math::cos(x),
+      Window::Blackman => 0.42 - 0.5 * math::cos(x) + 0.08 * math::cos(2. * x),
+    }
+  }
+
+  /// How far from the filter frequency, in bins, a tone still reads half its power.
+  pub fn half_power_bins(self) -> f32 {
+    match self {
+      Window::Rectangular => 0.443,
+      Window::Hann => 0.72,
+      Window::Hamming => 0.65,
+      Window::Blackman => 0.84,
+    }
+  }
+
+  /// Block size at which a tone `tolerance` Hz off the filter frequency reads half its power.
+  pub fn block_size_for(self, tolerance: f32, samplef: f32) -> usize {
+    let n = self.half_power_bins() * samplef / tolerance.max(f32::MIN_POSITIVE);
+    (math::round(n) as usize).max(1)
+  }
+}
+
+/// A power measurement that tells "tone absent" from "no signal at all".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reading {
+  /// The input's mean-square level is at or below the silence floor; the ratio would only be
+  /// noise over noise.
+  Silence,
+  Power(f32),
+}
+
+/// Recursion state of one of the two staggered filters of a `Goertzel`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FilterState {
+  pub s_prev: f32,
+  pub s_prev2: f32,
+  /// Energy of the samples fed since the last reset.
+  pub totalpower: f32,
+  /// Samples fed since the last reset.
+  pub n: usize,
+}
+
+/// Internal values of a `Goertzel`, for diagnosing numerical trouble; see
+/// [`Goertzel::snapshot`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Snapshot {
+  pub freq: f32,
+  pub coeff: f32,
+  /// Samples fed in total.
+  pub samples: u64,
+  /// Which of `filters` `filter` reports.
+  pub active: usize,
+  pub filters: [FilterState; 2],
+}
+
+/// Tandem real-time Goertzel filter.
+///
+/// Two filters run in parallel, each reset every `block_size` samples (1000 by default) and
+/// staggered by half a block, so a power estimate is available on every sample.
+#[derive(Debug)]
+pub struct Goertzel {
+  s_prev: [f32; 2],
+  s_prev2: [f32; 2],
+  s_prev_q: [f32; 2],
+  s_prev2_q: [f32; 2],
+  totalpower: [f32; 2],
+  freq: f32,
+  samplef: f32,
+  coeff: f32,
+  /// `sin(omega)`, for the phase correction of `filter_iq`.
+  sin: f32,
+  n_total: u64,
+  active: usize,
+  n: [i32; 2],
+  block_size: i32,
+  normalization: Normalization,
+  window: Window,
+  epsilon: f32,
+  silence_floor: f32,
+}
+
+impl Goertzel {
+  pub fn new(freq: impl Into<Hertz>, samplef: impl Into<SampleRate>) -> Self {
+    Self::with_block_size(freq, samplef, BLOCK_SIZE)
+  }
+
+  pub fn with_block_size(
+    freq: impl Into<Hertz>,
+    samplef: impl Into<SampleRate>,
+    block_size: impl Into<Samples>,
+  ) -> Self {
+    let (freq, samplef) = (freq.into().0, samplef.into().0);
+    let (sin, cos) = math::sin_cos(2.*PI*freq/samplef);
+    Self { sin, ..Self::with_coeff(freq, samplef, block_size, 2.*cos) }
+  }
+
+  /// Filter with a recursion coefficient computed ahead of time, `2 cos(2 pi freq / samplef)`,
+  /// e.g. from a [`CoefficientTable`](crate::CoefficientTable), so that building it takes no
+  /// `cos()`.
+  pub fn with_coeff(
+    freq: impl Into<Hertz>,
+    samplef: impl Into<SampleRate>,
+    block_size: impl Into<Samples>,
+    coeff: f32,
+  ) -> Self {
+    let (freq, samplef) = (freq.into().0, samplef.into().0);
+    let block_size = block_size.into().get();
+    // sin(omega) from the coefficient, negative for frequencies in the lower half turn.
+    let cos = (coeff / 2.).clamp(-1., 1.);
+    let turns = freq / samplef;
+    let sin = math::sqrt(1. - cos * cos);
+    Self {
+      s_prev: [0., 0.],
+      s_prev2: [0., 0.],
+      s_prev_q: [0., 0.],
+      s_prev2_q: [0., 0.],
+      totalpower: [0., 0.],
+      freq,
+      samplef,
+      coeff,
+      sin: if turns - math::round(turns) < 0. { -sin } else { sin },
+      n_total: 0,
+      active: 0,
+      n: [0, 0],
+      block_size: block_size.max(1) as i32,
+      normalization: Normalization::default(),
+      window: Window::Rectangular,
+      epsilon: DEFAULT_EPSILON,
+      silence_floor: 0.,
+    }
+  }
+
+  /// Filter accepting tones within `tolerance` Hz of `freq`, e.g. 1000 Hz ±15 Hz, and rejecting
+  /// tones further off.
+  ///
+  /// A tone at `freq ± tolerance` reads half of what it would at `freq` in blocks of
+  /// `block_size`; the Hann window keeps strong tones well outside the band from leaking in.
+  /// Narrow tolerances need long blocks and so react slowly: ±15 Hz is 48 ms at any rate.
+  pub fn with_tolerance(
+    freq: impl Into<Hertz>,
+    samplef: impl Into<SampleRate>,
+    tolerance: impl Into<Hertz>,
+  ) -> Self {
+    let (samplef, tolerance) = (samplef.into().0, tolerance.into().0);
+    let block_size = Window::Hann.block_size_for(tolerance, samplef);
+    Self::with_block_size(freq, samplef, block_size).with_window(Window::Hann)
+  }
+
+  /// Regularization of the relative normalizations, [`DEFAULT_EPSILON`] unless set. Larger values
+  /// pull the readings of very quiet input towards 0.
+  pub fn with_epsilon(mut self, epsilon: f32) -> Self {
+    self.epsilon = epsilon;
+    self
+  }
+
+  /// Mean-square input level at or below which readings are [`Reading::Silence`]; 0 (only
+  /// digital silence) unless set. For example `1e-8` is -77 dBFS for a sine.
+  pub fn with_silence_floor(mut self, mean_square: f32) -> Self {
+    self.set_silence_floor(mean_square);
+    self
+  }
+
+  pub fn set_silence_floor(&mut self, mean_square: f32) {
+    self.silence_floor = mean_square;
+  }
+
+  pub fn with_normalization(mut self, normalization: Normalization) -> Self {
+    self.set_normalization(normalization);
+    self
+  }
+
+  pub fn set_normalization(&mut self, normalization: Normalization) {
+    self.normalization = normalization;
+  }
+
+  pub fn normalization(&self) -> Normalization {
+    self.normalization
+  }
+
+  /// Taper used by the block methods; the sliding `filter` is never windowed.
+  pub fn with_window(mut self, window: Window) -> Self {
+    self.window = window;
+    self
+  }
+
+  pub fn window(&self) -> Window {
+    self.window
+  }
+
+  /// Filter at bin `k` of a `block_size` DFT. `k` need not be an integer.
+  pub fn from_bin(k: f32, samplef: f32, block_size: usize) -> Self {
+    Self::with_block_size(k * samplef / block_size.max(1) as f32, samplef, block_size)
+  }
+
+  pub fn freq(&self) -> f32 {
+    self.freq
+  }
+
+  /// Bin index of `freq` at this filter's block size, fractional in between bins.
+  pub fn bin(&self) -> f32 {
+    self.freq * self.block_size as f32 / self.samplef
+  }
+
+  pub fn block_size(&self) -> usize {
+    self.block_size as usize
+  }
+
+  pub fn sample_rate(&self) -> f32 {
+    self.samplef
+  }
+
+  /// The running state of the sliding filter.
+  pub fn snapshot(&self) -> Snapshot {
+    let state = |k: usize| FilterState {
+      s_prev: self.s_prev[k],
+      s_prev2: self.s_prev2[k],
+      totalpower: self.totalpower[k],
+      n: self.n[k] as usize,
+    };
+    Snapshot {
+      freq: self.freq,
+      coeff: self.coeff(),
+      samples: self.n_total,
+      active: self.active,
+      filters: [state(0), state(1)],
+    }
+  }
+
+  fn omega(&self) -> f32 {
+    let normalizedfreq: f32 = self.freq/self.samplef;
+    2.*PI*normalizedfreq
+  }
+
+  fn rotate(&mut self) {
+    self.n_total += 1;
+    self.active = ((self.n_total / self.block_size as u64) & 0x01) as usize;
+
+    let activen = 1-self.active;
+
+    if self.n[activen] >= self.block_size {
+      self.s_prev[activen] = 0.0;
+      self.s_prev2[activen] = 0.0;
+      self.s_prev_q[activen] = 0.0;
+      self.s_prev2_q[activen] = 0.0;
+      self.totalpower[activen] = 0.0;
+      self.n[activen] = 0;
+    }
+  }
+
+  /// Feeds one sample and returns the normalized power of the active window. With the default
+  /// normalization: close to 1 for a pure tone at `freq`, close to 0 when it is absent.
+  pub fn filter (&mut self, sample: f32) -> f32 {
+    let coeff = self.coeff();
+    for k in 0..2 {
+      let s = sample + coeff * self.s_prev[k] - self.s_prev2[k];
+      self.s_prev2[k] = self.s_prev[k];
+      self.s_prev[k] = s;
+      self.n[k] += 1;
+    }
+    self.rotate();
+    self.totalpower[0] += sample*sample;
+    self.totalpower[1] += sample*sample;
+
+    let power = self.s_prev2[self.active] * self.s_prev2[self.active] + self.s_prev[self.active]
+      * self.s_prev[self.active] - coeff * self.s_prev[self.active] * self.s_prev2[self.active];
+    let (total, n) = (self.totalpower[self.active], self.n[self.active] as usize);
+    self.normalization.apply(power, total, n, true, self.epsilon)
+  }
+
+  /// Normalized power of a single, independent block, using this filter's frequency but
+  /// leaving its running state untouched.
+  pub fn block_power(&self, block: &[f32]) -> f32 {
+    let coeff = self.coeff();
+    let (mut s_prev, mut s_prev2, mut totalpower) = (0f32, 0f32, 0f32);
+    for (i, &sample) in block.iter().enumerate() {
+      let s = self.windowed(sample, i, block.len()) + coeff * s_prev - s_prev2;
+      s_prev2 = s_prev;
+      s_prev = s;
+      totalpower += sample*sample;
+    }
+    self.finish_block(s_prev, s_prev2, totalpower, block.len())
+  }
+
+  /// `block_power` from the final recursion state of an `n`-sample block, for backends that run
+  /// the recursion elsewhere.
+  pub(crate) fn finish_block(&self, s_prev: f32, s_prev2: f32, totalpower: f32, n: usize) -> f32 {
+    let coeff = self.coeff();
+    let gain = self.window_gain(n);
+    let power = (s_prev2 * s_prev2 + s_prev * s_prev - coeff * s_prev * s_prev2) / (gain * gain);
+    self.normalization.apply(power, totalpower, n, true, self.epsilon)
+  }
+
+  /// Coefficient of the recursion, `2 cos(omega)`.
+  pub(crate) fn coeff(&self) -> f32 {
+    self.coeff
+  }
+
+  fn windowed(&self, sample: f32, i: usize, n: usize) -> f32 {
+    match self.window {
+      Window::Rectangular => sample,
+      window => sample * window.weight(i, n),
+    }
+  }
+
+  /// Mean weight of the window over `n` samples, by which it scales a tone's amplitude.
+  fn window_gain(&self, n: usize) -> f32 {
+    match self.window {
+      Window::Rectangular => 1.,
+      window => (0..n).map(|i| window.weight(i, n)).sum::<f32>() / n.max(1) as f32,
+    }
+  }
+
+  /// `filter`, reporting silence when the active window's level is at or below the floor.
+  pub fn filter_reading(&mut self, sample: f32) -> Reading {
+    let power = self.filter(sample);
+    let a = self.active;
+    self.reading(power, self.totalpower[a], self.n[a] as usize)
+  }
+
+  /// `block_power`, reporting silence when the block's level is at or below the floor.
+  pub fn block_reading(&self, block: &[f32]) -> Reading {
+    let totalpower: f32 = block.iter().map(|x| x * x).sum();
+    self.reading(self.block_power(block), totalpower, block.len())
+  }
+
+  fn reading(&self, power: f32, totalpower: f32, n: usize) -> Reading {
+    if totalpower / n.max(1) as f32 <= self.silence_floor {
+      Reading::Silence
+    } else {
+      Reading::Power(power)
+    }
+  }
+
+  /// DTFT of `block` at `freq`, as (re, im), with the phase referred to the first sample.
+  ///
+  /// This is the generalized Goertzel algorithm (Sysel and Rajmic, 2012): the usual recursion
+  /// followed by a phase correction that holds for any frequency, not only whole bins of
+  /// `block.len()`. The magnitude equals what `block_power` measures; a real tone of amplitude
+  /// `a` gives about `a * block.len() / 2`.
+  pub fn block_dft(&self, block: &[f32]) -> (f32, f32) {
+    let omega = self.omega();
+    let coeff = self.coeff;
+    let (mut s_prev, mut s_prev2) = (0f32, 0f32);
+    for (i, &sample) in block.iter().enumerate() {
+      let s = self.windowed(sample, i, block.len()) + coeff * s_prev - s_prev2;
+      s_prev2 = s_prev;
+      s_prev = s;
+    }
+    let gain = self.window_gain(block.len());
+    let (s_prev, s_prev2) = (s_prev / gain, s_prev2 / gain);
+    // y = s_prev - e^(-j omega) * s_prev2 = sum x[n] e^(j omega (N-1-n))
+    let (sin, cos) = math::sin_cos(omega);
+    let (y_re, y_im) = (s_prev - cos * s_prev2, sin * s_prev2);
+    // X = e^(-j omega (N-1)) * y
+    let (sin, cos) = math::sin_cos(-omega * (block.len().max(1) - 1) as f32);
+    (y_re * cos - y_im * sin, y_re * sin + y_im * cos)
+  }
+
+  /// Complex-input variant for baseband IQ streams.
+  ///
+  /// Unlike `filter`, positive and negative frequencies are distinguished: a tone at `+freq`
+  /// relative to the tuned centre is detected, its mirror image at `-freq` is not. A full-scale
+  /// complex tone yields a normalized power close to 1.
+  pub fn filter_iq (&mut self, i: f32, q: f32) -> f32 {
+    let (coeff, sin) = (self.coeff, self.sin);
+    let cos = coeff / 2.;
+    for k in 0..2 {
+      let s = i + coeff * self.s_prev[k] - self.s_prev2[k];
+      self.s_prev2[k] = self.s_prev[k];
+      self.s_prev[k] = s;
+      let s = q + coeff * self.s_prev_q[k] - self.s_prev2_q[k];
+      self.s_prev2_q[k] = self.s_prev_q[k];
+      self.s_prev_q[k] = s;
+      self.n[k] += 1;
+    }
+    self.rotate();
+    self.totalpower[0] += i*i + q*q;
+    self.totalpower[1] += i*i + q*q;
+
+    // y = s_prev - e^(-j omega) * s_prev2
+    let a = self.active;
+    let re = self.s_prev[a] - cos * self.s_prev2[a] - sin * self.s_prev2_q[a];
+    let im = self.s_prev_q[a] - cos * self.s_prev2_q[a] + sin * self.s_prev2[a];
+    let (total, n) = (self.totalpower[a], self.n[a] as usize);
+    self.normalization.apply(re*re + im*im, total, n, false, self.epsilon)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::coefficient;
+
+  #[test]
+  fn exploration() {
+    //assert_eq!(2 + 2, 4);
+    let _x = Goertzel::new(440., 44e3);
+  }
+
+  #[test]
+  fn snapshot_follows_the_staggered_filters() {
+    let mut g = Goertzel::with_block_size(1000., 8000., 4);
+    for _ in 0..6 {
+      g.filter(0.5);
+    }
+    let s = g.snapshot();
+    assert_eq!((s.samples, s.active), (6, 1));
+    assert!((s.coeff - 2. * (PI / 4.).cos()).abs() < 1e-6);
+    // Filter 0 was reset after its fourth sample, filter 1 runs on.
+    assert_eq!((s.filters[0].n, s.filters[1].n), (2, 6));
+    assert_eq!(s.filters[1].totalpower, 1.5);
+  }
+
+  #[test]
+  fn block_power_of_pure_tone() {
+    let g = Goertzel::new(1000., 8000.);
+    let block: Vec<f32> = (0..200).map(|t| (2.*PI*1000.*(t as f32)/8000.).sin()).collect();
+    assert!((g.block_power(&block) - 1.).abs() < 0.02);
+  }
+
+  #[test]
+  fn normalizations_have_documented_scales() {
+    let tone = |len: usize| -> Vec<f32> {
+      (0..len)
+        .map(|t| {
+          let t = t as f32 / 8000.;
+          0.5 * (2.*PI*1000.*t).sin() + 0.5 * (2.*PI*2000.*t).sin()
+        })
+        .collect()
+    };
+    let level = Goertzel::new(1000., 8000.).with_normalization(Normalization::PerSampleCount);
+    assert!((level.block_power(&tone(200)) - 0.125).abs() < 1e-3);
+    assert!((level.block_power(&tone(800)) - 0.125).abs() < 1e-3);
+    let amplitude = Goertzel::new(1000., 8000.).with_normalization(Normalization::Amplitude);
+    assert!((amplitude.block_power(&tone(800)) - 0.5).abs() < 1e-3);
+    // The 2 kHz tone is as strong as the 1 kHz one.
+    let snr = Goertzel::new(1000., 8000.).with_normalization(Normalization::RelativeToNoiseFloor);
+    assert!((snr.block_power(&tone(800)) - 1.).abs() < 1e-2);
+    let share = Goertzel::new(1000., 8000.);
+    assert!((share.block_power(&tone(800)) - 0.5).abs() < 1e-2);
+  }
+
+  #[test]
+  fn quiet_input_reads_as_silence() {
+    let g = Goertzel::new(1000., 8000.).with_silence_floor(1e-8);
+    let hiss: Vec<f32> = (0..200).map(|t| if t % 2 == 0 { 1e-5 } else { -1e-5 }).collect();
+    assert_eq!(g.block_reading(&hiss), Reading::Silence);
+    assert_eq!(g.block_reading(&[0.; 200]), Reading::Silence);
+    let tone: Vec<f32> = (0..200).map(|t| 0.01 * (2.*PI*1000.*(t as f32)/8000.).sin()).collect();
+    assert!(matches!(g.block_reading(&tone), Reading::Power(p) if p > 0.9));
+
+    // Sample by sample, the level of the active window decides.
+    let mut g = Goertzel::with_block_size(1000., 8000., 200).with_silence_floor(1e-8);
+    let readings: Vec<Reading> = hiss.iter().chain(&tone).map(|&x| g.filter_reading(x)).collect();
+    assert!(readings[..200].iter().all(|&r| r == Reading::Silence));
+    assert!(matches!(readings[399], Reading::Power(p) if p > 0.9));
+  }
+
+  #[test]
+  fn windows_keep_the_level_and_cut_leakage() {
+    // 1030 Hz falls between the 40 Hz bins of a 200-sample block.
+    let block: Vec<f32> = (0..200).map(|t| (2.*PI*1030.*(t as f32)/8000.).sin()).collect();
+    let rect = Goertzel::new(1200., 8000.);
+    for window in [Window::Hann, Window::Hamming, Window::Blackman] {
+      let g = Goertzel::new(1030., 8000.).with_window(window);
+      assert!((g.block_power(&block) - 1.).abs() < 0.05, "{:?} {}", window, g.block_power(&block));
+      let far = Goertzel::new(1200., 8000.).with_window(window);
+      assert!(far.block_power(&block) < rect.block_power(&block) / 10., "{:?}", window);
+    }
+  }
+
+  #[test]
+  fn tolerance_sets_the_half_power_band() {
+    let g = Goertzel::with_tolerance(1000., 8000., 15.);
+    assert_eq!((g.block_size(), g.window()), (384, Window::Hann));
+    let read = |freq: f32| {
+      let block: Vec<f32> =
+        (0..g.block_size()).map(|t| (2.*PI*freq*(t as f32)/8000. + 0.4).sin()).collect();
+      g.block_power(&block)
+    };
+    assert!((read(1015.) - 0.5).abs() < 0.03, "{}", read(1015.));
+    assert!((read(985.) - 0.5).abs() < 0.03, "{}", read(985.));
+    assert!(read(1000.) > 0.98 && read(1045.) < 0.01, "{} {}", read(1000.), read(1045.));
+  }
+
+  #[test]
+  fn block_dft_is_exact_between_bins() {
+    let g = Goertzel::from_bin(25.37, 8000., 1000);
+    let omega = 2.*PI*g.freq()/8000.;
+    let block: Vec<f32> = (0..1000).map(|t| 0.8 * (omega * t as f32 + 0.6).cos()).collect();
+    let (re, im) = g.block_dft(&block);
+    let direct = block.iter().enumerate().fold((0f32, 0f32), |(re, im), (n, &x)| {
+      (re + x * (omega * n as f32).cos(), im - x * (omega * n as f32).sin())
+    });
+    assert!((re - direct.0).abs() < 0.05 && (im - direct.1).abs() < 0.05, "{:?} vs {:?}", (re, im), direct);
+    assert!(((re*re + im*im).sqrt() / 400. - 1.).abs() < 0.02);
+    assert!((im.atan2(re) - 0.6).abs() < 0.02);
+  }
+
+  #[test]
+  fn iq_distinguishes_sign_of_frequency() {
+    let samplef = 48e3;
+    let mut pos = Goertzel::new(1200., samplef);
+    let mut neg = Goertzel::new(1200., samplef);
+    let (mut p, mut n) = (0., 0.);
+    for t in 0..4000 {
+      let phase = 2.*PI*1200.*(t as f32)/samplef;
+      p = pos.filter_iq(phase.cos(), phase.sin());
+      n = neg.filter_iq(phase.cos(), -phase.sin());
+    }
+    assert!(p > 0.9, "positive tone power {}", p);
+    assert!(n < 0.05, "mirror tone power {}", n);
+  }
+
+  #[test]
+  fn iq_with_a_table_coefficient_reads_like_a_computed_one() {
+    let samplef = 8000.;
+    for freq in [-1200., 67., 1633., 3100.] {
+      let mut computed = Goertzel::new(freq, samplef);
+      let mut table = Goertzel::with_coeff(freq, samplef, BLOCK_SIZE, coefficient(freq, samplef));
+      for t in 0..3000 {
+        let phase = 2.*PI*1633.*(t as f32)/samplef;
+        let (i, q) = (phase.cos(), phase.sin());
+        let (a, b) = (computed.filter_iq(i, q), table.filter_iq(i, q));
+        assert!((a - b).abs() < 1e-3, "{} Hz: {} vs {}", freq, a, b);
+      }
+    }
+  }
+
+  #[test]
+  fn keeps_alternating_past_i32_samples() {
+    let mut g = Goertzel::with_block_size(1000., 8000., 4);
+    g.n_total = i32::MAX as u64 - 1;
+    let active: Vec<usize> = (0..6).map(|_| { g.filter(0.); g.active }).collect();
+    // i32::MAX + 1 = 2^31 is a multiple of the block size.
+    assert_eq!(active, vec![1, 0, 0, 0, 0, 1]);
+    assert_eq!(g.snapshot().samples, i32::MAX as u64 + 5);
+  }
+
+  #[test]
+  fn iq_sees_half_of_a_real_tone() {
+    let mut real = Goertzel::new(440., 44e3);
+    let mut iq = Goertzel::new(440., 44e3);
+    for t in 0..3000 {
+      let x = (2.*PI*440.*(t as f32)/44e3).sin();
+      let a = real.filter(x);
+      // The real filter counts the mirror image at -freq as well.
+      let b = iq.filter_iq(x, 0.);
+      assert!((a - 2.*b).abs() < 2e-3);
+    }
+  }
+
+  mod invariants {
+    use super::*;
+    use proptest::prelude::*;
+
+    const SAMPLEF: f32 = 8000.;
+
+    fn tone(freq: f32, phase: f32, len: usize) -> Vec<f32> {
+      (0..len).map(|t| (2.*PI*freq*(t as f32)/SAMPLEF + phase).sin()).collect()
+    }
+
+    proptest! {
+      #[test]
+      fn power_is_non_negative(
+        freq in 1f32..3999.,
+        samples in proptest::collection::vec(-1f32..1., 1..3000),
+      ) {
+        let mut g = Goertzel::new(freq, SAMPLEF);
+        prop_assert!(g.block_power(&samples) >= 0.);
+        for &s in &samples {
+          prop_assert!(g.filter(s) >= 0.);
+        }
+      }
+
+      #[test]
+      fn full_scale_tone_reads_one(freq in 100f32..3900., phase in 0f32..2.*PI) {
+        let g = Goertzel::new(freq, SAMPLEF);
+        let p = g.block_power(&tone(freq, phase, 1000));
+        prop_assert!((p - 1.).abs() < 0.05, "power {}", p);
+      }
+
+      #[test]
+      fn silence_reads_zero(freq in 1f32..3999., len in 1usize..3000) {
+        let mut g = Goertzel::new(freq, SAMPLEF);
+        let silence = vec![0.; len];
+        prop_assert_eq!(g.block_power(&silence), 0.);
+        prop_assert!(silence.iter().all(|&s| g.filter(s) == 0.));
+      }
+
+      #[test]
+      fn block_alignment_does_not_matter(
+        freq in 200f32..3800.,
+        offset in 0usize..1000,
+        len in 200usize..1000,
+      ) {
+        let g = Goertzel::new(freq, SAMPLEF);
+        let signal = tone(freq, 0., 2000);
+        let aligned = g.block_power(&signal[..len]);
+        let shifted = g.block_power(&signal[offset..offset + len]);
+        prop_assert!((aligned - shifted).abs() < 0.1, "{} vs {}", aligned, shifted);
+      }
+    }
+  }
+}
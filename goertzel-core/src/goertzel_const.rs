@@ -0,0 +1,167 @@
+use core::f64::consts::PI;
+
+use crate::goertzel::normalize_real;
+
+/// `2cos(2*pi*freq/samplef)`, evaluable at compile time.
+pub const fn coefficient(freq: f32, samplef: f32) -> f32 {
+  (2. * cos(2. * PI * freq as f64 / samplef as f64)) as f32
+}
+
+/// Sample rates a [`CoefficientTable`] holds coefficients for.
+pub const TABLE_RATES: [f32; 4] = [8000., 16000., 44100., 48000.];
+
+/// Coefficients of a fixed set of frequencies at each of [`TABLE_RATES`], computed by the
+/// compiler when the table is a `const` or `static`:
+///
+/// ```
+/// use goertzel_core::{CoefficientTable, Goertzel};
+/// static TONES: CoefficientTable<2> = CoefficientTable::new(&[697., 1209.]);
+/// let coeffs = TONES.get(8000.).unwrap();
+/// let filter = Goertzel::with_coeff(697., 8000., 205, coeffs[0]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoefficientTable<const N: usize> {
+  rows: [[f32; N]; TABLE_RATES.len()],
+}
+
+impl<const N: usize> CoefficientTable<N> {
+  pub const fn new(freqs: &[f32; N]) -> Self {
+    let mut rows = [[0.; N]; TABLE_RATES.len()];
+    let mut r = 0;
+    while r < TABLE_RATES.len() {
+      let mut i = 0;
+      while i < N {
+        rows[r][i] = coefficient(freqs[i], TABLE_RATES[r]);
+        i += 1;
+      }
+      r += 1;
+    }
+    Self { rows }
+  }
+
+  /// The coefficients at `samplef`, if it is one of [`TABLE_RATES`].
+  pub fn get(&self, samplef: f32) -> Option<&[f32; N]> {
+    TABLE_RATES.iter().position(|&rate| rate == samplef).map(|r| &self.rows[r])
+  }
+}
+
+/// Cosine for const contexts: reduce to [0, pi/2] and sum the Taylor series, which is exact to
+/// f32 precision there well before the 14th power.
+const fn cos(x: f64) -> f64 {
+  let tau = 2. * PI;
+  let mut x = if x < 0. { -x } else { x };
+  x -= tau * ((x / tau) as u64 as f64);
+  if x > PI {
+    x = tau - x;
+  }
+  let (x, sign) = if x > PI / 2. { (PI - x, -1.) } else { (x, 1.) };
+  let x2 = x * x;
+  let mut term = 1.;
+  let mut sum = 1.;
+  let mut k = 1;
+  while k <= 8 {
+    term *= -x2 / ((2 * k - 1) * (2 * k)) as f64;
+    sum += term;
+    k += 1;
+  }
+  sign * sum
+}
+
+/// Single Goertzel filter with the block size fixed at compile time.
+///
+/// Holds no buffers, so it can live in a `static` or on an MCU stack. Use it with `const`
+/// construction to get the coefficient computed by the compiler:
+///
+/// ```
+/// use goertzel_core::GoertzelConst;
+/// const DETECTOR: GoertzelConst<205> = GoertzelConst::new(697., 8000.);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct GoertzelConst<const N: usize> {
+  coeff: f32,
+  s_prev: f32,
+  s_prev2: f32,
+  totalpower: f32,
+  n: usize,
+}
+
+impl<const N: usize> GoertzelConst<N> {
+  pub const fn new(freq: f32, samplef: f32) -> Self {
+    Self::from_coeff(coefficient(freq, samplef))
+  }
+
+  pub const fn from_coeff(coeff: f32) -> Self {
+    Self {
+      coeff,
+      s_prev: 0.,
+      s_prev2: 0.,
+      totalpower: 0.,
+      n: 0,
+    }
+  }
+
+  /// Feeds one sample. Every `N` samples returns the normalized power of the block just
+  /// completed and starts a new one.
+  pub fn push(&mut self, sample: f32) -> Option<f32> {
+    let s = sample + self.coeff * self.s_prev - self.s_prev2;
+    self.s_prev2 = self.s_prev;
+    self.s_prev = s;
+    self.totalpower += sample * sample;
+    self.n += 1;
+    if self.n < N {
+      return None;
+    }
+    let power = self.s_prev2 * self.s_prev2 + self.s_prev * self.s_prev
+      - self.coeff * self.s_prev * self.s_prev2;
+    let power = normalize_real(power, self.totalpower, N);
+    *self = Self::from_coeff(self.coeff);
+    Some(power)
+  }
+
+  /// Normalized power of a whole block, discarding any partially accumulated one.
+  pub fn process_block(&mut self, block: &[f32; N]) -> f32 {
+    *self = Self::from_coeff(self.coeff);
+    let mut power = 0.;
+    for &sample in block {
+      if let Some(p) = self.push(sample) {
+        power = p;
+      }
+    }
+    power
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Goertzel;
+
+  #[test]
+  fn const_cos_matches_std() {
+    for i in -2000..2000 {
+      let x = i as f64 * 0.01;
+      assert!((cos(x) - x.cos()).abs() < 1e-9, "cos({})", x);
+    }
+  }
+
+  #[test]
+  fn table_matches_runtime_coefficients() {
+    const TABLE: CoefficientTable<3> = CoefficientTable::new(&[67., 1633., 3100.]);
+    for &rate in &TABLE_RATES {
+      for (&coeff, &freq) in TABLE.get(rate).unwrap().iter().zip(&[67., 1633., 3100.]) {
+        assert!((coeff - Goertzel::new(freq, rate).coeff()).abs() < 1e-6, "{} at {}", freq, rate);
+      }
+    }
+    assert_eq!(TABLE.get(22050.), None);
+  }
+
+  #[test]
+  fn agrees_with_block_power() {
+    const G: GoertzelConst<205> = GoertzelConst::new(770., 8000.);
+    let mut g = G;
+    let block: [f32; 205] = core::array::from_fn(|t| (2. * std::f32::consts::PI * 770. * t as f32 / 8000.).sin());
+    let expected = Goertzel::new(770., 8000.).block_power(&block);
+    assert!((g.process_block(&block) - expected).abs() < 1e-4);
+  }
+}
@@ -0,0 +1,375 @@
+//! Bank updates on the GPU, for scans with thousands of bins over many channels.
+//!
+//! `GpuBank` runs the recursion of every (channel, filter) pair of a block in a wgpu compute
+//! shader, one invocation each, and finishes the powers on the CPU exactly as
+//! `GoertzelBank::process_into` would. `BankEngine` picks the GPU when an adapter is available
+//! and the CPU bank otherwise, so callers need not care which one they got.
+//!
+//! Uploading the block and reading the state back costs a round trip per call, so the GPU only
+//! pays off for large banks and long blocks; small banks are faster on the CPU.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+use crate::{GoertzelBank, Window};
+
+const SHADER: &str = r#"
+struct Params { len: u32, channels: u32, filters: u32, pad: u32 }
+struct Filter { coeff: f32, span: u32, window: u32, pad: u32 }
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> filters: array<Filter>;
+@group(0) @binding(2) var<storage, read> audio: array<f32>;
+@group(0) @binding(3) var<storage, read_write> state: array<vec4<f32>>;
+
+const TAU: f32 = 6.283185307179586;
+
+fn weight(window: u32, i: u32, n: u32) -> f32 {
+  let x = TAU * f32(i) / f32(n);
+  switch window {
+    case 1u: { return 0.5 - 0.5 * cos(x); }
+    case 2u: { return 0.54 - 0.46 * cos(x); }
+    case 3u: { return 0.42 - 0.5 * cos(x) + 0.08 * cos(2.0 * x); }
+    default: { return 1.0; }
+  }
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+  let index = id.x;
+  if index >= params.channels * params.filters {
+    return;
+  }
+  let f = filters[index % params.filters];
+  let base = index / params.filters * params.len;
+  var s_prev = 0.0;
+  var s_prev2 = 0.0;
+  var total = 0.0;
+  for (var i = 0u; i < f.span; i++) {
+    let x = audio[base + i];
+    let s = x * weight(f.window, i, f.span) + f.coeff * s_prev - s_prev2;
+    s_prev2 = s_prev;
+    s_prev = s;
+    total += x * x;
+  }
+  state[index] = vec4<f32>(s_prev, s_prev2, total, 0.0);
+}
+"#;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+#[derive(Debug)]
+pub enum GpuError {
+  /// No GPU, or none wgpu can drive.
+  NoAdapter,
+  Device(wgpu::RequestDeviceError),
+}
+
+impl fmt::Display for GpuError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      GpuError::NoAdapter => f.write_str("no GPU adapter available"),
+      GpuError::Device(e) => write!(f, "could not open the GPU: {}", e),
+    }
+  }
+}
+
+impl std::error::Error for GpuError {}
+
+/// Buffers sized for a block length and channel count.
+struct Buffers {
+  len: usize,
+  channels: usize,
+  audio: wgpu::Buffer,
+  state: wgpu::Buffer,
+  readback: wgpu::Buffer,
+  bind_group: wgpu::BindGroup,
+}
+
+/// An opened device with the bank shader compiled.
+struct Gpu {
+  device: wgpu::Device,
+  queue: wgpu::Queue,
+  pipeline: wgpu::ComputePipeline,
+}
+
+impl Gpu {
+  fn open() -> Result<Self, GpuError> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+      power_preference: wgpu::PowerPreference::HighPerformance,
+      force_fallback_adapter: false,
+      compatible_surface: None,
+    }))
+    .ok_or(GpuError::NoAdapter)?;
+    let descriptor = wgpu::DeviceDescriptor {
+      label: Some("goertzel"),
+      required_features: wgpu::Features::empty(),
+      required_limits: wgpu::Limits::downlevel_defaults(),
+      memory_hints: wgpu::MemoryHints::Performance,
+    };
+    let (device, queue) =
+      block_on(adapter.request_device(&descriptor, None)).map_err(GpuError::Device)?;
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("goertzel bank"),
+      source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SHADER)),
+    });
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+      label: Some("goertzel bank"),
+      layout: None,
+      module: &module,
+      entry_point: "main",
+      compilation_options: Default::default(),
+      cache: None,
+    });
+    Ok(Gpu { device, queue, pipeline })
+  }
+}
+
+/// A `GoertzelBank` measured on the GPU.
+pub struct GpuBank {
+  bank: GoertzelBank,
+  device: wgpu::Device,
+  queue: wgpu::Queue,
+  pipeline: wgpu::ComputePipeline,
+  params: wgpu::Buffer,
+  filters: wgpu::Buffer,
+  buffers: Option<Buffers>,
+  /// Filter table last uploaded, for the block length it was made for.
+  table_len: Option<usize>,
+}
+
+impl GpuBank {
+  pub fn new(bank: GoertzelBank) -> Result<Self, GpuError> {
+    Ok(Self::on(Gpu::open()?, bank))
+  }
+
+  fn on(gpu: Gpu, bank: GoertzelBank) -> Self {
+    let Gpu { device, queue, pipeline } = gpu;
+    let params = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("params"),
+      size: 16,
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false,
+    });
+    let filters = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("filters"),
+      size: 16 * bank.len().max(1) as u64,
+      usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false,
+    });
+    GpuBank { bank, device, queue, pipeline, params, filters, buffers: None, table_len: None }
+  }
+
+  pub fn bank(&self) -> &GoertzelBank {
+    &self.bank
+  }
+
+  /// Measures one block per channel, all of the same length, writing the powers channel by
+  /// channel: `out[c * bank.len() + i]` is filter `i` on channel `c`.
+  ///
+  /// Panics if the channels differ in length or `out` is not `channels × bank.len()` long.
+  pub fn process_into(&mut self, channels: &[&[f32]], out: &mut [f32]) {
+    let filters = self.bank.len();
+    assert_eq!(out.len(), channels.len() * filters, "one output per channel and frequency");
+    let len = channels.first().map_or(0, |c| c.len());
+    assert!(channels.iter().all(|c| c.len() == len), "channels of equal length");
+    if filters == 0 || len == 0 {
+      out.iter_mut().for_each(|p| *p = 0.);
+      return;
+    }
+    self.upload(channels, len);
+    let buffers = self.buffers.as_ref().expect("allocated by upload");
+
+    let mut encoder = self.device.create_command_encoder(&Default::default());
+    {
+      let mut pass = encoder.begin_compute_pass(&Default::default());
+      pass.set_pipeline(&self.pipeline);
+      pass.set_bind_group(0, &buffers.bind_group, &[]);
+      let invocations = (channels.len() * filters) as u32;
+      pass.dispatch_workgroups(invocations.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+    let size = (16 * out.len()) as u64;
+    encoder.copy_buffer_to_buffer(&buffers.state, 0, &buffers.readback, 0, size);
+    self.queue.submit([encoder.finish()]);
+
+    let slice = buffers.readback.slice(..size);
+    slice.map_async(wgpu::MapMode::Read, |_| ());
+    self.device.poll(wgpu::Maintain::Wait);
+    {
+      let state = slice.get_mapped_range();
+      for (i, (out, s)) in out.iter_mut().zip(state.chunks_exact(16)).enumerate() {
+        let v = |k: usize| f32::from_le_bytes([s[4 * k], s[4 * k + 1], s[4 * k + 2], s[4 * k + 3]]);
+        let g = &self.bank.filters()[i % filters];
+        *out = g.finish_block(v(0), v(1), v(2), self.bank.span(g, len));
+      }
+    }
+    buffers.readback.unmap();
+  }
+
+  /// Writes the block, and the filter table and buffers when the shape changed.
+  fn upload(&mut self, channels: &[&[f32]], len: usize) {
+    if self.table_len != Some(len) {
+      let mut table = Vec::with_capacity(16 * self.bank.len());
+      for g in self.bank.filters() {
+        let window = match g.window() {
+          Window::Rectangular => 0u32,
+          Window::Hann => 1,
+          Window::Hamming => 2,
+          Window::Blackman => 3,
+        };
+        table.extend_from_slice(&g.coeff().to_le_bytes());
+        table.extend_from_slice(&(self.bank.span(g, len) as u32).to_le_bytes());
+        table.extend_from_slice(&window.to_le_bytes());
+        table.extend_from_slice(&0u32.to_le_bytes());
+      }
+      self.queue.write_buffer(&self.filters, 0, &table);
+      self.table_len = Some(len);
+    }
+    let shape = Some((len, channels.len()));
+    if self.buffers.as_ref().map(|b| (b.len, b.channels)) != shape {
+      self.buffers = Some(self.allocate(len, channels.len()));
+      let params: Vec<u8> = [len, channels.len(), self.bank.len(), 0]
+        .iter()
+        .flat_map(|&v| (v as u32).to_le_bytes())
+        .collect();
+      self.queue.write_buffer(&self.params, 0, &params);
+    }
+    let audio: Vec<u8> = channels.iter().flat_map(|c| c.iter()).flat_map(|x| x.to_le_bytes()).collect();
+    let buffers = self.buffers.as_ref().expect("just allocated");
+    self.queue.write_buffer(&buffers.audio, 0, &audio);
+  }
+
+  fn allocate(&self, len: usize, channels: usize) -> Buffers {
+    let buffer = |label, size: usize, usage| {
+      self.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: size as u64,
+        usage,
+        mapped_at_creation: false,
+      })
+    };
+    use wgpu::BufferUsages as U;
+    let state_size = 16 * channels * self.bank.len();
+    let audio = buffer("audio", 4 * len * channels, U::STORAGE | U::COPY_DST);
+    let state = buffer("state", state_size, U::STORAGE | U::COPY_SRC);
+    let readback = buffer("readback", state_size, U::MAP_READ | U::COPY_DST);
+    let entry = |binding, resource| wgpu::BindGroupEntry { binding, resource };
+    let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("goertzel bank"),
+      layout: &self.pipeline.get_bind_group_layout(0),
+      entries: &[
+        entry(0, self.params.as_entire_binding()),
+        entry(1, self.filters.as_entire_binding()),
+        entry(2, audio.as_entire_binding()),
+        entry(3, state.as_entire_binding()),
+      ],
+    });
+    Buffers { len, channels, audio, state, readback, bind_group }
+  }
+}
+
+/// A bank on the GPU when there is one, on the CPU otherwise.
+pub enum BankEngine {
+  Gpu(Box<GpuBank>),
+  Cpu(GoertzelBank),
+}
+
+impl BankEngine {
+  pub fn new(bank: GoertzelBank) -> Self {
+    match Gpu::open() {
+      Ok(gpu) => BankEngine::Gpu(Box::new(GpuBank::on(gpu, bank))),
+      Err(_) => BankEngine::Cpu(bank),
+    }
+  }
+
+  pub fn is_gpu(&self) -> bool {
+    matches!(self, BankEngine::Gpu(_))
+  }
+
+  pub fn bank(&self) -> &GoertzelBank {
+    match self {
+      BankEngine::Gpu(gpu) => gpu.bank(),
+      BankEngine::Cpu(bank) => bank,
+    }
+  }
+
+  /// See `GpuBank::process_into`.
+  pub fn process_into(&mut self, channels: &[&[f32]], out: &mut [f32]) {
+    match self {
+      BankEngine::Gpu(gpu) => gpu.process_into(channels, out),
+      BankEngine::Cpu(bank) => {
+        assert_eq!(out.len(), channels.len() * bank.len(), "one output per channel and frequency");
+        for (block, out) in channels.iter().zip(out.chunks_mut(bank.len().max(1))) {
+          bank.process_into(block, out);
+        }
+      }
+    }
+  }
+}
+
+/// Runs a future to completion on the current thread; wgpu's native futures are ready almost at
+/// once, so there is no need for an executor.
+fn block_on<F: Future>(future: F) -> F::Output {
+  struct Unpark(std::thread::Thread);
+
+  impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+      self.0.unpark();
+    }
+  }
+
+  let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+  let mut context = Context::from_waker(&waker);
+  let mut future = std::pin::pin!(future);
+  loop {
+    match future.as_mut().poll(&mut context) {
+      Poll::Ready(output) => return output,
+      Poll::Pending => std::thread::park(),
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Goertzel;
+  use std::f32::consts::PI;
+
+  #[test]
+  fn engine_matches_the_cpu_bank() {
+    let bank = || {
+      let mut members: Vec<Goertzel> =
+        (1..200).map(|k| Goertzel::with_block_size(k as f32 * 20., 8000., 400)).collect();
+      members.push(Goertzel::with_block_size(1000., 8000., 160).with_window(Window::Hann));
+      GoertzelBank::from_filters(members)
+    };
+    let cpu = bank();
+    let n = cpu.len();
+    let channels: Vec<Vec<f32>> = [440., 1000., 3000.]
+      .iter()
+      .map(|&f| (0..400).map(|t| (2.*PI*f*(t as f32)/8000.).sin()).collect())
+      .collect();
+    let blocks: Vec<&[f32]> = channels.iter().map(|c| c.as_slice()).collect();
+
+    let mut expected = vec![0.; 3 * n];
+    for (block, out) in blocks.iter().zip(expected.chunks_mut(n)) {
+      cpu.process_into(block, out);
+    }
+    let mut engine = BankEngine::new(bank());
+    let mut out = vec![0.; 3 * n];
+    // Twice, to go through the cached buffers as well.
+    for _ in 0..2 {
+      engine.process_into(&blocks, &mut out);
+      for (i, (a, b)) in out.iter().zip(&expected).enumerate() {
+        assert!((a - b).abs() < 1e-3, "{} at {} on {}", a, i, engine.is_gpu());
+      }
+    }
+    // 440 Hz on channel 0, the windowed 1 kHz member on channel 1.
+    assert!(out[21] > 0.99 && out[2 * n - 1] > 0.99, "{} {}", out[21], out[2 * n - 1]);
+  }
+}
@@ -0,0 +1,94 @@
+//! The latest stretch of a stream's audio, so that what led up to a trigger of one's own can be
+//! kept, e.g. the seconds before a detection saved as a recording.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// A circular buffer of the last samples of one channel, which knows where in the stream they
+/// are.
+#[derive(Debug, Clone)]
+pub struct AudioHistory {
+  samples: VecDeque<f32>,
+  capacity: usize,
+  sample_rate: f32,
+  /// Frame after the last one kept.
+  end: u64,
+}
+
+impl AudioHistory {
+  /// Keeps up to `length` of audio at `sample_rate`.
+  pub fn new(length: Duration, sample_rate: f32) -> Self {
+    let capacity = (length.as_secs_f32() * sample_rate) as usize;
+    Self { samples: VecDeque::with_capacity(capacity), capacity, sample_rate, end: 0 }
+  }
+
+  pub fn push(&mut self, samples: &[f32]) {
+    let skipped = samples.len().saturating_sub(self.capacity);
+    for &sample in &samples[skipped..] {
+      if self.samples.len() == self.capacity {
+        self.samples.pop_front();
+      }
+      self.samples.push_back(sample);
+    }
+    self.end += samples.len() as u64;
+  }
+
+  /// The latest `duration` of audio, or as much of it as is kept.
+  pub fn last(&self, duration: Duration) -> Vec<f32> {
+    let frames = (duration.as_secs_f64() * self.sample_rate as f64) as u64;
+    self.range(self.end.saturating_sub(frames), self.end)
+  }
+
+  /// The audio from frame `start` of the stream up to `end`, as much of it as is kept.
+  pub fn range(&self, start: u64, end: u64) -> Vec<f32> {
+    let first = self.first();
+    let from = (start.max(first) - first) as usize;
+    let to = (end.clamp(first, self.end) - first) as usize;
+    self.samples.range(from.min(to)..to).copied().collect()
+  }
+
+  /// Frame of the stream of the first sample kept.
+  pub fn first(&self) -> u64 {
+    self.end - self.samples.len() as u64
+  }
+
+  /// Frame of the stream after the last sample kept.
+  pub fn end(&self) -> u64 {
+    self.end
+  }
+
+  pub fn sample_rate(&self) -> f32 {
+    self.sample_rate
+  }
+
+  /// Makes the next frame the `frame`th of the stream, forgetting the audio before.
+  pub fn set_position(&mut self, frame: u64) {
+    self.samples.clear();
+    self.end = frame;
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keeps_the_latest_audio() {
+    let mut history = AudioHistory::new(Duration::from_millis(500), 100.);
+    let ramp: Vec<f32> = (0..120).map(|i| i as f32).collect();
+    for block in ramp.chunks(7) {
+      history.push(block);
+    }
+    assert_eq!((history.first(), history.end()), (70, 120));
+    assert_eq!(history.last(Duration::from_millis(30)), [117., 118., 119.]);
+    assert_eq!(history.last(Duration::from_secs(2)).len(), 50);
+    assert_eq!(history.range(60, 73), [70., 71., 72.]);
+    assert_eq!(history.range(130, 140), [] as [f32; 0]);
+    history.set_position(1000);
+    history.push(&ramp);
+    assert_eq!(history.range(1000, 1070), [] as [f32; 0]);
+    assert_eq!(history.range(1118, 2000), [118., 119.]);
+  }
+}
@@ -0,0 +1,99 @@
+//! Goertzel tone detection: the DSP core.
+//!
+//! Builds without std for bare-metal targets and has no dependencies beyond libm. Features:
+//!
+//! * `std` (default): implies `alloc`.
+//! * `alloc`: heap-backed types (`GoertzelBank`, `Pipeline`, presets). Without it only `Goertzel`,
+//!   `GoertzelConst`, `fm::FmDemod`, `fm::Deemphasis`, `estimate::FrequencyEstimator`,
+//!   `generator`, `precision`, `report::Reporter`, `units`, `envelope::AmplitudeTracker` and
+//!   `weighting::WeightingFilter` remain.
+//! * `cpal`: `cpal::input_stream`, a pipeline on a cpal input stream in any sample format;
+//!   implies `std`.
+//! * `dasp`: adapters for `dasp` frames and signals; implies `std`.
+//! * `gpu`: `GpuBank` and `BankEngine`, running large banks in wgpu compute shaders; implies
+//!   `std`.
+//! * `rodio`: `TappedSource`, analyzing what a rodio sink plays; implies `std`.
+//! * `verify`: FFT cross-check of the filter against rustfft.
+//!
+//! Protocol decoders live in their own crates (`goertzel-dtmf`, `goertzel-mf`), the capture
+//! binary in `goertzel-cli`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+mod bank;
+#[cfg(feature = "alloc")]
+pub mod cadence;
+#[cfg(feature = "alloc")]
+pub mod coincidence;
+#[cfg(feature = "cpal")]
+pub mod cpal;
+#[cfg(feature = "dasp")]
+pub mod dasp;
+#[cfg(feature = "alloc")]
+pub mod detector;
+pub mod envelope;
+#[cfg(feature = "alloc")]
+pub mod episode;
+pub mod estimate;
+#[cfg(feature = "alloc")]
+pub mod evaluate;
+mod goertzel;
+mod goertzel_const;
+#[cfg(feature = "alloc")]
+pub mod harmonics;
+#[cfg(feature = "alloc")]
+pub mod history;
+pub mod fm;
+#[cfg(feature = "alloc")]
+pub mod fsk;
+pub mod generator;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod math;
+#[cfg(feature = "alloc")]
+pub mod multires;
+#[cfg(feature = "alloc")]
+pub mod octave;
+#[cfg(feature = "alloc")]
+pub mod presets;
+#[cfg(feature = "alloc")]
+mod pipeline;
+pub mod precision;
+pub mod report;
+#[cfg(feature = "alloc")]
+pub mod resample;
+#[cfg(feature = "rodio")]
+pub mod rodio;
+#[cfg(feature = "alloc")]
+pub mod schedule;
+#[cfg(feature = "alloc")]
+pub mod sequence;
+#[cfg(feature = "alloc")]
+pub mod shared;
+#[cfg(feature = "alloc")]
+pub mod sim;
+#[cfg(feature = "alloc")]
+pub mod simd;
+#[cfg(feature = "alloc")]
+pub mod tempo;
+#[cfg(feature = "alloc")]
+pub mod tonality;
+pub mod units;
+#[cfg(any(test, feature = "verify"))]
+pub mod verify;
+#[cfg(feature = "alloc")]
+pub mod watchdog;
+pub mod weighting;
+
+#[cfg(feature = "alloc")]
+pub use bank::{Alignment, GoertzelBank};
+#[cfg(feature = "alloc")]
+pub use pipeline::{ChannelPipelines, Pipeline, WarmUp};
+pub use goertzel::{
+  FilterState, Goertzel, Normalization, Reading, Snapshot, Window, DEFAULT_EPSILON,
+};
+pub use goertzel_const::{coefficient, CoefficientTable, GoertzelConst, TABLE_RATES};
@@ -0,0 +1,35 @@
+//! f32 math, and the f64 cosine of the `precision` filters, routed to std when available and to
+//! libm on bare metal, shared with the decoder crates so they stay no_std too.
+
+// Which of these get used depends on the enabled features.
+#![allow(dead_code)]
+
+#[cfg(feature = "std")]
+mod imp {
+  pub fn cos(x: f32) -> f32 { x.cos() }
+  pub fn cos64(x: f64) -> f64 { x.cos() }
+  pub fn sin_cos(x: f32) -> (f32, f32) { x.sin_cos() }
+  pub fn atan2(y: f32, x: f32) -> f32 { y.atan2(x) }
+  pub fn log10(x: f32) -> f32 { x.log10() }
+  pub fn powf(x: f32, y: f32) -> f32 { x.powf(y) }
+  pub fn exp(x: f32) -> f32 { x.exp() }
+  pub fn sqrt(x: f32) -> f32 { x.sqrt() }
+  pub fn round(x: f32) -> f32 { x.round() }
+  pub fn ceil(x: f32) -> f32 { x.ceil() }
+}
+
+#[cfg(not(feature = "std"))]
+mod imp {
+  pub fn cos(x: f32) -> f32 { libm::cosf(x) }
+  pub fn cos64(x: f64) -> f64 { libm::cos(x) }
+  pub fn sin_cos(x: f32) -> (f32, f32) { libm::sincosf(x) }
+  pub fn atan2(y: f32, x: f32) -> f32 { libm::atan2f(y, x) }
+  pub fn log10(x: f32) -> f32 { libm::log10f(x) }
+  pub fn powf(x: f32, y: f32) -> f32 { libm::powf(x, y) }
+  pub fn exp(x: f32) -> f32 { libm::expf(x) }
+  pub fn sqrt(x: f32) -> f32 { libm::sqrtf(x) }
+  pub fn round(x: f32) -> f32 { libm::roundf(x) }
+  pub fn ceil(x: f32) -> f32 { libm::ceilf(x) }
+}
+
+pub use imp::*;
@@ -0,0 +1,190 @@
+//! Two-stage tone detection: short blocks to notice a tone quickly, a long block to make sure
+//! of it.
+//!
+//! A single block size is a compromise: short blocks react fast but let neighbouring tones and
+//! noise bursts through, long blocks are selective but late. `TwoStageDetector` flags a
+//! candidate on the first short block above the threshold, then confirms its level and
+//! frequency over one long block before reporting it, timed from the short block's onset.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use crate::detector::{Detector, Event, EventKind, Time};
+use crate::estimate::FrequencyEstimator;
+use crate::{Goertzel, Window};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+  Idle,
+  /// The coarse pass saw the tone from `onset` on.
+  Candidate { onset: Time },
+  On,
+  /// The fine pass turned the candidate down; wait for the coarse pass to lose it.
+  Rejected,
+}
+
+/// See the module documentation.
+///
+/// Both passes use `threshold`. A tone is turned off as soon as a short block falls below it.
+#[derive(Debug)]
+pub struct TwoStageDetector {
+  coarse: Goertzel,
+  fine: Goertzel,
+  sample_rate: f32,
+  threshold: f32,
+  /// Largest offset, in Hz, of the measured frequency from the nominal one.
+  tolerance: f32,
+  state: State,
+  /// Short block being filled, and the time of its first sample.
+  block: Vec<f32>,
+  block_start: Option<Time>,
+  /// The last `fine.block_size()` samples.
+  history: VecDeque<f32>,
+}
+
+impl TwoStageDetector {
+  /// 10 ms coarse and 80 ms fine blocks.
+  pub fn new(freq: f32, sample_rate: f32, threshold: f32) -> Self {
+    let ms = |ms: f32| (sample_rate * ms / 1000.) as usize;
+    Self::with_blocks(freq, sample_rate, threshold, ms(10.), ms(80.))
+  }
+
+  /// `coarse` and `fine` are block sizes in samples; `fine` should be several times `coarse`.
+  pub fn with_blocks(
+    freq: f32,
+    sample_rate: f32,
+    threshold: f32,
+    coarse: usize,
+    fine: usize,
+  ) -> Self {
+    let (coarse, fine) = (coarse.max(1), fine.max(2));
+    Self {
+      coarse: Goertzel::with_block_size(freq, sample_rate, coarse),
+      fine: Goertzel::with_block_size(freq, sample_rate, fine).with_window(Window::Hann),
+      sample_rate,
+      threshold,
+      tolerance: Window::Hann.half_power_bins() * sample_rate / fine as f32,
+      state: State::Idle,
+      block: Vec::with_capacity(coarse),
+      block_start: None,
+      history: VecDeque::with_capacity(fine),
+    }
+  }
+
+  /// Accepts tones measured up to `hz` off the nominal frequency; by default the fine block's
+  /// half-power width.
+  pub fn with_tolerance(mut self, hz: f32) -> Self {
+    self.tolerance = hz;
+    self
+  }
+
+  /// Level and frequency of the last fine block, if the tone passes both.
+  fn confirm(&mut self) -> Option<f32> {
+    let n = self.fine.block_size();
+    let window = self.history.make_contiguous();
+    let power = self.fine.block_power(window);
+    // The phase advance between the two halves measures the frequency.
+    let mut estimator = FrequencyEstimator::new(self.fine.freq(), self.sample_rate);
+    estimator.push_block(&window[..n / 2]);
+    let freq = estimator.push_block(&window[n / 2..n / 2 * 2])?;
+    let on_target = (freq - self.fine.freq()).abs() <= self.tolerance;
+    (power >= self.threshold && on_target).then_some(power)
+  }
+
+  fn coarse_block(&mut self, t: Time) -> Option<Event> {
+    let present = self.coarse.block_power(&self.block) >= self.threshold;
+    let freq = self.coarse.freq();
+    let end = t.offset(self.block.len());
+    match self.state {
+      State::Idle | State::Rejected if !present => self.state = State::Idle,
+      State::Idle => self.state = State::Candidate { onset: t },
+      State::Rejected => {}
+      State::Candidate { .. } if !present => self.state = State::Idle,
+      State::Candidate { onset } => {
+        if end.sample - onset.sample >= self.fine.block_size() as u64 {
+          return match self.confirm() {
+            Some(power) => {
+              self.state = State::On;
+              Some(Event::new(onset, EventKind::ToneOn { freq, power }))
+            }
+            None => {
+              self.state = State::Rejected;
+              None
+            }
+          };
+        }
+      }
+      State::On if !present => {
+        self.state = State::Idle;
+        return Some(Event::new(t, EventKind::ToneOff { freq }));
+      }
+      State::On => {}
+    }
+    None
+  }
+}
+
+impl Detector for TwoStageDetector {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let mut events = Vec::new();
+    for (i, &sample) in block.iter().enumerate() {
+      if self.block.is_empty() {
+        self.block_start = Some(t.offset(i));
+      }
+      self.block.push(sample);
+      if self.history.len() == self.fine.block_size() {
+        self.history.pop_front();
+      }
+      self.history.push_back(sample);
+      if self.block.len() == self.coarse.block_size() {
+        let start = self.block_start.unwrap_or(t);
+        events.extend(self.coarse_block(start));
+        self.block.clear();
+      }
+    }
+    events
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use core::f32::consts::PI;
+
+  fn run(d: &mut TwoStageDetector, signal: &[f32]) -> Vec<Event> {
+    signal
+      .chunks(160)
+      .enumerate()
+      .flat_map(|(i, b)| d.process_block(b, Time { sample: 160 * i as u64, sample_rate: 8000. }))
+      .collect()
+  }
+
+  fn tone(freq: f32, on: core::ops::Range<usize>, len: usize) -> Vec<f32> {
+    (0..len)
+      .map(|t| if on.contains(&t) { (2.*PI*freq*(t as f32)/8000.).sin() } else { 0. })
+      .collect()
+  }
+
+  #[test]
+  fn confirms_on_the_long_block_and_times_from_the_short_one() {
+    let mut d = TwoStageDetector::new(1000., 8000., 0.5);
+    let events = run(&mut d, &tone(1000., 1000..4000, 6000));
+    let times: Vec<(u64, bool)> =
+      events.iter().map(|e| (e.time.sample, matches!(e.kind, EventKind::ToneOn { .. }))).collect();
+    // Onset within one short block, release at the first quiet one.
+    assert_eq!(times, vec![(1040, true), (4000, false)]);
+  }
+
+  #[test]
+  fn rejects_bursts_and_neighbouring_tones() {
+    // A 40 ms burst fades before the fine block is full.
+    let mut d = TwoStageDetector::new(1000., 8000., 0.5);
+    assert!(run(&mut d, &tone(1000., 1000..1320, 4000)).is_empty());
+    // 1040 Hz passes the 100 Hz wide coarse filter but not the fine one.
+    let mut d = TwoStageDetector::new(1000., 8000., 0.5);
+    let short = Goertzel::with_block_size(1000., 8000., 80);
+    assert!(short.block_power(&tone(1040., 0..80, 80)) > 0.5);
+    assert!(run(&mut d, &tone(1040., 0..4000, 4000)).is_empty());
+  }
+}
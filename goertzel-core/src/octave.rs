@@ -0,0 +1,187 @@
+//! 1/3-octave band levels (IEC 61260 base-ten bands from 25 Hz to 20 kHz), for quick room and
+//! noise surveys.
+//!
+//! Each band is covered by a few adjacent Hann-windowed Goertzel bins spanning its edges; their
+//! powers add up to the band's, whether it holds a tone or noise.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use crate::detector::{Detector, Event, EventKind, Time};
+use crate::{math, Goertzel, Normalization, Window};
+
+/// Nominal centre frequencies of the bands, in Hz, as they are labeled.
+pub const THIRD_OCTAVE_BANDS: [f32; 30] = [
+  25., 31.5, 40., 50., 63., 80., 100., 125., 160., 200., 250., 315., 400., 500., 630., 800.,
+  1000., 1250., 1600., 2000., 2500., 3150., 4000., 5000., 6300., 8000., 10000., 12500., 16000.,
+  20000.,
+];
+
+/// Bins per band. More make the band edges sharper and the blocks longer: 0.7 s for the 25 Hz
+/// band at 48 kHz with four.
+const BINS: usize = 4;
+
+/// Sum of the powers a Hann-windowed tone leaves in bins one bin apart, relative to its own.
+const HANN_SPREAD: f32 = 1.5;
+
+/// Reports `BandLevel` of every band below the Nyquist frequency once per interval, in dB
+/// relative to a full-scale sine: the mean power of the band's blocks completed during it.
+///
+/// High bands average many short blocks, low ones only a few long ones, so their readings of
+/// noise vary more. A band whose block is longer than the interval is skipped by the reports
+/// it has no block for.
+#[derive(Debug)]
+pub struct ThirdOctaveAnalyzer {
+  bands: Vec<Band>,
+  history: VecDeque<f32>,
+  longest: usize,
+  /// Samples between reports, and until the next one.
+  interval: usize,
+  due: usize,
+}
+
+#[derive(Debug)]
+struct Band {
+  nominal: f32,
+  bins: Vec<Goertzel>,
+  /// Samples until the next block is complete.
+  due: usize,
+  /// Sum of the mean squares of the blocks since the last report, and their number.
+  sum: f32,
+  blocks: usize,
+}
+
+impl ThirdOctaveAnalyzer {
+  /// Reports once a second.
+  pub fn new(sample_rate: f32) -> Self {
+    let bands: Vec<Band> = THIRD_OCTAVE_BANDS
+      .iter()
+      .enumerate()
+      .filter_map(|(i, &nominal)| {
+        let centre = 1000. * math::powf(10., (i as f32 - 16.) / 10.);
+        let (low, high) = (centre * math::powf(10., -0.05), centre * math::powf(10., 0.05));
+        if high >= sample_rate / 2. {
+          return None;
+        }
+        let spacing = (high - low) / BINS as f32;
+        let block_size = math::round(sample_rate / spacing) as usize;
+        let bins = (0..BINS)
+          .map(|k| {
+            Goertzel::with_block_size(low + (k as f32 + 0.5) * spacing, sample_rate, block_size)
+              .with_window(Window::Hann)
+              .with_normalization(Normalization::PerSampleCount)
+          })
+          .collect();
+        Some(Band { nominal, bins, due: block_size, sum: 0., blocks: 0 })
+      })
+      .collect();
+    let longest = bands.iter().map(|b| b.bins[0].block_size()).max().unwrap_or(1);
+    let interval = sample_rate as usize;
+    Self { bands, history: VecDeque::with_capacity(longest), longest, interval, due: interval }
+  }
+
+  /// Time between reports, in samples.
+  pub fn with_interval(mut self, samples: usize) -> Self {
+    self.interval = samples.max(1);
+    self.due = self.interval;
+    self
+  }
+
+  /// Nominal centres of the bands reported.
+  pub fn bands(&self) -> Vec<f32> {
+    self.bands.iter().map(|b| b.nominal).collect()
+  }
+}
+
+impl Detector for ThirdOctaveAnalyzer {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let mut events = Vec::new();
+    for (i, &sample) in block.iter().enumerate() {
+      if self.history.len() == self.longest {
+        self.history.pop_front();
+      }
+      self.history.push_back(sample);
+      for band in &mut self.bands {
+        band.due -= 1;
+        if band.due == 0 {
+          let block_size = band.bins[0].block_size();
+          let history = self.history.make_contiguous();
+          let latest = &history[history.len() - block_size..];
+          band.sum += band.bins.iter().map(|g| g.block_power(latest)).sum::<f32>() / HANN_SPREAD;
+          band.blocks += 1;
+          band.due = block_size;
+        }
+      }
+      self.due -= 1;
+      if self.due > 0 {
+        continue;
+      }
+      self.due = self.interval;
+      for band in self.bands.iter_mut().filter(|b| b.blocks > 0) {
+        let mean_square = band.sum / band.blocks as f32;
+        // A full-scale sine has a mean square of 1/2.
+        let dbfs = 10. * math::log10(2. * mean_square + 1e-20);
+        let kind = EventKind::BandLevel { freq: band.nominal, dbfs };
+        events.push(Event::new(t.offset(i + 1), kind));
+        (band.sum, band.blocks) = (0., 0);
+      }
+    }
+    events
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::f32::consts::PI;
+
+  fn levels(analyzer: &mut ThirdOctaveAnalyzer, signal: &[f32]) -> Vec<(f32, f32)> {
+    let events = analyzer.process_block(signal, Time { sample: 0, sample_rate: 48000. });
+    events
+      .into_iter()
+      .map(|e| match e.kind {
+        EventKind::BandLevel { freq, dbfs } => (freq, dbfs),
+        _ => unreachable!(),
+      })
+      .collect()
+  }
+
+  #[test]
+  fn tone_reads_its_level_in_its_band_only() {
+    let mut analyzer = ThirdOctaveAnalyzer::new(48000.);
+    assert_eq!(analyzer.bands().len(), 30);
+    let tone: Vec<f32> =
+      (0..48000).map(|t| 0.1 * (2. * PI * 1000. * t as f32 / 48000.).sin()).collect();
+    let levels = levels(&mut analyzer, &tone);
+    assert_eq!(levels.len(), 30);
+    for (band, dbfs) in levels {
+      match band as u32 {
+        1000 => assert!((dbfs + 20.).abs() < 0.5, "{}", dbfs),
+        800 | 1250 => assert!(dbfs < -40., "{} Hz: {}", band, dbfs),
+        _ => assert!(dbfs < -60., "{} Hz: {}", band, dbfs),
+      }
+    }
+  }
+
+  #[test]
+  fn band_levels_of_noise_add_up_to_its_level() {
+    // White noise of mean square 1/12 from a linear congruential generator.
+    let mut state = 1u32;
+    let noise: Vec<f32> = (0..48000)
+      .map(|_| {
+        state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+        state as f32 / u32::MAX as f32 - 0.5
+      })
+      .collect();
+    let mut analyzer = ThirdOctaveAnalyzer::new(48000.);
+    let levels = levels(&mut analyzer, &noise);
+    // Flat noise puts about a tenth more power in each band than in the one below.
+    let (low, high) = (levels[10].1, levels[20].1);
+    assert!((high - low - 10.).abs() < 1.5, "{} {}", low, high);
+    // The bands cover 22 Hz to 22 kHz, nearly all of the noise.
+    let total: f32 = levels.iter().map(|(_, dbfs)| math::powf(10., dbfs / 10.)).sum();
+    let dbfs = 10. * math::log10(total);
+    assert!((dbfs - 10. * math::log10(2. / 12.)).abs() < 0.5, "{}", dbfs);
+  }
+}
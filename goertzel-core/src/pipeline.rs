@@ -0,0 +1,312 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::detector::{Detector, Event, Time};
+use crate::math;
+use crate::tonality::Tonality;
+use crate::units::{SampleRate, Samples};
+
+/// How long the pipeline ignores its input after starting, so filter start-up transients and
+/// device power-on pops are not reported as detections.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WarmUp {
+  /// Skip the first blocks.
+  Blocks(usize),
+  /// Skip blocks until the energy of one block is within `tolerance_db` of the previous one, or
+  /// at most `max_blocks`.
+  Settled { tolerance_db: f32, max_blocks: usize },
+}
+
+/// Cuts the input into fixed-size blocks and runs every registered detector on each of them.
+pub struct Pipeline {
+  detectors: Vec<Box<dyn Detector + Send>>,
+  sample_rate: f32,
+  block: Vec<f32>,
+  block_size: usize,
+  /// Stream position of `block[0]`.
+  position: u64,
+  warm_up: Option<WarmUp>,
+  /// Blocks seen during the warm-up, and the energy of the last one.
+  warm_up_blocks: usize,
+  last_energy: Option<f32>,
+  /// Blocks flatter than the limit are not passed on.
+  tonality_gate: Option<(Tonality, f32)>,
+  energy_gate: Option<EnergyGate>,
+  /// Blocks seen, and how many of them the detectors ran on.
+  blocks: u64,
+  active_blocks: u64,
+}
+
+/// Wakes the detectors only when a block's mean-square level reaches `floor`, and keeps them
+/// running for `hold` quiet blocks afterwards so they can report the tone ending.
+#[derive(Debug, Clone, Copy)]
+struct EnergyGate {
+  floor: f32,
+  hold: usize,
+  /// Quiet blocks left before the detectors are put to sleep.
+  remaining: usize,
+}
+
+impl Pipeline {
+  pub fn new(sample_rate: impl Into<SampleRate>, block_size: impl Into<Samples>) -> Self {
+    let (sample_rate, block_size) = (sample_rate.into().0, block_size.into().get());
+    Self {
+      detectors: Vec::new(),
+      sample_rate,
+      block: Vec::with_capacity(block_size),
+      block_size: block_size.max(1),
+      position: 0,
+      warm_up: None,
+      warm_up_blocks: 0,
+      last_energy: None,
+      tonality_gate: None,
+      energy_gate: None,
+      blocks: 0,
+      active_blocks: 0,
+    }
+  }
+
+  /// Only runs the detectors on blocks whose spectral flatness, over a 16-bin bank, is at most
+  /// `max_flatness`, so broadband noise never reaches them. A tone already on when noise starts
+  /// stays on for the detectors until a tonal block shows otherwise.
+  pub fn set_tonality_gate(&mut self, max_flatness: f32) {
+    self.tonality_gate = Some((Tonality::new(self.sample_rate, 16), max_flatness));
+  }
+
+  /// Duty-cycled listening for battery-powered monitors: only a block's mean square is computed
+  /// while it stays below `floor_dbfs` (dB relative to a full-scale sine), and the detectors
+  /// run from the first block above it until `hold` blocks after the last one. Tones quieter
+  /// than the floor are never seen.
+  pub fn set_energy_gate(&mut self, floor_dbfs: f32, hold: usize) {
+    let floor = 0.5 * math::powf(10., floor_dbfs / 10.);
+    self.energy_gate = Some(EnergyGate { floor, hold, remaining: 0 });
+  }
+
+  /// Share of the blocks so far the detectors ran on, 1 before the first block.
+  pub fn duty_cycle(&self) -> f32 {
+    match self.blocks {
+      0 => 1.,
+      n => self.active_blocks as f32 / n as f32,
+    }
+  }
+
+  /// Whether the energy gate keeps the detectors asleep for the current block.
+  fn asleep(&mut self) -> bool {
+    let Some(gate) = &mut self.energy_gate else { return false };
+    let mean_square = self.block.iter().map(|x| x * x).sum::<f32>() / self.block.len() as f32;
+    if mean_square >= gate.floor {
+      gate.remaining = gate.hold + 1;
+    }
+    match gate.remaining {
+      0 => true,
+      _ => {
+        gate.remaining -= 1;
+        false
+      }
+    }
+  }
+
+  /// Detectors are not run (and so report nothing) until the warm-up is over; the stream
+  /// position still advances.
+  pub fn set_warm_up(&mut self, warm_up: WarmUp) {
+    self.warm_up = Some(warm_up);
+  }
+
+  pub fn with_warm_up(mut self, warm_up: WarmUp) -> Self {
+    self.set_warm_up(warm_up);
+    self
+  }
+
+  /// Whether the current block still belongs to the warm-up.
+  fn warming_up(&mut self) -> bool {
+    let blocks = self.warm_up_blocks;
+    let done = match self.warm_up {
+      None => return false,
+      Some(WarmUp::Blocks(n)) => blocks >= n,
+      Some(WarmUp::Settled { tolerance_db, max_blocks }) => {
+        let energy = self.block.iter().map(|x| x * x).sum::<f32>();
+        let last = self.last_energy.replace(energy);
+        blocks >= max_blocks
+          || match last {
+            Some(last) if last == 0. && energy == 0. => true,
+            Some(last) => (10. * math::log10(energy / last)).abs() <= tolerance_db,
+            None => false,
+          }
+      }
+    };
+    if done {
+      self.warm_up = None;
+      return false;
+    }
+    self.warm_up_blocks += 1;
+    true
+  }
+
+  pub fn add(&mut self, detector: Box<dyn Detector + Send>) {
+    self.detectors.push(detector);
+  }
+
+  pub fn with(mut self, detector: Box<dyn Detector + Send>) -> Self {
+    self.add(detector);
+    self
+  }
+
+  pub fn len(&self) -> usize {
+    self.detectors.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.detectors.is_empty()
+  }
+
+  pub fn block_size(&self) -> usize {
+    self.block_size
+  }
+
+  /// Makes the next sample pushed the `sample`th of the stream, e.g. when resuming an analysis
+  /// part way through a file. A partly filled block is discarded.
+  pub fn set_position(&mut self, sample: u64) {
+    self.position = sample;
+    self.block.clear();
+  }
+
+  /// Feeds samples and returns the events of every block completed by them.
+  pub fn push(&mut self, samples: &[f32]) -> Vec<Event> {
+    let mut events = Vec::new();
+    for &sample in samples {
+      self.block.push(sample);
+      if self.block.len() == self.block_size {
+        let t = Time { sample: self.position, sample_rate: self.sample_rate };
+        let gated = match &self.tonality_gate {
+          Some((tonality, max)) => tonality.flatness(&self.block) > *max,
+          None => false,
+        };
+        self.blocks += 1;
+        let warming_up = self.warming_up();
+        if !self.asleep() && !warming_up && !gated {
+          self.active_blocks += 1;
+          for detector in &mut self.detectors {
+            events.extend(detector.process_block(&self.block, t));
+          }
+        }
+        self.position += self.block_size as u64;
+        self.block.clear();
+      }
+    }
+    events
+  }
+
+  /// Events the detectors still hold back, at the end of the input. A partly filled block is
+  /// not analyzed.
+  pub fn finish(&mut self) -> Vec<Event> {
+    self.detectors.iter_mut().flat_map(|d| d.finish()).collect()
+  }
+}
+
+/// One pipeline per channel of interleaved input, such as a raw cpal buffer; the pipelines are
+/// built by `make` as channels first appear.
+pub struct ChannelPipelines {
+  make: Box<dyn FnMut() -> Pipeline + Send>,
+  pipelines: Vec<Pipeline>,
+  /// Samples of the channel being processed.
+  scratch: Vec<f32>,
+}
+
+impl ChannelPipelines {
+  pub fn new(make: impl FnMut() -> Pipeline + Send + 'static) -> Self {
+    Self { make: Box::new(make), pipelines: Vec::new(), scratch: Vec::new() }
+  }
+
+  /// Splits `interleaved` frames of `channels` samples and pushes each channel to its own
+  /// pipeline, returning the events of every channel in channel order. A trailing partial frame
+  /// is dropped.
+  pub fn process_frames(&mut self, interleaved: &[f32], channels: usize) -> Vec<Vec<Event>> {
+    let channels = channels.max(1);
+    while self.pipelines.len() < channels {
+      self.pipelines.push((self.make)());
+    }
+    let frames = interleaved.len() / channels;
+    let mut events = Vec::with_capacity(channels);
+    for (channel, pipeline) in self.pipelines[..channels].iter_mut().enumerate() {
+      self.scratch.clear();
+      self.scratch.extend(interleaved.iter().skip(channel).step_by(channels).take(frames));
+      events.push(pipeline.push(&self.scratch));
+    }
+    events
+  }
+
+  pub fn channels(&self) -> &[Pipeline] {
+    &self.pipelines
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::detector::EventKind;
+
+  struct Counter(usize);
+
+  impl Detector for Counter {
+    fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+      self.0 += 1;
+      vec![Event::new(t, EventKind::Custom(format!("{} samples", block.len())))]
+    }
+  }
+
+  #[test]
+  fn runs_detectors_on_whole_blocks() {
+    let mut pipeline = Pipeline::new(8000., 100).with(Box::new(Counter(0)));
+    assert!(pipeline.push(&[0.; 150]).len() == 1);
+    let events = pipeline.push(&[0.; 150]);
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[1].time.sample, 200);
+    assert_eq!(events[1].kind, EventKind::Custom("100 samples".into()));
+
+    pipeline.push(&[0.; 50]);
+    pipeline.set_position(10_000);
+    assert_eq!(pipeline.push(&[0.; 100])[0].time.sample, 10_000);
+  }
+
+  #[test]
+  fn splits_frames_into_a_pipeline_per_channel() {
+    let mut channels =
+      ChannelPipelines::new(|| Pipeline::new(8000., 2).with(Box::new(Counter(0))));
+    // Three stereo frames and half a frame: one block on each channel.
+    let events = channels.process_frames(&[0., 1., 0., 1., 0., 1., 0.], 2);
+    assert_eq!(events.len(), 2);
+    assert!(events.iter().all(|e| e.len() == 1 && e[0].time.sample == 0));
+    let events = channels.process_frames(&[0., 1.], 2);
+    assert_eq!(events.iter().map(Vec::len).collect::<Vec<_>>(), vec![1, 1]);
+    assert_eq!(events[1][0].time.sample, 2);
+    assert_eq!(channels.channels().len(), 2);
+  }
+
+  #[test]
+  fn energy_gate_sleeps_through_quiet_blocks() {
+    let mut pipeline = Pipeline::new(8000., 100).with(Box::new(Counter(0)));
+    pipeline.set_energy_gate(-40., 2);
+    let mut signal = vec![0.001; 1000];
+    signal[300..400].fill(0.5);
+    let events = pipeline.push(&signal);
+    // The loud block and the two held after it.
+    let times: Vec<u64> = events.iter().map(|e| e.time.sample).collect();
+    assert_eq!(times, vec![300, 400, 500]);
+    assert!((pipeline.duty_cycle() - 0.3).abs() < 1e-6);
+  }
+
+  #[test]
+  fn warm_up_skips_until_energy_settles() {
+    let mut pipeline = Pipeline::new(8000., 100)
+      .with(Box::new(Counter(0)))
+      .with_warm_up(WarmUp::Settled { tolerance_db: 1., max_blocks: 10 });
+    // A decaying pop, then steady noise-free silence.
+    let pop: Vec<f32> = (0..300).map(|i| math::powf(0.98, i as f32)).collect();
+    assert!(pipeline.push(&pop).is_empty());
+    let events = pipeline.push(&[0.; 200]);
+    // The first silent block still differs from the pop's tail, the second one settles.
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].time.sample, 400);
+  }
+}
@@ -0,0 +1,108 @@
+//! Block filters at other precisions than the f32 of [`Goertzel`](crate::Goertzel): f64 for
+//! very long blocks, where f32 recursion loses the tone in rounding noise, and fixed point for
+//! microcontrollers without a floating-point unit.
+//!
+//! Both measure a block the way `Goertzel::block_power` does with the default normalization and
+//! a rectangular window: the share of the block's energy at the filter frequency.
+
+use core::f64::consts::PI;
+
+use crate::{math, DEFAULT_EPSILON};
+
+/// Block Goertzel filter computed in f64 throughout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GoertzelF64 {
+  freq: f64,
+  coeff: f64,
+}
+
+impl GoertzelF64 {
+  pub fn new(freq: f64, samplef: f64) -> Self {
+    Self { freq, coeff: 2. * math::cos64(2. * PI * freq / samplef) }
+  }
+
+  pub fn freq(&self) -> f64 {
+    self.freq
+  }
+
+  /// Share of `block`'s energy at the filter frequency: close to 1 for a pure tone.
+  pub fn block_power(&self, block: &[f32]) -> f64 {
+    let (mut s_prev, mut s_prev2, mut totalpower) = (0f64, 0f64, 0f64);
+    for &sample in block {
+      let sample = sample as f64;
+      let s = sample + self.coeff * s_prev - s_prev2;
+      s_prev2 = s_prev;
+      s_prev = s;
+      totalpower += sample * sample;
+    }
+    let power = s_prev2 * s_prev2 + s_prev * s_prev - self.coeff * s_prev * s_prev2;
+    let tone = 2. * power.max(0.) / block.len().max(1) as f64;
+    tone / (totalpower + DEFAULT_EPSILON as f64)
+  }
+}
+
+/// Fractional bits of the fixed-point coefficient.
+const COEFF_BITS: u32 = 14;
+
+/// Block Goertzel filter on 16-bit samples in integer arithmetic only: a Q14 coefficient and
+/// 64-bit state. Only the final ratio is a float, once per block.
+///
+/// The state of a full-scale tone grows by about 16 k per sample and the power is its square,
+/// so blocks must stay under about 100 000 samples; at telephony block sizes there is room to
+/// spare.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GoertzelFixed {
+  freq: f32,
+  coeff: i32,
+}
+
+impl GoertzelFixed {
+  pub fn new(freq: f32, samplef: f32) -> Self {
+    let coeff = 2. * math::cos(2. * core::f32::consts::PI * freq / samplef);
+    Self { freq, coeff: math::round(coeff * (1 << COEFF_BITS) as f32) as i32 }
+  }
+
+  pub fn freq(&self) -> f32 {
+    self.freq
+  }
+
+  /// Share of `block`'s energy at the filter frequency: close to 1 for a pure tone.
+  pub fn block_power(&self, block: &[i16]) -> f32 {
+    let coeff = self.coeff as i64;
+    let (mut s_prev, mut s_prev2, mut totalpower) = (0i64, 0i64, 0i64);
+    for &sample in block {
+      let sample = sample as i64;
+      let s = sample + ((coeff * s_prev) >> COEFF_BITS) - s_prev2;
+      s_prev2 = s_prev;
+      s_prev = s;
+      totalpower += sample * sample;
+    }
+    let power = s_prev2 * s_prev2 + s_prev * s_prev - ((coeff * s_prev) >> COEFF_BITS) * s_prev2;
+    let tone = 2. * power.max(0) as f32 / block.len().max(1) as f32;
+    tone / (totalpower as f32 + DEFAULT_EPSILON)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Goertzel;
+
+  #[test]
+  fn precisions_agree_with_the_f32_filter() {
+    let tone = |f: f32| -> Vec<f32> {
+      (0..205).map(|t| 0.5 * math::sin_cos(2. * core::f32::consts::PI * f * t as f32 / 8000.).0)
+        .collect()
+    };
+    for (freq, signal) in [(697., 697.), (697., 770.), (1633., 1633.)] {
+      let block = tone(signal);
+      let expected = Goertzel::new(freq, 8000.).block_power(&block);
+      let double = GoertzelF64::new(freq as f64, 8000.).block_power(&block);
+      assert!((double as f32 - expected).abs() < 1e-4, "{} Hz: {}", freq, double);
+      let fixed: Vec<i16> = block.iter().map(|x| (x * 32767.) as i16).collect();
+      let fixed = GoertzelFixed::new(freq, 8000.).block_power(&fixed);
+      assert!((fixed - expected).abs() < 1e-2, "{} Hz: {}", freq, fixed);
+    }
+  }
+}
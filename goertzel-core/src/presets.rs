@@ -0,0 +1,323 @@
+//! Ready-made frequency banks for common signalling systems. The DTMF detector preset is
+//! `goertzel_dtmf::DtmfDetector::standard`.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::cadence::{BurstTemplate, Bursts, Cadence, CadenceTemplate};
+use crate::detector::{DualToneDetector, DualToneOptions, ToneDetector};
+use crate::envelope::EnvelopeDetector;
+use crate::fsk::{FskDetector, MODEM_CARRIERS};
+use crate::sequence::{SequenceMatcher, Symbol};
+use crate::{math, CoefficientTable, GoertzelBank};
+
+/// The 50 EIA/TIA-603 CTCSS tones, in Hz.
+pub const CTCSS_TONES: [f32; 50] = [
+  67.0, 69.3, 71.9, 74.4, 77.0, 79.7, 82.5, 85.4, 88.5, 91.5,
+  94.8, 97.4, 100.0, 103.5, 107.2, 110.9, 114.8, 118.8, 123.0, 127.3,
+  131.8, 136.5, 141.3, 146.2, 150.0, 151.4, 156.7, 159.8, 162.2, 165.5,
+  167.9, 171.3, 173.8, 177.3, 179.9, 183.5, 186.2, 189.9, 192.8, 196.6,
+  199.5, 203.5, 206.5, 210.7, 218.1, 225.7, 229.1, 233.6, 241.8, 250.3,
+];
+
+/// DCS squelch tail elimination ("turn-off") tone.
+pub const DCS_TURN_OFF: f32 = 134.4;
+
+// Coefficients of the fixed banks at the common sample rates, so building them there takes no
+// `cos()`.
+static CTCSS_TABLE: CoefficientTable<50> = CoefficientTable::new(&CTCSS_TONES);
+static DCS_TABLE: CoefficientTable<1> = CoefficientTable::new(&[DCS_TURN_OFF]);
+static CALL_PROGRESS_TABLE: CoefficientTable<4> = CoefficientTable::new(&CALL_PROGRESS);
+static EAS_TABLE: CoefficientTable<3> = CoefficientTable::new(&EAS);
+static TEST_TONE_TABLE: CoefficientTable<1> = CoefficientTable::new(&[TEST_TONE]);
+
+/// North American precise call-progress tones: dial = 350+440, ringback = 440+480,
+/// busy/reorder = 480+620.
+pub const CALL_PROGRESS: [f32; 4] = [350., 440., 480., 620.];
+
+/// EAS/NWR attention signal pair, plus the 1050 Hz NOAA weather alert tone.
+pub const EAS: [f32; 3] = [853., 960., 1050.];
+
+pub const TEST_TONE: f32 = 1000.;
+
+/// ACTS coin deposit tone of North American payphones, a pair sounded in short beeps.
+pub const COIN_TONE: [f32; 2] = [1700., 2200.];
+
+/// Typical piezo horn pitch of a smoke alarm; actual units sit anywhere from about 2.9 to 3.5 kHz.
+pub const SMOKE_ALARM: f32 = 3100.;
+
+/// Common appliance piezo buzzer pitches (microwaves, washing machines, dishwashers), in Hz.
+pub const APPLIANCE_BEEPS: [f32; 3] = [2000., 2400., 4000.];
+
+/// FM stereo pilot tone, carried in the multiplex signal of a stereo broadcast.
+pub const FM_PILOT: f32 = 19000.;
+
+/// Lowest sample rate the pilot presets accept: 19 kHz needs more than 38 kHz, and a margin
+/// below Nyquist for the filter's main lobe.
+pub const FM_PILOT_MIN_RATE: f32 = 40000.;
+
+/// Share of the block's energy the pilot must hold to count as present. A 9% injection still
+/// holds several percent of a multiplex signal under full-scale program.
+pub const FM_PILOT_THRESHOLD: f32 = 0.01;
+
+/// A preset's tones do not fit below the Nyquist frequency of the sample rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateTooLow {
+  pub sample_rate: f32,
+  pub min: f32,
+}
+
+impl fmt::Display for RateTooLow {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "a sample rate of {} Hz is too low, this needs {} Hz", self.sample_rate, self.min)
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RateTooLow {}
+
+/// Block length the beep detectors are meant to be fed, in seconds. Short blocks keep the
+/// filters wide (about 40 Hz either side at half power) for units slightly off their pitch.
+pub const BEEP_BLOCK: f32 = 0.01;
+
+/// Block size covering `seconds` of input.
+fn block_for(sample_rate: f32, seconds: f32) -> usize {
+  math::round(sample_rate * seconds) as usize
+}
+
+/// CTCSS tones are under 3 Hz apart at the low end, so this uses half-second blocks.
+pub fn ctcss(sample_rate: f32) -> GoertzelBank {
+  GoertzelBank::from_table(&CTCSS_TONES, &CTCSS_TABLE, sample_rate, block_for(sample_rate, 0.5))
+}
+
+pub fn dcs_turn_off(sample_rate: f32) -> GoertzelBank {
+  GoertzelBank::from_table(&[DCS_TURN_OFF], &DCS_TABLE, sample_rate, block_for(sample_rate, 0.1))
+}
+
+pub fn call_progress(sample_rate: f32) -> GoertzelBank {
+  let block_size = block_for(sample_rate, 0.05);
+  GoertzelBank::from_table(&CALL_PROGRESS, &CALL_PROGRESS_TABLE, sample_rate, block_size)
+}
+
+/// Cadences of the North American call-progress tones.
+pub fn call_progress_cadences() -> Vec<CadenceTemplate> {
+  alloc::vec![
+    CadenceTemplate::new("busy", 0.5, 0.5),
+    CadenceTemplate::new("reorder", 0.25, 0.25),
+    CadenceTemplate::new("ringback", 2., 4.),
+  ]
+}
+
+pub fn eas(sample_rate: f32) -> GoertzelBank {
+  GoertzelBank::from_table(&EAS, &EAS_TABLE, sample_rate, block_for(sample_rate, 0.05))
+}
+
+/// ISO 8201 / NFPA 72 "T3" evacuation pattern: three 0.5 s beeps 0.5 s apart, then 1.5 s of
+/// silence. Reports `Match("smoke alarm")` on the third beep of every group.
+///
+/// `freq` is [`SMOKE_ALARM`], or the measured pitch of a unit further off than the filter covers.
+pub fn smoke_alarm(freq: f32, sample_rate: f32) -> Cadence<ToneDetector> {
+  let beep = ToneDetector::new(freq, sample_rate, 0.3);
+  Cadence::new(beep, alloc::vec![CadenceTemplate::new("smoke alarm", 0.5, 0.5)], 0.2, 2)
+}
+
+/// Beeps of the coin deposit tones: a nickel is one 66 ms beep, a dime two, a quarter five of
+/// 33 ms, each separated by a pause as long as the beeps.
+pub fn coin_tone_bursts() -> Vec<BurstTemplate> {
+  alloc::vec![
+    BurstTemplate::new("nickel", 1, 0.066),
+    BurstTemplate::new("dime", 2, 0.066),
+    BurstTemplate::new("quarter", 5, 0.033),
+  ]
+}
+
+/// Reports `Match("nickel")`, `"dime"` or `"quarter"` for each coin deposit tone, once its burst
+/// is over. The beeps are short: feed it blocks of [`BEEP_BLOCK`].
+pub fn coin_tones(sample_rate: f32) -> Bursts<DualToneDetector> {
+  let options = DualToneOptions { max_onset_skew: 0.01, ..DualToneOptions::default() };
+  let pair = DualToneDetector::new(COIN_TONE[0], COIN_TONE[1], sample_rate, options);
+  Bursts::new(pair, coin_tone_bursts(), 0.4, 0.15)
+}
+
+/// `count` beeps at `freq` within `within` seconds, e.g. the three beeps of a finished
+/// microwave at one of the [`APPLIANCE_BEEPS`] pitches.
+pub fn beeps(freq: f32, sample_rate: f32, count: usize, within: f32) -> SequenceMatcher<ToneDetector> {
+  let beep = ToneDetector::new(freq, sample_rate, 0.3);
+  SequenceMatcher::new(beep, Symbol::Tone(freq).repeat(count), within, "beeps")
+}
+
+/// Equal-tempered notes of the octave from C4 to B4, tuned to A4 = 440 Hz.
+pub fn tuner(sample_rate: f32) -> GoertzelBank {
+  let notes: Vec<f32> = (-9..3).map(|semitone| 440. * math::powf(2., semitone as f32 / 12.)).collect();
+  GoertzelBank::with_block_size(&notes, sample_rate, block_for(sample_rate, 0.2))
+}
+
+/// Reports the stereo pilot coming and going. Feed it the demodulated multiplex signal before
+/// any de-emphasis, which would take most of the pilot off.
+pub fn fm_pilot(sample_rate: f32) -> Result<ToneDetector, RateTooLow> {
+  check_pilot_rate(sample_rate)?;
+  Ok(ToneDetector::new(FM_PILOT, sample_rate, FM_PILOT_THRESHOLD).with_min_duration(0.1))
+}
+
+/// The pilot's amplitude as `Level` events, smoothed over about half a second; 1 is full scale,
+/// so a 9% injection reads about 0.09 with a discriminator scaled to the full deviation.
+pub fn fm_pilot_level(sample_rate: f32) -> Result<EnvelopeDetector, RateTooLow> {
+  check_pilot_rate(sample_rate)?;
+  Ok(EnvelopeDetector::new(FM_PILOT, sample_rate, 500., 500.))
+}
+
+fn check_pilot_rate(sample_rate: f32) -> Result<(), RateTooLow> {
+  if sample_rate < FM_PILOT_MIN_RATE {
+    return Err(RateTooLow { sample_rate, min: FM_PILOT_MIN_RATE });
+  }
+  Ok(())
+}
+
+/// Bell 103 and V.21 carriers, both directions; feed it blocks of
+/// [`FSK_BLOCK`](crate::fsk::FSK_BLOCK).
+pub fn modems(sample_rate: f32) -> FskDetector {
+  FskDetector::new(&MODEM_CARRIERS, sample_rate)
+}
+
+pub fn test_tone(sample_rate: f32) -> GoertzelBank {
+  let block_size = block_for(sample_rate, 0.05);
+  GoertzelBank::from_table(&[TEST_TONE], &TEST_TONE_TABLE, sample_rate, block_size)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn tuner_spans_one_octave_around_a4() {
+    let freqs = tuner(48000.).frequencies();
+    assert_eq!(freqs.len(), 12);
+    assert!((freqs[0] - 261.63).abs() < 0.01);
+    assert_eq!(freqs[9], 440.);
+  }
+
+  #[test]
+  fn smoke_alarm_matches_each_t3_group() {
+    use crate::detector::{Detector, EventKind, Time};
+    use std::f32::consts::PI;
+
+    let rate = 16000.;
+    // Two T3 groups from a unit 30 Hz sharp of nominal.
+    let signal: Vec<f32> = (0..(8. * rate) as usize)
+      .map(|i| {
+        let t = i as f32 / rate;
+        let beeping = t % 4. < 3. && t % 1. < 0.5;
+        if beeping { 0.5 * (2.*PI*3130.*t).sin() } else { 0. }
+      })
+      .collect();
+    let mut alarm = smoke_alarm(SMOKE_ALARM, rate);
+    let block = block_for(rate, BEEP_BLOCK);
+    let matches: Vec<f64> = signal
+      .chunks(block)
+      .enumerate()
+      .flat_map(|(i, b)| alarm.process_block(b, Time { sample: (i * block) as u64, sample_rate: rate }))
+      .filter(|e| e.kind == EventKind::Match("smoke alarm".into()))
+      .map(|e| e.time.seconds())
+      .collect();
+    assert_eq!(matches, vec![2., 6.]);
+  }
+
+  #[test]
+  fn beeps_need_the_count_within_the_time() {
+    use crate::detector::{Detector, EventKind, Time};
+    use std::f32::consts::PI;
+
+    let rate = 16000.;
+    // 200 ms beeps at 2.4 kHz starting at `onsets`, in 4 s of input.
+    let matches = |onsets: &[f32]| {
+      let signal: Vec<f32> = (0..(4. * rate) as usize)
+        .map(|i| {
+          let t = i as f32 / rate;
+          let beeping = onsets.iter().any(|&on| (on..on + 0.2).contains(&t));
+          if beeping { 0.5 * (2.*PI*APPLIANCE_BEEPS[1]*t).sin() } else { 0. }
+        })
+        .collect();
+      let mut beeps = beeps(APPLIANCE_BEEPS[1], rate, 3, 2.);
+      let block = block_for(rate, BEEP_BLOCK);
+      signal
+        .chunks(block)
+        .enumerate()
+        .flat_map(|(i, b)| {
+          beeps.process_block(b, Time { sample: (i * block) as u64, sample_rate: rate })
+        })
+        .filter(|e| e.kind == EventKind::Match("beeps".into()))
+        .count()
+    };
+    assert_eq!(matches(&[0.5, 1., 1.5]), 1);
+    assert_eq!(matches(&[0.5, 1.]), 0);
+    // Three beeps, but spread over 2.5 s.
+    assert_eq!(matches(&[0.5, 1.75, 3.]), 0);
+  }
+
+  #[test]
+  fn coin_tones_name_the_coins() {
+    use crate::detector::{Detector, EventKind, Time};
+    use std::f32::consts::PI;
+
+    let rate = 8000.;
+    let beeps = |count: usize, ms: usize| -> Vec<f32> {
+      let len = ms * 8;
+      (0..2 * count * len)
+        .map(|i| {
+          let t = i as f32 / rate;
+          let on = (i / len).is_multiple_of(2);
+          if on { 0.3 * ((2.*PI*1700.*t).sin() + (2.*PI*2200.*t).sin()) } else { 0. }
+        })
+        .collect()
+    };
+    // A quarter, a dime and a nickel, half a second apart.
+    let mut signal = vec![0.; 4000];
+    for (count, ms) in [(5, 33), (2, 66), (1, 66)] {
+      signal.extend(beeps(count, ms));
+      signal.extend(vec![0.; 4000]);
+    }
+    let mut coins = coin_tones(rate);
+    let block = block_for(rate, BEEP_BLOCK);
+    let names: Vec<String> = signal
+      .chunks(block)
+      .enumerate()
+      .flat_map(|(i, b)| {
+        coins.process_block(b, Time { sample: (i * block) as u64, sample_rate: rate })
+      })
+      .filter_map(|e| match e.kind { EventKind::Match(name) => Some(name), _ => None })
+      .collect();
+    assert_eq!(names, vec!["quarter", "dime", "nickel"]);
+  }
+
+  #[test]
+  fn fm_pilot_needs_a_high_enough_rate_and_reads_its_level() {
+    use crate::detector::{Detector, EventKind, Time};
+    use std::f32::consts::PI;
+
+    assert_eq!(fm_pilot(32000.).unwrap_err(), RateTooLow { sample_rate: 32000., min: 40000. });
+    let rate = 48000.;
+    // A 9% pilot under a 1 kHz program tone, both in the multiplex signal.
+    let signal: Vec<f32> = (0..(2. * rate) as usize)
+      .map(|i| {
+        let t = i as f32 / rate;
+        0.09 * (2.*PI*FM_PILOT*t).sin() + 0.5 * (2.*PI*1000.*t).sin()
+      })
+      .collect();
+    let mut pilot = fm_pilot(rate).unwrap();
+    let mut level = fm_pilot_level(rate).unwrap();
+    let (mut on, mut last) = (false, 0.);
+    for (i, block) in signal.chunks(960).enumerate() {
+      let t = Time { sample: (i * 960) as u64, sample_rate: rate };
+      let events = pilot.process_block(block, t);
+      on |= events.iter().any(|e| matches!(e.kind, EventKind::ToneOn { .. }));
+      for event in level.process_block(block, t) {
+        if let EventKind::Level { level, .. } = event.kind {
+          last = level;
+        }
+      }
+    }
+    assert!(on);
+    assert!((last - 0.09).abs() < 0.005);
+  }
+}
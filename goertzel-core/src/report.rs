@@ -0,0 +1,149 @@
+//! Reporting fewer values than are measured: every `n` readings are folded into one, so a
+//! display or log gets a steady rate instead of a value per sample or block.
+
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "alloc")]
+use crate::detector::{Event, EventKind};
+
+/// How the readings of an interval are folded into the one reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Aggregate {
+  /// The largest, so short peaks are not lost.
+  #[default]
+  Max,
+  Mean,
+}
+
+/// Aggregates every `every` values pushed into one.
+#[derive(Debug, Clone)]
+pub struct Reporter {
+  every: usize,
+  aggregate: Aggregate,
+  count: usize,
+  acc: f32,
+}
+
+impl Reporter {
+  pub fn new(every: usize, aggregate: Aggregate) -> Self {
+    Self { every: every.max(1), aggregate, count: 0, acc: 0. }
+  }
+
+  /// Adds a value, returning the aggregate once the interval is complete.
+  pub fn push(&mut self, value: f32) -> Option<f32> {
+    self.acc = match (self.aggregate, self.count) {
+      (_, 0) => value,
+      (Aggregate::Max, _) => self.acc.max(value),
+      (Aggregate::Mean, _) => self.acc + value,
+    };
+    self.count += 1;
+    if self.count < self.every {
+      return None;
+    }
+    let value = match self.aggregate {
+      Aggregate::Max => self.acc,
+      Aggregate::Mean => self.acc / self.count as f32,
+    };
+    self.count = 0;
+    Some(value)
+  }
+}
+
+/// Kind of a per-block event, with its frequency or weighting; together with the label it tells
+/// the streams apart.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq)]
+enum Stream {
+  Level(f32),
+  SoundLevel(char),
+  BandLevel(f32),
+  Flatness,
+}
+
+/// Thins out the events reported on every block (`Level`, `SoundLevel`, `BandLevel`,
+/// `Flatness`), one
+/// aggregate per `every` blocks and stream; all other events pass unchanged.
+///
+/// An aggregated event carries the time of the last block of its interval.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone)]
+pub struct Decimator {
+  every: usize,
+  aggregate: Aggregate,
+  streams: Vec<(Stream, Option<String>, Reporter)>,
+}
+
+#[cfg(feature = "alloc")]
+impl Decimator {
+  pub fn new(every: usize, aggregate: Aggregate) -> Self {
+    Self { every, aggregate, streams: Vec::new() }
+  }
+
+  pub fn push(&mut self, mut event: Event) -> Option<Event> {
+    let (stream, value) = match event.kind {
+      EventKind::Level { freq, level } => (Stream::Level(freq), level),
+      EventKind::SoundLevel { weighting, dbfs } => (Stream::SoundLevel(weighting), dbfs),
+      EventKind::BandLevel { freq, dbfs } => (Stream::BandLevel(freq), dbfs),
+      EventKind::Flatness { value } => (Stream::Flatness, value),
+      _ => return Some(event),
+    };
+    let found = self.streams.iter().position(|(s, l, _)| *s == stream && *l == event.label);
+    let at = found.unwrap_or_else(|| {
+      let reporter = Reporter::new(self.every, self.aggregate);
+      self.streams.push((stream, event.label.clone(), reporter));
+      self.streams.len() - 1
+    });
+    let value = self.streams[at].2.push(value)?;
+    match &mut event.kind {
+      EventKind::Level { level, .. } => *level = value,
+      EventKind::SoundLevel { dbfs, .. } | EventKind::BandLevel { dbfs, .. } => *dbfs = value,
+      EventKind::Flatness { value: v } => *v = value,
+      _ => {}
+    }
+    Some(event)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::detector::Time;
+
+  #[test]
+  fn folds_each_interval_into_one_value() {
+    let mut max = Reporter::new(3, Aggregate::Max);
+    let mut mean = Reporter::new(3, Aggregate::Mean);
+    let values = [1., 4., 1., 2., 2., 5.];
+    let max: Vec<f32> = values.iter().filter_map(|&v| max.push(v)).collect();
+    let mean: Vec<f32> = values.iter().filter_map(|&v| mean.push(v)).collect();
+    assert_eq!(max, [4., 5.]);
+    assert_eq!(mean, [2., 3.]);
+  }
+
+  #[test]
+  fn decimates_per_block_events_only() {
+    let mut decimator = Decimator::new(2, Aggregate::Mean);
+    let t = |i: u64| Time { sample: 160 * i, sample_rate: 8000. };
+    let events = vec![
+      Event::new(t(0), EventKind::Level { freq: 100., level: 0.2 }),
+      Event::new(t(0), EventKind::Level { freq: 200., level: 0.8 }),
+      Event::new(t(1), EventKind::ToneOff { freq: 100. }),
+      Event::new(t(1), EventKind::Level { freq: 100., level: 0.4 }),
+      Event::new(t(1), EventKind::Level { freq: 200., level: 0.6 }),
+    ];
+    let out: Vec<Event> = events.into_iter().filter_map(|e| decimator.push(e)).collect();
+    let levels: Vec<(u64, f32)> = out
+      .iter()
+      .filter_map(|e| match e.kind {
+        EventKind::Level { freq, level } => Some((e.time.sample, freq + level)),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(out[0].kind, EventKind::ToneOff { freq: 100. });
+    assert_eq!(levels.len(), 2);
+    assert!((levels[0].1 - 100.3).abs() < 1e-3 && (levels[1].1 - 200.7).abs() < 1e-3);
+    assert_eq!((levels[0].0, levels[1].0), (160, 160));
+  }
+}
@@ -0,0 +1,124 @@
+//! Sample rate conversion, for input that does not come at the rate the detectors were built
+//! for, such as a sound card that cannot run at a telephony profile's 8 kHz.
+//!
+//! Good enough for tone detection rather than for listening: a windowed-sinc low-pass against
+//! aliasing when going down, then linear interpolation.
+
+use alloc::vec::Vec;
+use core::f32::consts::PI;
+
+use crate::{math, Window};
+
+/// Filter taps on each side of the centre per unit of the decimation ratio.
+const TAPS_PER_RATIO: usize = 8;
+/// Cutoff of the low-pass, as a fraction of the output Nyquist frequency.
+const CUTOFF: f32 = 0.9;
+
+/// Converts interleaved audio from one rate to another, block by block.
+///
+/// Going down delays the audio by half the low-pass, `TAPS_PER_RATIO` input frames per unit of
+/// the ratio, e.g. 1 ms from 48 to 8 kHz.
+#[derive(Debug, Clone)]
+pub struct Resampler {
+  channels: usize,
+  /// Input frames per output frame.
+  step: f64,
+  /// Low-pass taps; a single 1 when going up.
+  taps: Vec<f32>,
+  /// The latest input frames, `taps.len()` of them per channel, oldest first from `next`.
+  delay: Vec<f32>,
+  next: usize,
+  /// Filtered frames before and at the current input frame.
+  prev: Vec<f32>,
+  current: Vec<f32>,
+  /// Where the next output frame falls, in input frames after `prev`.
+  position: f64,
+}
+
+impl Resampler {
+  pub fn new(from: f32, to: f32, channels: usize) -> Self {
+    let ratio = from / to;
+    let taps = if ratio > 1. {
+      let half = TAPS_PER_RATIO * math::round(ratio) as usize;
+      let n = 2 * half + 1;
+      // Cutoff in cycles per input sample.
+      let cutoff = CUTOFF * 0.5 / ratio;
+      let taps: Vec<f32> = (0..n)
+        .map(|i| {
+          let x = i as f32 - half as f32;
+          let sinc =
+            if x == 0. { 2. * cutoff } else { math::sin_cos(2. * PI * cutoff * x).0 / (PI * x) };
+          sinc * Window::Blackman.weight(i, n - 1)
+        })
+        .collect();
+      let gain: f32 = taps.iter().sum();
+      taps.iter().map(|t| t / gain).collect()
+    } else {
+      alloc::vec![1.]
+    };
+    Self {
+      channels: channels.max(1),
+      step: ratio as f64,
+      delay: alloc::vec![0.; taps.len() * channels.max(1)],
+      taps,
+      next: 0,
+      prev: alloc::vec![0.; channels.max(1)],
+      current: alloc::vec![0.; channels.max(1)],
+      // The first output frame is the first input frame.
+      position: 1.,
+    }
+  }
+
+  /// Appends `interleaved` at the new rate to `out`.
+  pub fn process(&mut self, interleaved: &[f32], out: &mut Vec<f32>) {
+    let (channels, len) = (self.channels, self.taps.len());
+    for frame in interleaved.chunks_exact(channels) {
+      self.delay[self.next * channels..][..channels].copy_from_slice(frame);
+      self.next = (self.next + 1) % len;
+      core::mem::swap(&mut self.prev, &mut self.current);
+      let (taps, delay, next) = (&self.taps, &self.delay, self.next);
+      for (c, filtered) in self.current.iter_mut().enumerate() {
+        *filtered = (0..len).map(|i| taps[i] * delay[(next + i) % len * channels + c]).sum();
+      }
+      while self.position <= 1. {
+        let t = self.position as f32;
+        out.extend(self.prev.iter().zip(&self.current).map(|(a, b)| a + (b - a) * t));
+        self.position += self.step;
+      }
+      self.position -= 1.;
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Goertzel;
+
+  #[test]
+  fn keeps_tones_and_drops_what_would_alias() {
+    // 1 kHz and 5 kHz at 48 kHz, in 10 ms blocks, down to 8 kHz, where 5 kHz would fold onto
+    // 3 kHz.
+    let tone = |f: f32, n: usize| math::sin_cos(2. * PI * f * n as f32 / 48000.).0;
+    let input: Vec<f32> =
+      (0..48_000).map(|n| 0.5 * tone(1000., n) + 0.5 * tone(5000., n)).collect();
+    let mut resampler = Resampler::new(48000., 8000., 1);
+    let mut out = Vec::new();
+    for block in input.chunks(480) {
+      resampler.process(block, &mut out);
+    }
+    assert_eq!(out.len(), 8000);
+    let settled = &out[400..];
+    let power = |f: f32| Goertzel::new(f, 8000.).block_power(settled);
+    assert!(power(1000.) > 0.2, "{}", power(1000.));
+    assert!(power(3000.) < 1e-3, "{}", power(3000.));
+
+    // Going up, stereo: 441 frames at 44.1 kHz are 480 at 48 kHz.
+    let mut resampler = Resampler::new(44100., 48000., 2);
+    let mut out = Vec::new();
+    resampler.process(&[0.25; 2 * 441], &mut out);
+    assert!((958..=960).contains(&out.len()), "{}", out.len());
+    assert!(out.iter().all(|&s| (s - 0.25).abs() < 1e-6));
+  }
+}
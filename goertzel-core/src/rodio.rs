@@ -0,0 +1,117 @@
+//! Tone detection on rodio playback.
+//!
+//! `TappedSource` wraps any rodio `Source` and passes its samples through unchanged while
+//! measuring them with a `GoertzelBank`; the powers come out of the paired `GoertzelReader`, so
+//! the program can watch what the sink is playing from its own thread.
+
+use core::time::Duration;
+
+use rodio::{Sample, Source};
+
+use crate::shared::{self, GoertzelHandle, GoertzelReader};
+use crate::GoertzelBank;
+
+/// A `Source` that measures what flows through it, one block of the mono mix at a time.
+pub struct TappedSource<S> {
+  inner: S,
+  handle: GoertzelHandle,
+  block: Vec<f32>,
+  block_size: usize,
+  /// Sum of the current frame's channels and how many were seen so far.
+  frame: (f32, u16),
+}
+
+impl<S> TappedSource<S>
+where
+  S: Source,
+  S::Item: Sample,
+{
+  /// Taps `inner` with `bank`, which should be built for `inner.sample_rate()`. Blocks are as
+  /// long as the bank's longest filter.
+  pub fn new(inner: S, bank: GoertzelBank) -> (Self, GoertzelReader) {
+    let block_size = bank.block_sizes().into_iter().max().unwrap_or(0).max(1);
+    let (handle, reader) = shared::split(bank);
+    let block = Vec::with_capacity(block_size);
+    (TappedSource { inner, handle, block, block_size, frame: (0., 0) }, reader)
+  }
+
+  pub fn inner(&self) -> &S {
+    &self.inner
+  }
+
+  fn tap(&mut self, sample: f32) {
+    let channels = self.inner.channels().max(1);
+    self.frame.0 += sample;
+    self.frame.1 += 1;
+    if self.frame.1 < channels {
+      return;
+    }
+    self.block.push(self.frame.0 / channels as f32);
+    self.frame = (0., 0);
+    if self.block.len() == self.block_size {
+      self.handle.process(&self.block);
+      self.block.clear();
+    }
+  }
+}
+
+impl<S> Iterator for TappedSource<S>
+where
+  S: Source,
+  S::Item: Sample,
+{
+  type Item = S::Item;
+
+  fn next(&mut self) -> Option<S::Item> {
+    let sample = self.inner.next()?;
+    self.tap(sample.to_f32());
+    Some(sample)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.inner.size_hint()
+  }
+}
+
+impl<S> Source for TappedSource<S>
+where
+  S: Source,
+  S::Item: Sample,
+{
+  fn current_frame_len(&self) -> Option<usize> {
+    self.inner.current_frame_len()
+  }
+
+  fn channels(&self) -> u16 {
+    self.inner.channels()
+  }
+
+  fn sample_rate(&self) -> u32 {
+    self.inner.sample_rate()
+  }
+
+  fn total_duration(&self) -> Option<Duration> {
+    self.inner.total_duration()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rodio::source::SineWave;
+
+  #[test]
+  fn passes_audio_through_and_measures_it() {
+    let sine = SineWave::new(440.).take_duration(Duration::from_millis(100));
+    let bank = GoertzelBank::with_block_size(&[440., 1000.], 48_000., 960);
+    let (tapped, reader) = TappedSource::new(sine, bank);
+    assert_eq!((tapped.channels(), tapped.sample_rate()), (1, 48_000));
+
+    let played: Vec<f32> = tapped.collect();
+    let direct: Vec<f32> = SineWave::new(440.).take_duration(Duration::from_millis(100)).collect();
+    assert_eq!(played, direct);
+    assert!(reader.power(0) > 0.9, "{}", reader.power(0));
+    assert!(reader.power(1) < 0.01, "{}", reader.power(1));
+  }
+}
@@ -0,0 +1,143 @@
+//! Detectors watched only at certain times of day, e.g. a doorbell from 08:00 to 22:00, for
+//! unattended deployments.
+
+use alloc::vec::Vec;
+
+use crate::detector::{Detector, Event, EventKind, Time};
+
+const DAY: u64 = 86_400;
+
+/// A stretch of every day, in seconds since midnight. One ending before it starts runs past
+/// midnight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DailyWindow {
+  pub start: u32,
+  pub end: u32,
+}
+
+impl DailyWindow {
+  /// `08:00-22:00` or `22:30:00-06:00`.
+  pub fn parse(spec: &str) -> Option<Self> {
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (time_of_day(start.trim())?, time_of_day(end.trim())?);
+    (start != end).then_some(DailyWindow { start, end })
+  }
+
+  /// A comma-separated list of windows.
+  pub fn parse_list(spec: &str) -> Option<Vec<Self>> {
+    spec.split(',').map(Self::parse).collect()
+  }
+
+  pub fn contains(&self, second_of_day: u32) -> bool {
+    match self.start < self.end {
+      true => (self.start..self.end).contains(&second_of_day),
+      false => second_of_day >= self.start || second_of_day < self.end,
+    }
+  }
+}
+
+/// `HH:MM` or `HH:MM:SS` in seconds since midnight.
+fn time_of_day(text: &str) -> Option<u32> {
+  let mut fields = text.split(':').map(|f| f.parse::<u32>().ok());
+  let (hours, minutes) = (fields.next()??, fields.next()??);
+  let seconds = fields.next().unwrap_or(Some(0))?;
+  if fields.next().is_some() || hours > 23 || minutes > 59 || seconds > 59 {
+    return None;
+  }
+  Some(hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Passes on the events of `detector` only while the time of day is within one of its windows,
+/// and reports `Schedule` when that changes, and for the first block.
+///
+/// The first block is taken to start at `second_of_day`, and the time of day to follow the
+/// stream from there. The detector keeps listening outside its windows, so that it is in step
+/// when one opens: a tone already on then is not reported, only its end.
+#[derive(Debug)]
+pub struct Scheduled<D> {
+  detector: D,
+  windows: Vec<DailyWindow>,
+  second_of_day: f64,
+  /// Stream time of the first block.
+  first: Option<f64>,
+  active: Option<bool>,
+}
+
+impl<D: Detector> Scheduled<D> {
+  pub fn new(detector: D, windows: Vec<DailyWindow>, second_of_day: f64) -> Self {
+    Self { detector, windows, second_of_day, first: None, active: None }
+  }
+
+  /// Whether the detector is being watched; unknown until the first block.
+  pub fn is_active(&self) -> Option<bool> {
+    self.active
+  }
+}
+
+impl<D: Detector> Detector for Scheduled<D> {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let mut events = self.detector.process_block(block, t);
+    let elapsed = t.seconds() - *self.first.get_or_insert(t.seconds());
+    let now = ((self.second_of_day + elapsed).max(0.) as u64 % DAY) as u32;
+    let active = self.windows.iter().any(|w| w.contains(now));
+    if !active {
+      events.clear();
+    }
+    if self.active.replace(active) != Some(active) {
+      events.insert(0, Event::new(t, EventKind::Schedule { active }));
+    }
+    events
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::detector::ToneDetector;
+  use std::f32::consts::PI;
+
+  #[test]
+  fn parses_windows_across_midnight() {
+    let night = DailyWindow::parse("22:30-06:00").unwrap();
+    assert_eq!(night, DailyWindow { start: 81_000, end: 21_600 });
+    assert!(night.contains(0) && night.contains(81_000) && !night.contains(21_600));
+    let windows = DailyWindow::parse_list("08:00-12:00, 14:00:30-22:00").unwrap();
+    assert_eq!(windows[1].start, 50_430);
+    for bad in ["8-12", "08:00", "24:00-01:00", "08:00-08:00", "08:60-09:00"] {
+      assert_eq!(DailyWindow::parse(bad), None, "{}", bad);
+    }
+  }
+
+  #[test]
+  fn events_pass_only_within_the_window() {
+    // Starts at 07:59:59 with a one second beep every two seconds; the window opens at 08:00
+    // and closes at 08:00:06.
+    let window = DailyWindow::parse("08:00-08:00:06").unwrap();
+    let detector = ToneDetector::new(1000., 8000., 0.3);
+    let mut scheduled = Scheduled::new(detector, vec![window], 28_799.);
+    let mut events = Vec::new();
+    for i in 0..900 {
+      let beeping = i % 200 < 100;
+      let block: Vec<f32> = (0..80)
+        .map(|n| (2. * PI * 1000. * (i * 80 + n) as f32 / 8000.).sin() * beeping as u8 as f32)
+        .collect();
+      let t = Time { sample: i * 80, sample_rate: 8000. };
+      events.extend(scheduled.process_block(&block, t));
+    }
+    let seen: Vec<(f64, &EventKind)> =
+      events.iter().map(|e| (e.time.seconds(), &e.kind)).collect();
+    assert!(matches!(seen[0], (t, EventKind::Schedule { active: false }) if t == 0.));
+    assert!(matches!(seen[1], (t, EventKind::Schedule { active: true }) if t == 1.));
+    // The beep already on when the window opened is not reported, only its end.
+    assert!(matches!(seen[2], (t, EventKind::ToneOff { .. }) if t == 1.));
+    let onsets: Vec<f64> = seen
+      .iter()
+      .filter(|(_, k)| matches!(k, EventKind::ToneOn { .. }))
+      .map(|(t, _)| *t)
+      .collect();
+    assert_eq!(onsets, vec![2., 4., 6.]);
+    assert!(matches!(seen.last(), Some((t, EventKind::Schedule { active: false })) if *t == 7.));
+    assert_eq!(scheduled.is_active(), Some(false));
+  }
+}
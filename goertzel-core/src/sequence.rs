@@ -0,0 +1,156 @@
+//! Patterns over the events of another detector, e.g. "three 1 kHz beeps within 2 s" or the
+//! DTMF code `*#*#`.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::detector::{Detector, Event, EventKind, Time};
+
+/// One element of a pattern.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symbol {
+  Digit(char),
+  AnyDigit,
+  /// The onset of a tone, matched within half a hertz.
+  Tone(f32),
+}
+
+impl Symbol {
+  /// Pattern of literal digits.
+  pub fn digits(keys: &str) -> Vec<Symbol> {
+    keys.chars().map(Symbol::Digit).collect()
+  }
+
+  /// The same symbol `count` times.
+  pub fn repeat(self, count: usize) -> Vec<Symbol> {
+    alloc::vec![self; count]
+  }
+
+  fn matches(&self, kind: &EventKind) -> bool {
+    match (self, kind) {
+      (Symbol::Digit(a), EventKind::Digit(b)) => a == b,
+      (Symbol::AnyDigit, EventKind::Digit(_)) => true,
+      (Symbol::Tone(a), EventKind::ToneOn { freq, .. }) => (a - freq).abs() <= 0.5,
+      _ => false,
+    }
+  }
+}
+
+/// Events that can take part in a pattern; everything else is ignored by the matcher.
+fn is_symbol(kind: &EventKind) -> bool {
+  matches!(kind, EventKind::Digit(_) | EventKind::ToneOn { .. })
+}
+
+/// Runs `source` and reports `Match(name)` when its events spell out `pattern` within `within`
+/// seconds of the first symbol.
+///
+/// Pattern symbols must follow each other without another digit or tone onset in between; the source's own events are
+/// passed through. After a match the matcher starts over, so matches never overlap.
+#[derive(Debug)]
+pub struct SequenceMatcher<D> {
+  source: D,
+  pattern: Vec<Symbol>,
+  within: f32,
+  name: String,
+  /// Start time and next pattern index of every partial match.
+  partial: Vec<(Time, usize)>,
+}
+
+impl<D: Detector> SequenceMatcher<D> {
+  pub fn new(source: D, pattern: Vec<Symbol>, within: f32, name: impl Into<String>) -> Self {
+    Self {
+      source,
+      pattern,
+      within,
+      name: name.into(),
+      partial: Vec::new(),
+    }
+  }
+
+  /// The detector whose events are matched against the pattern.
+  pub fn source_mut(&mut self) -> &mut D {
+    &mut self.source
+  }
+
+  /// Advances the partial matches with one event; returns whether the pattern completed.
+  fn step(&mut self, event: &Event) -> bool {
+    let (pattern, within) = (&self.pattern, self.within);
+    self.partial.push((event.time, 0));
+    self.partial.retain_mut(|(start, next)| {
+      let in_time = event.time.seconds() - start.seconds() <= within as f64;
+      if in_time && pattern[*next].matches(&event.kind) {
+        *next += 1;
+        true
+      } else {
+        false
+      }
+    });
+    if self.partial.iter().any(|&(_, next)| next == self.pattern.len()) {
+      self.partial.clear();
+      return true;
+    }
+    false
+  }
+}
+
+impl<D: Detector> Detector for SequenceMatcher<D> {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let mut events = Vec::new();
+    for event in self.source.process_block(block, t) {
+      let done = !self.pattern.is_empty() && is_symbol(&event.kind) && self.step(&event);
+      let time = event.time;
+      events.push(event);
+      if done {
+        events.push(Event::new(time, EventKind::Match(self.name.clone())));
+      }
+    }
+    events
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Replays a fixed list of events, one per block.
+  struct Script(Vec<EventKind>);
+
+  impl Detector for Script {
+    fn process_block(&mut self, _: &[f32], t: Time) -> Vec<Event> {
+      if self.0.is_empty() {
+        return Vec::new();
+      }
+      alloc::vec![Event::new(t, self.0.remove(0))]
+    }
+  }
+
+  fn matches(matcher: &mut SequenceMatcher<Script>, blocks: u64) -> Vec<u64> {
+    (0..blocks)
+      .flat_map(|i| matcher.process_block(&[], Time { sample: i * 4000, sample_rate: 8000. }))
+      .filter(|e| matches!(e.kind, EventKind::Match(_)))
+      .map(|e| e.time.sample)
+      .collect()
+  }
+
+  #[test]
+  fn matches_digit_code_after_false_start() {
+    let digits = "1**#*#".chars().map(EventKind::Digit).collect();
+    let mut m = SequenceMatcher::new(Script(digits), Symbol::digits("*#*#"), 10., "code");
+    assert_eq!(matches(&mut m, 6), vec![20000]);
+  }
+
+  #[test]
+  fn tone_repeats_must_fit_the_window() {
+    let beep = EventKind::ToneOn { freq: 1000., power: 1. };
+    let off = EventKind::ToneOff { freq: 1000. };
+    // A beep every second: the third one lands right at the end of the window.
+    let kinds = alloc::vec![beep.clone(), off.clone(), beep.clone(), off.clone(), beep.clone(), off];
+    let mut m = SequenceMatcher::new(Script(kinds), Symbol::Tone(1000.).repeat(3), 2., "three beeps");
+    assert_eq!(matches(&mut m, 6), vec![16000]);
+    // A digit in between breaks the run.
+    let broken = alloc::vec![beep.clone(), EventKind::ToneOff { freq: 1000. }, EventKind::Digit('x'), beep.clone(), beep];
+    let mut m = SequenceMatcher::new(Script(broken), Symbol::Tone(1000.).repeat(3), 2., "three beeps");
+    assert!(matches(&mut m, 5).is_empty());
+  }
+}
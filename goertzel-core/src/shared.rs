@@ -0,0 +1,188 @@
+//! Sharing a bank between the audio thread and the rest of the program.
+//!
+//! Every filter type in this crate is plain data: `Goertzel`, `GoertzelConst` and `GoertzelBank`
+//! are `Send + Sync`, and only the methods taking `&mut self` change them. `Pipeline` holds boxed
+//! detectors and is only `Send`: move it into the thread that feeds it. Usually the bank lives in
+//! the audio callback and something else (a UI, a meter) wants the latest powers; `split` hands
+//! out the two ends for that without any locking. When the reader needs every power from the same
+//! block, publish them through `latest_power` instead.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+
+use crate::GoertzelBank;
+
+/// Splits `bank` into a handle to move into the audio callback and a reader for other threads.
+pub fn split(bank: GoertzelBank) -> (GoertzelHandle, GoertzelReader) {
+  let powers: Arc<[AtomicU32]> = (0..bank.len()).map(|_| AtomicU32::new(0)).collect();
+  let reader = GoertzelReader { freqs: bank.frequencies(), powers: powers.clone() };
+  let scratch = alloc::vec![0.; bank.len()];
+  (GoertzelHandle { bank, scratch, powers }, reader)
+}
+
+/// The audio side: owns the bank and publishes each block's powers. Never allocates.
+pub struct GoertzelHandle {
+  bank: GoertzelBank,
+  scratch: Vec<f32>,
+  powers: Arc<[AtomicU32]>,
+}
+
+impl GoertzelHandle {
+  /// Measures `block` and publishes the powers, returning them as well.
+  pub fn process(&mut self, block: &[f32]) -> &[f32] {
+    self.bank.process_into(block, &mut self.scratch);
+    for (shared, &power) in self.powers.iter().zip(&self.scratch) {
+      shared.store(power.to_bits(), Ordering::Relaxed);
+    }
+    &self.scratch
+  }
+
+  pub fn bank(&self) -> &GoertzelBank {
+    &self.bank
+  }
+}
+
+/// The reading side: cheap to clone, never blocks the audio thread. Each power is read atomically
+/// on its own, so two frequencies may come from consecutive blocks; see `LatestPower` for whole
+/// snapshots.
+#[derive(Clone)]
+pub struct GoertzelReader {
+  freqs: Vec<f32>,
+  powers: Arc<[AtomicU32]>,
+}
+
+impl GoertzelReader {
+  pub fn frequencies(&self) -> &[f32] {
+    &self.freqs
+  }
+
+  /// Latest power of the `i`th frequency, 0 until the first block.
+  pub fn power(&self, i: usize) -> f32 {
+    f32::from_bits(self.powers[i].load(Ordering::Relaxed))
+  }
+
+  /// Latest powers of every frequency into `out`.
+  pub fn read_into(&self, out: &mut [f32]) {
+    for (out, shared) in out.iter_mut().zip(self.powers.iter()) {
+      *out = f32::from_bits(shared.load(Ordering::Relaxed));
+    }
+  }
+}
+
+/// Set in `TripleBuffer::middle` when the writer has published since the reader last looked.
+const FRESH: u8 = 4;
+
+struct TripleBuffer {
+  slots: [UnsafeCell<Vec<f32>>; 3],
+  /// Index of the slot owned by neither side, plus `FRESH`.
+  middle: AtomicU8,
+}
+
+// Each slot is only touched by the side that currently owns its index, and indices only change
+// hands through `middle`.
+unsafe impl Sync for TripleBuffer {}
+
+/// A triple buffer of `len` powers: the writer publishes whole blocks, the single reader always
+/// gets the most recent complete one. Both sides are wait-free and never allocate, so the writer
+/// belongs in the audio callback and the reader can poll at a GUI's frame rate.
+pub fn latest_power(len: usize) -> (PowerWriter, LatestPower) {
+  let shared = Arc::new(TripleBuffer {
+    slots: core::array::from_fn(|_| UnsafeCell::new(alloc::vec![0.; len])),
+    middle: AtomicU8::new(1),
+  });
+  (PowerWriter { shared: shared.clone(), back: 0 }, LatestPower { shared, front: 2 })
+}
+
+/// Audio side of `latest_power`.
+pub struct PowerWriter {
+  shared: Arc<TripleBuffer>,
+  back: u8,
+}
+
+impl PowerWriter {
+  /// Publishes `powers`, which must have the length given to `latest_power`.
+  pub fn publish(&mut self, powers: &[f32]) {
+    // SAFETY: `back` is owned by the writer until it is swapped into `middle` below.
+    let slot = unsafe { &mut *self.shared.slots[self.back as usize].get() };
+    slot.copy_from_slice(powers);
+    self.back = self.shared.middle.swap(self.back | FRESH, Ordering::AcqRel) & !FRESH;
+  }
+}
+
+/// Reading side of `latest_power`.
+pub struct LatestPower {
+  shared: Arc<TripleBuffer>,
+  front: u8,
+}
+
+impl LatestPower {
+  /// Whether a block was published since the last `read`.
+  pub fn is_fresh(&self) -> bool {
+    self.shared.middle.load(Ordering::Relaxed) & FRESH != 0
+  }
+
+  /// The most recently published powers, all from the same block (zeros before the first).
+  pub fn read(&mut self) -> &[f32] {
+    if self.is_fresh() {
+      self.front = self.shared.middle.swap(self.front, Ordering::AcqRel) & !FRESH;
+    }
+    // SAFETY: `front` is owned by the reader until the next swap, which needs `&mut self`.
+    unsafe { &*self.shared.slots[self.front as usize].get() }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::f32::consts::PI;
+
+  fn assert_send<T: Send>() {}
+  fn assert_send_sync<T: Send + Sync>() {}
+
+  #[test]
+  fn everything_can_cross_threads() {
+    assert_send_sync::<crate::Goertzel>();
+    assert_send_sync::<crate::GoertzelConst<205>>();
+    assert_send_sync::<GoertzelBank>();
+    assert_send_sync::<GoertzelHandle>();
+    assert_send_sync::<GoertzelReader>();
+    assert_send::<crate::Pipeline>();
+    assert_send::<PowerWriter>();
+    assert_send::<LatestPower>();
+  }
+
+  #[test]
+  fn reader_sees_audio_thread_powers() {
+    let (mut handle, reader) = split(GoertzelBank::new(&[440., 1000.], 8000.));
+    assert_eq!(reader.power(0), 0.);
+    let block: Vec<f32> = (0..400).map(|t| (2.*PI*440.*(t as f32)/8000.).sin()).collect();
+    let audio = std::thread::spawn(move || handle.process(&block).to_vec());
+    let powers = audio.join().unwrap();
+    let mut seen = [0.; 2];
+    reader.read_into(&mut seen);
+    assert_eq!(seen.to_vec(), powers);
+    assert!(seen[0] > 0.9 && seen[1] < 0.01);
+  }
+
+  #[test]
+  fn latest_power_hands_over_whole_blocks() {
+    let (mut writer, mut latest) = latest_power(2);
+    assert_eq!(latest.read(), [0., 0.]);
+    let audio = std::thread::spawn(move || {
+      for i in 1..=1000 {
+        writer.publish(&[i as f32, -(i as f32)]);
+      }
+    });
+    let mut last = 0.;
+    while !audio.is_finished() || latest.is_fresh() {
+      let powers = latest.read();
+      assert_eq!(powers[0], -powers[1]);
+      assert!(powers[0] >= last);
+      last = powers[0];
+    }
+    assert_eq!(latest.read(), [1000., -1000.]);
+  }
+}
@@ -0,0 +1,178 @@
+//! Deterministic simulation: a pipeline driven by a script of synthetic input, with time taken
+//! from the sample count alone.
+//!
+//! Nothing waits on the wall clock, so a script covering minutes of cadences and durations runs
+//! in milliseconds and reports the same events, at the same sample, on every run.
+//!
+//! ```
+//! use goertzel_core::detector::{EventKind, ToneDetector};
+//! use goertzel_core::sim::Simulation;
+//! use goertzel_core::Pipeline;
+//!
+//! let pipeline = Pipeline::new(8000., 160).with(Box::new(ToneDetector::new(1000., 8000., 0.5)));
+//! let mut sim = Simulation::new(pipeline, 8000.);
+//! sim.silence(1.).tone(&[1000.], 0.5, 2.).silence(1.);
+//! assert_eq!(sim.now().seconds(), 4.);
+//! assert!(matches!(sim.events()[0].kind, EventKind::ToneOn { .. }));
+//! ```
+
+use alloc::vec::Vec;
+
+use crate::detector::{Event, Time};
+use crate::generator::{GaussianNoise, Sine};
+use crate::Pipeline;
+
+/// Time as a count of samples: it moves only when input is fed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VirtualClock {
+  sample: u64,
+  sample_rate: f32,
+}
+
+impl VirtualClock {
+  pub fn new(sample_rate: f32) -> Self {
+    Self { sample: 0, sample_rate }
+  }
+
+  pub fn now(&self) -> Time {
+    Time { sample: self.sample, sample_rate: self.sample_rate }
+  }
+
+  pub fn advance(&mut self, samples: u64) {
+    self.sample += samples;
+  }
+
+  /// Samples in `seconds` of input.
+  pub fn samples(&self, seconds: f32) -> usize {
+    (seconds as f64 * self.sample_rate as f64 + 0.5) as usize
+  }
+}
+
+/// See the module documentation.
+pub struct Simulation {
+  pipeline: Pipeline,
+  clock: VirtualClock,
+  /// Samples per push, like the buffers of a capture callback.
+  chunk: usize,
+  noise: Option<GaussianNoise>,
+  events: Vec<Event>,
+  buffer: Vec<f32>,
+}
+
+impl Simulation {
+  /// Starts `pipeline` at sample 0, fed one block at a time.
+  pub fn new(mut pipeline: Pipeline, sample_rate: f32) -> Self {
+    pipeline.set_position(0);
+    let chunk = pipeline.block_size();
+    Self {
+      pipeline,
+      clock: VirtualClock::new(sample_rate),
+      chunk,
+      noise: None,
+      events: Vec::new(),
+      buffer: Vec::new(),
+    }
+  }
+
+  /// Feeds the pipeline `chunk` samples at a time instead of a block.
+  pub fn with_chunk(mut self, chunk: usize) -> Self {
+    self.chunk = chunk.max(1);
+    self
+  }
+
+  /// Adds seeded Gaussian noise of level `rms` to everything fed from now on.
+  pub fn with_noise(mut self, seed: u64, rms: f32) -> Self {
+    self.noise = Some(GaussianNoise::new(seed, rms));
+    self
+  }
+
+  /// `seconds` of the sum of sines at `freqs`, each of `amplitude`.
+  pub fn tone(&mut self, freqs: &[f32], amplitude: f32, seconds: f32) -> &mut Self {
+    let sample_rate = self.clock.sample_rate;
+    let mut sines: Vec<Sine> =
+      freqs.iter().map(|&f| Sine::new(f, sample_rate, amplitude)).collect();
+    let len = self.clock.samples(seconds);
+    self.feed((0..len).map(|_| sines.iter_mut().filter_map(Iterator::next).sum()))
+  }
+
+  /// `seconds` without a tone (only the noise, if any).
+  pub fn silence(&mut self, seconds: f32) -> &mut Self {
+    let len = self.clock.samples(seconds);
+    self.feed(core::iter::repeat_n(0., len))
+  }
+
+  /// Feeds arbitrary samples.
+  pub fn feed(&mut self, samples: impl IntoIterator<Item = f32>) -> &mut Self {
+    let mut samples = samples.into_iter();
+    loop {
+      self.buffer.clear();
+      self.buffer.extend(samples.by_ref().take(self.chunk));
+      if self.buffer.is_empty() {
+        return self;
+      }
+      if let Some(noise) = &mut self.noise {
+        for (s, n) in self.buffer.iter_mut().zip(noise) {
+          *s += n;
+        }
+      }
+      self.events.extend(self.pipeline.push(&self.buffer));
+      self.clock.advance(self.buffer.len() as u64);
+    }
+  }
+
+  /// Time of the next sample to be fed.
+  pub fn now(&self) -> Time {
+    self.clock.now()
+  }
+
+  /// Every event so far.
+  pub fn events(&self) -> &[Event] {
+    &self.events
+  }
+
+  /// Events since the last call.
+  pub fn take_events(&mut self) -> Vec<Event> {
+    core::mem::take(&mut self.events)
+  }
+
+  pub fn pipeline(&self) -> &Pipeline {
+    &self.pipeline
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::detector::EventKind;
+  use crate::presets;
+  use alloc::boxed::Box;
+
+  #[test]
+  fn cadences_run_faster_than_real_time_and_repeat_exactly() {
+    let run = || {
+      let alarm = presets::smoke_alarm(presets::SMOKE_ALARM, 8000.);
+      let pipeline = Pipeline::new(8000., 80).with(Box::new(alarm));
+      // Ten minutes of T3 groups, in odd-sized callback buffers.
+      let mut sim = Simulation::new(pipeline, 8000.).with_chunk(333).with_noise(5, 0.05);
+      for _ in 0..150 {
+        for _ in 0..3 {
+          sim.tone(&[presets::SMOKE_ALARM], 0.5, 0.5).silence(0.5);
+        }
+        sim.silence(1.);
+      }
+      assert_eq!(sim.now().seconds(), 600.);
+      sim.take_events()
+    };
+    let events = run();
+    let matches: Vec<u64> = events
+      .iter()
+      .filter(|e| matches!(e.kind, EventKind::Match(_)))
+      .map(|e| e.time.sample)
+      .collect();
+    // The third beep of every group, four seconds apart.
+    assert_eq!(matches.len(), 150);
+    assert!(matches.windows(2).all(|w| w[1] - w[0] == 32_000));
+    assert_eq!(events, run());
+  }
+}
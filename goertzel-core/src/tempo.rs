@@ -0,0 +1,167 @@
+//! Tempo of periodic tone bursts, such as metronome clicks or a beeping alarm, from the
+//! intervals between their onsets.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use crate::detector::{Detector, Event, EventKind, Time};
+use crate::math;
+
+/// Statistics of the intervals between the latest onsets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoStats {
+  /// Beats per minute, from the mean interval.
+  pub bpm: f32,
+  /// Standard deviation of the intervals, in seconds.
+  pub jitter: f32,
+  /// Largest distance of an interval from the mean, in seconds.
+  pub max_deviation: f32,
+  pub intervals: usize,
+}
+
+impl TempoStats {
+  fn of(intervals: &VecDeque<f32>) -> Option<Self> {
+    if intervals.is_empty() {
+      return None;
+    }
+    let n = intervals.len() as f32;
+    let mean = intervals.iter().sum::<f32>() / n;
+    let variance = intervals.iter().map(|i| (i - mean) * (i - mean)).sum::<f32>() / n;
+    let max_deviation = intervals.iter().map(|i| (i - mean).abs()).fold(0., f32::max);
+    let jitter = math::sqrt(variance);
+    Some(Self { bpm: 60. / mean, jitter, max_deviation, intervals: intervals.len() })
+  }
+}
+
+/// Measures the intervals between the onsets reported by `source` and emits `Tempo` at every
+/// onset once `window` intervals are known, over the latest `window` of them.
+///
+/// Single and dual tones count; the source's own events are passed through. A gap longer than
+/// `max_interval` (2 s, 30 BPM, unless set) starts the measurement over.
+#[derive(Debug)]
+pub struct Tempo<D> {
+  source: D,
+  window: usize,
+  max_interval: f32,
+  last_onset: Option<Time>,
+  intervals: VecDeque<f32>,
+}
+
+impl<D: Detector> Tempo<D> {
+  pub fn new(source: D, window: usize) -> Self {
+    let window = window.max(1);
+    Self {
+      source,
+      window,
+      max_interval: 2.,
+      last_onset: None,
+      intervals: VecDeque::with_capacity(window),
+    }
+  }
+
+  /// Longest interval between two beats, in seconds.
+  pub fn with_max_interval(mut self, seconds: f32) -> Self {
+    self.max_interval = seconds;
+    self
+  }
+
+  /// Statistics of the intervals measured so far, up to the latest `window`.
+  pub fn stats(&self) -> Option<TempoStats> {
+    TempoStats::of(&self.intervals)
+  }
+
+  /// Takes one onset; returns the tempo once the window is full.
+  fn onset(&mut self, time: Time) -> Option<TempoStats> {
+    let last = self.last_onset.replace(time)?;
+    let interval = (time.seconds() - last.seconds()) as f32;
+    if interval > self.max_interval {
+      self.intervals.clear();
+      return None;
+    }
+    if self.intervals.len() == self.window {
+      self.intervals.pop_front();
+    }
+    self.intervals.push_back(interval);
+    match self.intervals.len() == self.window {
+      true => self.stats(),
+      false => None,
+    }
+  }
+}
+
+impl<D: Detector> Detector for Tempo<D> {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let mut events = Vec::new();
+    for event in self.source.process_block(block, t) {
+      let onset = matches!(event.kind, EventKind::ToneOn { .. } | EventKind::DualToneOn { .. });
+      let time = event.time;
+      events.push(event);
+      if let Some(stats) = onset.then(|| self.onset(time)).flatten() {
+        let kind = EventKind::Tempo { bpm: stats.bpm, jitter: stats.jitter };
+        events.push(Event::new(time, kind));
+      }
+    }
+    events
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Reports a tone on at each of the given samples.
+  struct Clicks(Vec<u64>);
+
+  impl Detector for Clicks {
+    fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+      let end = t.sample + block.len() as u64;
+      self
+        .0
+        .iter()
+        .filter(|&&s| s >= t.sample && s < end)
+        .map(|&s| Event::new(Time { sample: s, ..t }, EventKind::ToneOn { freq: 1000., power: 1. }))
+        .collect()
+    }
+  }
+
+  fn tempos(clicks: Vec<u64>) -> (Vec<(u64, f32, f32)>, Option<TempoStats>) {
+    let end = clicks.iter().max().copied().unwrap_or(0) + 80;
+    let mut tempo = Tempo::new(Clicks(clicks), 4);
+    let events: Vec<Event> = (0..end / 80)
+      .flat_map(|i| tempo.process_block(&[0.; 80], Time { sample: i * 80, sample_rate: 8000. }))
+      .collect();
+    let tempos = events
+      .into_iter()
+      .filter_map(|e| match e.kind {
+        EventKind::Tempo { bpm, jitter } => Some((e.time.sample, bpm, jitter)),
+        _ => None,
+      })
+      .collect();
+    (tempos, tempo.stats())
+  }
+
+  #[test]
+  fn measures_tempo_and_jitter_of_clicks() {
+    // 120 BPM, the odd clicks 10 ms late.
+    let clicks = (0..8).map(|i| i * 4000 + if i % 2 == 1 { 80 } else { 0 }).collect();
+    let (tempos, stats) = tempos(clicks);
+    assert_eq!(tempos.iter().map(|t| t.0).collect::<Vec<_>>(), vec![16000, 20080, 24000, 28080]);
+    for (_, bpm, jitter) in tempos {
+      assert!((bpm - 120.).abs() < 0.01 && (jitter - 0.01).abs() < 1e-4, "{} {}", bpm, jitter);
+    }
+    let stats = stats.unwrap();
+    assert_eq!(stats.intervals, 4);
+    assert!((stats.max_deviation - 0.01).abs() < 1e-4);
+  }
+
+  #[test]
+  fn a_long_gap_starts_over() {
+    // 60 BPM, stopped for 3 s after the fifth click.
+    let clicks = vec![0, 8000, 16000, 24000, 32000, 56000, 64000];
+    let (tempos, stats) = tempos(clicks);
+    assert_eq!(tempos.len(), 1);
+    assert!((tempos[0].1 - 60.).abs() < 0.01);
+    assert_eq!(stats.unwrap().intervals, 1);
+  }
+}
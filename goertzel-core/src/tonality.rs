@@ -0,0 +1,92 @@
+//! Spectral flatness: how noise-like, rather than tonal, a block is.
+
+use alloc::vec::Vec;
+
+use crate::detector::{Detector, Event, EventKind, Time};
+use crate::{math, GoertzelBank};
+
+/// Geometric over arithmetic mean of `powers`: near 0 when a few bins hold all the energy (a
+/// tone), 1 for a flat spectrum (white noise) and for silence.
+pub fn spectral_flatness(powers: &[f32]) -> f32 {
+  if powers.is_empty() {
+    return 1.;
+  }
+  let n = powers.len() as f32;
+  let powers = powers.iter().map(|p| p.max(0.) + 1e-12);
+  let (log_sum, sum) = powers.fold((0., 0.), |(l, s), p| (l + math::log10(p), s + p));
+  math::powf(10., log_sum / n) / (sum / n)
+}
+
+/// Flatness measured over a coarse bank spread evenly between 0 Hz and Nyquist.
+///
+/// The filters are the bins of a short DFT, so each one is as wide as the spacing between them
+/// and no tone falls in a gap; their powers are averaged over the short chunks of the block.
+#[derive(Debug)]
+pub struct Tonality {
+  bank: GoertzelBank,
+  chunk: usize,
+}
+
+impl Tonality {
+  /// `bins` filters, leaving out DC and Nyquist; 16 to 32 are enough to tell a tone from noise.
+  pub fn new(sample_rate: f32, bins: usize) -> Self {
+    let chunk = 2 * (bins.max(1) + 1);
+    let freqs: Vec<f32> = (1..chunk / 2).map(|k| k as f32 * sample_rate / chunk as f32).collect();
+    Self { bank: GoertzelBank::new(&freqs, sample_rate), chunk }
+  }
+
+  pub fn flatness(&self, block: &[f32]) -> f32 {
+    let mut powers = alloc::vec![0.; self.bank.len()];
+    for chunk in block.chunks_exact(self.chunk) {
+      for (sum, p) in powers.iter_mut().zip(self.bank.block_power(chunk)) {
+        *sum += p;
+      }
+    }
+    spectral_flatness(&powers)
+  }
+}
+
+/// Reports the spectral flatness of every block as a `Flatness` event.
+#[derive(Debug)]
+pub struct FlatnessDetector {
+  tonality: Tonality,
+}
+
+impl FlatnessDetector {
+  pub fn new(sample_rate: f32, bins: usize) -> Self {
+    Self { tonality: Tonality::new(sample_rate, bins) }
+  }
+}
+
+impl Detector for FlatnessDetector {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let value = self.tonality.flatness(block);
+    alloc::vec![Event::new(t, EventKind::Flatness { value })]
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::f32::consts::PI;
+
+  #[test]
+  fn tone_is_less_flat_than_noise() {
+    let tonality = Tonality::new(8000., 16);
+    let tone: Vec<f32> = (0..400).map(|t| (2.*PI*1000.*(t as f32)/8000.).sin()).collect();
+    // xorshift noise, roughly white.
+    let mut x = 0x2545_f491u32;
+    let noise: Vec<f32> = (0..400)
+      .map(|_| {
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        x as f32 / u32::MAX as f32 - 0.5
+      })
+      .collect();
+    assert!(tonality.flatness(&tone) < 0.1, "{}", tonality.flatness(&tone));
+    assert!(tonality.flatness(&noise) > 0.4, "{}", tonality.flatness(&noise));
+    assert!((spectral_flatness(&[0.; 4]) - 1.).abs() < 1e-6);
+  }
+}
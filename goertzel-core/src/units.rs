@@ -0,0 +1,186 @@
+//! Units for the quantities the API mixes up most easily: a frequency, a sample rate, a length
+//! in samples and a duration in milliseconds.
+//!
+//! Constructors such as [`Goertzel::new`](crate::Goertzel::new) and
+//! [`Pipeline::new`](crate::Pipeline::new) take `impl Into<Hertz>`, `impl Into<SampleRate>` and
+//! so on, so plain numbers keep working, while a call written with the units cannot swap a
+//! frequency for a sample rate or a duration for a block size:
+//!
+//! ```
+//! use goertzel_core::units::{Hertz, Millis, SampleRate};
+//! use goertzel_core::Goertzel;
+//!
+//! let rate = SampleRate::from(44_100);
+//! let block = rate.samples(Millis(20.));
+//! assert_eq!(block.get(), 882);
+//! let filter = Goertzel::with_block_size(Hertz(1000.), rate, block);
+//! assert_eq!(filter.block_size(), 882);
+//! ```
+//!
+//! Sample rates convert exactly from integers, so `44_100` cannot come out as `44e3`.
+
+use core::fmt;
+
+/// A frequency, in Hz.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Hertz(pub f32);
+
+/// Samples per second of a stream.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct SampleRate(pub f32);
+
+/// A length or position counted in samples (per channel).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Samples(pub u64);
+
+/// A duration, in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Millis(pub f32);
+
+impl Hertz {
+  /// Cycles per sample at `rate`, 0.5 at the Nyquist frequency.
+  pub fn normalized(self, rate: SampleRate) -> f32 {
+    self.0 / rate.0
+  }
+
+  /// Length of one period, in milliseconds.
+  pub fn period(self) -> Millis {
+    Millis(1000. / self.0)
+  }
+}
+
+impl SampleRate {
+  pub fn hz(self) -> f32 {
+    self.0
+  }
+
+  pub fn nyquist(self) -> Hertz {
+    Hertz(self.0 / 2.)
+  }
+
+  /// Samples in `duration`, rounded to the nearest.
+  pub fn samples(self, duration: Millis) -> Samples {
+    Samples((duration.0 as f64 * self.0 as f64 / 1000. + 0.5).max(0.) as u64)
+  }
+
+  /// How long `samples` last.
+  pub fn millis(self, samples: Samples) -> Millis {
+    Millis((samples.0 as f64 * 1000. / self.0 as f64) as f32)
+  }
+
+  /// Spacing of the DFT bins of a `block` sample block.
+  pub fn bin_width(self, block: Samples) -> Hertz {
+    Hertz(self.0 / block.0.max(1) as f32)
+  }
+}
+
+impl Samples {
+  pub fn get(self) -> usize {
+    self.0 as usize
+  }
+}
+
+impl Millis {
+  pub fn from_seconds(seconds: f32) -> Self {
+    Millis(seconds * 1000.)
+  }
+
+  pub fn seconds(self) -> f32 {
+    self.0 / 1000.
+  }
+}
+
+impl From<f32> for Hertz {
+  fn from(hz: f32) -> Self {
+    Hertz(hz)
+  }
+}
+
+impl From<Hertz> for f32 {
+  fn from(hz: Hertz) -> Self {
+    hz.0
+  }
+}
+
+impl From<f32> for SampleRate {
+  fn from(hz: f32) -> Self {
+    SampleRate(hz)
+  }
+}
+
+impl From<u32> for SampleRate {
+  fn from(hz: u32) -> Self {
+    SampleRate(hz as f32)
+  }
+}
+
+impl From<SampleRate> for f32 {
+  fn from(rate: SampleRate) -> Self {
+    rate.0
+  }
+}
+
+impl From<usize> for Samples {
+  fn from(n: usize) -> Self {
+    Samples(n as u64)
+  }
+}
+
+impl From<Samples> for u64 {
+  fn from(n: Samples) -> Self {
+    n.0
+  }
+}
+
+impl From<f32> for Millis {
+  fn from(ms: f32) -> Self {
+    Millis(ms)
+  }
+}
+
+impl From<Millis> for f32 {
+  fn from(ms: Millis) -> Self {
+    ms.0
+  }
+}
+
+impl fmt::Display for Hertz {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} Hz", self.0)
+  }
+}
+
+impl fmt::Display for SampleRate {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} Hz", self.0)
+  }
+}
+
+impl fmt::Display for Samples {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} samples", self.0)
+  }
+}
+
+impl fmt::Display for Millis {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} ms", self.0)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn converts_between_durations_and_samples() {
+    let rate = SampleRate::from(8000);
+    assert_eq!(rate.samples(Millis(20.)), Samples(160));
+    assert_eq!(rate.samples(Millis::from_seconds(0.0005)), Samples(4));
+    assert_eq!(rate.millis(Samples(80)), Millis(10.));
+    assert_eq!(rate.bin_width(Samples(160)), Hertz(50.));
+    assert_eq!(Hertz(1000.).normalized(rate), 0.125);
+    assert_eq!(SampleRate::from(44_100).hz(), 44_100.);
+  }
+}
@@ -0,0 +1,66 @@
+//! Cross-check of the recursive filter against an FFT of the same block.
+
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+
+use crate::{Goertzel, DEFAULT_EPSILON};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Discrepancy {
+  /// Frequency of the FFT bin nearest the requested one, which both sides are evaluated at.
+  pub freq: f32,
+  pub goertzel: f32,
+  pub fft: f32,
+}
+
+impl Discrepancy {
+  pub fn abs_error(&self) -> f32 {
+    (self.goertzel - self.fft).abs()
+  }
+
+  pub fn rel_error(&self) -> f32 {
+    self.abs_error() / self.fft.abs().max(1e-12)
+  }
+}
+
+/// Evaluates `block` at the FFT bin closest to `freq` with both `Goertzel::block_power` and
+/// rustfft, using the same normalization.
+pub fn compare_block(freq: f32, samplef: f32, block: &[f32]) -> Discrepancy {
+  let n = block.len().max(1);
+  let k = (freq * n as f32 / samplef).round() as usize % n;
+  let bin_freq = k as f32 * samplef / n as f32;
+
+  let mut buf: Vec<Complex<f32>> = block.iter().map(|&x| Complex::new(x, 0.)).collect();
+  buf.resize(n, Complex::new(0., 0.));
+  FftPlanner::new().plan_fft_forward(n).process(&mut buf);
+  let totalpower: f32 = block.iter().map(|x| x*x).sum();
+  let fft = 2. * buf[k].norm_sqr() / (totalpower + DEFAULT_EPSILON) / n as f32;
+
+  Discrepancy {
+    freq: bin_freq,
+    goertzel: Goertzel::new(bin_freq, samplef).block_power(block),
+    fft,
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::f32::consts::PI;
+
+  #[test]
+  fn goertzel_matches_fft_bin() {
+    let samplef = 8000.;
+    let block: Vec<f32> = (0..1000)
+      .map(|t| {
+        let t = t as f32 / samplef;
+        0.7 * (2.*PI*440.*t).sin() + 0.2 * (2.*PI*1250.*t + 0.3).sin()
+      })
+      .collect();
+    for &freq in &[440., 1250., 2000., 3000.] {
+      let d = compare_block(freq, samplef, &block);
+      assert!(d.abs_error() < 1e-4, "{:?}", d);
+    }
+  }
+}
@@ -0,0 +1,123 @@
+//! Alarms on the absence of something: a pilot or line-up tone that should always be there, or
+//! any sound at all.
+
+use alloc::vec::Vec;
+
+use crate::detector::{Detector, Event, EventKind, Time};
+use crate::{math, Goertzel};
+
+/// Reports `ToneLost` once the expected tone has been below its threshold for longer than
+/// allowed, and `Silence` once the input has been below a floor for longer than allowed.
+///
+/// Each alarm is reported once per outage, timed from the block it started in, and re-armed
+/// when the tone or the sound comes back. An outage already under way when the stream starts
+/// counts from the first block.
+#[derive(Debug)]
+pub struct Watchdog {
+  tone: Option<(Goertzel, f32, Outage)>,
+  /// Mean-square floor of the input.
+  silence: Option<(f32, Outage)>,
+}
+
+/// Time since something went missing.
+#[derive(Debug, Clone, Copy)]
+struct Outage {
+  /// Longest outage allowed, in seconds.
+  limit: f32,
+  /// Start of the current outage, and whether it was reported.
+  since: Option<(Time, bool)>,
+}
+
+impl Outage {
+  fn new(limit: f32) -> Self {
+    Self { limit, since: None }
+  }
+
+  /// The length of the outage, the first time it goes past the limit.
+  fn update(&mut self, present: bool, t: Time, end: Time) -> Option<(Time, f32)> {
+    if present {
+      self.since = None;
+      return None;
+    }
+    let (since, reported) = self.since.get_or_insert((t, false));
+    let duration = (end.seconds() - since.seconds()) as f32;
+    if *reported || duration <= self.limit {
+      return None;
+    }
+    *reported = true;
+    Some((*since, duration))
+  }
+}
+
+impl Watchdog {
+  /// Alarms when `freq` reads below `threshold` for more than `max_absence` seconds, e.g. a
+  /// 19 kHz stereo pilot or a 1 kHz line-up tone.
+  pub fn tone(freq: f32, sample_rate: f32, threshold: f32, max_absence: f32) -> Self {
+    let filter = Goertzel::new(freq, sample_rate);
+    Self { tone: Some((filter, threshold, Outage::new(max_absence))), silence: None }
+  }
+
+  /// Alarms when the input stays below `floor_dbfs` (relative to a full-scale sine) for more
+  /// than `max_silence` seconds: dead air.
+  pub fn silence(floor_dbfs: f32, max_silence: f32) -> Self {
+    Self { tone: None, silence: None }.with_silence(floor_dbfs, max_silence)
+  }
+
+  /// Watches for silence as well.
+  pub fn with_silence(mut self, floor_dbfs: f32, max_silence: f32) -> Self {
+    let floor = 0.5 * math::powf(10., floor_dbfs / 10.);
+    self.silence = Some((floor, Outage::new(max_silence)));
+    self
+  }
+}
+
+impl Detector for Watchdog {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let end = t.offset(block.len());
+    let mut events = Vec::new();
+    if let Some((filter, threshold, outage)) = &mut self.tone {
+      let present = filter.block_power(block) >= *threshold;
+      if let Some((since, duration)) = outage.update(present, t, end) {
+        events.push(Event::new(since, EventKind::ToneLost { freq: filter.freq(), duration }));
+      }
+    }
+    if let Some((floor, outage)) = &mut self.silence {
+      let mean_square = block.iter().map(|x| x * x).sum::<f32>() / block.len().max(1) as f32;
+      if let Some((since, duration)) = outage.update(mean_square >= *floor, t, end) {
+        events.push(Event::new(since, EventKind::Silence { duration }));
+      }
+    }
+    events
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::f32::consts::PI;
+
+  #[test]
+  fn alarms_once_per_outage() {
+    let mut watchdog = Watchdog::tone(1000., 8000., 0.5, 1.).with_silence(-50., 2.);
+    let pilot = |seconds: usize| -> Vec<f32> {
+      (0..seconds * 8000).map(|t| 0.5 * (2. * PI * 1000. * t as f32 / 8000.).sin()).collect()
+    };
+    // Pilot, 1.5 s of hum instead, pilot, then 3 s of dead air.
+    let mut signal = pilot(2);
+    signal.extend((0..12000).map(|t| 0.5 * (2. * PI * 60. * t as f32 / 8000.).sin()));
+    signal.extend(pilot(1));
+    signal.extend(vec![0.; 24000]);
+    let mut events = Vec::new();
+    for (i, block) in signal.chunks(800).enumerate() {
+      let t = Time { sample: i as u64 * 800, sample_rate: 8000. };
+      events.extend(watchdog.process_block(block, t));
+    }
+    let kinds: Vec<(u64, &EventKind)> = events.iter().map(|e| (e.time.sample, &e.kind)).collect();
+    assert_eq!(kinds, vec![
+      (16000, &EventKind::ToneLost { freq: 1000., duration: 1.1 }),
+      (36000, &EventKind::ToneLost { freq: 1000., duration: 1.1 }),
+      (36000, &EventKind::Silence { duration: 2.1 }),
+    ]);
+  }
+}
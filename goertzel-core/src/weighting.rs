@@ -0,0 +1,180 @@
+//! Frequency weightings (IEC 61672 A and C) for broadband level measurements.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::f32::consts::PI;
+
+#[cfg(feature = "alloc")]
+use crate::detector::{Detector, Event, EventKind, Time};
+use crate::math;
+
+/// Pole frequencies of the analog weighting curves, in Hz.
+const F1: f32 = 20.598997;
+const F2: f32 = 107.65265;
+const F3: f32 = 737.86223;
+const F4: f32 = 12194.217;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Weighting {
+  /// Flat, no weighting.
+  Z,
+  A,
+  C,
+}
+
+impl Weighting {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "Z" | "z" => Some(Weighting::Z),
+      "A" | "a" => Some(Weighting::A),
+      "C" | "c" => Some(Weighting::C),
+      _ => None,
+    }
+  }
+
+  pub fn letter(&self) -> char {
+    match self {
+      Weighting::Z => 'Z',
+      Weighting::A => 'A',
+      Weighting::C => 'C',
+    }
+  }
+}
+
+/// First-order section `y = (b0 x + b1 x[-1] - a1 y[-1])`.
+#[derive(Debug, Clone, Copy, Default)]
+struct Section {
+  b0: f32,
+  b1: f32,
+  a1: f32,
+  x1: f32,
+  y1: f32,
+}
+
+impl Section {
+  /// Bilinear transform of `s / (s + w)` (high pass) or `1 / (s + w)` (low pass).
+  fn new(pole_hz: f32, high_pass: bool, sample_rate: f32) -> Self {
+    let (k, w) = (2. * sample_rate, 2.*PI*pole_hz);
+    let a0 = k + w;
+    let (b0, b1) = if high_pass { (k, -k) } else { (1., 1.) };
+    Section { b0: b0 / a0, b1: b1 / a0, a1: (w - k) / a0, x1: 0., y1: 0. }
+  }
+
+  fn process(&mut self, x: f32) -> f32 {
+    let y = self.b0 * x + self.b1 * self.x1 - self.a1 * self.y1;
+    self.x1 = x;
+    self.y1 = y;
+    y
+  }
+
+  /// Squared magnitude of the response at `omega` radians per sample.
+  fn gain2(&self, omega: f32) -> f32 {
+    let (sin, cos) = math::sin_cos(omega);
+    // H(e^jw) = (b0 + b1 e^-jw) / (1 + a1 e^-jw)
+    let (nr, ni) = (self.b0 + self.b1 * cos, -self.b1 * sin);
+    let (dr, di) = (1. + self.a1 * cos, -self.a1 * sin);
+    (nr*nr + ni*ni) / (dr*dr + di*di)
+  }
+}
+
+/// Weighting as a cascade of first-order sections, normalized to 0 dB at 1 kHz.
+///
+/// Uses the bilinear transform without prewarping, so above about a quarter of the sample rate
+/// the curve falls off faster than the standard one; good enough for a rough level monitor.
+#[derive(Debug, Clone)]
+pub struct WeightingFilter {
+  weighting: Weighting,
+  sections: [Section; 6],
+  len: usize,
+  gain: f32,
+  sample_rate: f32,
+}
+
+impl WeightingFilter {
+  pub fn new(weighting: Weighting, sample_rate: f32) -> Self {
+    let poles: &[(f32, bool)] = match weighting {
+      Weighting::Z => &[],
+      Weighting::A => &[(F1, true), (F1, true), (F2, true), (F3, true), (F4, false), (F4, false)],
+      Weighting::C => &[(F1, true), (F1, true), (F4, false), (F4, false)],
+    };
+    let mut sections = [Section::default(); 6];
+    for (section, &(pole, high_pass)) in sections.iter_mut().zip(poles) {
+      *section = Section::new(pole, high_pass, sample_rate);
+    }
+    let mut filter = Self { weighting, sections, len: poles.len(), gain: 1., sample_rate };
+    filter.gain = 1. / filter.response(1000.);
+    filter
+  }
+
+  pub fn weighting(&self) -> Weighting {
+    self.weighting
+  }
+
+  pub fn process(&mut self, sample: f32) -> f32 {
+    let y = self.sections[..self.len].iter_mut().fold(sample, |x, s| s.process(x));
+    y * self.gain
+  }
+
+  /// Gain at `freq`, in dB.
+  pub fn response_db(&self, freq: f32) -> f32 {
+    20. * math::log10(self.gain * self.response(freq))
+  }
+
+  /// Magnitude at `freq` before normalization.
+  fn response(&self, freq: f32) -> f32 {
+    let omega = 2.*PI*freq/self.sample_rate;
+    math::sqrt(self.sections[..self.len].iter().map(|s| s.gain2(omega)).product())
+  }
+}
+
+/// Broadband level of every block through a weighting filter, reported as `SoundLevel` in dB
+/// relative to a full-scale sine.
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct LevelMeter {
+  filter: WeightingFilter,
+}
+
+#[cfg(feature = "alloc")]
+impl LevelMeter {
+  pub fn new(weighting: Weighting, sample_rate: f32) -> Self {
+    Self { filter: WeightingFilter::new(weighting, sample_rate) }
+  }
+}
+
+#[cfg(feature = "alloc")]
+impl Detector for LevelMeter {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let energy: f32 = block.iter().map(|&x| self.filter.process(x)).map(|y| y * y).sum();
+    let mean_square = energy / block.len().max(1) as f32;
+    // A full-scale sine has a mean square of 1/2.
+    let dbfs = 10. * math::log10(2. * mean_square + 1e-20);
+    let weighting = self.filter.weighting().letter();
+    alloc::vec![Event::new(t, EventKind::SoundLevel { weighting, dbfs })]
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn weighting_curves_match_the_standard() {
+    let a = WeightingFilter::new(Weighting::A, 48000.);
+    assert!(a.response_db(1000.).abs() < 1e-3);
+    assert!((a.response_db(100.) + 19.1).abs() < 0.2, "{}", a.response_db(100.));
+    assert!((a.response_db(2000.) - 1.2).abs() < 0.2, "{}", a.response_db(2000.));
+    let c = WeightingFilter::new(Weighting::C, 48000.);
+    assert!((c.response_db(50.) + 1.3).abs() < 0.2, "{}", c.response_db(50.));
+    assert!(WeightingFilter::new(Weighting::Z, 48000.).response_db(50.).abs() < 1e-6);
+  }
+
+  #[test]
+  fn meter_reads_full_scale_sine_at_zero_db() {
+    let mut meter = LevelMeter::new(Weighting::A, 48000.);
+    let tone: Vec<f32> = (0..48000).map(|t| (2.*PI*1000.*(t as f32)/48000.).sin()).collect();
+    let events = meter.process_block(&tone, Time { sample: 0, sample_rate: 48000. });
+    assert!(matches!(events[0].kind, EventKind::SoundLevel { weighting: 'A', dbfs } if dbfs.abs() < 0.05));
+  }
+}
@@ -0,0 +1,37 @@
+//! DTMF detector throughput.
+//!
+//! Run with `cargo bench -p goertzel-dtmf`. Criterion reports the throughput in samples/s, its
+//! `thrpt` line, and keeps the results under `target/criterion`; the numbers depend on the
+//! machine, so compare runs on the same one with `-- --save-baseline before`, then
+//! `-- --baseline before` after a change.
+
+use std::f32::consts::PI;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use goertzel_dtmf::DtmfDetector;
+
+const SAMPLE_RATE: f32 = 8000.;
+const LEN: usize = 8000;
+
+fn detector(c: &mut Criterion) {
+  let input: Vec<f32> = (0..LEN)
+    .map(|t| {
+      let t = t as f32 / SAMPLE_RATE;
+      0.5 * (2.*PI*770.*t).sin() + 0.5 * (2.*PI*1336.*t).sin()
+    })
+    .collect();
+  let mut group = c.benchmark_group("dtmf");
+  group.throughput(Throughput::Elements(LEN as u64));
+  group.bench_function("detector", |b| {
+    let mut dtmf = DtmfDetector::standard(SAMPLE_RATE);
+    b.iter(|| {
+      for &s in &input {
+        black_box(dtmf.process(s));
+      }
+    })
+  });
+  group.finish();
+}
+
+criterion_group!(benches, detector);
+criterion_main!(benches);
@@ -0,0 +1,267 @@
+//! DTMF digit detection on top of `goertzel-core`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use goertzel_core::detector::{confidence, share_snr_db, Detector, Event, EventKind, Time};
+use goertzel_core::{math, CoefficientTable, Goertzel};
+
+pub const LOW_GROUP: [f32; 4] = [697., 770., 852., 941.];
+pub const HIGH_GROUP: [f32; 4] = [1209., 1336., 1477., 1633.];
+
+/// Coefficients of both groups at the common sample rates, worked out by the compiler; other
+/// rates compute them when the detector is built.
+static LOW_TABLE: CoefficientTable<4> = CoefficientTable::new(&LOW_GROUP);
+static HIGH_TABLE: CoefficientTable<4> = CoefficientTable::new(&HIGH_GROUP);
+
+const KEYS: [[char; 4]; 4] = [
+  ['1', '2', '3', 'A'],
+  ['4', '5', '6', 'B'],
+  ['7', '8', '9', 'C'],
+  ['*', '0', '#', 'D'],
+];
+
+/// Acceptance limits applied to every block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DtmfConfig {
+  /// Minimum normalized power each tone of the pair must reach. An equal-level pair with
+  /// nothing else in the signal gives 0.5 per tone.
+  pub threshold: f32,
+  /// How many dB the low group tone may be above the high group tone.
+  pub max_normal_twist_db: f32,
+  /// How many dB the high group tone may be above the low group tone.
+  pub max_reverse_twist_db: f32,
+  /// Shortest silence, in seconds, that separates two presses. Shorter dropouts within a held
+  /// key are bridged, and a change of digit without one is ignored.
+  pub min_pause: f32,
+  /// Most digits per second; a press starting sooner after the previous one is ignored.
+  pub max_digit_rate: f32,
+}
+
+impl Default for DtmfConfig {
+  fn default() -> Self {
+    Self {
+      threshold: 0.2,
+      max_normal_twist_db: 8.,
+      max_reverse_twist_db: 4.,
+      min_pause: 0.03,
+      max_digit_rate: 10.,
+    }
+  }
+}
+
+/// Block-based DTMF detector built from eight Goertzel filters.
+#[derive(Debug)]
+pub struct DtmfDetector {
+  low: Vec<Goertzel>,
+  high: Vec<Goertzel>,
+  block: Vec<f32>,
+  block_size: usize,
+  config: DtmfConfig,
+  min_pause: usize,
+  min_interval: usize,
+  /// Digit of the current press, until the pause after it is long enough.
+  held: Option<char>,
+  /// Samples since the held digit was last seen, and since the last reported press.
+  gap: usize,
+  since_press: usize,
+  /// Confidence of the last digit reported.
+  confidence: f32,
+}
+
+impl DtmfDetector {
+  pub fn new(sample_rate: f32, block_size: usize, config: DtmfConfig) -> Self {
+    Self {
+      low: group(&LOW_GROUP, &LOW_TABLE, sample_rate, block_size),
+      high: group(&HIGH_GROUP, &HIGH_TABLE, sample_rate, block_size),
+      block: Vec::with_capacity(block_size),
+      block_size: block_size.max(1),
+      config,
+      min_pause: math::round(config.min_pause * sample_rate) as usize,
+      min_interval: math::round(sample_rate / config.max_digit_rate) as usize,
+      held: None,
+      gap: 0,
+      since_press: usize::MAX,
+      confidence: 0.,
+    }
+  }
+
+  /// Detector with the default limits and 12.75 ms blocks.
+  pub fn standard(sample_rate: f32) -> Self {
+    Self::new(sample_rate, math::round(sample_rate * 0.01275) as usize, DtmfConfig::default())
+  }
+
+  /// Feeds one sample. Returns a digit on the block where a key press is first seen; a held key
+  /// is reported once.
+  pub fn process(&mut self, sample: f32) -> Option<char> {
+    self.block.push(sample);
+    if self.block.len() < self.block_size {
+      return None;
+    }
+    let digit = self.detect();
+    self.block.clear();
+    let since_press = self.since_press;
+    self.since_press = self.since_press.saturating_add(self.block_size);
+    let (digit, confidence) = match digit {
+      Some(digit) => digit,
+      None => {
+        self.gap += self.block_size;
+        if self.gap >= self.min_pause {
+          self.held = None;
+        }
+        return None;
+      }
+    };
+    self.gap = 0;
+    // Same press, a digit change without a pause, or a press too soon after the last one.
+    if self.held.replace(digit).is_some() || since_press < self.min_interval {
+      return None;
+    }
+    self.since_press = self.block_size;
+    self.confidence = confidence;
+    Some(digit)
+  }
+
+  /// How sure the detector was of the last digit `process` returned, from 0 to 1; see
+  /// [`confidence`]. The guard bins are the other tones of each group.
+  pub fn last_confidence(&self) -> f32 {
+    self.confidence
+  }
+
+  /// Digit present in the current block, if both tones pass the level and twist checks, with
+  /// its confidence.
+  fn detect(&self) -> Option<(char, f32)> {
+    let (row, low, low_runner_up) = strongest(&self.low, &self.block);
+    let (col, high, high_runner_up) = strongest(&self.high, &self.block);
+    if low < self.config.threshold || high < self.config.threshold {
+      return None;
+    }
+    let twist_db = 10. * math::log10(low / high);
+    if twist_db > self.config.max_normal_twist_db || -twist_db > self.config.max_reverse_twist_db {
+      return None;
+    }
+    let contrast = |tone: f32, other: f32| 10. * math::log10(tone / other.max(tone * 1e-6));
+    let contrast_db = contrast(low, low_runner_up).min(contrast(high, high_runner_up));
+    Some((KEYS[row][col], confidence(share_snr_db(low + high), contrast_db, 1.)))
+  }
+}
+
+impl Detector for DtmfDetector {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let mut events = Vec::new();
+    for (i, &sample) in block.iter().enumerate() {
+      if let Some(digit) = self.process(sample) {
+        // Time the digit from the start of the block it was detected in.
+        let start = (t.sample + i as u64 + 1).saturating_sub(self.block_size as u64);
+        let event = Event::new(Time { sample: start, ..t }, EventKind::Digit(digit));
+        events.push(event.with_confidence(self.confidence));
+      }
+    }
+    events
+  }
+}
+
+fn group(freqs: &[f32; 4], table: &CoefficientTable<4>, rate: f32, n: usize) -> Vec<Goertzel> {
+  match table.get(rate) {
+    Some(coeffs) => {
+      freqs.iter().zip(coeffs).map(|(&f, &coeff)| Goertzel::with_coeff(f, rate, n, coeff)).collect()
+    }
+    None => freqs.iter().map(|&f| Goertzel::with_block_size(f, rate, n)).collect(),
+  }
+}
+
+/// Index and power of the strongest filter of a group, and the power of the next strongest.
+fn strongest(filters: &[Goertzel], block: &[f32]) -> (usize, f32, f32) {
+  filters.iter().map(|g| g.block_power(block)).enumerate().fold((0, 0., 0.), |best, (i, p)| {
+    if p > best.1 {
+      (i, p, best.1)
+    } else {
+      (best.0, best.1, best.2.max(p))
+    }
+  })
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::f32::consts::PI;
+
+  fn dual_tone(f1: f32, a1: f32, f2: f32, a2: f32, len: usize) -> Vec<f32> {
+    (0..len)
+      .map(|t| {
+        let t = t as f32 / 8000.;
+        a1 * (2.*PI*f1*t).sin() + a2 * (2.*PI*f2*t).sin()
+      })
+      .collect()
+  }
+
+  #[test]
+  fn detects_digit_once_per_press() {
+    let mut dtmf = DtmfDetector::new(8000., 102, DtmfConfig::default());
+    let mut signal = dual_tone(770., 0.5, 1336., 0.5, 800);
+    signal.extend(vec![0.; 400]);
+    signal.extend(dual_tone(941., 0.5, 1477., 0.5, 800));
+    let digits: Vec<char> = signal.iter().filter_map(|&s| dtmf.process(s)).collect();
+    assert_eq!(digits, vec!['5', '#']);
+  }
+
+  #[test]
+  fn bridges_dropouts_and_limits_rate() {
+    let config = DtmfConfig { max_digit_rate: 5., ..DtmfConfig::default() };
+    let mut dtmf = DtmfDetector::new(8000., 102, config);
+    // A 1 held with a 15 ms dropout, then 1 again 140 ms after the press started: too fast for
+    // 5 digits a second. A third press, later, is reported.
+    let mut signal = dual_tone(697., 0.5, 1209., 0.5, 400);
+    signal.extend(vec![0.; 120]);
+    signal.extend(dual_tone(697., 0.5, 1209., 0.5, 200));
+    signal.extend(vec![0.; 400]);
+    signal.extend(dual_tone(697., 0.5, 1209., 0.5, 600));
+    signal.extend(vec![0.; 400]);
+    signal.extend(dual_tone(697., 0.5, 1209., 0.5, 600));
+    let digits: Vec<char> = signal.iter().filter_map(|&s| dtmf.process(s)).collect();
+    assert_eq!(digits, vec!['1', '1']);
+  }
+
+  #[test]
+  fn rejects_excessive_twist() {
+    let mut dtmf = DtmfDetector::new(8000., 102, DtmfConfig::default());
+    // High group 12 dB below the low group.
+    let signal = dual_tone(697., 0.5, 1209., 0.125, 800);
+    assert!(signal.iter().all(|&s| dtmf.process(s).is_none()));
+  }
+
+  #[test]
+  fn digit_events_are_timed_from_their_block() {
+    let mut dtmf = DtmfDetector::new(8000., 100, DtmfConfig::default());
+    let mut signal = vec![0.; 250];
+    signal.extend(dual_tone(852., 0.5, 1209., 0.5, 400));
+    let events = dtmf.process_block(&signal, Time { sample: 1000, sample_rate: 8000. });
+    let time = Time { sample: 1200, sample_rate: 8000. };
+    let timed: Vec<(Time, &EventKind)> = events.iter().map(|e| (e.time, &e.kind)).collect();
+    assert_eq!(timed, vec![(time, &EventKind::Digit('7'))]);
+  }
+
+  #[test]
+  fn noisy_digits_are_less_certain() {
+    let confidence = |noise: f32| {
+      let mut dtmf = DtmfDetector::new(8000., 102, DtmfConfig::default());
+      let mut state = 1u32;
+      let signal: Vec<f32> = dual_tone(852., 0.5, 1336., 0.5, 800)
+        .into_iter()
+        .map(|s| {
+          state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+          s + noise * (state as f32 / u32::MAX as f32 - 0.5)
+        })
+        .collect();
+      let events = dtmf.process_block(&signal, Time { sample: 0, sample_rate: 8000. });
+      assert_eq!(events[0].kind, EventKind::Digit('8'));
+      events[0].confidence.unwrap()
+    };
+    let (clean, noisy) = (confidence(0.), confidence(1.));
+    assert!(clean > 0.9 && noisy < clean - 0.2 && noisy > 0., "{} {}", clean, noisy);
+  }
+}
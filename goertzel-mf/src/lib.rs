@@ -0,0 +1,481 @@
+//! Multi-frequency inter-register signaling on top of `goertzel-core`: the 2-of-6 codes of
+//! R2 MFC (forward and backward), Signalling System No. 5 and North American MF (R1), and the
+//! SS5 and SF line signals.
+//!
+//! A 2-of-6 signal is two of six tones sounding together; the fifteen pairs are numbered in the
+//! usual order, 1 = f0+f1, 2 = f0+f2, 3 = f1+f2, 4 = f0+f3 up to 15 = f4+f5, and reported as
+//! `Digit` events with the symbol their [`ToneSet`] gives them.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use goertzel_core::detector::{Detector, Event, EventKind, Time};
+use goertzel_core::{math, Goertzel};
+
+/// The six tones of a 2-of-6 code, and the symbol of each of the fifteen signals.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneSet {
+  pub freqs: [f32; 6],
+  /// Symbols of signals 1 to 15.
+  pub symbols: [char; 15],
+}
+
+/// Signals 1 to 15 as `1`–`9`, `0` (signal 10) and `A`–`E` (11 to 15).
+const R2_SYMBOLS: [char; 15] =
+  ['1', '2', '3', '4', '5', '6', '7', '8', '9', '0', 'A', 'B', 'C', 'D', 'E'];
+
+/// R2 MFC forward signals, from the calling exchange (ITU-T Q.441).
+pub const R2_FORWARD: ToneSet =
+  ToneSet { freqs: [1380., 1500., 1620., 1740., 1860., 1980.], symbols: R2_SYMBOLS };
+
+/// R2 MFC backward signals, from the called exchange; f0 is the highest tone.
+pub const R2_BACKWARD: ToneSet =
+  ToneSet { freqs: [1140., 1020., 900., 780., 660., 540.], symbols: R2_SYMBOLS };
+
+/// SS5 register signals (ITU-T Q.151): digits, codes 11 and 12 as `B` and `C`, KP1 and KP2 as
+/// `K` and `L`, and ST as `S`.
+pub const SS5: ToneSet = ToneSet {
+  freqs: [700., 900., 1100., 1300., 1500., 1700.],
+  symbols: ['1', '2', '3', '4', '5', '6', '7', '8', '9', '0', 'B', 'C', 'K', 'L', 'S'],
+};
+
+/// North American MF (R1) trunk signals: digits, KP as `K`, ST as `S`, and ST', ST'' and
+/// ST''' as `A`, `B` and `C`.
+pub const R1: ToneSet = ToneSet {
+  freqs: [700., 900., 1100., 1300., 1500., 1700.],
+  symbols: ['1', '2', '3', '4', '5', '6', '7', '8', '9', '0', 'C', 'A', 'K', 'B', 'S'],
+};
+
+/// The two SS5 line signal tones, f1 and f2.
+pub const SS5_LINE: [f32; 2] = [2400., 2600.];
+
+/// The single-frequency trunk supervision tone of North American SF signaling.
+pub const SF: f32 = 2600.;
+
+/// Block length the decoders are meant to be fed, in seconds.
+pub const MF_BLOCK: f32 = 0.01;
+
+/// Longest backward R2 signal accepted without a forward signal to acknowledge, in seconds:
+/// pulsed signals last 150 ± 30 ms.
+pub const MAX_PULSE: f32 = 0.2;
+
+/// Acceptance limits applied to every block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MfConfig {
+  /// Minimum normalized power each tone of the pair must reach. An equal-level pair with
+  /// nothing else in the signal gives 0.5 per tone, both directions of a compelled exchange
+  /// about 0.25.
+  pub threshold: f32,
+  /// How many dB either tone may be above the other.
+  pub max_twist_db: f32,
+  /// How many dB the third strongest tone of the set must be below the weaker of the pair.
+  pub min_rejection_db: f32,
+  /// How long, in seconds, a signal must last to be reported.
+  pub min_on: f32,
+  /// Shortest silence, in seconds, that ends a signal. Shorter dropouts are bridged, and a
+  /// change of signal without one is ignored.
+  pub min_pause: f32,
+}
+
+impl Default for MfConfig {
+  fn default() -> Self {
+    Self { threshold: 0.1, max_twist_db: 6., min_rejection_db: 10., min_on: 0.03, min_pause: 0.03 }
+  }
+}
+
+/// Decodes the signals of one tone set.
+///
+/// A signal is reported once, when it has lasted `min_on`, timed from its onset, however long
+/// it is then held: compelled signals stay on until the far end answers.
+#[derive(Debug)]
+pub struct MfDecoder {
+  set: ToneSet,
+  filters: Vec<Goertzel>,
+  config: MfConfig,
+  /// Signal of the recent blocks and its onset, until it lasted `min_on`.
+  candidate: Option<(usize, Time)>,
+  /// Reported signal, its onset and the end of the last block it was seen in.
+  held: Option<(usize, Time, Time)>,
+}
+
+impl MfDecoder {
+  pub fn new(set: ToneSet, sample_rate: f32, config: MfConfig) -> Self {
+    let filters = set.freqs.iter().map(|&f| Goertzel::new(f, sample_rate)).collect();
+    Self { set, filters, config, candidate: None, held: None }
+  }
+
+  /// The signal being held, with its onset and the end of the last block it was seen in.
+  pub fn held(&self) -> Option<(char, Time, Time)> {
+    self.held.map(|(signal, onset, last)| (self.set.symbols[signal], onset, last))
+  }
+
+  /// Index of the signal present in the block, if the pair passes the level, twist and
+  /// rejection checks.
+  fn detect(&self, block: &[f32]) -> Option<usize> {
+    let mut powers: Vec<(usize, f32)> =
+      self.filters.iter().map(|g| g.block_power(block)).enumerate().collect();
+    powers.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let [(i, p1), (j, p2), (_, p3), ..] = powers[..] else {
+      return None;
+    };
+    let db = |a: f32, b: f32| 10. * math::log10(a / b);
+    if p2 < self.config.threshold
+      || db(p1, p2) > self.config.max_twist_db
+      || db(p2, p3) < self.config.min_rejection_db
+    {
+      return None;
+    }
+    let (low, high) = (i.min(j), i.max(j));
+    Some(high * (high - 1) / 2 + low)
+  }
+}
+
+impl Detector for MfDecoder {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let end = t.offset(block.len());
+    let signal = self.detect(block);
+    if let Some((held, _, last)) = &mut self.held {
+      if signal == Some(*held) {
+        *last = end;
+        return Vec::new();
+      }
+      if ((t.seconds() - last.seconds()) as f32) < self.config.min_pause {
+        return Vec::new();
+      }
+      self.held = None;
+    }
+    let Some(signal) = signal else {
+      self.candidate = None;
+      return Vec::new();
+    };
+    let onset = match self.candidate {
+      Some((candidate, onset)) if candidate == signal => onset,
+      _ => {
+        self.candidate = Some((signal, t));
+        t
+      }
+    };
+    if ((end.seconds() - onset.seconds()) as f32) < self.config.min_on {
+      return Vec::new();
+    }
+    self.candidate = None;
+    self.held = Some((signal, onset, end));
+    alloc::vec![Event::new(onset, EventKind::Digit(self.set.symbols[signal]))]
+  }
+}
+
+/// Decodes both directions of an R2 MFC exchange recorded on one channel, labeling the
+/// signals `forward` and `backward`.
+///
+/// Backward signals count when they acknowledge a forward signal, as compelled signaling has
+/// them, or as pulses of at most [`MAX_PULSE`] on their own; a pulse is reported once it ended,
+/// still timed from its onset.
+#[derive(Debug)]
+pub struct R2Decoder {
+  forward: MfDecoder,
+  backward: MfDecoder,
+  /// Backward signal seen without a forward one, and the end of the last block it was seen in,
+  /// until it is known to be a pulse.
+  pending: Option<(Event, Time)>,
+}
+
+impl R2Decoder {
+  pub fn new(sample_rate: f32, config: MfConfig) -> Self {
+    Self {
+      forward: MfDecoder::new(R2_FORWARD, sample_rate, config),
+      backward: MfDecoder::new(R2_BACKWARD, sample_rate, config),
+      pending: None,
+    }
+  }
+}
+
+impl Detector for R2Decoder {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let label = |mut event: Event, direction: &str| {
+      event.label = Some(String::from(direction));
+      event
+    };
+    let mut events: Vec<Event> = self
+      .forward
+      .process_block(block, t)
+      .into_iter()
+      .map(|e| label(e, "forward"))
+      .collect();
+    let backward = self.backward.process_block(block, t);
+    let compelled = self.forward.held().is_some();
+    for event in backward {
+      if compelled {
+        events.push(label(event, "backward"));
+      } else {
+        self.pending = Some((event, t));
+      }
+    }
+    if let Some((pending, last)) = self.pending.take() {
+      match self.backward.held() {
+        Some(_) if compelled => events.push(label(pending, "backward")),
+        Some((_, _, last)) => self.pending = Some((pending, last)),
+        None => {
+          if ((last.seconds() - pending.time.seconds()) as f32) <= MAX_PULSE {
+            events.push(label(pending, "backward"));
+          }
+        }
+      }
+    }
+    events
+  }
+}
+
+/// Lengths an R1 signal and the pause before it must have, in seconds.
+///
+/// The defaults are the transmit tolerances with a 10 ms block to spare either side: KP lasts
+/// 90 to 120 ms, other signals and the pauses between them 61 to 75 ms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct R1Timing {
+  pub kp: (f32, f32),
+  pub signal: (f32, f32),
+  pub min_pause: f32,
+}
+
+impl Default for R1Timing {
+  fn default() -> Self {
+    Self { kp: (0.08, 0.13), signal: (0.051, 0.085), min_pause: 0.051 }
+  }
+}
+
+/// Decodes North American MF, checking the length of every signal and of the pause before
+/// it; a signal out of its limits is dropped.
+///
+/// Signals are reported once they ended, still timed from their onset.
+#[derive(Debug)]
+pub struct R1Decoder {
+  decoder: MfDecoder,
+  timing: R1Timing,
+  /// Signal being measured and the end of the last block it was seen in.
+  pending: Option<(Event, Time)>,
+  /// End of the previous signal, valid or not.
+  last_end: Option<Time>,
+}
+
+impl R1Decoder {
+  pub fn new(sample_rate: f32, config: MfConfig, timing: R1Timing) -> Self {
+    Self { decoder: MfDecoder::new(R1, sample_rate, config), timing, pending: None, last_end: None }
+  }
+
+  fn valid(&self, event: &Event, end: Time) -> bool {
+    let seconds = |from: Time, to: Time| (to.seconds() - from.seconds()) as f32;
+    let (min, max) = match event.kind {
+      EventKind::Digit('K') => self.timing.kp,
+      _ => self.timing.signal,
+    };
+    let length = seconds(event.time, end);
+    let paused =
+      self.last_end.is_none_or(|last| seconds(last, event.time) >= self.timing.min_pause);
+    length >= min && length <= max && paused
+  }
+}
+
+impl Detector for R1Decoder {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let started = self.decoder.process_block(block, t);
+    let mut events = Vec::new();
+    if let Some((pending, last)) = self.pending.take() {
+      match self.decoder.held() {
+        Some((_, onset, end)) if onset == pending.time => self.pending = Some((pending, end)),
+        _ => {
+          if self.valid(&pending, last) {
+            events.push(pending);
+          }
+          self.last_end = Some(last);
+        }
+      }
+    }
+    for event in started {
+      let end = self.decoder.held().map_or(t, |(_, _, end)| end);
+      self.pending = Some((event, end));
+    }
+    events
+  }
+}
+
+/// Reports SS5 or SF line signals: `Match` with the tones present, e.g. `2400`, `2600` or
+/// `2400+2600`, once they have lasted `min_on` (40 ms unless set), timed from their onset.
+///
+/// A change of tones reports the new signal; each tone must hold at least the threshold of
+/// the block's energy, 0.2 unless set.
+#[derive(Debug)]
+pub struct LineSignals {
+  filters: Vec<Goertzel>,
+  threshold: f32,
+  min_on: f32,
+  /// Tones of the recent blocks, as a bit mask, since when, and whether they were reported.
+  current: Option<(u32, Time, bool)>,
+}
+
+impl LineSignals {
+  pub fn new(freqs: &[f32], sample_rate: f32) -> Self {
+    let filters = freqs.iter().map(|&f| Goertzel::new(f, sample_rate)).collect();
+    Self { filters, threshold: 0.2, min_on: 0.04, current: None }
+  }
+
+  /// The SS5 line signals, on [`SS5_LINE`].
+  pub fn ss5(sample_rate: f32) -> Self {
+    Self::new(&SS5_LINE, sample_rate)
+  }
+
+  /// The 2600 Hz [`SF`] tone.
+  pub fn sf(sample_rate: f32) -> Self {
+    Self::new(&[SF], sample_rate)
+  }
+
+  pub fn with_threshold(mut self, threshold: f32) -> Self {
+    self.threshold = threshold;
+    self
+  }
+
+  pub fn with_min_on(mut self, seconds: f32) -> Self {
+    self.min_on = seconds;
+    self
+  }
+
+  fn name(&self, tones: u32) -> String {
+    let mut name = String::new();
+    for (_, filter) in self.filters.iter().enumerate().filter(|(i, _)| tones >> i & 1 == 1) {
+      if !name.is_empty() {
+        name.push('+');
+      }
+      name.push_str(&alloc::format!("{}", filter.freq()));
+    }
+    name
+  }
+}
+
+impl Detector for LineSignals {
+  fn process_block(&mut self, block: &[f32], t: Time) -> Vec<Event> {
+    let tones = self
+      .filters
+      .iter()
+      .enumerate()
+      .filter(|(_, g)| g.block_power(block) >= self.threshold)
+      .fold(0, |mask, (i, _)| mask | 1 << i);
+    if tones == 0 {
+      self.current = None;
+      return Vec::new();
+    }
+    let (_, onset, reported) = match &mut self.current {
+      Some(current) if current.0 == tones => current,
+      current => current.insert((tones, t, false)),
+    };
+    let lasted = (t.offset(block.len()).seconds() - onset.seconds()) as f32;
+    if *reported || lasted < self.min_on {
+      return Vec::new();
+    }
+    *reported = true;
+    let onset = *onset;
+    alloc::vec![Event::new(onset, EventKind::Match(self.name(tones)))]
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::f32::consts::PI;
+
+  /// `tones` from `start` to `end` ms, added to `signal` (8 kHz).
+  fn add(signal: &mut Vec<f32>, tones: &[f32], start: usize, end: usize) {
+    if signal.len() < end * 8 {
+      signal.resize(end * 8, 0.);
+    }
+    for (i, s) in signal.iter_mut().enumerate().take(end * 8).skip(start * 8) {
+      let t = i as f32 / 8000.;
+      *s += tones.iter().map(|f| 0.3 * (2. * PI * f * t).sin()).sum::<f32>();
+    }
+  }
+
+  fn run(detector: &mut impl Detector, signal: &[f32]) -> Vec<Event> {
+    signal
+      .chunks(80)
+      .enumerate()
+      .flat_map(|(i, block)| {
+        detector.process_block(block, Time { sample: (i * 80) as u64, sample_rate: 8000. })
+      })
+      .collect()
+  }
+
+  fn at(ms: u64, kind: EventKind, direction: &str) -> Event {
+    let time = Time { sample: ms * 8, sample_rate: 8000. };
+    Event { label: Some(direction.into()), ..Event::new(time, kind) }
+  }
+
+  #[test]
+  fn decodes_ss5_register_signals() {
+    let mut signal = Vec::new();
+    // KP1, 4, 7, ST, with 60 ms pauses.
+    add(&mut signal, &[1100., 1700.], 0, 100);
+    add(&mut signal, &[700., 1300.], 160, 220);
+    add(&mut signal, &[700., 1500.], 280, 340);
+    add(&mut signal, &[1500., 1700.], 400, 460);
+    add(&mut signal, &[], 460, 520);
+    let mut decoder = MfDecoder::new(SS5, 8000., MfConfig::default());
+    let symbols: Vec<EventKind> = run(&mut decoder, &signal).into_iter().map(|e| e.kind).collect();
+    assert_eq!(symbols, "K47S".chars().map(EventKind::Digit).collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn r2_backward_signals_must_be_compelled_or_pulsed() {
+    let mut signal = Vec::new();
+    // Forward 5 acknowledged by backward 1: the forward signal stops once the backward one is
+    // heard, then the backward one stops.
+    add(&mut signal, &[1500., 1740.], 0, 300);
+    add(&mut signal, &[1140., 1020.], 100, 400);
+    // A 150 ms pulse of backward 3, then backward 6 held for 600 ms with nothing to answer.
+    add(&mut signal, &[1020., 900.], 600, 750);
+    add(&mut signal, &[900., 780.], 1000, 1600);
+    add(&mut signal, &[], 1600, 1700);
+    let events = run(&mut R2Decoder::new(8000., MfConfig::default()), &signal);
+    assert_eq!(events, vec![
+      at(0, EventKind::Digit('5'), "forward"),
+      at(100, EventKind::Digit('1'), "backward"),
+      at(600, EventKind::Digit('3'), "backward"),
+    ]);
+  }
+
+  #[test]
+  fn r1_signals_must_keep_to_their_timing() {
+    let mut signal = Vec::new();
+    // KP, 1, 2, ST at the nominal 100 and 68 ms, 68 ms apart.
+    add(&mut signal, &[1100., 1700.], 0, 100);
+    add(&mut signal, &[700., 900.], 168, 236);
+    add(&mut signal, &[700., 1100.], 304, 372);
+    add(&mut signal, &[1500., 1700.], 440, 508);
+    // A 3 held for 200 ms, and a KP only 60 ms long.
+    add(&mut signal, &[900., 1100.], 700, 900);
+    add(&mut signal, &[1100., 1700.], 1100, 1160);
+    add(&mut signal, &[], 1160, 1300);
+    let mut decoder = R1Decoder::new(8000., MfConfig::default(), R1Timing::default());
+    let events = run(&mut decoder, &signal);
+    let times: Vec<(u64, EventKind)> =
+      events.into_iter().map(|e| (e.time.sample / 8, e.kind)).collect();
+    assert_eq!(times, vec![
+      (0, EventKind::Digit('K')),
+      (170, EventKind::Digit('1')),
+      (300, EventKind::Digit('2')),
+      (440, EventKind::Digit('S')),
+    ]);
+  }
+
+  #[test]
+  fn names_ss5_line_signals() {
+    let mut signal = Vec::new();
+    // Seizing (f1), then clear-forward (f1 and f2).
+    add(&mut signal, &[2400.], 0, 150);
+    add(&mut signal, &[2400., 2600.], 400, 800);
+    let events = run(&mut LineSignals::ss5(8000.), &signal);
+    let names: Vec<EventKind> = events.into_iter().map(|e| e.kind).collect();
+    assert_eq!(names, vec![EventKind::Match("2400".into()), EventKind::Match("2400+2600".into())]);
+  }
+}